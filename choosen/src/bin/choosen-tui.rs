@@ -0,0 +1,219 @@
+//! A live draw in the terminal, for small events. Enabled by the `tui`
+//! feature.
+//!
+//! ```text
+//! choosen-tui ROSTER NAME=CAP... [--seed SEED]
+//! ```
+//!
+//! `ROSTER` holds one entry per line. The entries stream into the draw while
+//! the pool rolls on screen. `space` pauses or resumes the stream, `enter`
+//! closes the entries, then reveals the positions one at a time, the last
+//! one first, and `q` quits.
+#![deny(clippy::panic, clippy::unwrap_used, clippy::expect_used)]
+use std::time::Duration;
+
+use choosen::{parse_seed_hex, seed_hex, Choosen, ChoosenBuilder, Position, SeedSource, Stage};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+const USAGE: &str = "usage: choosen-tui ROSTER NAME=CAP... [--seed SEED]";
+
+/// How long an entry stays on screen before the next one streams in.
+const TICK: Duration = Duration::from_millis(40);
+
+struct App {
+    choosen: Choosen<String>,
+    roster: std::vec::IntoIter<String>,
+    paused: bool,
+    // 还没揭晓的奖项数，从最后一个往前揭晓
+    unrevealed: usize,
+    revealed: Vec<(String, Vec<String>)>,
+    status: String,
+}
+
+impl App {
+    fn new(choosen: Choosen<String>, roster: Vec<String>) -> Self {
+        let unrevealed = choosen.positions().len();
+        Self {
+            choosen,
+            roster: roster.into_iter(),
+            paused: false,
+            unrevealed,
+            revealed: Vec::new(),
+            status: "streaming entries".into(),
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.paused || self.choosen.stage() != Stage::Collecting {
+            return;
+        }
+        match self.roster.next() {
+            Some(entry) => {
+                let _ = self.choosen.poll_one(entry);
+            }
+            None => self.status = "every entry is in, press enter to close".into(),
+        }
+    }
+
+    /// Closes the entries first, then reveals the next position.
+    fn advance(&mut self) {
+        match self.choosen.stage() {
+            Stage::Collecting => match self.choosen.lock() {
+                Ok(()) => self.status = "entries closed, press enter to reveal".into(),
+                Err(e) => self.status = e.to_string(),
+            },
+            Stage::Locked => {
+                let idx = self.unrevealed.saturating_sub(1);
+                match self.choosen.release_position(idx) {
+                    Ok(winners) => {
+                        self.revealed.push(winners);
+                        self.unrevealed = idx;
+                        self.status = "press enter to reveal the next position".into();
+                    }
+                    Err(e) => self.status = e.to_string(),
+                }
+            }
+            Stage::Released => self.status = "every position is revealed, press q".into(),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [pool, winners] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(body);
+
+        let positions: Vec<String> = self
+            .choosen
+            .positions()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let stage = Paragraph::new(format!(
+            "{} | {} | seed {}",
+            self.choosen.stage(),
+            positions.join(", "),
+            seed_hex(&self.choosen.seed())
+        ))
+        .block(Block::bordered().title("choosen"));
+        frame.render_widget(stage, header);
+
+        // 截止后就不再显示池子，免得提前剧透
+        let slots: Vec<String> = if self.choosen.stage() == Stage::Collecting {
+            self.choosen
+                .lucky()
+                .iter()
+                .map(|it| it.as_deref().unwrap_or("-").to_string())
+                .collect()
+        } else {
+            vec!["sealed".into()]
+        };
+        frame.render_widget(
+            List::new(slots).block(Block::bordered().title("pool")),
+            pool,
+        );
+
+        let mut lines = Vec::new();
+        for (name, luck) in &self.revealed {
+            lines.push(Line::styled(
+                name.as_str(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+            lines.extend(luck.iter().map(|it| Line::from(format!("  {}", it))));
+        }
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("winners")),
+            winners,
+        );
+
+        let help = format!(
+            "{} | space: pause/resume  enter: close/reveal  q: quit",
+            self.status
+        );
+        frame.render_widget(Paragraph::new(help), footer);
+    }
+}
+
+fn run(mut terminal: DefaultTerminal, mut app: App) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if !event::poll(TICK)? {
+            app.tick();
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char(' ') => app.paused = !app.paused,
+                KeyCode::Enter => app.advance(),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn parse_args() -> Result<(Choosen<String>, Vec<String>), String> {
+    let mut args = std::env::args().skip(1);
+    let roster = args.next().ok_or(USAGE)?;
+    let roster = std::fs::read_to_string(&roster).map_err(|e| format!("{}: {}", roster, e))?;
+    let roster = roster
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+
+    let mut builder = ChoosenBuilder::<Position>::new();
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            // 界面上显示的十六进制种子，或者一个数字
+            let seed = args
+                .next()
+                .and_then(|seed| {
+                    parse_seed_hex(&seed).or_else(|| seed.parse().ok().map(|n: u64| n.seed()))
+                })
+                .ok_or("--seed takes a number, or the 64 hex digits of a seed")?;
+            let _ = builder.seed_from(seed);
+            continue;
+        }
+        let (name, cap) = arg.rsplit_once('=').ok_or(USAGE)?;
+        let cap = cap.parse().map_err(|_| format!("{}: bad cap", arg))?;
+        let _ = builder
+            .add_position(name, cap)
+            .map_err(|e| format!("{}: {:?}", arg, e))?;
+    }
+
+    let choosen = builder.build().map_err(|e| format!("{:?}", e))?;
+    Ok((choosen, roster))
+}
+
+fn main() {
+    let (choosen, roster) = match parse_args() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let terminal = ratatui::init();
+    let result = run(terminal, App::new(choosen, roster));
+    ratatui::restore();
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,12 +1,24 @@
+use crate::fingerprint::{DuplicateCheck, Duplicates, Fingerprint};
+use crate::pos::Weighting;
 use crate::pos::{Position, PositionType, PositionTypeError};
-use crate::Choosen;
-use reservoir_sampler::Reservoir;
+use crate::seed::{OsEntropy, SeedSource};
+use crate::{Choosen, PerPositionChoosen, Stage, WeightedChoosen};
+use reservoir_sampler::{Deterministic, GrowingReservoir, Reservoir, TicketReservoir};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum BuildChoosenError {
     EmptyBuilder,
     WrongPositionType(PositionTypeError),
     PositionOutBound(usize),
+    /// The draw only takes positions of fixed caps, but the position of this
+    /// index goes to a share of the entrants.
+    SharedPosition(usize),
+    /// There are not as many ratios as positions.
+    RatioCount(usize),
+    /// The ratio of this index is not a positive number.
+    InvalidRatio(usize),
+    /// The caps of the positions add up to more than a `usize` holds.
+    TooManyWinners,
 }
 
 /// A `Builder` promises that only after all positions are confirmed would we
@@ -17,6 +29,16 @@ where
     PT: PositionType + Default + Clone,
 {
     positions: Vec<PT>,
+    seed: Option<[u8; 32]>,
+}
+
+impl<PT> Default for ChoosenBuilder<PT>
+where
+    PT: PositionType + Default + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<PT> ChoosenBuilder<PT>
@@ -26,15 +48,49 @@ where
     pub fn new() -> Self {
         ChoosenBuilder {
             positions: Vec::<PT>::new(),
+            seed: None,
         }
     }
 
+    /// Takes the seed of the draw from `source`, right away. Without it, every
+    /// build seeds from [`OsEntropy`].
+    pub fn seed_from(&mut self, source: impl SeedSource) -> &mut Self {
+        self.seed = Some(source.seed());
+        self
+    }
+
+    pub(crate) fn seed(&self) -> [u8; 32] {
+        self.seed.unwrap_or_else(|| OsEntropy.seed())
+    }
+
     pub fn add_position(&mut self, name: &str, cap: usize) -> Result<&mut Self, BuildChoosenError> {
         let mut pos = PT::default();
         pos.set_name(name)
-            .map_err(|e| BuildChoosenError::WrongPositionType(e))?;
+            .map_err(BuildChoosenError::WrongPositionType)?;
         pos.set_cap(cap)
-            .map_err(|e| BuildChoosenError::WrongPositionType(e))?;
+            .map_err(BuildChoosenError::WrongPositionType)?;
+        self.positions.push(pos);
+        Ok(self)
+    }
+
+    /// Adds a position going to `share` of the entrants, like `0.01` for 1%.
+    /// Its cap is fixed when the entries close, rounding up, so any entrant
+    /// makes at least one winner. Until then [`Choosen::lucky`] shows no
+    /// pool, for the pool only takes its size then.
+    ///
+    /// Only [`ChoosenBuilder::build`] and [`ChoosenBuilder::build_checked`]
+    /// take such positions, the other draws report
+    /// [`BuildChoosenError::SharedPosition`].
+    pub fn add_position_percent(
+        &mut self,
+        name: &str,
+        share: f64,
+    ) -> Result<&mut Self, BuildChoosenError> {
+        let mut pos = PT::default();
+        pos.set_name(name)
+            .map_err(BuildChoosenError::WrongPositionType)?;
+        pos.set_share(share)
+            .map_err(BuildChoosenError::WrongPositionType)?;
         self.positions.push(pos);
         Ok(self)
     }
@@ -55,7 +111,7 @@ where
         self.check_idx(idx)?;
         self.positions[idx]
             .set_name(name)
-            .map_err(|e| BuildChoosenError::WrongPositionType(e))?;
+            .map_err(BuildChoosenError::WrongPositionType)?;
         Ok(self)
     }
 
@@ -67,7 +123,53 @@ where
         self.check_idx(idx)?;
         self.positions[idx]
             .set_cap(new_cap)
-            .map_err(|e| BuildChoosenError::WrongPositionType(e))?;
+            .map_err(BuildChoosenError::WrongPositionType)?;
+        Ok(self)
+    }
+
+    pub fn set_position_weighting(
+        &mut self,
+        weighting: Weighting,
+        idx: usize,
+    ) -> Result<&mut Self, BuildChoosenError> {
+        self.check_idx(idx)?;
+        self.positions[idx]
+            .set_weighting(weighting)
+            .map_err(BuildChoosenError::WrongPositionType)?;
+        Ok(self)
+    }
+
+    /// Splits `total` winners, like the expected entrants when everyone goes
+    /// home with something, among the positions added so far by `ratios`,
+    /// one for each position, like `[1.0, 2.0, 10.0]`, setting their caps.
+    ///
+    /// The caps add up to `total`, the parts left over by rounding down
+    /// going to the positions of the largest remainders, earlier positions
+    /// first on a tie. Every position gets at least one winner though, so
+    /// with fewer winners than positions the caps add up to more.
+    pub fn auto_balance(
+        &mut self,
+        total: usize,
+        ratios: &[f64],
+    ) -> Result<&mut Self, BuildChoosenError> {
+        if self.positions.is_empty() {
+            return Err(BuildChoosenError::EmptyBuilder);
+        }
+        if ratios.len() != self.positions.len() {
+            return Err(BuildChoosenError::RatioCount(ratios.len()));
+        }
+        if let Some(idx) = ratios.iter().position(|&r| !(r > 0.0 && r.is_finite())) {
+            return Err(BuildChoosenError::InvalidRatio(idx));
+        }
+        if let Some(idx) = self.positions.iter().position(|p| p.share().is_some()) {
+            return Err(BuildChoosenError::SharedPosition(idx));
+        }
+
+        for (p, cap) in self.positions.iter_mut().zip(balance(total, ratios)?) {
+            let _ = p
+                .set_cap(cap)
+                .map_err(BuildChoosenError::WrongPositionType)?;
+        }
         Ok(self)
     }
 
@@ -81,20 +183,175 @@ where
         &self.positions
     }
 
+    /// Builds a [`PerPositionChoosen`], where every position draws its own
+    /// winners, turning what `weight` gives into tickets by its
+    /// [`Weighting`].
+    pub fn per_position<P: Clone, W: Fn(&P) -> u64>(
+        &self,
+        weight: W,
+    ) -> Result<PerPositionChoosen<P, W, PT>, BuildChoosenError> {
+        let _ = self.lucky_cap()?;
+        let seed = self.seed();
+
+        Ok(PerPositionChoosen {
+            lucky: (0..)
+                .zip(&self.positions)
+                .map(|(i, p)| {
+                    let rng = Deterministic::from_seed(seed).with_stream(i);
+                    TicketReservoir::<P, _>::with_rng(p.cap(), rng)
+                })
+                .collect(),
+            positions: self.positions.clone(),
+            weight,
+            seed,
+            same: None,
+        })
+    }
+
+    /// Builds a [`PerPositionChoosen`] like [`ChoosenBuilder::per_position`],
+    /// where no attendee wins more than one position. Positions hand out in
+    /// order, and the pool of every position holds as many extra attendees as
+    /// the positions before it have places, to stand in for their winners.
+    pub fn per_position_exclusive<P: Clone + PartialEq, W: Fn(&P) -> u64>(
+        &self,
+        weight: W,
+    ) -> Result<PerPositionChoosen<P, W, PT>, BuildChoosenError> {
+        let mut choosen = self.per_position(weight)?;
+        let seed = choosen.seed;
+        let mut before = 0usize;
+        for (i, (p, lucky)) in (0..).zip(self.positions.iter().zip(&mut choosen.lucky)) {
+            let rng = Deterministic::from_seed(seed).with_stream(i);
+            *lucky = TicketReservoir::<P, _>::with_rng(p.cap().saturating_add(before), rng);
+            before = before.saturating_add(p.cap());
+        }
+        choosen.same = Some(<P as PartialEq>::eq);
+        Ok(choosen)
+    }
+
+    pub(crate) fn lucky_cap(&self) -> Result<usize, BuildChoosenError> {
+        if self.positions.is_empty() {
+            return Err(BuildChoosenError::EmptyBuilder);
+        }
+        if let Some(idx) = self.positions.iter().position(|p| p.share().is_some()) {
+            return Err(BuildChoosenError::SharedPosition(idx));
+        }
+
+        self.positions
+            .iter()
+            .try_fold(0usize, |sum, p| sum.checked_add(p.cap()))
+            .ok_or(BuildChoosenError::TooManyWinners)
+    }
+
     pub fn build<P: Clone>(&self) -> Result<Choosen<P, PT>, BuildChoosenError> {
         if self.positions.is_empty() {
             return Err(BuildChoosenError::EmptyBuilder);
         }
+        let seed = self.seed();
 
-        let lucky_cap = self.positions.iter().map(|p| p.cap()).sum::<usize>();
+        // 按比例的奖项到截止时才知道名额，先用会增长的池子
+        let shares: Vec<f64> = self.positions.iter().filter_map(|p| p.share()).collect();
+        let (lucky_cap, growing) = if shares.is_empty() {
+            (self.lucky_cap()?, None)
+        } else {
+            let fixed: usize = self
+                .positions
+                .iter()
+                .filter(|p| p.share().is_none())
+                .map(|p| p.cap())
+                .sum();
+            let base = fixed + shares.len();
+            let share = shares.iter().sum();
+            (
+                0,
+                Some(GrowingReservoir::with_rng(
+                    base,
+                    share,
+                    Deterministic::from_seed(seed),
+                )),
+            )
+        };
 
         Ok(Choosen {
             positions: self.positions.clone(),
-            lucky: Reservoir::<P>::with_capacity(lucky_cap),
+            lucky: Reservoir::<P, _>::with_rng(lucky_cap, Deterministic::from_seed(seed)),
+            growing,
+            check: None,
+            seed,
+            stage: Stage::Collecting,
+            revealed: Vec::new(),
+        })
+    }
+
+    /// Builds a [`Choosen`] which fingerprints every polled attendee and
+    /// deals with repeated entries by `policy`.
+    pub fn build_checked<P: Clone + Fingerprint>(
+        &self,
+        policy: Duplicates,
+    ) -> Result<Choosen<P, PT>, BuildChoosenError> {
+        let mut choosen = self.build::<P>()?;
+        choosen.check = Some(DuplicateCheck::new(P::fingerprint, policy));
+        Ok(choosen)
+    }
+
+    /// Builds a [`WeightedChoosen`], where `weight` tells how many tickets an
+    /// attendee holds.
+    pub fn weighted<P: Clone, W: Fn(&P) -> u64>(
+        &self,
+        weight: W,
+    ) -> Result<WeightedChoosen<P, W, PT>, BuildChoosenError> {
+        let lucky_cap = self.lucky_cap()?;
+
+        let seed = self.seed();
+
+        Ok(WeightedChoosen {
+            positions: self.positions.clone(),
+            lucky: TicketReservoir::<P, _>::with_rng(lucky_cap, Deterministic::from_seed(seed)),
+            weight,
+            seed,
         })
     }
 }
 
+/// Caps of at least 1 in proportion to `ratios`, adding up to `total` when
+/// it is at least the number of ratios, by the largest remainders.
+fn balance(total: usize, ratios: &[f64]) -> Result<Vec<usize>, BuildChoosenError> {
+    // 先除以最大的比例，再大的比例相加也不会变成无穷
+    let max = ratios.iter().copied().fold(0.0, f64::max);
+    let sum: f64 = ratios.iter().map(|r| r / max).sum();
+    let quotas: Vec<f64> = ratios
+        .iter()
+        .map(|r| r / max / sum * total as f64)
+        .collect();
+    let mut caps: Vec<usize> = quotas.iter().map(|q| (q.floor() as usize).max(1)).collect();
+    let assigned = caps
+        .iter()
+        .try_fold(0usize, |sum, &cap| sum.checked_add(cap))
+        .ok_or(BuildChoosenError::TooManyWinners)?;
+
+    // 余数大的先加一
+    let mut order: Vec<usize> = (0..caps.len()).collect();
+    order.sort_by(|&a, &b| {
+        let remainder = |i: usize| quotas[i] - caps[i] as f64;
+        remainder(b).total_cmp(&remainder(a))
+    });
+    for &i in order.iter().take(total.saturating_sub(assigned)) {
+        caps[i] += 1;
+    }
+
+    // 保底的 1 名多出来的，从超出配额最多的减回去
+    for _ in total..assigned {
+        let over = (0..caps.len()).filter(|&i| caps[i] > 1).max_by(|&a, &b| {
+            let excess = |i: usize| caps[i] as f64 - quotas[i];
+            excess(a).total_cmp(&excess(b)).then(b.cmp(&a))
+        });
+        match over {
+            Some(i) => caps[i] -= 1,
+            None => break,
+        }
+    }
+    Ok(caps)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -252,6 +509,115 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn same_seed_same_draw() -> Result<(), BuildChoosenError> {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        builder.add_position("pos", 3)?.seed_from([27; 32]);
+
+        let draw = || -> Result<Vec<_>, BuildChoosenError> {
+            let mut choosen = builder.build::<usize>()?;
+            choosen.poll_many(0..100);
+            Ok(choosen.lucky().to_vec())
+        };
+        assert_eq!(draw()?, draw()?);
+        assert_eq!(builder.build::<usize>()?.seed(), [27; 32]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn percent_positions() -> Result<(), BuildChoosenError> {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        builder
+            .add_position("一等奖", 1)?
+            .add_position_percent("参与奖", 0.1)?
+            .seed_from(27);
+        assert_eq!(
+            builder.weighted(|_: &u32| 1).err(),
+            Some(BuildChoosenError::SharedPosition(1))
+        );
+        assert_eq!(
+            builder.add_position_percent("x", 1.5).err(),
+            Some(BuildChoosenError::WrongPositionType(
+                PositionTypeError::InvalidShare
+            ))
+        );
+
+        let draw = || -> Result<_, BuildChoosenError> {
+            let mut choosen = builder.build::<u32>()?;
+            let _ = choosen.poll_many(0..95);
+            assert!(choosen.lucky().is_empty());
+            Ok(choosen.release().unwrap())
+        };
+        let released = draw()?;
+        // 95 人的 10% 向上取整是 10 名
+        assert_eq!(released[0].1.len(), 1);
+        assert_eq!(released[1].1.len(), 10);
+        let mut winners: Vec<_> = released.iter().flat_map(|(_, w)| w).collect();
+        winners.sort_unstable();
+        winners.dedup();
+        assert_eq!(winners.len(), 11);
+        assert_eq!(released, draw()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn auto_balance() -> Result<(), BuildChoosenError> {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        builder
+            .add_position("一等奖", 1)?
+            .add_position("二等奖", 1)?
+            .add_position("三等奖", 1)?
+            .auto_balance(130, &[1.0, 2.0, 10.0])?;
+        let caps: Vec<usize> = builder.positions().iter().map(|p| p.cap()).collect();
+        assert_eq!(caps, vec![10, 20, 100]);
+
+        // 配额 1.43, 2.86, 14.29：余数最大的二等奖多得一名
+        let _ = builder.auto_balance(19, &[1.0, 2.0, 10.0])?;
+        let caps: Vec<usize> = builder.positions().iter().map(|p| p.cap()).collect();
+        assert_eq!(caps, vec![1, 3, 15]);
+
+        // 保底每个奖项一名
+        let _ = builder.auto_balance(5, &[100.0, 1.0, 1.0])?;
+        let caps: Vec<usize> = builder.positions().iter().map(|p| p.cap()).collect();
+        assert_eq!(caps, vec![3, 1, 1]);
+        let _ = builder.auto_balance(2, &[1.0, 1.0, 1.0])?;
+        let caps: Vec<usize> = builder.positions().iter().map(|p| p.cap()).collect();
+        assert_eq!(caps, vec![1, 1, 1]);
+
+        assert_eq!(
+            builder.auto_balance(10, &[1.0, 2.0]).err(),
+            Some(BuildChoosenError::RatioCount(2))
+        );
+        assert_eq!(
+            builder.auto_balance(10, &[1.0, 0.0, f64::NAN]).err(),
+            Some(BuildChoosenError::InvalidRatio(1))
+        );
+
+        // 比例之和超出 f64，名额之和超出 usize
+        let _ = builder.auto_balance(5, &[f64::MAX, f64::MAX, f64::MAX / 2.0])?;
+        let caps: Vec<usize> = builder.positions().iter().map(|p| p.cap()).collect();
+        assert_eq!(caps, vec![2, 2, 1]);
+        assert_eq!(
+            builder.auto_balance(usize::MAX, &[1.0, 1.0, 2.0]).err(),
+            Some(BuildChoosenError::TooManyWinners)
+        );
+        let _ = builder
+            .set_position_cap(usize::MAX, 0)?
+            .set_position_cap(1, 1)?;
+        assert_eq!(
+            builder.build::<usize>().err(),
+            Some(BuildChoosenError::TooManyWinners)
+        );
+        let _ = builder.add_position_percent("参与奖", 0.1)?;
+        assert_eq!(
+            builder.auto_balance(10, &[1.0; 4]).err(),
+            Some(BuildChoosenError::SharedPosition(3))
+        );
+        Ok(())
+    }
+
     #[test]
     fn build() -> Result<(), BuildChoosenError> {
         let mut builder = ChoosenBuilder::<Position>::new();
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use reservoir_sampler::CancelToken;
+use tokio::time::{timeout_at, Instant};
+use tokio_stream::{Stream, StreamExt};
+
+use crate::pos::PositionType;
+use crate::{Choosen, ChoosenError, Released};
+
+impl<P, PT> Choosen<P, PT>
+where
+    PT: PositionType,
+{
+    /// Polls the entries of `entries` until `deadline` or until the stream
+    /// ends, whichever comes first, then releases. An entry arriving after the
+    /// deadline is not polled.
+    pub async fn run_until<S>(
+        mut self,
+        deadline: Instant,
+        entries: S,
+    ) -> Result<Released<P>, ChoosenError>
+    where
+        S: Stream<Item = P>,
+    {
+        tokio::pin!(entries);
+
+        // 截止时间一到就停止接收
+        while let Ok(Some(it)) = timeout_at(deadline, entries.next()).await {
+            self.poll_one(it);
+        }
+
+        self.release()
+    }
+
+    /// The same as `run_until`, also ending once `token` is cancelled, like
+    /// by an operator aborting the entries, then releasing the entries
+    /// polled so far. Waiting for an entry, it looks at the token every
+    /// `check`.
+    pub async fn run_until_cancelled<S>(
+        mut self,
+        deadline: Instant,
+        entries: S,
+        token: &CancelToken,
+        check: Duration,
+    ) -> Result<Released<P>, ChoosenError>
+    where
+        S: Stream<Item = P>,
+    {
+        tokio::pin!(entries);
+
+        // 令牌无法唤醒等待，只能每隔 check 醒来看一眼
+        while !token.is_cancelled() && Instant::now() < deadline {
+            match timeout_at(deadline.min(Instant::now() + check), entries.next()).await {
+                Ok(Some(it)) => {
+                    self.poll_one(it);
+                }
+                Ok(None) => break,
+                Err(_) => {}
+            }
+        }
+
+        self.release()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{BuildChoosenError, ChoosenBuilder, ChoosenError, Position};
+    use reservoir_sampler::CancelToken;
+    use tokio::time::Instant;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn ends_with_the_stream() -> Result<(), BuildChoosenError> {
+        let choosen = ChoosenBuilder::<Position>::new()
+            .add_position("一等奖", 2)?
+            .build::<u32>()?;
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let released = choosen
+            .run_until(deadline, tokio_stream::iter(0..10))
+            .await
+            .unwrap();
+        assert_eq!(released[0].1.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ends_at_the_deadline() -> Result<(), BuildChoosenError> {
+        let choosen = ChoosenBuilder::<Position>::new()
+            .add_position("一等奖", 2)?
+            .build::<u32>()?;
+
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let result = choosen.run_until(deadline, tokio_stream::pending()).await;
+        assert_eq!(result.err(), Some(ChoosenError::NoOneIsChoosen));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ends_when_cancelled() -> Result<(), BuildChoosenError> {
+        let choosen = ChoosenBuilder::<Position>::new()
+            .add_position("一等奖", 2)?
+            .build::<u32>()?;
+
+        let token = CancelToken::new();
+        let operator = token.clone();
+        // 报名五个之后，运营方中止
+        let entries = tokio_stream::iter(0..5).chain(tokio_stream::pending());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            operator.cancel();
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let released = choosen
+            .run_until_cancelled(deadline, entries, &token, Duration::from_millis(5))
+            .await
+            .unwrap();
+        assert_eq!(released[0].1.len(), 2);
+        assert!(released[0].1.iter().all(|&it| it < 5));
+        Ok(())
+    }
+}
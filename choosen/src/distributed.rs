@@ -0,0 +1,303 @@
+//! One draw shared by many entry-collecting servers through Redis, with a
+//! single coordinator releasing it. Enabled by the `redis` feature.
+//!
+//! Every poll runs Algorithm R inside a Lua script, so it is atomic however
+//! many servers poll at once, and the pool never holds more than the sum of
+//! the caps. Redis keeps these keys, tagged by the draw's name so they stay on
+//! one cluster node:
+//! - `choosen:{name}:config`: a hash of `seed` in hex, `cap`, `total`, and
+//!   `released` once released
+//! - `choosen:{name}:positions`: a list of `cap<TAB>name`, in order
+//! - `choosen:{name}:pool`: a hash from slot (counting from 1) to entry
+//!
+//! The `n`-th poll draws from the SHA-1 of `seed:n` instead of
+//! [`Deterministic`], which makes every draw reproducible from the seed and
+//! the order Redis ran the polls in.
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use rand::seq::SliceRandom;
+use redis::{Commands, ConnectionLike, RedisError, Script};
+use reservoir_sampler::Deterministic;
+
+use crate::{
+    hand_out, BuildChoosenError, ChoosenBuilder, ChoosenError, Position, PositionType, Released,
+};
+use crate::{parse_seed_hex, seed_hex};
+
+const CREATE: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    return redis.error_reply('EXISTS the draw exists already')
+end
+redis.call('HSET', KEYS[1], 'seed', ARGV[1], 'cap', ARGV[2], 'total', 0)
+for i = 3, #ARGV do
+    redis.call('RPUSH', KEYS[2], ARGV[i])
+end
+return redis.status_reply('OK')
+";
+
+const POLL: &str = r"
+if redis.call('HEXISTS', KEYS[1], 'released') == 1 then
+    return redis.error_reply('RELEASED the draw has been released')
+end
+local cap = tonumber(redis.call('HGET', KEYS[1], 'cap'))
+if not cap then
+    return redis.error_reply('NODRAW no such draw')
+end
+local seed = redis.call('HGET', KEYS[1], 'seed')
+local total = redis.call('HINCRBY', KEYS[1], 'total', 1)
+local digest = redis.sha1hex(seed .. ':' .. total)
+local r = tonumber(string.sub(digest, 1, 12), 16) % total + 1
+local replaced = false
+if r <= cap then
+    replaced = redis.call('HGET', KEYS[2], r)
+    redis.call('HSET', KEYS[2], r, ARGV[1])
+    -- 池子未满时，被替换的挪到第 total 个槽位
+    if replaced and total <= cap and r < total then
+        redis.call('HSET', KEYS[2], total, replaced)
+        replaced = false
+    end
+end
+return {r, total, replaced}
+";
+
+const RELEASE: &str = r"
+if redis.call('HEXISTS', KEYS[1], 'cap') == 0 then
+    return redis.error_reply('NODRAW no such draw')
+end
+if redis.call('HSETNX', KEYS[1], 'released', 1) == 0 then
+    return redis.error_reply('RELEASED the draw has been released')
+end
+return redis.call('HGETALL', KEYS[2])
+";
+
+#[derive(Debug)]
+pub enum DistributedError {
+    Redis(RedisError),
+    Build(BuildChoosenError),
+    Choosen(ChoosenError),
+    /// Creating a draw under a name already taken.
+    Exists,
+    /// Joining or releasing a draw nobody created.
+    NoDraw,
+    /// Polling or releasing a draw after it was released.
+    Released,
+    /// Redis holds something this crate never writes, like an entry which
+    /// does not parse back.
+    Corrupt,
+}
+
+impl From<RedisError> for DistributedError {
+    fn from(e: RedisError) -> Self {
+        match e.code() {
+            Some("EXISTS") => DistributedError::Exists,
+            Some("NODRAW") => DistributedError::NoDraw,
+            Some("RELEASED") => DistributedError::Released,
+            _ => DistributedError::Redis(e),
+        }
+    }
+}
+
+/// A handle on a draw kept in Redis. The coordinator `create`s the draw, every
+/// server `join`s it and polls, and the coordinator `release`s it once.
+/// Entries are stored as their `Display` text and read back by `FromStr`.
+pub struct DistributedChoosen<P, C> {
+    conn: C,
+    keys: [String; 3],
+    positions: Vec<Position>,
+    seed: [u8; 32],
+    _entries: PhantomData<P>,
+}
+
+fn keys(name: &str) -> [String; 3] {
+    ["config", "positions", "pool"].map(|key| format!("choosen:{{{}}}:{}", name, key))
+}
+
+impl<P: Display + FromStr, C: ConnectionLike> DistributedChoosen<P, C> {
+    /// Creates the draw `name`, of the positions and seed of `builder`.
+    pub fn create(
+        mut conn: C,
+        name: &str,
+        builder: &ChoosenBuilder<Position>,
+    ) -> Result<Self, DistributedError> {
+        let cap = builder.lucky_cap().map_err(DistributedError::Build)?;
+        let positions = builder.positions().to_vec();
+        let seed = builder.seed();
+        let keys = keys(name);
+
+        let script = Script::new(CREATE);
+        let mut create = script.key(&keys[0]);
+        let _ = create.key(&keys[1]).arg(seed_hex(&seed)).arg(cap);
+        for p in &positions {
+            let _ = create.arg(format!("{}\t{}", p.cap(), p.name()));
+        }
+        create.invoke::<()>(&mut conn)?;
+
+        Ok(Self {
+            conn,
+            keys,
+            positions,
+            seed,
+            _entries: PhantomData,
+        })
+    }
+
+    /// Joins the draw `name` someone created.
+    pub fn join(mut conn: C, name: &str) -> Result<Self, DistributedError> {
+        let keys = keys(name);
+        let seed: Option<String> = conn.hget(&keys[0], "seed")?;
+        let seed = seed.ok_or(DistributedError::NoDraw)?;
+        let seed = parse_seed_hex(&seed).ok_or(DistributedError::Corrupt)?;
+
+        let lines: Vec<String> = conn.lrange(&keys[1], 0, -1)?;
+        let mut builder = ChoosenBuilder::<Position>::new();
+        for line in lines {
+            let (cap, name) = line.split_once('\t').ok_or(DistributedError::Corrupt)?;
+            let cap = cap.parse().map_err(|_| DistributedError::Corrupt)?;
+            let _ = builder
+                .add_position(name, cap)
+                .map_err(DistributedError::Build)?;
+        }
+
+        Ok(Self {
+            conn,
+            keys,
+            positions: builder.positions().to_vec(),
+            seed,
+            _entries: PhantomData,
+        })
+    }
+
+    pub fn seed(&self) -> [u8; 32] {
+        self.seed
+    }
+
+    /// Polls an entry into the shared pool, returning the same as
+    /// [`Choosen::poll_one`](crate::Choosen::poll_one), with `total` counting
+    /// the polls of every server.
+    pub fn poll_one(&mut self, it: P) -> Result<(u64, u64, Option<P>), DistributedError> {
+        let (r, total, replaced): (u64, u64, Option<String>) = Script::new(POLL)
+            .key(&self.keys[0])
+            .key(&self.keys[2])
+            .arg(it.to_string())
+            .invoke(&mut self.conn)?;
+
+        let replaced = replaced
+            .map(|it| it.parse().map_err(|_| DistributedError::Corrupt))
+            .transpose()?;
+        Ok((r, total, replaced))
+    }
+
+    /// Closes the draw to further polls and hands out the lucky ones, in a
+    /// shuffle drawn from the seed. Only the first release succeeds.
+    pub fn release(mut self) -> Result<Released<P>, DistributedError> {
+        let held: Vec<(usize, String)> = Script::new(RELEASE)
+            .key(&self.keys[0])
+            .key(&self.keys[2])
+            .invoke(&mut self.conn)?;
+
+        let cap = self.positions.iter().map(|p| p.cap()).sum();
+        let slots = slots(cap, held)?;
+        shuffled_hand_out(self.positions, slots, self.seed).map_err(DistributedError::Choosen)
+    }
+}
+
+/// Lays the held entries out in their slots.
+fn slots<P: FromStr>(
+    cap: usize,
+    held: Vec<(usize, String)>,
+) -> Result<Vec<Option<P>>, DistributedError> {
+    let mut slots: Vec<Option<P>> = std::iter::repeat_with(|| None).take(cap).collect();
+    for (slot, it) in held {
+        let it = it.parse().map_err(|_| DistributedError::Corrupt)?;
+        *slot
+            .checked_sub(1)
+            .and_then(|i| slots.get_mut(i))
+            .ok_or(DistributedError::Corrupt)? = Some(it);
+    }
+    Ok(slots)
+}
+
+fn shuffled_hand_out<P>(
+    positions: Vec<Position>,
+    mut slots: Vec<Option<P>>,
+    seed: [u8; 32],
+) -> Result<Released<P>, ChoosenError> {
+    slots.shuffle(&mut Deterministic::from_seed(seed));
+    hand_out(positions, slots)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SeedSource;
+
+    fn builder() -> ChoosenBuilder<Position> {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        let _ = builder
+            .add_position("一等奖", 1)
+            .unwrap()
+            .add_position("二等奖", 2)
+            .unwrap()
+            .seed_from(27);
+        builder
+    }
+
+    #[test]
+    fn keys_share_a_hash_tag() {
+        assert_eq!(
+            keys("年会"),
+            [
+                "choosen:{年会}:config",
+                "choosen:{年会}:positions",
+                "choosen:{年会}:pool"
+            ]
+        );
+    }
+
+    #[test]
+    fn release_lays_out_slots() {
+        let held = vec![(3, "c".to_string()), (1, "a".to_string())];
+        let laid = slots::<String>(3, held).unwrap();
+        assert_eq!(laid, [Some("a".to_string()), None, Some("c".to_string())]);
+        assert!(slots::<String>(3, vec![(4, "d".into())]).is_err());
+        assert!(slots::<u32>(3, vec![(1, "a".into())]).is_err());
+
+        let positions = builder().positions().to_vec();
+        let seed = 27.seed();
+        let released = shuffled_hand_out(positions.clone(), laid.clone(), seed).unwrap();
+        assert_eq!(released, shuffled_hand_out(positions, laid, seed).unwrap());
+        let winners: usize = released.iter().map(|(_, w)| w.len()).sum();
+        assert_eq!(winners, 2);
+    }
+
+    /// Needs a Redis server at `REDIS_URL`.
+    #[test]
+    #[ignore]
+    fn servers_share_a_draw() {
+        let url = std::env::var("REDIS_URL").unwrap();
+        let client = redis::Client::open(url).unwrap();
+        let name = format!("test-{}", std::process::id());
+
+        let coordinator = DistributedChoosen::<u32, _>::create(
+            client.get_connection().unwrap(),
+            &name,
+            &builder(),
+        )
+        .unwrap();
+        let mut a =
+            DistributedChoosen::<u32, _>::join(client.get_connection().unwrap(), &name).unwrap();
+        let mut b =
+            DistributedChoosen::<u32, _>::join(client.get_connection().unwrap(), &name).unwrap();
+        assert_eq!(a.seed(), 27.seed());
+        for it in 0..50 {
+            let _ = a.poll_one(it).unwrap();
+            let _ = b.poll_one(it + 50).unwrap();
+        }
+
+        let released = coordinator.release().unwrap();
+        assert_eq!(released.len(), 2);
+        assert!(matches!(a.poll_one(100), Err(DistributedError::Released)));
+    }
+}
@@ -0,0 +1,441 @@
+//! Telling other systems, like the big screen of an event, how a draw goes as
+//! it goes, instead of them polling for it. Enabled by the `http` feature.
+//!
+//! An [`EventedChoosen`] hands an [`Event`] to its [`EventSink`] on every
+//! [`Milestones`] it is told to, and a [`Webhook`] POSTs every event as JSON,
+//! like:
+//! - `{"event":"first_entry","entrants":1}`
+//! - `{"event":"entries","entrants":1000}`
+//! - `{"event":"locked","entrants":1243,"winners":17}`
+//! - `{"event":"position_released","position":0,"name":"一等奖","winners":["alice"]}`
+use std::fmt::{Display, Write};
+use std::time::Duration;
+
+use crate::{Choosen, ChoosenError, Progress};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event {
+    /// The first entry is polled.
+    FirstEntry { entrants: u64 },
+    /// Another `every` entries are polled, see [`Milestones::every`].
+    Entries { entrants: u64 },
+    /// The entries are closed.
+    Locked { entrants: u64, winners: usize },
+    /// The winners of a position are released, as their `Display` text.
+    PositionReleased {
+        position: usize,
+        name: String,
+        winners: Vec<String>,
+    },
+}
+
+impl Event {
+    pub fn to_json(&self) -> String {
+        match self {
+            Event::FirstEntry { entrants } => {
+                format!(r#"{{"event":"first_entry","entrants":{}}}"#, entrants)
+            }
+            Event::Entries { entrants } => {
+                format!(r#"{{"event":"entries","entrants":{}}}"#, entrants)
+            }
+            Event::Locked { entrants, winners } => format!(
+                r#"{{"event":"locked","entrants":{},"winners":{}}}"#,
+                entrants, winners
+            ),
+            Event::PositionReleased {
+                position,
+                name,
+                winners,
+            } => {
+                let winners: Vec<String> = winners.iter().map(|w| json_string(w)).collect();
+                format!(
+                    r#"{{"event":"position_released","position":{},"name":{},"winners":[{}]}}"#,
+                    position,
+                    json_string(name),
+                    winners.join(",")
+                )
+            }
+        }
+    }
+}
+
+/// `s` as a JSON string, quoted and escaped.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Where the events of an [`EventedChoosen`] go.
+pub trait EventSink {
+    type Error;
+
+    fn emit(&mut self, event: &Event) -> Result<(), Self::Error>;
+}
+
+/// Which events an [`EventedChoosen`] emits, all but `every` by default.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Milestones {
+    pub first_entry: bool,
+    /// Emits [`Event::Entries`] on every this many entries.
+    pub every: Option<u64>,
+    pub lock: bool,
+    pub release: bool,
+}
+
+impl Default for Milestones {
+    fn default() -> Self {
+        Self {
+            first_entry: true,
+            every: None,
+            lock: true,
+            release: true,
+        }
+    }
+}
+
+/// POSTs every event as JSON to a URL.
+pub struct Webhook {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl Webhook {
+    /// A webhook giving up on a request after 10 seconds.
+    pub fn new(url: &str) -> Self {
+        Self::with_timeout(url, Duration::from_secs(10))
+    }
+
+    pub fn with_timeout(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.into(),
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl EventSink for Webhook {
+    type Error = Box<ureq::Error>;
+
+    /// Fails on an error status, as well as when the request does.
+    fn emit(&mut self, event: &Event) -> Result<(), Self::Error> {
+        let _ = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&event.to_json())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EventError<E> {
+    Choosen(ChoosenError),
+    /// The step took effect, but its event did not go through.
+    Sink(E),
+}
+
+/// What [`Choosen::poll_one`] returns.
+type Polled<P> = (u64, u64, Option<P>);
+
+/// A poll which took effect, but some of whose events did not go through.
+#[derive(Debug, PartialEq)]
+pub struct PollEventError<P, E> {
+    /// What the poll returned.
+    pub polled: Polled<P>,
+    /// The errors of the events which did not go through, in order.
+    pub errors: Vec<E>,
+}
+
+/// A [`Choosen`] emitting an [`Event`] to its sink on every milestone.
+///
+/// An event which does not go through is reported, but the poll or release it
+/// is emitted for has taken effect all the same.
+pub struct EventedChoosen<P, S> {
+    choosen: Choosen<P>,
+    sink: S,
+    milestones: Milestones,
+}
+
+impl<P: Clone + Display, S: EventSink> EventedChoosen<P, S> {
+    pub fn new(choosen: Choosen<P>, sink: S, milestones: Milestones) -> Self {
+        Self {
+            choosen,
+            sink,
+            milestones,
+        }
+    }
+
+    pub fn choosen(&self) -> &Choosen<P> {
+        &self.choosen
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.choosen.progress()
+    }
+
+    /// Polls the entry, then emits the events it makes. A poll after the
+    /// entries are closed is dropped and emits nothing.
+    ///
+    /// Every event is emitted, even after an earlier one fails; the errors
+    /// come back with what the poll returned.
+    pub fn poll_one(&mut self, it: P) -> Result<Polled<P>, PollEventError<P, S::Error>> {
+        let before = self.choosen.entrant_count();
+        let polled = self.choosen.poll_one(it);
+        let entrants = self.choosen.entrant_count();
+        if entrants == before {
+            return Ok(polled);
+        }
+
+        let mut events = Vec::new();
+        if entrants == 1 && self.milestones.first_entry {
+            events.push(Event::FirstEntry { entrants });
+        }
+        if let Some(every) = self.milestones.every {
+            if every > 0 && entrants.is_multiple_of(every) {
+                events.push(Event::Entries { entrants });
+            }
+        }
+        let errors: Vec<S::Error> = events
+            .iter()
+            .filter_map(|event| self.sink.emit(event).err())
+            .collect();
+
+        if errors.is_empty() {
+            Ok(polled)
+        } else {
+            Err(PollEventError { polled, errors })
+        }
+    }
+
+    pub fn lock(&mut self) -> Result<(), EventError<S::Error>> {
+        self.choosen.lock().map_err(EventError::Choosen)?;
+        if self.milestones.lock {
+            let progress = self.choosen.progress();
+            let event = Event::Locked {
+                entrants: progress.entrants,
+                winners: progress.winners,
+            };
+            self.sink.emit(&event).map_err(EventError::Sink)?;
+        }
+        Ok(())
+    }
+
+    /// See [`Choosen::release_position`].
+    pub fn release_position(
+        &mut self,
+        idx: usize,
+    ) -> Result<(String, Vec<P>), EventError<S::Error>> {
+        let (name, winners) = self
+            .choosen
+            .release_position(idx)
+            .map_err(EventError::Choosen)?;
+        if self.milestones.release {
+            let event = Event::PositionReleased {
+                position: idx,
+                name: name.clone(),
+                winners: winners.iter().map(ToString::to_string).collect(),
+            };
+            self.sink.emit(&event).map_err(EventError::Sink)?;
+        }
+        Ok((name, winners))
+    }
+
+    pub fn into_inner(self) -> (Choosen<P>, S) {
+        (self.choosen, self.sink)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChoosenBuilder, Position, Stage};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    #[derive(Default)]
+    struct Recorder(Vec<Event>);
+
+    impl EventSink for Recorder {
+        type Error = ();
+
+        fn emit(&mut self, event: &Event) -> Result<(), ()> {
+            self.0.push(event.clone());
+            Ok(())
+        }
+    }
+
+    fn choosen() -> Choosen<String> {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        builder
+            .add_position("一等奖", 1)
+            .unwrap()
+            .add_position("二等奖", 2)
+            .unwrap()
+            .seed_from(27)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn emits_on_milestones() {
+        let milestones = Milestones {
+            every: Some(10),
+            ..Milestones::default()
+        };
+        let mut draw = EventedChoosen::new(choosen(), Recorder::default(), milestones);
+        for it in 0..25 {
+            let _ = draw.poll_one(it.to_string()).unwrap();
+        }
+        draw.lock().unwrap();
+        // 截止后的报名不算
+        let _ = draw.poll_one("late".into()).unwrap();
+        let (name, winners) = draw.release_position(1).unwrap();
+        assert_eq!(
+            draw.lock(),
+            Err(EventError::Choosen(ChoosenError::WrongStage(Stage::Locked)))
+        );
+
+        let (_, Recorder(events)) = draw.into_inner();
+        assert_eq!(
+            events,
+            vec![
+                Event::FirstEntry { entrants: 1 },
+                Event::Entries { entrants: 10 },
+                Event::Entries { entrants: 20 },
+                Event::Locked {
+                    entrants: 25,
+                    winners: 3
+                },
+                Event::PositionReleased {
+                    position: 1,
+                    name,
+                    winners,
+                },
+            ]
+        );
+    }
+
+    /// Fails on the first entry only.
+    #[derive(Default)]
+    struct Flaky(Vec<Event>);
+
+    impl EventSink for Flaky {
+        type Error = Event;
+
+        fn emit(&mut self, event: &Event) -> Result<(), Event> {
+            if let Event::FirstEntry { .. } = event {
+                return Err(event.clone());
+            }
+            self.0.push(event.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_events_keep_the_poll() {
+        let milestones = Milestones {
+            every: Some(1),
+            ..Milestones::default()
+        };
+        let mut draw = EventedChoosen::new(choosen(), Flaky::default(), milestones);
+        assert_eq!(
+            draw.poll_one("alice".into()),
+            Err(PollEventError {
+                polled: (1, 1, None),
+                errors: vec![Event::FirstEntry { entrants: 1 }],
+            })
+        );
+        assert_eq!(draw.poll_one("bob".into()), Ok((2, 2, None)));
+
+        // 首位报名的事件失败了，后面的事件照发
+        let (choosen, Flaky(events)) = draw.into_inner();
+        assert_eq!(choosen.entrant_count(), 2);
+        assert_eq!(
+            events,
+            vec![
+                Event::Entries { entrants: 1 },
+                Event::Entries { entrants: 2 }
+            ]
+        );
+    }
+
+    #[test]
+    fn events_as_json() {
+        let event = Event::PositionReleased {
+            position: 0,
+            name: "一等奖".into(),
+            winners: vec!["alice".into(), "\"bob\"\n".into()],
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"event":"position_released","position":0,"name":"一等奖","winners":["alice","\"bob\"\n"]}"#
+        );
+        assert_eq!(
+            Event::Locked {
+                entrants: 1_243,
+                winners: 17
+            }
+            .to_json(),
+            r#"{"event":"locked","entrants":1243,"winners":17}"#
+        );
+    }
+
+    #[test]
+    fn webhook_posts_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/draw", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                let _ = reader.read_line(&mut line).unwrap();
+                if let Some(n) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    len = n.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        let mut webhook = Webhook::new(&url);
+        webhook.emit(&Event::FirstEntry { entrants: 1 }).unwrap();
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /draw HTTP/1.1"));
+        assert_eq!(body, r#"{"event":"first_entry","entrants":1}"#);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crate::pos::PositionType;
+use crate::{Choosen, ChoosenError, Released};
+
+/// Attendees who enter more than once are told apart by a fingerprint, like
+/// a hash of their [`normalize`]d name, email or employee id.
+pub trait Fingerprint {
+    fn fingerprint(&self) -> u64;
+}
+
+/// Lower cases `s` and drops all whitespace, so `" Alice@Example.com"` and
+/// `"alice@example.com "` share a fingerprint.
+pub fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// What to do with an entry whose fingerprint was seen before.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Duplicates {
+    /// Poll it anyway, only counting it for the report.
+    Flag,
+    /// Drop it, so every fingerprint is polled only once.
+    Merge,
+}
+
+/// A choosen attendee whose fingerprint was entered more than once.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Collision {
+    pub position: String,
+    pub fingerprint: u64,
+    pub entries: usize,
+}
+
+pub(crate) struct DuplicateCheck<P> {
+    pub(crate) fingerprint: fn(&P) -> u64,
+    pub(crate) policy: Duplicates,
+    pub(crate) seen: HashMap<u64, usize>,
+}
+
+impl<P> DuplicateCheck<P> {
+    pub(crate) fn new(fingerprint: fn(&P) -> u64, policy: Duplicates) -> Self {
+        Self {
+            fingerprint,
+            policy,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Counts the entry, returning whether it should be polled.
+    pub(crate) fn admit(&mut self, it: &P) -> bool {
+        let entries = self.seen.entry((self.fingerprint)(it)).or_insert(0);
+        *entries += 1;
+        *entries == 1 || self.policy == Duplicates::Flag
+    }
+}
+
+impl<P, PT> Choosen<P, PT>
+where
+    PT: PositionType,
+{
+    /// Fingerprints entered more than once so far, with how many times.
+    pub fn duplicates(&self) -> Vec<(u64, usize)> {
+        let mut duplicates: Vec<_> = self
+            .check
+            .iter()
+            .flat_map(|check| check.seen.iter())
+            .filter(|(_, &entries)| entries > 1)
+            .map(|(&fingerprint, &entries)| (fingerprint, entries))
+            .collect();
+        duplicates.sort_unstable();
+        duplicates
+    }
+
+    /// The same as `release`, also reporting the choosen attendees whose
+    /// fingerprint was entered more than once. Without a duplicate check, see
+    /// [`ChoosenBuilder::build_checked`], the report is always empty.
+    ///
+    /// [`ChoosenBuilder::build_checked`]: crate::ChoosenBuilder::build_checked
+    pub fn release_with_report(mut self) -> Result<(Released<P>, Vec<Collision>), ChoosenError> {
+        let check = self.check.take();
+        let released = self.release()?;
+
+        let mut collisions = Vec::new();
+        if let Some(check) = check {
+            for (position, luck) in &released {
+                for it in luck {
+                    let fingerprint = (check.fingerprint)(it);
+                    match check.seen.get(&fingerprint) {
+                        Some(&entries) if entries > 1 => collisions.push(Collision {
+                            position: position.clone(),
+                            fingerprint,
+                            entries,
+                        }),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Ok((released, collisions))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BuildChoosenError, ChoosenBuilder, Position};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    #[derive(Clone)]
+    struct Entry(&'static str);
+
+    impl Fingerprint for Entry {
+        fn fingerprint(&self) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            normalize(self.0).hash(&mut hasher);
+            hasher.finish()
+        }
+    }
+
+    #[test]
+    fn merge_duplicates() -> Result<(), BuildChoosenError> {
+        let mut choosen = ChoosenBuilder::<Position>::new()
+            .add_position("一等奖", 5)?
+            .build_checked::<Entry>(Duplicates::Merge)?;
+
+        let entries = vec![
+            Entry("alice@a.com"),
+            Entry(" Alice@A.com"),
+            Entry("bob@a.com"),
+        ];
+        assert_eq!(choosen.poll_many(entries), 2);
+        assert_eq!(
+            choosen.duplicates(),
+            vec![(Entry("alice@a.com").fingerprint(), 2)]
+        );
+
+        let (released, collisions) = choosen.release_with_report().unwrap();
+        assert_eq!(released[0].1.len(), 2);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].entries, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn flag_duplicates() -> Result<(), BuildChoosenError> {
+        let mut choosen = ChoosenBuilder::<Position>::new()
+            .add_position("一等奖", 5)?
+            .build_checked::<Entry>(Duplicates::Flag)?;
+
+        assert_eq!(
+            choosen.poll_many(vec![Entry("x"), Entry("X"), Entry("y")]),
+            3
+        );
+
+        let (released, collisions) = choosen.release_with_report().unwrap();
+        assert_eq!(released[0].1.len(), 3);
+        assert_eq!(collisions.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! Loading a roster from text, one entry per line, and polling it, with a
+//! report of what became of every line.
+//!
+//! [`load_roster`] parses the lines into [`Row`]s, and
+//! [`Choosen::validate_roster`] tells which of them would be rejected, and
+//! why, before [`Choosen::poll_roster`] polls the accepted ones.
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::pos::PositionType;
+use crate::{Choosen, ChoosenError, Duplicates, Stage};
+
+/// An entry of a roster, by the line it is on, counting from 1.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Row<P> {
+    pub line: usize,
+    /// The entry, or why the line does not parse.
+    pub entry: Result<P, String>,
+}
+
+/// Parses a roster holding one entry per line, skipping blank lines.
+pub fn load_roster<P>(text: &str) -> Vec<Row<P>>
+where
+    P: FromStr,
+    P::Err: Display,
+{
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line, text)| Row {
+            line,
+            entry: text.parse().map_err(|e: P::Err| e.to_string()),
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RowStatus {
+    Accepted,
+    /// Dropped by a duplicate check that merges, see
+    /// [`ChoosenBuilder::build_checked`](crate::ChoosenBuilder::build_checked).
+    /// The line of the roster entering it first, unless it was entered before
+    /// the roster.
+    Duplicate(Option<usize>),
+    /// Refused by the eligibility check.
+    Ineligible,
+    ParseError(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RowReport {
+    pub line: usize,
+    pub status: RowStatus,
+}
+
+/// What became of every row of a roster, in the order of the roster.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ImportReport {
+    pub rows: Vec<RowReport>,
+}
+
+impl ImportReport {
+    pub fn accepted(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|row| row.status == RowStatus::Accepted)
+            .count()
+    }
+
+    pub fn rejected(&self) -> impl Iterator<Item = &RowReport> {
+        self.rows
+            .iter()
+            .filter(|row| row.status != RowStatus::Accepted)
+    }
+
+    /// Whether every row is accepted.
+    pub fn is_clean(&self) -> bool {
+        self.rejected().next().is_none()
+    }
+}
+
+impl<P, PT> Choosen<P, PT>
+where
+    PT: PositionType,
+{
+    /// Reports what `poll_roster` would make of every row, without polling
+    /// any, so the roster can be fixed before the draw.
+    pub fn validate_roster(
+        &self,
+        rows: &[Row<P>],
+        mut eligible: impl FnMut(&P) -> bool,
+    ) -> ImportReport {
+        let mut firsts = HashMap::new();
+        let rows = rows
+            .iter()
+            .map(|row| RowReport {
+                line: row.line,
+                status: self.row_status(row, &mut eligible, &mut firsts),
+            })
+            .collect();
+        ImportReport { rows }
+    }
+
+    /// Polls every row of a roster which parses, passes `eligible` and is
+    /// not a duplicate, like `poll_many`, reporting what became of every row.
+    /// The entries must not be closed.
+    pub fn poll_roster(
+        &mut self,
+        rows: impl IntoIterator<Item = Row<P>>,
+        mut eligible: impl FnMut(&P) -> bool,
+    ) -> Result<ImportReport, ChoosenError> {
+        self.expect(Stage::Collecting)?;
+        let mut firsts = HashMap::new();
+        let mut report = ImportReport::default();
+        for row in rows {
+            let status = self.row_status(&row, &mut eligible, &mut firsts);
+            match (&status, row.entry) {
+                // 重复的也照常投入，由查重计数后丢弃
+                (RowStatus::Accepted, Ok(it)) | (RowStatus::Duplicate(_), Ok(it)) => {
+                    let _ = self.poll_one(it);
+                }
+                _ => {}
+            }
+            report.rows.push(RowReport {
+                line: row.line,
+                status,
+            });
+        }
+        Ok(report)
+    }
+
+    /// `firsts` maps the fingerprints of the roster to the lines they are
+    /// first on.
+    fn row_status(
+        &self,
+        row: &Row<P>,
+        eligible: &mut impl FnMut(&P) -> bool,
+        firsts: &mut HashMap<u64, usize>,
+    ) -> RowStatus {
+        let it = match &row.entry {
+            Ok(it) => it,
+            Err(e) => return RowStatus::ParseError(e.clone()),
+        };
+        if !eligible(it) {
+            return RowStatus::Ineligible;
+        }
+        if let Some(check) = &self.check {
+            let fingerprint = (check.fingerprint)(it);
+            let first = firsts.get(&fingerprint).copied();
+            if first.is_none() && !check.seen.contains_key(&fingerprint) {
+                let _ = firsts.insert(fingerprint, row.line);
+            } else if check.policy == Duplicates::Merge {
+                return RowStatus::Duplicate(first);
+            }
+        }
+        RowStatus::Accepted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChoosenBuilder, Fingerprint, Locale, Position};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Employee(u32);
+
+    impl FromStr for Employee {
+        type Err = std::num::ParseIntError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            s.parse().map(Employee)
+        }
+    }
+
+    impl Fingerprint for Employee {
+        fn fingerprint(&self) -> u64 {
+            u64::from(self.0)
+        }
+    }
+
+    const ROSTER: &str = "1001\n1002\n\nabc\n1001\n9001\n1003\n";
+
+    #[test]
+    fn reports_every_row() {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        let mut choosen = builder
+            .add_position("一等奖", 5)
+            .unwrap()
+            .build_checked::<Employee>(Duplicates::Merge)
+            .unwrap();
+        let rows = load_roster::<Employee>(ROSTER);
+        assert_eq!(rows.len(), 6);
+        // 9 开头的是外包，不参加
+        let eligible = |e: &Employee| e.0 < 9000;
+
+        let checked = choosen.validate_roster(&rows, eligible);
+        assert_eq!(choosen.entrant_count(), 0);
+        let report = choosen.poll_roster(rows, eligible).unwrap();
+        assert_eq!(report, checked);
+
+        let statuses: Vec<_> = report
+            .rows
+            .iter()
+            .map(|row| (row.line, row.status.clone()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (1, RowStatus::Accepted),
+                (2, RowStatus::Accepted),
+                (
+                    4,
+                    RowStatus::ParseError("invalid digit found in string".into())
+                ),
+                (5, RowStatus::Duplicate(Some(1))),
+                (6, RowStatus::Ineligible),
+                (7, RowStatus::Accepted),
+            ]
+        );
+        assert_eq!(report.accepted(), 3);
+        assert_eq!(report.rows[3].to_string(), "line 5: duplicate of line 1");
+        assert_eq!(
+            Locale::Chinese.show(&report.rows[2]).to_string(),
+            "第 4 行：无法解析（invalid digit found in string）"
+        );
+        assert!(!report.is_clean());
+        assert_eq!(choosen.entrant_count(), 3);
+        assert_eq!(choosen.duplicates(), vec![(1001, 2)]);
+
+        // 之前已报名的，找不到首次出现的行
+        let again = choosen.validate_roster(&load_roster("1002"), eligible);
+        assert_eq!(again.rows[0].status, RowStatus::Duplicate(None));
+
+        choosen.lock().unwrap();
+        assert_eq!(
+            choosen.poll_roster(load_roster("1004"), eligible),
+            Err(ChoosenError::WrongStage(Stage::Locked))
+        );
+    }
+
+    #[test]
+    fn flagged_duplicates_are_accepted() {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        let mut choosen = builder
+            .add_position("一等奖", 5)
+            .unwrap()
+            .build_checked::<Employee>(Duplicates::Flag)
+            .unwrap();
+        let report = choosen
+            .poll_roster(load_roster("1001\n1001"), |_| true)
+            .unwrap();
+        assert!(report.is_clean());
+        assert_eq!(choosen.entrant_count(), 2);
+    }
+}
@@ -1,14 +1,78 @@
-use reservoir_sampler::{Reservoir, ReservoirSampler};
+//! `choosen` is not meant to panic on any sequence of builder, polling or
+//! releasing calls: misuse is reported through `BuildChoosenError` and
+//! `ChoosenError`. Clippy only rules out explicit panics, `unwrap` and
+//! `expect`, not indexing or arithmetic overflow; the `fuzz` directory of the
+//! repository drives random call sequences to catch those.
+#![cfg_attr(
+    not(test),
+    deny(clippy::panic, clippy::unwrap_used, clippy::expect_used)
+)]
+use reservoir_sampler::{Deterministic, GrowingReservoir, Inspectable, Reservoir, StreamSampler};
+
+use crate::fingerprint::DuplicateCheck;
 
 mod builder;
+#[cfg(feature = "tokio")]
+mod deadline;
+#[cfg(feature = "redis")]
+mod distributed;
+#[cfg(feature = "http")]
+mod events;
+mod fingerprint;
+mod import;
+mod locale;
+#[cfg(feature = "sqlite")]
+mod persist;
 mod pos;
+mod preset;
+mod progress;
+mod release;
+#[cfg(feature = "report")]
+mod report;
+mod seed;
+mod stage;
+mod team;
+mod weighted;
 
 pub use crate::builder::{BuildChoosenError, ChoosenBuilder};
-pub use crate::pos::{Position, PositionType, PositionTypeError};
+#[cfg(feature = "redis")]
+pub use crate::distributed::{DistributedChoosen, DistributedError};
+#[cfg(feature = "http")]
+pub use crate::events::{
+    Event, EventError, EventSink, EventedChoosen, Milestones, PollEventError, Webhook,
+};
+pub use crate::fingerprint::{normalize, Collision, Duplicates, Fingerprint};
+pub use crate::import::{load_roster, ImportReport, Row, RowReport, RowStatus};
+pub use crate::locale::{Locale, Localized};
+#[cfg(feature = "sqlite")]
+pub use crate::persist::{PersistError, PersistentChoosen};
+pub use crate::pos::{Position, PositionType, PositionTypeError, Weighting};
+pub use crate::preset::Preset;
+pub use crate::progress::Progress;
+pub use crate::release::{ReleaseResult, Substitution};
+#[cfg(feature = "beacon")]
+pub use crate::seed::Beacon;
+pub use crate::seed::{parse_seed_hex, seed_hex, OsEntropy, SeedSource};
+pub use crate::stage::Stage;
+pub use crate::team::Team;
+pub use crate::weighted::{PerPositionChoosen, WeightedChoosen};
+
+/// The lucky ones of every position, in the order of positions.
+pub type Released<P> = Vec<(String, Vec<P>)>;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ChoosenError {
     NoOneIsChoosen,
+    /// The call does not fit the stage the draw is in, see [`Stage`].
+    WrongStage(Stage),
+    /// There is no position of this index.
+    NoSuchPosition(usize),
+    /// The position of this index has been revealed already.
+    AlreadyRevealed(usize),
+    /// The position has no winner of this index.
+    NoSuchWinner(usize),
+    /// The replacement source ran dry.
+    NoReplacement,
 }
 
 pub struct Choosen<P, PT = Position>
@@ -16,45 +80,133 @@ where
     PT: PositionType,
 {
     positions: Vec<PT>,
-    lucky: Reservoir<P>,
+    lucky: Reservoir<P, Deterministic>,
+    // 有按比例的奖项时，截止前在这里抽
+    growing: Option<GrowingReservoir<P, Deterministic>>,
+    check: Option<DuplicateCheck<P>>,
+    seed: [u8; 32],
+    stage: Stage,
+    // 分阶段揭晓时，哪些奖项已经揭晓
+    revealed: Vec<bool>,
 }
 
 impl<P, PT> Choosen<P, PT>
 where
     PT: PositionType,
 {
-    pub fn poll_one(&mut self, it: P) -> (usize, usize, Option<P>) {
-        self.lucky.sample(it)
+    /// With a duplicate check that merges, an entry seen before is dropped,
+    /// drawing 0. So is every entry once the entries are closed, see
+    /// [`Choosen::lock`].
+    pub fn poll_one(&mut self, it: P) -> (u64, u64, Option<P>) {
+        if self.stage != Stage::Collecting {
+            return (0, self.total(), None);
+        }
+        if let Some(check) = &mut self.check {
+            if !check.admit(&it) {
+                return (0, self.total(), None);
+            }
+        }
+        match &mut self.growing {
+            Some(growing) => growing.sample(it),
+            None => self.lucky.sample(it),
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.growing
+            .as_ref()
+            .map_or_else(|| self.lucky.total(), |growing| growing.total())
+    }
+
+    /// Polls every one of `its`, returning how many got into the pool. See
+    /// [`Choosen::poll_roster`] to be told what became of every entry.
+    pub fn poll_many(&mut self, its: impl IntoIterator<Item = P>) -> usize {
+        let mut held = 0;
+        for it in its {
+            let (r, _, _) = self.poll_one(it);
+            let kept = match &self.growing {
+                Some(growing) => growing.is_held(&r),
+                None => self.lucky.is_held(&r),
+            };
+            if kept {
+                held += 1;
+            }
+        }
+        held
     }
 
     pub fn lucky(&self) -> &[Option<P>] {
         self.lucky.samples()
     }
 
-    pub fn release(self) -> Result<Vec<(String, Vec<P>)>, ChoosenError> {
-        let mut final_lucky = self.lucky.lock();
+    pub fn positions(&self) -> &[PT] {
+        &self.positions
+    }
 
-        if !final_lucky.iter().any(|it| it.is_some()) {
-            return Err(ChoosenError::NoOneIsChoosen);
-        }
+    /// The seed the draw started from, see [`ChoosenBuilder::seed_from`].
+    pub fn seed(&self) -> [u8; 32] {
+        self.seed
+    }
 
-        let mut counted = 0;
-        let mut result = Vec::new();
-        for p in self.positions {
-            let mut luck = Vec::with_capacity(p.cap());
+    /// Hands out the lucky ones position by position, closing the entries
+    /// when they are still open. Positions that the pool cannot cover simply
+    /// get fewer (or no) winners, this never panics.
+    pub fn release(mut self) -> Result<Vec<(String, Vec<P>)>, ChoosenError> {
+        if self.stage == Stage::Collecting {
+            self.lock()?;
+        }
+        match self.stage {
+            // 锁定时已经打乱过，保持和已揭晓的奖项一致
+            Stage::Locked => hand_out(self.positions, self.lucky.into_samples()),
+            stage => Err(ChoosenError::WrongStage(stage)),
+        }
+    }
+}
 
-            for i in 0..p.cap() {
-                if let Some(it) = final_lucky[counted + i].take() {
-                    luck.push(it);
-                }
-            }
+#[cfg(feature = "state-hash")]
+impl<P, PT> Choosen<P, PT>
+where
+    P: std::hash::Hash,
+    PT: PositionType,
+{
+    /// A BLAKE3 hash of the positions, the pool and the duplicate check, for
+    /// publishing while the draw goes on. See
+    /// [`Reservoir::state_hash`](reservoir_sampler::Reservoir::state_hash).
+    pub fn state_hash(&self) -> [u8; 32] {
+        use reservoir_sampler::{HashState, StateHasher};
+        use std::hash::Hash;
 
-            result.push((p.name().into(), luck));
-            counted += p.cap();
+        let mut state = StateHasher::new();
+        for p in &self.positions {
+            p.name().hash(&mut state);
+            p.cap().hash(&mut state);
         }
+        self.lucky.hash_state(&mut state);
+        if let Some(growing) = &self.growing {
+            growing.hash_state(&mut state);
+        }
+        self.duplicates().hash(&mut state);
+        state.finalize()
+    }
+}
 
-        Ok(result)
+/// Fills the positions in order from the locked slots.
+fn hand_out<P, PT: PositionType>(
+    positions: Vec<PT>,
+    final_lucky: Vec<Option<P>>,
+) -> Result<Vec<(String, Vec<P>)>, ChoosenError> {
+    if !final_lucky.iter().any(|it| it.is_some()) {
+        return Err(ChoosenError::NoOneIsChoosen);
     }
+
+    let mut slots = final_lucky.into_iter();
+    let mut result = Vec::new();
+    for p in positions {
+        let luck = slots.by_ref().take(p.cap()).flatten().collect();
+        result.push((p.name().into(), luck));
+    }
+
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -78,6 +230,20 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "state-hash")]
+    #[test]
+    fn state_hash_changes_with_polls() -> Result<(), BuildChoosenError> {
+        let mut choosen = ChoosenBuilder::<Position>::new()
+            .add_position("一等奖", 2)?
+            .build::<usize>()?;
+
+        let before = choosen.state_hash();
+        assert_eq!(before, choosen.state_hash());
+        choosen.poll_one(1);
+        assert_ne!(before, choosen.state_hash());
+        Ok(())
+    }
+
     #[test]
     fn have_nothhing_choosen() -> Result<(), BuildChoosenError> {
         let choosen = ChoosenBuilder::<Position>::new()
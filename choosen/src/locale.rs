@@ -0,0 +1,296 @@
+//! The language of every human-readable output: the `Display` of positions,
+//! stages and errors, the transcript lines written by the crate, and the
+//! reports. Prize names and entries are shown as they are, only the words
+//! around them change.
+use std::fmt::{self, Display, Formatter};
+
+use crate::{ChoosenError, Position, PositionType, Progress, RowReport, RowStatus, Stage};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    Chinese,
+}
+
+/// The fixed words of a locale. Most of them only show in reports.
+#[cfg_attr(not(feature = "report"), allow(dead_code))]
+pub(crate) struct Messages {
+    pub report: &'static str,
+    pub generated_at: &'static str,
+    pub winners: &'static str,
+    pub no_winner: &'static str,
+    pub seed: &'static str,
+    pub no_seed: &'static str,
+    pub state_hashes: &'static str,
+    pub taken: &'static str,
+    pub substitutions: &'static str,
+    pub at: &'static str,
+    pub position: &'static str,
+    pub replaced: &'static str,
+    pub replacement: &'static str,
+    pub reason: &'static str,
+    pub transcript: &'static str,
+    pub replaced_by: &'static str,
+}
+
+const ENGLISH: Messages = Messages {
+    report: "Draw report",
+    generated_at: "Generated at {}, in seconds since the Unix epoch.",
+    winners: "Winners",
+    no_winner: "No winner.",
+    seed: "Seed",
+    no_seed: "No seed was published.",
+    state_hashes: "State hashes",
+    taken: "Taken",
+    substitutions: "Substitutions",
+    at: "At",
+    position: "Position",
+    replaced: "Replaced",
+    replacement: "Replacement",
+    reason: "Reason",
+    transcript: "Transcript",
+    replaced_by: "replaced by",
+};
+
+const CHINESE: Messages = Messages {
+    report: "抽奖报告",
+    generated_at: "生成于 Unix 时间 {} 秒。",
+    winners: "中奖名单",
+    no_winner: "无人中奖。",
+    seed: "种子",
+    no_seed: "未公布种子。",
+    state_hashes: "状态哈希",
+    taken: "时机",
+    substitutions: "替补记录",
+    at: "时间",
+    position: "奖项",
+    replaced: "原中奖者",
+    replacement: "替补者",
+    reason: "原因",
+    transcript: "过程记录",
+    replaced_by: "替换为",
+};
+
+impl Locale {
+    pub(crate) fn messages(self) -> &'static Messages {
+        match self {
+            Locale::English => &ENGLISH,
+            Locale::Chinese => &CHINESE,
+        }
+    }
+
+    /// Shows `it` in this locale, like `Locale::Chinese.show(&stage)`.
+    pub fn show<T: ?Sized>(self, it: &T) -> Localized<'_, T> {
+        Localized { it, locale: self }
+    }
+}
+
+/// Something shown in a [`Locale`], see [`Locale::show`].
+pub struct Localized<'a, T: ?Sized> {
+    it: &'a T,
+    locale: Locale,
+}
+
+impl Display for Localized<'_, Stage> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let text = match (self.locale, self.it) {
+            (Locale::English, Stage::Collecting) => "collecting entries",
+            (Locale::English, Stage::Locked) => "locked",
+            (Locale::English, Stage::Released) => "released",
+            (Locale::Chinese, Stage::Collecting) => "报名中",
+            (Locale::Chinese, Stage::Locked) => "已截止",
+            (Locale::Chinese, Stage::Released) => "已揭晓",
+        };
+        f.write_str(text)
+    }
+}
+
+impl Display for Localized<'_, ChoosenError> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let locale = self.locale;
+        match (locale, *self.it) {
+            (Locale::English, ChoosenError::NoOneIsChoosen) => write!(f, "no one is chosen"),
+            (Locale::English, ChoosenError::WrongStage(stage)) => {
+                write!(f, "the draw is {}", locale.show(&stage))
+            }
+            (Locale::English, ChoosenError::NoSuchPosition(idx)) => {
+                write!(f, "there is no position #{}", idx)
+            }
+            (Locale::English, ChoosenError::AlreadyRevealed(idx)) => {
+                write!(f, "position #{} has been revealed already", idx)
+            }
+            (Locale::English, ChoosenError::NoSuchWinner(idx)) => {
+                write!(f, "there is no winner #{}", idx)
+            }
+            (Locale::English, ChoosenError::NoReplacement) => write!(f, "no replacement is left"),
+            (Locale::Chinese, ChoosenError::NoOneIsChoosen) => write!(f, "无人中奖"),
+            (Locale::Chinese, ChoosenError::WrongStage(stage)) => {
+                write!(f, "抽奖{}", locale.show(&stage))
+            }
+            (Locale::Chinese, ChoosenError::NoSuchPosition(idx)) => {
+                write!(f, "没有第 {} 个奖项", idx)
+            }
+            (Locale::Chinese, ChoosenError::AlreadyRevealed(idx)) => {
+                write!(f, "第 {} 个奖项已经揭晓", idx)
+            }
+            (Locale::Chinese, ChoosenError::NoSuchWinner(idx)) => {
+                write!(f, "没有第 {} 名中奖者", idx)
+            }
+            (Locale::Chinese, ChoosenError::NoReplacement) => write!(f, "没有替补了"),
+        }
+    }
+}
+
+impl Display for Localized<'_, Position> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (name, cap) = (self.it.name(), self.it.cap());
+        if let Some(share) = self.it.share() {
+            // 只留四位小数，免得 7.000000000000001%
+            let percent = (share * 1e6).round() / 1e4;
+            return match self.locale {
+                Locale::English => write!(f, "{}: {}% of entrants", name, percent),
+                Locale::Chinese => write!(f, "{}：{}% 的参与者", name, percent),
+            };
+        }
+        match self.locale {
+            Locale::English if cap == 1 => write!(f, "{}: 1 winner", name),
+            Locale::English => write!(f, "{}: {} winners", name, cap),
+            Locale::Chinese => write!(f, "{}：{} 名", name, cap),
+        }
+    }
+}
+
+/// `n` with a comma every three digits, like `1,243`.
+fn grouped(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() * 4 / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+impl Display for Localized<'_, Progress> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Progress {
+            stage,
+            entrants,
+            winners,
+        } = *self.it;
+        let n = grouped(entrants);
+        let entries = if entrants == 1 { "entry" } else { "entries" };
+        let people = if winners == 1 { "winner" } else { "winners" };
+        match (self.locale, stage) {
+            (Locale::English, Stage::Collecting) => write!(
+                f,
+                "{} {} received, drawing {} {}",
+                n, entries, winners, people
+            ),
+            (Locale::English, _) => write!(f, "{} {}, {} {}", n, entries, winners, people),
+            (Locale::Chinese, Stage::Collecting) => {
+                write!(f, "已收到 {} 份报名，将抽出 {} 名", n, winners)
+            }
+            (Locale::Chinese, _) => write!(f, "共 {} 份报名，抽出 {} 名", n, winners),
+        }
+    }
+}
+
+impl Display for Localized<'_, RowReport> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let line = self.it.line;
+        match (self.locale, &self.it.status) {
+            (Locale::English, RowStatus::Accepted) => write!(f, "line {}: accepted", line),
+            (Locale::English, RowStatus::Duplicate(Some(first))) => {
+                write!(f, "line {}: duplicate of line {}", line, first)
+            }
+            (Locale::English, RowStatus::Duplicate(None)) => {
+                write!(f, "line {}: entered before", line)
+            }
+            (Locale::English, RowStatus::Ineligible) => write!(f, "line {}: not eligible", line),
+            (Locale::English, RowStatus::ParseError(e)) => {
+                write!(f, "line {}: does not parse ({})", line, e)
+            }
+            (Locale::Chinese, RowStatus::Accepted) => write!(f, "第 {} 行：已接受", line),
+            (Locale::Chinese, RowStatus::Duplicate(Some(first))) => {
+                write!(f, "第 {} 行：与第 {} 行重复", line, first)
+            }
+            (Locale::Chinese, RowStatus::Duplicate(None)) => {
+                write!(f, "第 {} 行：此前已报名", line)
+            }
+            (Locale::Chinese, RowStatus::Ineligible) => write!(f, "第 {} 行：不符合资格", line),
+            (Locale::Chinese, RowStatus::ParseError(e)) => {
+                write!(f, "第 {} 行：无法解析（{}）", line, e)
+            }
+        }
+    }
+}
+
+// 默认用英文
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Locale::English.show(self).fmt(f)
+    }
+}
+
+impl Display for ChoosenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Locale::English.show(self).fmt(f)
+    }
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Locale::English.show(self).fmt(f)
+    }
+}
+
+impl Display for RowReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Locale::English.show(self).fmt(f)
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Locale::English.show(self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn english_by_default() {
+        assert_eq!(Locale::default(), Locale::English);
+        assert_eq!(
+            ChoosenError::WrongStage(Stage::Locked).to_string(),
+            "the draw is locked"
+        );
+        assert_eq!(
+            Locale::Chinese
+                .show(&ChoosenError::WrongStage(Stage::Locked))
+                .to_string(),
+            "抽奖已截止"
+        );
+    }
+
+    #[test]
+    fn positions_keep_their_names() {
+        let mut position = Position::default();
+        let _ = position.set_name("一等奖").and_then(|p| p.set_cap(3));
+        assert_eq!(position.to_string(), "一等奖: 3 winners");
+        assert_eq!(Locale::Chinese.show(&position).to_string(), "一等奖：3 名");
+
+        let _ = position.set_share(0.07);
+        assert_eq!(position.to_string(), "一等奖: 7% of entrants");
+        assert_eq!(
+            Locale::Chinese.show(&position).to_string(),
+            "一等奖：7% 的参与者"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! A draw kept in a SQLite file, so it survives crashes, and auditors can read
+//! its raw event log with SQL. Enabled by the `sqlite` feature.
+//!
+//! The file holds four tables:
+//! - `draw`: the 32 byte seed, in its only row
+//! - `positions`: `idx`, `name` and `cap` of every position
+//! - `polls`: every polled entry by `seq`, with what it `drawn` out of `total`
+//! - `winners`: the released winners, by position and `rank`
+//!
+//! Since a draw only depends on its seed and its entries, resuming replays the
+//! polls, which leads to the very same pool.
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use rusqlite::{params, Connection};
+
+use crate::{
+    BuildChoosenError, Choosen, ChoosenBuilder, ChoosenError, Position, PositionType, Released,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE draw (seed BLOB NOT NULL);
+    CREATE TABLE positions (
+        idx INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        cap INTEGER NOT NULL
+    );
+    CREATE TABLE polls (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        entry TEXT NOT NULL,
+        drawn INTEGER,
+        total INTEGER
+    );
+    CREATE TABLE winners (
+        position INTEGER NOT NULL,
+        rank INTEGER NOT NULL,
+        entry TEXT NOT NULL,
+        PRIMARY KEY (position, rank)
+    );
+";
+
+#[derive(Debug, PartialEq)]
+pub enum PersistError {
+    Sqlite(rusqlite::Error),
+    Build(BuildChoosenError),
+    Choosen(ChoosenError),
+    /// A logged entry does not parse back, at this `seq`.
+    Entry(i64),
+    /// The draw in the file has been released already.
+    Released,
+}
+
+impl From<rusqlite::Error> for PersistError {
+    fn from(e: rusqlite::Error) -> Self {
+        PersistError::Sqlite(e)
+    }
+}
+
+/// A [`Choosen`] writing every poll into a SQLite file before drawing it.
+/// Entries are stored as their `Display` text and read back by `FromStr`.
+pub struct PersistentChoosen<P> {
+    choosen: Choosen<P>,
+    conn: Connection,
+}
+
+impl<P: Clone + Display + FromStr> PersistentChoosen<P> {
+    /// Starts a new draw of the positions and seed of `builder`, in a new
+    /// SQLite file at `path`. A file already holding a draw is refused.
+    pub fn create(
+        path: impl AsRef<Path>,
+        builder: &ChoosenBuilder<Position>,
+    ) -> Result<Self, PersistError> {
+        // 文件里只记了名额，按比例的奖项续不上
+        if let Some(idx) = builder.positions().iter().position(|p| p.share().is_some()) {
+            return Err(PersistError::Build(BuildChoosenError::SharedPosition(idx)));
+        }
+        let choosen = builder.build::<P>().map_err(PersistError::Build)?;
+        let mut conn = Connection::open(path)?;
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(SCHEMA)?;
+        let _ = tx.execute("INSERT INTO draw (seed) VALUES (?1)", [&choosen.seed()[..]])?;
+        for (idx, p) in choosen.positions.iter().enumerate() {
+            let _ = tx.execute(
+                "INSERT INTO positions (idx, name, cap) VALUES (?1, ?2, ?3)",
+                params![idx as i64, p.name(), p.cap() as i64],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(Self { choosen, conn })
+    }
+
+    /// Picks up the draw in the SQLite file at `path`, replaying its polls.
+    pub fn resume(path: impl AsRef<Path>) -> Result<Self, PersistError> {
+        let conn = Connection::open(path)?;
+        let released: i64 = conn.query_row("SELECT COUNT(*) FROM winners", [], |row| row.get(0))?;
+        if released > 0 {
+            return Err(PersistError::Released);
+        }
+
+        let seed: [u8; 32] = conn.query_row("SELECT seed FROM draw", [], |row| row.get(0))?;
+        let mut builder = ChoosenBuilder::<Position>::new();
+        let _ = builder.seed_from(seed);
+        {
+            let mut positions = conn.prepare("SELECT name, cap FROM positions ORDER BY idx")?;
+            let mut rows = positions.query([])?;
+            while let Some(row) = rows.next()? {
+                let name: String = row.get(0)?;
+                let cap: i64 = row.get(1)?;
+                let _ = builder
+                    .add_position(&name, cap as usize)
+                    .map_err(PersistError::Build)?;
+            }
+        }
+
+        let mut choosen = builder.build::<P>().map_err(PersistError::Build)?;
+        {
+            let mut polls = conn.prepare("SELECT seq, entry FROM polls ORDER BY seq")?;
+            let mut rows = polls.query([])?;
+            while let Some(row) = rows.next()? {
+                let seq: i64 = row.get(0)?;
+                let entry: String = row.get(1)?;
+                let it = entry.parse().map_err(|_| PersistError::Entry(seq))?;
+                let _ = choosen.poll_one(it);
+            }
+        }
+
+        Ok(Self { choosen, conn })
+    }
+
+    /// Logs the entry, polls it, and notes down its draw, all in one
+    /// transaction. When logging fails, the entry is not polled. When noting
+    /// down the draw or committing fails, it is polled but not in the file, so
+    /// the draw should be resumed from the file before going on.
+    pub fn poll_one(&mut self, it: P) -> Result<(u64, u64, Option<P>), PersistError> {
+        let tx = self.conn.transaction()?;
+        let _ = tx.execute("INSERT INTO polls (entry) VALUES (?1)", [it.to_string()])?;
+        let seq = tx.last_insert_rowid();
+
+        let polled = self.choosen.poll_one(it);
+        let _ = tx.execute(
+            "UPDATE polls SET drawn = ?1, total = ?2 WHERE seq = ?3",
+            params![polled.0 as i64, polled.1 as i64, seq],
+        )?;
+        tx.commit()?;
+        Ok(polled)
+    }
+
+    pub fn lucky(&self) -> &[Option<P>] {
+        self.choosen.lucky()
+    }
+
+    pub fn seed(&self) -> [u8; 32] {
+        self.choosen.seed()
+    }
+
+    /// Releases the draw, writing the winners in one transaction.
+    pub fn release(self) -> Result<Released<P>, PersistError> {
+        let Self { choosen, mut conn } = self;
+        let released = choosen.release().map_err(PersistError::Choosen)?;
+
+        let tx = conn.transaction()?;
+        for (position, (_, winners)) in released.iter().enumerate() {
+            for (rank, winner) in winners.iter().enumerate() {
+                let _ = tx.execute(
+                    "INSERT INTO winners (position, rank, entry) VALUES (?1, ?2, ?3)",
+                    params![position as i64, rank as i64, winner.to_string()],
+                )?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(released)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SeedSource;
+
+    fn draw_file(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("choosen-{}-{}.db", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn builder() -> ChoosenBuilder<Position> {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        let _ = builder
+            .add_position("一等奖", 1)
+            .unwrap()
+            .add_position("二等奖", 2)
+            .unwrap()
+            .seed_from(27);
+        builder
+    }
+
+    #[test]
+    fn resume_replays_the_draw() {
+        let path = draw_file("resume");
+        let mut draw = PersistentChoosen::<u32>::create(&path, &builder()).unwrap();
+        for it in 0..50 {
+            let _ = draw.poll_one(it).unwrap();
+        }
+        let lucky = draw.lucky().to_vec();
+        // 模拟崩溃：直接丢掉
+        drop(draw);
+
+        let mut draw = PersistentChoosen::<u32>::resume(&path).unwrap();
+        assert_eq!(draw.lucky(), &lucky[..]);
+        assert_eq!(draw.seed(), 27.seed());
+        let _ = draw.poll_one(50).unwrap();
+
+        let released = draw.release().unwrap();
+        assert_eq!(released.len(), 2);
+        assert_eq!(
+            PersistentChoosen::<u32>::resume(&path).err(),
+            Some(PersistError::Released)
+        );
+
+        let conn = Connection::open(&path).unwrap();
+        let logged: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM polls WHERE drawn IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(logged, 51);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn one_draw_per_file() {
+        let path = draw_file("twice");
+        let _ = PersistentChoosen::<u32>::create(&path, &builder()).unwrap();
+        assert!(matches!(
+            PersistentChoosen::<u32>::create(&path, &builder()),
+            Err(PersistError::Sqlite(_))
+        ));
+        let _ = std::fs::remove_file(&path);
+    }
+}
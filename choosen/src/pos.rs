@@ -1,9 +1,43 @@
 use std::fmt::Debug;
 
+/// Why a position type refused a change.
+///
+/// `FixedWeighting`, `InvalidShare` and `FixedCapacity` are new in 0.2, which
+/// breaks exhaustive matches on this enum written against 0.1.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum PositionTypeError {
     EmptyName,
     ZeroCapacity,
+    /// The position type has no [`Weighting`] but the default.
+    FixedWeighting,
+    /// A share out of `(0, 1]`, or not a number.
+    InvalidShare,
+    FixedCapacity,
+}
+
+/// How a position turns the tickets of an attendee into its own tickets, in
+/// the per position mode of [`ChoosenBuilder::per_position`].
+///
+/// [`ChoosenBuilder::per_position`]: crate::ChoosenBuilder::per_position
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Weighting {
+    /// Every attendee holds one ticket, whatever their weight.
+    Uniform,
+    /// Every attendee holds as many tickets as their weight.
+    #[default]
+    Tickets,
+    /// Every attendee holds their weight times this many tickets.
+    Scaled(u64),
+}
+
+impl Weighting {
+    pub fn apply(self, tickets: u64) -> u64 {
+        match self {
+            Weighting::Uniform => 1,
+            Weighting::Tickets => tickets,
+            Weighting::Scaled(times) => tickets.saturating_mul(times),
+        }
+    }
 }
 
 pub trait PositionType {
@@ -11,12 +45,36 @@ pub trait PositionType {
     fn set_name(&mut self, name: &str) -> Result<&mut Self, PositionTypeError>;
     fn cap(&self) -> usize;
     fn set_cap(&mut self, new_cap: usize) -> Result<&mut Self, PositionTypeError>;
+
+    fn weighting(&self) -> Weighting {
+        Weighting::Tickets
+    }
+
+    /// Positions which cannot change their weighting report
+    /// `FixedWeighting`.
+    fn set_weighting(&mut self, _weighting: Weighting) -> Result<&mut Self, PositionTypeError> {
+        Err(PositionTypeError::FixedWeighting)
+    }
+
+    /// The share of the entrants the position goes to, like `0.01` for 1%,
+    /// which fixes its cap once the entries are closed. `None` for a position
+    /// of a fixed cap.
+    fn share(&self) -> Option<f64> {
+        None
+    }
+
+    /// Positions which cannot grow with the entrants report `FixedCapacity`.
+    fn set_share(&mut self, _share: f64) -> Result<&mut Self, PositionTypeError> {
+        Err(PositionTypeError::FixedCapacity)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Position {
     name: String,
     cap: usize,
+    weighting: Weighting,
+    share: Option<f64>,
 }
 
 impl Default for Position {
@@ -24,6 +82,8 @@ impl Default for Position {
         Self {
             name: String::from("default_name"),
             cap: 1,
+            weighting: Weighting::default(),
+            share: None,
         }
     }
 }
@@ -34,7 +94,7 @@ impl PositionType for Position {
     }
 
     fn set_name(&mut self, name: &str) -> Result<&mut Self, PositionTypeError> {
-        if name.len() == 0 {
+        if name.is_empty() {
             return Err(PositionTypeError::EmptyName);
         }
         self.name = name.into();
@@ -52,6 +112,27 @@ impl PositionType for Position {
         self.cap = new_cap;
         Ok(self)
     }
+
+    fn weighting(&self) -> Weighting {
+        self.weighting
+    }
+
+    fn set_weighting(&mut self, weighting: Weighting) -> Result<&mut Self, PositionTypeError> {
+        self.weighting = weighting;
+        Ok(self)
+    }
+
+    fn share(&self) -> Option<f64> {
+        self.share
+    }
+
+    fn set_share(&mut self, share: f64) -> Result<&mut Self, PositionTypeError> {
+        if !(share > 0.0 && share <= 1.0) {
+            return Err(PositionTypeError::InvalidShare);
+        }
+        self.share = Some(share);
+        Ok(self)
+    }
 }
 
 #[cfg(test)]
@@ -82,6 +163,30 @@ mod test {
         let _ = Position::default().set_cap(15);
     }
 
+    #[test]
+    fn test_weighting() {
+        let mut pos = Position::default();
+        assert_eq!(pos.weighting(), Weighting::Tickets);
+
+        let _ = pos.set_weighting(Weighting::Scaled(3));
+        assert_eq!(pos.weighting().apply(5), 15);
+        assert_eq!(Weighting::Uniform.apply(5), 1);
+    }
+
+    #[test]
+    fn test_share() {
+        let mut pos = Position::default();
+        assert_eq!(pos.share(), None);
+        for share in [0.0, -0.1, 1.5, f64::NAN] {
+            assert_eq!(
+                pos.set_share(share).err(),
+                Some(PositionTypeError::InvalidShare)
+            );
+        }
+        let _ = pos.set_share(0.01);
+        assert_eq!(pos.share(), Some(0.01));
+    }
+
     #[test]
     fn test_set_zero_cap() {
         let mut pos = Position::default();
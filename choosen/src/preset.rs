@@ -0,0 +1,115 @@
+//! Position layouts most draws use, see [`ChoosenBuilder::preset`].
+use crate::pos::PositionType;
+use crate::{BuildChoosenError, ChoosenBuilder};
+
+/// A common layout of positions. The names are placeholders, rename them by
+/// [`ChoosenBuilder::set_position_name`] when needed.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Preset {
+    /// One position, "Winner", of one winner.
+    SingleWinner,
+    /// One position, "Top n", of `n` winners.
+    TopN(usize),
+    /// "First prize", "Second prize" and "Third prize", of these caps.
+    ThreeTier {
+        first: usize,
+        second: usize,
+        third: usize,
+    },
+    /// "Tier 1", "Tier 2" and so on, of these caps, from the top.
+    Tiered(Vec<usize>),
+}
+
+impl Preset {
+    fn positions(&self) -> Vec<(String, usize)> {
+        match self {
+            Preset::SingleWinner => vec![("Winner".into(), 1)],
+            Preset::TopN(n) => vec![(format!("Top {}", n), *n)],
+            Preset::ThreeTier {
+                first,
+                second,
+                third,
+            } => vec![
+                ("First prize".into(), *first),
+                ("Second prize".into(), *second),
+                ("Third prize".into(), *third),
+            ],
+            Preset::Tiered(caps) => (1..)
+                .zip(caps)
+                .map(|(i, &cap)| (format!("Tier {}", i), cap))
+                .collect(),
+        }
+    }
+}
+
+impl<PT> ChoosenBuilder<PT>
+where
+    PT: PositionType + Default + Clone,
+{
+    /// A builder of the positions of `preset`, checked as `add_position`
+    /// does, so a zero cap is refused right away.
+    pub fn preset(preset: Preset) -> Result<Self, BuildChoosenError> {
+        let mut builder = Self::new();
+        for (name, cap) in preset.positions() {
+            let _ = builder.add_position(&name, cap)?;
+        }
+        let _ = builder.lucky_cap()?;
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Position, PositionTypeError};
+
+    fn layout(preset: Preset) -> Result<Vec<(String, usize)>, BuildChoosenError> {
+        let builder = ChoosenBuilder::<Position>::preset(preset)?;
+        Ok(builder
+            .positions()
+            .iter()
+            .map(|p| (p.name().to_string(), p.cap()))
+            .collect())
+    }
+
+    #[test]
+    fn presets_lay_out_positions() -> Result<(), BuildChoosenError> {
+        assert_eq!(layout(Preset::SingleWinner)?, [("Winner".into(), 1)]);
+        assert_eq!(layout(Preset::TopN(10))?, [("Top 10".into(), 10)]);
+        assert_eq!(
+            layout(Preset::ThreeTier {
+                first: 1,
+                second: 3,
+                third: 5
+            })?,
+            [
+                ("First prize".into(), 1),
+                ("Second prize".into(), 3),
+                ("Third prize".into(), 5)
+            ]
+        );
+        assert_eq!(
+            layout(Preset::Tiered(vec![2, 4]))?,
+            [("Tier 1".into(), 2), ("Tier 2".into(), 4)]
+        );
+
+        let mut choosen = ChoosenBuilder::<Position>::preset(Preset::TopN(3))?.build::<u32>()?;
+        choosen.poll_many(0..10);
+        assert_eq!(choosen.release().unwrap()[0].1.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_presets_are_refused() {
+        assert_eq!(
+            layout(Preset::TopN(0)),
+            Err(BuildChoosenError::WrongPositionType(
+                PositionTypeError::ZeroCapacity
+            ))
+        );
+        assert_eq!(
+            layout(Preset::Tiered(Vec::new())),
+            Err(BuildChoosenError::EmptyBuilder)
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! How far a draw has got, for the host to show without counting entries on
+//! its own.
+use crate::pos::PositionType;
+use crate::stage::share_cap;
+use crate::{Choosen, Stage};
+
+/// A snapshot of a draw, shown like "1,243 entries received, drawing 17
+/// winners", see [`Locale::show`](crate::Locale::show) for other languages.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Progress {
+    pub stage: Stage,
+    pub entrants: u64,
+    pub winners: usize,
+}
+
+impl<P, PT> Choosen<P, PT>
+where
+    PT: PositionType,
+{
+    /// How many entries were polled into the draw, the ones dropped as
+    /// duplicates or after the entries closed left out.
+    pub fn entrant_count(&self) -> u64 {
+        self.total()
+    }
+
+    /// How many winners the positions add up to. A position going to a
+    /// share of the entrants counts its share of the entrants so far, until
+    /// the entries close and fix it.
+    pub fn capacity_total(&self) -> usize {
+        let total = self.entrant_count();
+        self.positions
+            .iter()
+            .map(|p| match p.share() {
+                Some(share) if self.stage == Stage::Collecting => share_cap(share, total),
+                _ => p.cap(),
+            })
+            .sum()
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            stage: self.stage,
+            entrants: self.entrant_count(),
+            winners: self.capacity_total(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ChoosenBuilder, Locale, Position, Progress, Stage};
+
+    #[test]
+    fn counts_follow_the_draw() {
+        let mut builder = ChoosenBuilder::<Position>::new();
+        let mut choosen = builder
+            .add_position("一等奖", 2)
+            .unwrap()
+            .add_position_percent("参与奖", 0.01)
+            .unwrap()
+            .build::<u32>()
+            .unwrap();
+        assert_eq!(choosen.capacity_total(), 3);
+
+        choosen.poll_many(0..1_243);
+        assert_eq!(
+            choosen.progress(),
+            Progress {
+                stage: Stage::Collecting,
+                entrants: 1_243,
+                winners: 15,
+            }
+        );
+        assert_eq!(
+            choosen.progress().to_string(),
+            "1,243 entries received, drawing 15 winners"
+        );
+        assert_eq!(
+            Locale::Chinese.show(&choosen.progress()).to_string(),
+            "已收到 1,243 份报名，将抽出 15 名"
+        );
+
+        choosen.lock().unwrap();
+        choosen.poll_one(1_243);
+        assert_eq!(choosen.entrant_count(), 1_243);
+        assert_eq!(choosen.capacity_total(), 15);
+        assert_eq!(choosen.progress().to_string(), "1,243 entries, 15 winners");
+    }
+}
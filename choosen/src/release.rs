@@ -0,0 +1,361 @@
+use std::fmt::Display;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::seq::SliceRandom;
+use reservoir_sampler::Deterministic;
+
+use crate::pos::PositionType;
+use crate::{Choosen, ChoosenError, Locale, Released, SeedSource};
+
+/// Everything a public announcement of the draw needs: the winners of every
+/// position, the seed the draw started from when there was one, and a
+/// transcript of what happened, line by line.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReleaseResult<P> {
+    pub winners: Released<P>,
+    pub seed: Option<[u8; 32]>,
+    pub transcript: Vec<String>,
+    /// State hashes published while the draw went on, each with when it was
+    /// taken, like "entries closed".
+    pub state_hashes: Vec<(String, [u8; 32])>,
+    /// Every winner replaced after the draw, in order.
+    pub substitutions: Vec<Substitution<P>>,
+    /// The language of the transcript lines written here, and of the reports.
+    pub locale: Locale,
+    // 每个奖项尚未揭晓的名次，从后往前揭晓
+    unrevealed: Vec<Option<Vec<usize>>>,
+}
+
+/// A winner replaced after the draw, like one who declined or turned out to
+/// be ineligible.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Substitution<P> {
+    pub position: usize,
+    /// Which winner of the position, counting from 0.
+    pub index: usize,
+    pub replaced: P,
+    pub replacement: P,
+    pub reason: String,
+    pub at: SystemTime,
+}
+
+impl<P> ReleaseResult<P> {
+    pub fn new(winners: Released<P>) -> Self {
+        Self {
+            winners,
+            seed: None,
+            transcript: Vec::new(),
+            state_hashes: Vec::new(),
+            substitutions: Vec::new(),
+            locale: Locale::default(),
+            unrevealed: Vec::new(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: impl SeedSource) -> Self {
+        self.seed = Some(seed.seed());
+        self
+    }
+
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Appends a line to the transcript, like "entries closed at 18:00".
+    pub fn record(mut self, line: &str) -> Self {
+        self.transcript.push(line.into());
+        self
+    }
+
+    /// Notes down a state hash published while the draw went on, see
+    /// `Choosen::state_hash` of the `state-hash` feature.
+    pub fn with_state_hash(mut self, label: &str, hash: [u8; 32]) -> Self {
+        self.state_hashes.push((label.into(), hash));
+        self
+    }
+
+    /// The next winner of the position of index `position`, one at a time in
+    /// a random order, for suspense on stage. The winners themselves are
+    /// fixed, and so are `canonical_bytes`, however far the reveal went.
+    /// Returns `None` once every winner of the position is revealed.
+    ///
+    /// The order is drawn from the seed when there is one, so a rehearsal
+    /// shows the same order.
+    pub fn reveal_next(&mut self, position: usize) -> Option<&P> {
+        let (_, winners) = self.winners.get(position)?;
+        if self.unrevealed.len() < self.winners.len() {
+            self.unrevealed.resize(self.winners.len(), None);
+        }
+
+        let seed = self.seed;
+        let order = self.unrevealed.get_mut(position)?.get_or_insert_with(|| {
+            let mut order: Vec<usize> = (0..winners.len()).collect();
+            let seed = seed.unwrap_or_else(rand::random);
+            order.shuffle(&mut Deterministic::from_seed(seed).with_stream(position as u64));
+            order
+        });
+        winners.get(order.pop()?)
+    }
+}
+
+impl<P: Display> ReleaseResult<P> {
+    /// The bytes a signature covers. Every string, winners written out by
+    /// `Display`, is prefixed by its length, and every number is little
+    /// endian, so no two results share an encoding.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        fn put(bytes: &mut Vec<u8>, s: &str) {
+            bytes.extend_from_slice(&(s.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(s.as_bytes());
+        }
+
+        let mut bytes = b"choosen/release/v2".to_vec();
+        bytes.extend_from_slice(&(self.winners.len() as u64).to_le_bytes());
+        for (position, winners) in &self.winners {
+            put(&mut bytes, position);
+            bytes.extend_from_slice(&(winners.len() as u64).to_le_bytes());
+            for winner in winners {
+                put(&mut bytes, &winner.to_string());
+            }
+        }
+
+        match self.seed {
+            Some(seed) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&seed);
+            }
+            None => bytes.push(0),
+        }
+
+        bytes.extend_from_slice(&(self.transcript.len() as u64).to_le_bytes());
+        for line in &self.transcript {
+            put(&mut bytes, line);
+        }
+        // 没有状态哈希时保持原来的编码
+        if !self.state_hashes.is_empty() {
+            bytes.extend_from_slice(&(self.state_hashes.len() as u64).to_le_bytes());
+            for (label, hash) in &self.state_hashes {
+                put(&mut bytes, label);
+                bytes.extend_from_slice(hash);
+            }
+        }
+        bytes
+    }
+}
+
+impl<P: Clone + Display + PartialEq> ReleaseResult<P> {
+    /// Replaces winner `idx` of the position of index `position` by the next
+    /// one of `replacement_source`, like a reserve list drawn beforehand, and
+    /// returns the replaced winner. Candidates who already win a prize, of
+    /// this position or another, the replaced winner included, are skipped,
+    /// and so are winners replaced before.
+    /// The substitution is kept in `substitutions`, and written to the
+    /// transcript with its time and `reason`, so the signature covers it.
+    pub fn replace_winner(
+        &mut self,
+        position: usize,
+        idx: usize,
+        reason: &str,
+        mut replacement_source: impl Iterator<Item = P>,
+    ) -> Result<P, ChoosenError> {
+        let (_, winners) = self
+            .winners
+            .get(position)
+            .ok_or(ChoosenError::NoSuchPosition(position))?;
+        if idx >= winners.len() {
+            return Err(ChoosenError::NoSuchWinner(idx));
+        }
+        let all_winners = &self.winners;
+        let substitutions = &self.substitutions;
+        let replacement = replacement_source
+            .find(|candidate| {
+                !all_winners.iter().any(|(_, won)| won.contains(candidate))
+                    && !substitutions.iter().any(|s| &s.replaced == candidate)
+            })
+            .ok_or(ChoosenError::NoReplacement)?;
+
+        let (name, winners) = self
+            .winners
+            .get_mut(position)
+            .ok_or(ChoosenError::NoSuchPosition(position))?;
+        let winner = winners
+            .get_mut(idx)
+            .ok_or(ChoosenError::NoSuchWinner(idx))?;
+
+        let at = SystemTime::now();
+        let secs = at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        self.transcript.push(format!(
+            "{}: {} #{} {} {} {}, {}",
+            secs,
+            name,
+            idx,
+            winner,
+            self.locale.messages().replaced_by,
+            replacement,
+            reason
+        ));
+
+        let replaced = std::mem::replace(winner, replacement.clone());
+        self.substitutions.push(Substitution {
+            position,
+            index: idx,
+            replaced: replaced.clone(),
+            replacement,
+            reason: reason.into(),
+            at,
+        });
+        Ok(replaced)
+    }
+}
+
+#[cfg(feature = "signing")]
+impl<P: Display> ReleaseResult<P> {
+    /// A detached ed25519 signature over [`ReleaseResult::canonical_bytes`].
+    pub fn sign(&self, key: &ed25519_dalek::SigningKey) -> ed25519_dalek::Signature {
+        use ed25519_dalek::Signer;
+
+        key.sign(&self.canonical_bytes())
+    }
+
+    pub fn verify(
+        &self,
+        key: &ed25519_dalek::VerifyingKey,
+        signature: &ed25519_dalek::Signature,
+    ) -> Result<(), ed25519_dalek::SignatureError> {
+        key.verify_strict(&self.canonical_bytes(), signature)
+    }
+}
+
+impl<P, PT> Choosen<P, PT>
+where
+    PT: PositionType,
+{
+    /// The same as `release`, wrapped up for announcement with the seed.
+    pub fn release_result(self) -> Result<ReleaseResult<P>, ChoosenError> {
+        let seed = self.seed();
+        self.release()
+            .map(|winners| ReleaseResult::new(winners).with_seed(seed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn result() -> ReleaseResult<u32> {
+        ReleaseResult::new(vec![
+            ("一等奖".into(), vec![7]),
+            ("二等奖".into(), vec![3, 9]),
+        ])
+        .with_seed(27)
+        .record("报名截止")
+    }
+
+    #[test]
+    fn canonical_bytes_tell_results_apart() {
+        let mut other = result();
+        other.winners[1].1 = vec![39];
+        assert_ne!(result().canonical_bytes(), other.canonical_bytes());
+
+        let unseeded = ReleaseResult {
+            seed: None,
+            ..result()
+        };
+        assert_ne!(result().canonical_bytes(), unseeded.canonical_bytes());
+
+        let hashed = result().with_state_hash("报名截止", [27; 32]);
+        assert_ne!(result().canonical_bytes(), hashed.canonical_bytes());
+    }
+
+    #[test]
+    fn reveal_one_by_one() {
+        let mut result = result();
+        let bytes = result.canonical_bytes();
+
+        assert_eq!(result.reveal_next(0), Some(&7));
+        assert_eq!(result.reveal_next(0), None);
+        let mut second = vec![*result.reveal_next(1).unwrap()];
+        second.extend(result.reveal_next(1));
+        second.sort_unstable();
+        assert_eq!(second, [3, 9]);
+        assert_eq!(result.reveal_next(1), None);
+        assert_eq!(result.reveal_next(2), None);
+        assert_eq!(result.canonical_bytes(), bytes);
+    }
+
+    #[test]
+    fn replacements_are_recorded() {
+        let mut result = result();
+        let mut reserve = vec![12, 15].into_iter();
+
+        assert_eq!(result.replace_winner(1, 1, "放弃领奖", &mut reserve), Ok(9));
+        assert_eq!(result.winners[1].1, [3, 12]);
+        assert_eq!(
+            result.replace_winner(1, 2, "不符合资格", &mut reserve),
+            Err(ChoosenError::NoSuchWinner(2))
+        );
+        assert_eq!(
+            result.replace_winner(0, 0, "不符合资格", &mut reserve),
+            Ok(7)
+        );
+        assert_eq!(
+            result.replace_winner(0, 0, "放弃领奖", &mut reserve),
+            Err(ChoosenError::NoReplacement)
+        );
+
+        let first = &result.substitutions[0];
+        assert_eq!((first.position, first.index), (1, 1));
+        assert_eq!((first.replaced, first.replacement), (9, 12));
+        assert_eq!(result.transcript.len(), 3);
+        assert!(result.transcript[1].ends_with("二等奖 #1 9 replaced by 12, 放弃领奖"));
+
+        let mut result = result.with_locale(Locale::Chinese);
+        let _ = result.replace_winner(1, 0, "放弃领奖", std::iter::once(5));
+        assert!(result.transcript[3].ends_with("二等奖 #0 3 替换为 5, 放弃领奖"));
+    }
+
+    #[test]
+    fn winners_are_not_replacements() {
+        let mut result = result();
+        // 7 已是一等奖，9 是被替换的人自己，都要跳过
+        let mut reserve = vec![7, 9, 3, 12].into_iter();
+        assert_eq!(result.replace_winner(1, 1, "放弃领奖", &mut reserve), Ok(9));
+        assert_eq!(result.winners[1].1, [3, 12]);
+        assert_eq!(result.substitutions.len(), 1);
+
+        assert_eq!(
+            result.replace_winner(0, 0, "放弃领奖", vec![3, 12].into_iter()),
+            Err(ChoosenError::NoReplacement)
+        );
+        assert_eq!(result.winners[0].1, [7]);
+        assert_eq!(result.transcript.len(), 2);
+    }
+
+    #[test]
+    fn replaced_winners_do_not_come_back() {
+        let mut result = result();
+        // 同一份候补名单替换两次，第一次被换下的 9 不能再回来
+        let reserve = vec![9, 3, 12, 5];
+        assert_eq!(
+            result.replace_winner(1, 1, "放弃领奖", reserve.clone().into_iter()),
+            Ok(9)
+        );
+        assert_eq!(
+            result.replace_winner(1, 0, "放弃领奖", reserve.into_iter()),
+            Ok(3)
+        );
+        assert_eq!(result.winners[1].1, [5, 12]);
+        assert_eq!(result.substitutions.len(), 2);
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn sign_and_verify() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[27; 32]);
+        let signature = result().sign(&key);
+        assert!(result().verify(&key.verifying_key(), &signature).is_ok());
+
+        let tampered = result().record("追加的一行");
+        assert!(tampered.verify(&key.verifying_key(), &signature).is_err());
+    }
+}
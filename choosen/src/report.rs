@@ -0,0 +1,169 @@
+//! A complete report of a [`ReleaseResult`], in Markdown or HTML, ready to
+//! paste into a wiki. Enabled by the `report` feature.
+//!
+//! The layouts are the templates under `templates/`, rendered by minijinja,
+//! which escapes whatever goes into the HTML one. The Markdown one escapes
+//! names and text by the `md` filter, so a name can not break the tables.
+use std::fmt::Display;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use minijinja::{context, Environment, Error, Value};
+
+use crate::{seed_hex, ReleaseResult};
+
+const MARKDOWN: &str = include_str!("../templates/report.md");
+const HTML: &str = include_str!("../templates/report.html");
+
+fn secs(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Backslash escapes the characters Markdown would read as markup, and turns
+/// line breaks into spaces, so `value` stays one cell or one line.
+fn md(value: &Value) -> String {
+    let value = value.to_string();
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\r' | '\n' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl<P: Display> ReleaseResult<P> {
+    /// The report in Markdown: positions and winners, the seed, the state
+    /// hashes, the substitutions and the transcript.
+    pub fn to_markdown(&self) -> Result<String, Error> {
+        self.render("report.md", MARKDOWN)
+    }
+
+    /// The same report as a standalone HTML page.
+    pub fn to_html(&self) -> Result<String, Error> {
+        self.render("report.html", HTML)
+    }
+
+    fn render(&self, name: &'static str, template: &'static str) -> Result<String, Error> {
+        let mut env = Environment::new();
+        env.add_template(name, template)?;
+        env.add_filter("md", md);
+
+        let positions: Vec<Value> = self
+            .winners
+            .iter()
+            .map(|(name, winners)| {
+                let winners: Vec<String> = winners.iter().map(ToString::to_string).collect();
+                context! { name, winners }
+            })
+            .collect();
+        let state_hashes: Vec<Value> = self
+            .state_hashes
+            .iter()
+            .map(|(label, hash)| context! { label, hash => seed_hex(hash) })
+            .collect();
+        let substitutions: Vec<Value> = self
+            .substitutions
+            .iter()
+            .map(|s| {
+                let position = self.winners.get(s.position).map(|(name, _)| name);
+                context! {
+                    at => secs(s.at),
+                    position,
+                    index => s.index,
+                    replaced => s.replaced.to_string(),
+                    replacement => s.replacement.to_string(),
+                    reason => s.reason,
+                }
+            })
+            .collect();
+
+        let t = self.locale.messages();
+        let generated = t
+            .generated_at
+            .replace("{}", &secs(SystemTime::now()).to_string());
+        env.get_template(name)?.render(context! {
+            t => context! {
+                report => t.report,
+                winners => t.winners,
+                no_winner => t.no_winner,
+                seed => t.seed,
+                no_seed => t.no_seed,
+                state_hashes => t.state_hashes,
+                taken => t.taken,
+                substitutions => t.substitutions,
+                at => t.at,
+                position => t.position,
+                replaced => t.replaced,
+                replacement => t.replacement,
+                reason => t.reason,
+                transcript => t.transcript,
+            },
+            generated,
+            positions,
+            seed => self.seed.as_ref().map(seed_hex),
+            state_hashes,
+            substitutions,
+            transcript => self.transcript,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Locale, SeedSource};
+
+    fn result() -> ReleaseResult<String> {
+        let mut result = ReleaseResult::new(vec![
+            ("一等奖".into(), vec!["<张三>".into()]),
+            ("二等奖".into(), vec![]),
+        ])
+        .with_seed(27)
+        .with_state_hash("报名截止", [0xab; 32])
+        .record("报名截止");
+        let _ = result.replace_winner(0, 0, "放弃领奖", vec!["李四".to_string()].into_iter());
+        result
+    }
+
+    #[test]
+    fn markdown_report() {
+        let report = result().to_markdown().unwrap();
+        assert!(report.contains("### 一等奖\n\n1. 李四\n"));
+        assert!(report.contains("### 二等奖\n\nNo winner.\n"));
+        assert!(report.contains(&format!("`{}`", seed_hex(&27.seed()))));
+        assert!(report.contains(&format!("| 报名截止 | `{}` |", "ab".repeat(32))));
+        assert!(report.contains("| 一等奖 #0 | \\<张三\\> | 李四 | 放弃领奖 |"));
+        assert!(report.contains("- 报名截止\n"));
+    }
+
+    #[test]
+    fn markdown_is_escaped() {
+        let mut result = ReleaseResult::new(vec![("*一等奖*".into(), vec!["a|b".to_string()])]);
+        let _ = result.replace_winner(0, 0, "放弃\n领奖", vec!["[c](x)".to_string()].into_iter());
+        let report = result.to_markdown().unwrap();
+        assert!(report.contains("### \\*一等奖\\*\n"));
+        assert!(report.contains("1. \\[c\\](x)\n"));
+        assert!(report.contains("| \\*一等奖\\* #0 | a\\|b | \\[c\\](x) | 放弃 领奖 |"));
+    }
+
+    #[test]
+    fn html_report_is_escaped() {
+        let report = result().to_html().unwrap();
+        assert!(report.contains("<li>李四</li>"));
+        assert!(report.contains("&lt;张三&gt;"));
+        assert!(!report.contains("<张三>"));
+    }
+
+    #[test]
+    fn reports_follow_the_locale() {
+        let report = result().with_locale(Locale::Chinese).to_markdown().unwrap();
+        assert!(report.starts_with("# 抽奖报告\n"));
+        assert!(report.contains("### 二等奖\n\n无人中奖。\n"));
+        assert!(report.contains("## 替补记录"));
+    }
+}
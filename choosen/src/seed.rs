@@ -0,0 +1,124 @@
+use std::fmt::Write;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reservoir_sampler::Deterministic;
+
+/// Where the seed of a draw comes from. A public draw can anchor it to a value
+/// everyone can check, like a drand round or a block hash, see `Beacon` of the
+/// `beacon` feature.
+///
+/// A seed is a whole ChaCha20 key, so no entropy of the source is lost on the
+/// way to the rng.
+pub trait SeedSource {
+    fn seed(&self) -> [u8; 32];
+}
+
+/// A fixed seed, for replaying a draw. It is expanded into a key the way
+/// [`Deterministic::seed_from_u64`] does it.
+impl SeedSource for u64 {
+    fn seed(&self) -> [u8; 32] {
+        Deterministic::seed_from_u64(*self).get_seed()
+    }
+}
+
+/// A whole key, like the seed of an earlier draw, for replaying it.
+impl SeedSource for [u8; 32] {
+    fn seed(&self) -> [u8; 32] {
+        *self
+    }
+}
+
+/// Fresh entropy from the OS, the seed of a draw when none is given.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsEntropy;
+
+impl SeedSource for OsEntropy {
+    fn seed(&self) -> [u8; 32] {
+        let mut seed = [0; 32];
+        OsRng.fill_bytes(&mut seed);
+        seed
+    }
+}
+
+/// A randomness beacon value provided by the caller, like the randomness of
+/// a drand round or a block hash. It is hashed into the seed with BLAKE3, in
+/// its key derivation mode, so every byte of it counts. Enabled by the
+/// `beacon` feature.
+#[cfg(feature = "beacon")]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Beacon {
+    value: Vec<u8>,
+}
+
+#[cfg(feature = "beacon")]
+impl Beacon {
+    pub fn new(value: &[u8]) -> Self {
+        Self {
+            value: value.to_vec(),
+        }
+    }
+}
+
+#[cfg(feature = "beacon")]
+impl SeedSource for Beacon {
+    fn seed(&self) -> [u8; 32] {
+        blake3::derive_key("choosen 2026 beacon seed", &self.value)
+    }
+}
+
+/// A seed or a hash in lowercase hex, the way it is published.
+pub fn seed_hex(bytes: &[u8; 32]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+/// Reads a seed back from [`seed_hex`].
+pub fn parse_seed_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut seed = [0; 32];
+    for (byte, pair) in seed.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(seed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "beacon")]
+    #[test]
+    fn beacon_hashes() {
+        let a = Beacon::new(b"drand round 27").seed();
+        let b = Beacon::new(b"drand round 28").seed();
+        assert_ne!(a, b);
+        // 只改最后一个字节，前面的部分也整体改变
+        assert_ne!(a[..8], b[..8]);
+        assert_eq!(Beacon::new(&[]).seed(), Beacon::new(&[]).seed());
+    }
+
+    #[test]
+    fn u64_seeds_replay() {
+        let mut from_u64 = Deterministic::seed_from_u64(27);
+        let mut from_key = Deterministic::from_seed(27.seed());
+        assert_eq!(from_u64.next_u64(), from_key.next_u64());
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let seed = 27.seed();
+        let text = seed_hex(&seed);
+        assert_eq!(text.len(), 64);
+        assert_eq!(parse_seed_hex(&text), Some(seed));
+        assert_eq!(parse_seed_hex("27"), None);
+        assert_eq!(parse_seed_hex(&"g".repeat(64)), None);
+    }
+}
@@ -0,0 +1,204 @@
+//! The lifecycle of a [`Choosen`]: entries are collected, then closed, then
+//! the winners are revealed. Every transition is an explicit call, and a call
+//! out of order is reported as [`ChoosenError::WrongStage`].
+use reservoir_sampler::Inspectable;
+
+use crate::pos::PositionType;
+use crate::{hand_out, Choosen, ChoosenError, Released};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Stage {
+    /// Entries are being polled.
+    Collecting,
+    /// Entries are closed, and the winners are fixed but not revealed yet.
+    Locked,
+    /// The winners are revealed.
+    Released,
+}
+
+impl<P, PT> Choosen<P, PT>
+where
+    PT: PositionType,
+{
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// Closes the entries, fixing which winner goes to which position. Polls
+    /// from then on are dropped, see [`Choosen::try_poll_one`] to be told
+    /// instead.
+    pub fn lock(&mut self) -> Result<(), ChoosenError> {
+        self.expect(Stage::Collecting)?;
+        if let Some(growing) = self.growing.take() {
+            let cap = self.share_caps(growing.total());
+            self.lucky = growing.fix_capacity(cap);
+        }
+        self.lucky.compact_and_shuffle();
+        self.revealed = vec![false; self.positions.len()];
+        self.stage = Stage::Locked;
+        Ok(())
+    }
+
+    /// The same as `poll_one`, but reports a poll after the entries are
+    /// closed instead of dropping it.
+    pub fn try_poll_one(&mut self, it: P) -> Result<(u64, u64, Option<P>), ChoosenError> {
+        self.expect(Stage::Collecting)?;
+        Ok(self.poll_one(it))
+    }
+
+    /// Fixes the caps of the positions going to a share of the entrants,
+    /// returning the caps of all positions together.
+    fn share_caps(&mut self, total: u64) -> usize {
+        for p in &mut self.positions {
+            if let Some(share) = p.share() {
+                let _ = p.set_cap(share_cap(share, total));
+            }
+        }
+        self.positions.iter().map(|p| p.cap()).sum()
+    }
+
+    pub(crate) fn expect(&self, stage: Stage) -> Result<(), ChoosenError> {
+        if self.stage == stage {
+            Ok(())
+        } else {
+            Err(ChoosenError::WrongStage(self.stage))
+        }
+    }
+}
+
+/// The cap of a position going to `share` of `total` entrants, at least 1.
+pub(crate) fn share_cap(share: f64, total: u64) -> usize {
+    (share * total as f64).ceil().max(1.0) as usize
+}
+
+impl<P: Clone, PT> Choosen<P, PT>
+where
+    PT: PositionType + Clone,
+{
+    /// Reveals the winners of a locked draw, keeping the draw around, so the
+    /// stage can still be asked for. See `release` for the one-off way.
+    pub fn reveal(&mut self) -> Result<Released<P>, ChoosenError> {
+        self.expect(Stage::Locked)?;
+        self.stage = Stage::Released;
+        hand_out(self.positions.clone(), self.lucky.samples().to_vec())
+    }
+
+    /// Reveals the winners of the position of index `idx` alone, keeping the
+    /// others sealed, like a gala revealing the lowest prize first. Since
+    /// `lock` fixed every position's winners, revealing the positions in any
+    /// order, or all at once, gives the same winners. The draw is released
+    /// once every position is revealed.
+    pub fn release_position(&mut self, idx: usize) -> Result<(String, Vec<P>), ChoosenError> {
+        self.expect(Stage::Locked)?;
+        let p = self
+            .positions
+            .get(idx)
+            .ok_or(ChoosenError::NoSuchPosition(idx))?;
+        if self.revealed.get(idx).copied().unwrap_or(false) {
+            return Err(ChoosenError::AlreadyRevealed(idx));
+        }
+        if self.lucky.samples().iter().all(Option::is_none) {
+            return Err(ChoosenError::NoOneIsChoosen);
+        }
+
+        let offset: usize = self.positions[..idx].iter().map(|p| p.cap()).sum();
+        let winners = self
+            .lucky
+            .samples()
+            .iter()
+            .skip(offset)
+            .take(p.cap())
+            .flatten()
+            .cloned()
+            .collect();
+        let name = p.name().into();
+
+        if let Some(revealed) = self.revealed.get_mut(idx) {
+            *revealed = true;
+        }
+        if self.revealed.iter().all(|&revealed| revealed) {
+            self.stage = Stage::Released;
+        }
+        Ok((name, winners))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BuildChoosenError, ChoosenBuilder, Position};
+
+    #[test]
+    fn stages_go_in_order() -> Result<(), BuildChoosenError> {
+        let mut choosen = ChoosenBuilder::<Position>::new()
+            .add_position("一等奖", 2)?
+            .build::<u32>()?;
+        assert_eq!(choosen.stage(), Stage::Collecting);
+        assert_eq!(
+            choosen.reveal().err(),
+            Some(ChoosenError::WrongStage(Stage::Collecting))
+        );
+
+        choosen.poll_many(0..10);
+        assert_eq!(choosen.lock(), Ok(()));
+        assert_eq!(choosen.lock(), Err(ChoosenError::WrongStage(Stage::Locked)));
+
+        // 报名截止后的条目被丢弃
+        assert_eq!(choosen.poll_one(10), (0, 10, None));
+        assert_eq!(
+            choosen.try_poll_one(10),
+            Err(ChoosenError::WrongStage(Stage::Locked))
+        );
+
+        let released = choosen.reveal().unwrap();
+        assert_eq!(released[0].1.len(), 2);
+        assert!(released[0].1.iter().all(|&it| it < 10));
+        assert_eq!(choosen.stage(), Stage::Released);
+        assert_eq!(
+            choosen.release().err(),
+            Some(ChoosenError::WrongStage(Stage::Released))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn positions_one_at_a_time() -> Result<(), BuildChoosenError> {
+        let draw = || -> Result<Choosen<u32>, BuildChoosenError> {
+            let mut choosen = ChoosenBuilder::<Position>::new()
+                .add_position("一等奖", 1)?
+                .add_position("二等奖", 2)?
+                .add_position("三等奖", 3)?
+                .seed_from(27)
+                .build::<u32>()?;
+            choosen.poll_many(0..100);
+            let _ = choosen.lock();
+            Ok(choosen)
+        };
+
+        // 先揭晓三等奖，再揭晓一等奖，结果和一次性揭晓一致
+        let mut choosen = draw()?;
+        let third = choosen.release_position(2).unwrap();
+        assert_eq!(
+            choosen.release_position(2),
+            Err(ChoosenError::AlreadyRevealed(2))
+        );
+        assert_eq!(
+            choosen.release_position(3),
+            Err(ChoosenError::NoSuchPosition(3))
+        );
+        let first = choosen.release_position(0).unwrap();
+        assert_eq!(choosen.stage(), Stage::Locked);
+        let released = choosen.release().unwrap();
+
+        assert_eq!(released[2], third);
+        assert_eq!(released[0], first);
+        assert_eq!(released, draw()?.reveal().unwrap());
+
+        let mut choosen = draw()?;
+        for idx in 0..3 {
+            let _ = choosen.release_position(idx).unwrap();
+        }
+        assert_eq!(choosen.stage(), Stage::Released);
+        Ok(())
+    }
+}
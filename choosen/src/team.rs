@@ -0,0 +1,58 @@
+use crate::pos::PositionType;
+use crate::{Choosen, ChoosenError};
+
+/// A group of attendees polled as one, like a project team, so the cap of a
+/// position counts teams instead of individuals.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Team<M> {
+    pub name: String,
+    pub members: Vec<M>,
+}
+
+impl<M> Team<M> {
+    pub fn new(name: &str, members: Vec<M>) -> Self {
+        Self {
+            name: name.into(),
+            members,
+        }
+    }
+}
+
+impl<M, PT> Choosen<Team<M>, PT>
+where
+    PT: PositionType,
+{
+    pub fn poll_team(&mut self, name: &str, members: Vec<M>) -> (u64, u64, Option<Team<M>>) {
+        self.poll_one(Team::new(name, members))
+    }
+
+    /// The same as `release`, but expands the choosen teams of every
+    /// position into their members.
+    pub fn release_members(self) -> Result<Vec<(String, Vec<M>)>, ChoosenError> {
+        let released = self.release()?;
+
+        Ok(released
+            .into_iter()
+            .map(|(pos, teams)| (pos, teams.into_iter().flat_map(|t| t.members).collect()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{BuildChoosenError, ChoosenBuilder, Position, Team};
+
+    #[test]
+    fn cap_counts_teams() -> Result<(), BuildChoosenError> {
+        let mut choosen = ChoosenBuilder::<Position>::new()
+            .add_position("团队晚餐", 1)?
+            .build::<Team<&str>>()?;
+        choosen.poll_team("infra", vec!["alice", "bob", "carol"]);
+        choosen.poll_team("web", vec!["dave", "erin"]);
+
+        let released = choosen.release_members().unwrap();
+        assert_eq!(released.len(), 1);
+        assert!(released[0].1 == ["alice", "bob", "carol"] || released[0].1 == ["dave", "erin"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use crate::pos::{Position, PositionType};
+use crate::{hand_out, ChoosenError};
+use reservoir_sampler::{Deterministic, TicketReservoir};
+
+/// A `WeightedChoosen` chooses like a [`Choosen`](crate::Choosen), but every
+/// attendee holds as many tickets as `weight` gives them, like their years of
+/// service, so an attendee with twice the tickets is about twice as likely to
+/// be choosen. Attendees with no ticket are never choosen.
+pub struct WeightedChoosen<P, W, PT = Position>
+where
+    PT: PositionType,
+{
+    pub(crate) positions: Vec<PT>,
+    pub(crate) lucky: TicketReservoir<P, Deterministic>,
+    pub(crate) seed: [u8; 32],
+    pub(crate) weight: W,
+}
+
+impl<P, W, PT> WeightedChoosen<P, W, PT>
+where
+    PT: PositionType,
+    W: Fn(&P) -> u64,
+{
+    pub fn poll_one(&mut self, it: P) -> (u128, u128, Option<P>) {
+        let tickets = (self.weight)(&it);
+        self.lucky.sample(it, tickets)
+    }
+
+    pub fn lucky(&self) -> &[Option<P>] {
+        self.lucky.samples()
+    }
+
+    pub fn seed(&self) -> [u8; 32] {
+        self.seed
+    }
+
+    /// The same as [`Choosen::release`](crate::Choosen::release).
+    pub fn release(self) -> Result<Vec<(String, Vec<P>)>, ChoosenError> {
+        hand_out(self.positions, self.lucky.lock())
+    }
+}
+
+/// A `PerPositionChoosen` keeps a pool for every position, and hands every
+/// attendee to all of them, each with the tickets its [`Weighting`] gives.
+/// So the grand prize may ignore the weights while the consolation prizes
+/// count them, all from one roster.
+///
+/// Positions draw independently, so one attendee may win several of them,
+/// unless the choosen is built by [`ChoosenBuilder::per_position_exclusive`].
+///
+/// [`Weighting`]: crate::Weighting
+/// [`ChoosenBuilder::per_position_exclusive`]: crate::ChoosenBuilder::per_position_exclusive
+pub struct PerPositionChoosen<P, W, PT = Position>
+where
+    PT: PositionType,
+{
+    pub(crate) positions: Vec<PT>,
+    pub(crate) lucky: Vec<TicketReservoir<P, Deterministic>>,
+    pub(crate) seed: [u8; 32],
+    pub(crate) weight: W,
+    // 互斥模式下判断是否同一人，已在前面的奖项中奖的跳过
+    pub(crate) same: Option<fn(&P, &P) -> bool>,
+}
+
+impl<P, W, PT> PerPositionChoosen<P, W, PT>
+where
+    P: Clone,
+    PT: PositionType,
+    W: Fn(&P) -> u64,
+{
+    /// Polls the attendee into every position, returning what each pool
+    /// returned, in the order of positions.
+    pub fn poll_one(&mut self, it: P) -> Vec<(u128, u128, Option<P>)> {
+        let tickets = (self.weight)(&it);
+
+        self.positions
+            .iter()
+            .zip(&mut self.lucky)
+            .map(|(p, lucky)| lucky.sample(it.clone(), p.weighting().apply(tickets)))
+            .collect()
+    }
+
+    /// The current lucky ones of every position, in the order of positions.
+    /// In the exclusive mode, a pool also holds stand-ins for the winners of
+    /// the positions before it.
+    pub fn lucky(&self) -> Vec<&[Option<P>]> {
+        self.lucky.iter().map(|l| l.samples()).collect()
+    }
+
+    /// The seed of every position, each drawing from its own ChaCha stream of
+    /// it, by the index of the position.
+    pub fn seed(&self) -> [u8; 32] {
+        self.seed
+    }
+
+    /// The same as [`Choosen::release`](crate::Choosen::release). In the
+    /// exclusive mode, positions hand out in order, each skipping those who
+    /// won a position before it.
+    pub fn release(self) -> Result<Vec<(String, Vec<P>)>, ChoosenError> {
+        let same = self.same;
+        let mut result: Vec<(String, Vec<P>)> = Vec::with_capacity(self.positions.len());
+        for (p, lucky) in self.positions.iter().zip(self.lucky) {
+            let luck = match same {
+                Some(same) => lucky
+                    .lock_iter()
+                    .filter(|it| {
+                        !result
+                            .iter()
+                            .any(|(_, won)| won.iter().any(|winner| same(winner, it)))
+                    })
+                    .take(p.cap())
+                    .collect(),
+                None => lucky.lock_iter().collect(),
+            };
+            result.push((p.name().into(), luck));
+        }
+
+        if result.iter().all(|(_, luck)| luck.is_empty()) {
+            return Err(ChoosenError::NoOneIsChoosen);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{BuildChoosenError, ChoosenBuilder, Position, Weighting};
+
+    #[test]
+    fn seniority_counts() -> Result<(), BuildChoosenError> {
+        let mut senior = 0;
+        for _ in 0..2000 {
+            // (工号, 工龄)
+            let mut choosen = ChoosenBuilder::<Position>::new()
+                .add_position("一等奖", 1)?
+                .weighted::<(u32, u64), _>(|&(_, years)| years)?;
+            for it in &[(1, 1), (2, 1), (3, 2), (4, 0)] {
+                choosen.poll_one(*it);
+            }
+
+            let released = choosen.release().unwrap();
+            assert_ne!(released[0].1, vec![(4, 0)]);
+            if released[0].1 == vec![(3, 2)] {
+                senior += 1;
+            }
+        }
+
+        // 工龄 2 年的人中奖概率约为 1/2
+        assert!((850..1150).contains(&senior), "{}", senior);
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_weightings() -> Result<(), BuildChoosenError> {
+        let mut grand = 0;
+        let mut consolation = 0;
+        for _ in 0..2000 {
+            let mut choosen = ChoosenBuilder::<Position>::new()
+                .add_position("特等奖", 1)?
+                .set_position_weighting(Weighting::Uniform, 0)?
+                .add_position("安慰奖", 1)?
+                .per_position::<(u32, u64), _>(|&(_, tickets)| tickets)?;
+            for it in &[(1, 1), (2, 1), (3, 2), (4, 0)] {
+                assert_eq!(choosen.poll_one(*it).len(), 2);
+            }
+
+            let released = choosen.release().unwrap();
+            assert_ne!(released[1].1, vec![(4, 0)]);
+            grand += usize::from(released[0].1 == vec![(3, 2)]);
+            consolation += usize::from(released[1].1 == vec![(3, 2)]);
+        }
+
+        // 特等奖不看票数：约 1/4；安慰奖按票数：约 1/2
+        assert!((400..600).contains(&grand), "{}", grand);
+        assert!((850..1150).contains(&consolation), "{}", consolation);
+        Ok(())
+    }
+
+    #[test]
+    fn exclusive_positions() -> Result<(), BuildChoosenError> {
+        for seed in 0..200 {
+            let mut choosen = ChoosenBuilder::<Position>::new()
+                .seed_from(seed)
+                .add_position("一等奖", 1)?
+                .add_position("二等奖", 2)?
+                .per_position_exclusive::<(u32, u64), _>(|&(_, tickets)| tickets)?;
+            for it in &[(1, 1), (2, 1), (3, 2), (4, 1)] {
+                let _ = choosen.poll_one(*it);
+            }
+
+            let released = choosen.release().unwrap();
+            assert_eq!(released[0].1.len(), 1);
+            assert_eq!(released[1].1.len(), 2);
+            assert!(!released[1].1.contains(&released[0].1[0]));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Drives random sequences of sampler and builder operations. Any panic is a
+//! bug: both crates promise to report misuse through their error types.
+#![no_main]
+use arbitrary::Arbitrary;
+use choosen::{ChoosenBuilder, Position};
+use libfuzzer_sys::fuzz_target;
+use reservoir_sampler::{Inspectable, Lockable, Reservoir, StreamSampler, TicketReservoir};
+
+#[derive(Arbitrary, Debug)]
+enum SamplerOp {
+    Sample(u16),
+    SampleTickets(u16, u64),
+    Samples,
+}
+
+#[derive(Arbitrary, Debug)]
+enum BuilderOp {
+    AddPosition(String, u8),
+    SetName(String, u8),
+    SetCap(u8, u8),
+    Remove(u8),
+    Positions,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    cap: u8,
+    sampler_ops: Vec<SamplerOp>,
+    builder_ops: Vec<BuilderOp>,
+    polls: Vec<u16>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut reservoir = Reservoir::<u16>::with_capacity(input.cap.into());
+    let mut tickets = TicketReservoir::<u16>::with_capacity(input.cap.into());
+    for op in input.sampler_ops {
+        match op {
+            SamplerOp::Sample(it) => {
+                let _ = reservoir.sample(it);
+            }
+            SamplerOp::SampleTickets(it, n) => {
+                let _ = tickets.sample(it, n);
+            }
+            SamplerOp::Samples => {
+                let _ = reservoir.samples();
+                let _ = tickets.samples();
+            }
+        }
+    }
+    let _ = reservoir.lock();
+    let _ = tickets.lock();
+
+    let mut builder = ChoosenBuilder::<Position>::new();
+    for op in input.builder_ops {
+        let _ = match op {
+            BuilderOp::AddPosition(name, cap) => {
+                builder.add_position(&name, cap.into()).map(|_| ())
+            }
+            BuilderOp::SetName(name, idx) => {
+                builder.set_position_name(&name, idx.into()).map(|_| ())
+            }
+            BuilderOp::SetCap(cap, idx) => {
+                builder.set_position_cap(cap.into(), idx.into()).map(|_| ())
+            }
+            BuilderOp::Remove(idx) => builder.remove_position(idx.into()).map(|_| ()),
+            BuilderOp::Positions => {
+                let _ = builder.positions();
+                Ok(())
+            }
+        };
+    }
+
+    if let Ok(mut choosen) = builder.build::<u16>() {
+        for it in input.polls {
+            let _ = choosen.poll_one(it);
+            let _ = choosen.lucky();
+        }
+        let _ = choosen.release();
+    }
+});
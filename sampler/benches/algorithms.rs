@@ -0,0 +1,51 @@
+//! Compares the algorithms of `Reservoir` on a stream much longer than the
+//! pool, where skipping pays off.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use reservoir_sampler::{Algorithm, Deterministic, Lockable, Reservoir, StreamSampler};
+
+fn algorithms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("100 of 100_000");
+    for &algorithm in &[Algorithm::R, Algorithm::L, Algorithm::X] {
+        group.bench_function(format!("{:?}", algorithm), |b| {
+            b.iter(|| {
+                let rng = Deterministic::seed_from_u64(27);
+                let mut reservoir = Reservoir::with_rng_and_algorithm(100, rng, algorithm);
+                for it in 0..100_000u64 {
+                    let _ = reservoir.sample(black_box(it));
+                }
+                reservoir.lock()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// `sample` item by item against `sample_slice` over the same `u64` items.
+fn slices(c: &mut Criterion) {
+    let items: Vec<u64> = (0..1_000_000).collect();
+    let mut group = c.benchmark_group("100 of a slice of 1_000_000");
+    for &algorithm in &[Algorithm::R, Algorithm::L] {
+        group.bench_function(format!("{:?} by item", algorithm), |b| {
+            b.iter(|| {
+                let rng = Deterministic::seed_from_u64(27);
+                let mut reservoir = Reservoir::with_rng_and_algorithm(100, rng, algorithm);
+                for &it in black_box(&items) {
+                    let _ = reservoir.sample(it);
+                }
+                reservoir.lock()
+            })
+        });
+        group.bench_function(format!("{:?} by slice", algorithm), |b| {
+            b.iter(|| {
+                let rng = Deterministic::seed_from_u64(27);
+                let mut reservoir = Reservoir::with_rng_and_algorithm(100, rng, algorithm);
+                let _ = reservoir.sample_slice(black_box(&items));
+                reservoir.lock()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, algorithms, slices);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+//! Exact summaries of everything a sampler sees, next to its sample, so one
+//! pass over a stream yields both.
+use crate::{Inspectable, Lockable, StreamSampler};
+
+/// Something updated by every item passing through an [`Accumulated`]
+/// sampler, held or not. A pair of accumulators is one, updating both.
+pub trait Accumulator<T> {
+    fn add(&mut self, it: &T);
+}
+
+impl<T, A: Accumulator<T>, B: Accumulator<T>> Accumulator<T> for (A, B) {
+    fn add(&mut self, it: &T) {
+        self.0.add(it);
+        self.1.add(it);
+    }
+}
+
+/// The count, mean, variance, minimum and maximum of `value` over the items,
+/// the mean and variance by Welford's online algorithm. Values which are not
+/// numbers are skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct Summary<F> {
+    value: F,
+    count: u64,
+    mean: f64,
+    // 与均值之差的平方和
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl<F> Summary<F> {
+    pub fn new(value: F) -> Self {
+        Self {
+            value,
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// The sample variance, over `count - 1`.
+    pub fn variance(&self) -> Option<f64> {
+        (self.count > 1).then(|| self.m2 / (self.count - 1) as f64)
+    }
+
+    /// The variance of the items as the whole population, over `count`.
+    pub fn population_variance(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.m2 / self.count as f64)
+    }
+
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+impl<T, F: Fn(&T) -> f64> Accumulator<T> for Summary<F> {
+    fn add(&mut self, it: &T) {
+        let x = (self.value)(it);
+        if x.is_nan() {
+            return;
+        }
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+    }
+}
+
+/// A sampler whose accumulator sees every item it is fed, like
+/// `Accumulated::new(Reservoir::with_capacity(100), Summary::new(|r: &Request| r.latency))`.
+pub struct Accumulated<S, A> {
+    sampler: S,
+    accumulator: A,
+}
+
+impl<S: StreamSampler, A: Accumulator<S::Item>> Accumulated<S, A> {
+    pub fn new(sampler: S, accumulator: A) -> Self {
+        Self {
+            sampler,
+            accumulator,
+        }
+    }
+
+    pub fn sampler(&self) -> &S {
+        &self.sampler
+    }
+
+    /// What is accumulated so far.
+    pub fn accumulator(&self) -> &A {
+        &self.accumulator
+    }
+}
+
+impl<S: Lockable, A: Accumulator<S::Item>> Accumulated<S, A> {
+    /// End the sampling process, handing out the locked sample and the
+    /// accumulator.
+    pub fn lock(self) -> (Vec<Option<S::Item>>, A) {
+        (self.sampler.lock(), self.accumulator)
+    }
+}
+
+impl<S: StreamSampler, A: Accumulator<S::Item>> StreamSampler for Accumulated<S, A> {
+    type Item = S::Item;
+    type Draw = S::Draw;
+
+    fn sample(&mut self, it: S::Item) -> (S::Draw, u64, Option<S::Item>) {
+        self.accumulator.add(&it);
+        self.sampler.sample(it)
+    }
+
+    fn is_held(&self, draw: &S::Draw) -> bool {
+        self.sampler.is_held(draw)
+    }
+}
+
+impl<S: Inspectable, A: Accumulator<S::Item>> Inspectable for Accumulated<S, A> {
+    fn samples(&self) -> &[Option<S::Item>] {
+        self.sampler.samples()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Reservoir};
+
+    #[test]
+    fn summarizes_every_item() {
+        let mut sampler = Accumulated::new(
+            Reservoir::<u32, Deterministic>::deterministic(10, 1),
+            (
+                Summary::new(|it: &u32| f64::from(*it)),
+                Summary::new(|it: &u32| f64::from(it % 2)),
+            ),
+        );
+        let _ = sampler.sample_all(1..=1_000);
+        assert_eq!(sampler.samples().iter().flatten().count(), 10);
+
+        let (held, (summary, odd)) = sampler.lock();
+        assert_eq!(held.len(), 10);
+        assert_eq!(summary.count(), 1_000);
+        assert!((summary.mean().unwrap() - 500.5).abs() < 1e-9);
+        // 1..=n 的样本方差为 n(n+1)/12
+        let variance = summary.variance().unwrap();
+        assert!((variance - 1_000.0 * 1_001.0 / 12.0).abs() < 1e-6);
+        assert_eq!((summary.min(), summary.max()), (Some(1.0), Some(1_000.0)));
+        assert!((odd.mean().unwrap() - 0.5).abs() < 1e-9);
+        assert!((odd.population_variance().unwrap() - 0.25).abs() < 1e-9);
+
+        let empty = Summary::new(|it: &f64| *it);
+        assert_eq!(
+            (empty.mean(), empty.variance(), empty.min()),
+            (None, None, None)
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Sampling at a steady output rate, like 100 samples a minute, whatever the
+//! input volume.
+use std::time::{Duration, Instant};
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::weights::Sampled;
+
+/// An `AdaptiveSampler` accepts every item with the same probability, and at
+/// the end of every period sets it to `target` over the input rate, so about
+/// `target` items are accepted per period. The input rate is a moving average
+/// over periods, each period weighing half.
+///
+/// Every accepted item comes with the probability it was accepted with, so
+/// exporters can weight their counts by [`Sampled::weight`].
+pub struct AdaptiveSampler<R = ThreadRng> {
+    target: f64,
+    period: Duration,
+    probability: f64,
+    rate: Option<f64>,
+    seen: u64,
+    started: Option<Instant>,
+    rng: R,
+}
+
+impl AdaptiveSampler {
+    pub fn new(target: u64, period: Duration) -> Self {
+        Self::with_rng(target, period, thread_rng())
+    }
+}
+
+impl<R: Rng> AdaptiveSampler<R> {
+    /// Until the first period ends, every item is accepted.
+    pub fn with_rng(target: u64, period: Duration, rng: R) -> Self {
+        Self {
+            target: target as f64,
+            period,
+            probability: 1.0,
+            rate: None,
+            seen: 0,
+            started: None,
+            rng,
+        }
+    }
+
+    /// The probability the next item is accepted with.
+    pub fn probability(&self) -> f64 {
+        self.probability
+    }
+
+    pub fn sample<T>(&mut self, it: T) -> Option<Sampled<T>> {
+        self.sample_at(it, Instant::now())
+    }
+
+    /// The same as `sample`, at the given time instead of now.
+    pub fn sample_at<T>(&mut self, it: T, now: Instant) -> Option<Sampled<T>> {
+        self.adapt(now);
+        self.seen += 1;
+
+        if self.rng.gen::<f64>() >= self.probability {
+            return None;
+        }
+        Some(Sampled {
+            item: it,
+            probability: self.probability,
+        })
+    }
+
+    fn adapt(&mut self, now: Instant) {
+        let started = *self.started.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(started);
+        if self.period.is_zero() || elapsed < self.period {
+            return;
+        }
+
+        // 跨过多个周期时，按周期数平摊
+        let periods = (elapsed.as_secs_f64() / self.period.as_secs_f64()).floor();
+        let seen = self.seen as f64 / periods;
+        let rate = match self.rate {
+            Some(rate) => (rate + seen) / 2.0,
+            None => seen,
+        };
+
+        self.rate = Some(rate);
+        self.probability = if rate > self.target {
+            self.target / rate
+        } else {
+            1.0
+        };
+        self.seen = 0;
+        self.started = Some(started + self.period.mul_f64(periods));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Deterministic;
+
+    #[test]
+    fn hits_the_target_rate() {
+        let minute = Duration::from_secs(60);
+        let mut sampler = AdaptiveSampler::with_rng(100, minute, Deterministic::seed_from_u64(4));
+
+        let start = Instant::now();
+        let mut accepted = vec![0; 6];
+        for m in 0..6u32 {
+            // 前三分钟每分钟 1000 条，之后 5000 条
+            let volume = if m < 3 { 1000 } else { 5000 };
+            for i in 0..volume {
+                let now = start + minute * m + minute.mul_f64(f64::from(i) / f64::from(volume));
+                if let Some(sampled) = sampler.sample_at(i, now) {
+                    assert_eq!(sampled.probability, sampler.probability());
+                    accepted[m as usize] += 1;
+                }
+            }
+        }
+
+        assert_eq!(accepted[0], 1000);
+        assert!((80..120).contains(&accepted[2]), "{:?}", accepted);
+        // 平均值追上突增需要几个周期
+        assert!((80..150).contains(&accepted[5]), "{:?}", accepted);
+    }
+}
@@ -0,0 +1,128 @@
+//! The sampling algorithms themselves, written against a [`SampleStore`] so
+//! every kind of pool can share them.
+use rand::Rng;
+
+use crate::rng::below_u64;
+use crate::store::SampleStore;
+
+/// Algorithm R: the `total`-th item (counting from 1) draws `r` out of
+/// `1..=total` and replaces slot `r` when there is one. While the pool is
+/// still filling up, whatever the item replaced moves to slot `total`, so the
+/// first items always fill the pool.
+///
+/// Returns the draw and the replaced item. `pool` must not be empty.
+pub(crate) fn r<T, P, R>(pool: &mut P, total: u64, it: T, rng: &mut R) -> (u64, Option<T>)
+where
+    P: SampleStore<T> + ?Sized,
+    R: Rng + ?Sized,
+{
+    let r = r_draw(total, rng);
+    if r > pool.capacity() as u64 {
+        return (r, None);
+    }
+
+    (r, r_place(pool, total, r, it))
+}
+
+/// The draw of Algorithm R alone, for callers which only want to pay for
+/// preparing the item once it is known to be accepted, that is when the draw
+/// is no greater than the capacity.
+pub(crate) fn r_draw<R: Rng + ?Sized>(total: u64, rng: &mut R) -> u64 {
+    // 概率渐小的随机替换
+    below_u64(rng, total) + 1
+}
+
+/// Algorithm R over a block of items once the pool of `cap` slots is full,
+/// item `i` of the block being the `total + 1 + i`-th. Every item takes its
+/// draw out of its random word in `words` by a multiply and a shift, with no
+/// rejection loop, so the loop has no branch and vectorizes; the draws are
+/// off uniform by at most `total / 2^64`.
+///
+/// Writes the index and draw of every accepted item to the front of `hits`,
+/// which must be as long as `words`, returning how many there are.
+pub(crate) fn r_block(total: u64, cap: u64, words: &[u64], hits: &mut [(usize, u64)]) -> usize {
+    let mut n = 0;
+    for (i, (&word, n_items)) in words.iter().zip(total + 1..).enumerate() {
+        let r = ((u128::from(word) * u128::from(n_items)) >> 64) as u64;
+        // 不论是否入选都写入，入选的才往前推
+        if let Some(hit) = hits.get_mut(n) {
+            *hit = (i, r + 1);
+        }
+        n += usize::from(r < cap);
+    }
+    n
+}
+
+/// Puts an accepted item into the pool according to draw `r`, which is no
+/// greater than the capacity, so it fits a `usize`, and so does `total` while
+/// the pool fills up.
+pub(crate) fn r_place<T, P>(pool: &mut P, total: u64, r: u64, it: T) -> Option<T>
+where
+    P: SampleStore<T> + ?Sized,
+{
+    let replaced = pool.set(r as usize - 1, it);
+    // 填充阶段第 total 个槽位还空着，换下的条目挪过去，前 total 个槽位
+    // 因而恰好是前 total 个条目
+    if total <= pool.capacity() as u64 && r < total {
+        if let Some(moved) = replaced {
+            return pool.set(total as usize - 1, moved);
+        }
+    }
+
+    replaced
+}
+
+/// Which algorithm a [`Reservoir`](crate::Reservoir) samples with. All of them
+/// keep a uniform sample; they differ in how much work an item costs once the
+/// pool is full.
+///
+/// - `R` draws a random number for every item. It is the simplest, and the
+///   only one reproducible across platforms, see
+///   [`Deterministic`](crate::Deterministic).
+/// - `L` draws how many items to skip before the next replacement, with a
+///   constant number of draws per replacement. Skipped items cost almost
+///   nothing, so it is the fastest when the stream is much longer than the
+///   pool. It uses floating point math.
+/// - `X` also skips, but finds the skip length by a sequential search, which
+///   costs a little per skipped item. It sits between `R` and `L`.
+///
+/// `cargo bench` compares them.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Algorithm {
+    #[default]
+    R,
+    L,
+    X,
+}
+
+/// A uniform draw from `(0, 1]`, which is safe to take the logarithm of.
+pub(crate) fn unit<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    1.0 - rng.gen::<f64>()
+}
+
+/// Algorithm L: the next value of `w`, which shrinks with every replacement.
+/// Start from `w = 1.0` when the pool becomes full.
+pub(crate) fn l_next_w<R: Rng + ?Sized>(w: f64, k: usize, rng: &mut R) -> f64 {
+    w * (unit(rng).ln() / k as f64).exp()
+}
+
+/// Algorithm L: how many items to skip before the next replacement.
+pub(crate) fn l_skip<R: Rng + ?Sized>(w: f64, rng: &mut R) -> u64 {
+    (unit(rng).ln() / (1.0 - w).ln()).floor() as u64
+}
+
+/// Algorithm X: how many items to skip before the next replacement, when `t`
+/// items have passed through a pool of `k` slots.
+pub(crate) fn x_skip<R: Rng + ?Sized>(t: u64, k: usize, rng: &mut R) -> u64 {
+    let v = rng.gen::<f64>();
+    let k = k as u64;
+
+    // 逐个累乘“被跳过”的概率，直到低于 v
+    let mut s = 0;
+    let mut quot = (t + 1 - k) as f64 / (t + 1) as f64;
+    while quot > v {
+        s += 1;
+        quot *= (t + 1 + s - k) as f64 / (t + 1 + s) as f64;
+    }
+    s
+}
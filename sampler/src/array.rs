@@ -0,0 +1,121 @@
+//! A reservoir whose capacity is known at compile time.
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::{self, Deterministic};
+use crate::weights::{uniform, LockWithWeights, Sampled};
+use crate::{algorithm, Inspectable, Lockable, StreamSampler};
+
+/// An `ArrayReservoir` works exactly like a [`Reservoir`](crate::Reservoir),
+/// but holds its `K` slots in an inline array, so sampling never allocates.
+/// Only `lock` hands out a `Vec`; use `lock_array` to stay allocation free.
+pub struct ArrayReservoir<T, const K: usize, R = ThreadRng> {
+    total: u64,
+    pool: [Option<T>; K],
+    rng: R,
+}
+
+impl<T, const K: usize> ArrayReservoir<T, K> {
+    pub fn new() -> Self {
+        Self::with_rng(thread_rng())
+    }
+}
+
+impl<T, const K: usize> Default for ArrayReservoir<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const K: usize> ArrayReservoir<T, K, Deterministic> {
+    pub fn deterministic(seed: u64) -> Self {
+        Self::with_rng(Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, const K: usize, R: Rng> ArrayReservoir<T, K, R> {
+    pub fn with_rng(rng: R) -> Self {
+        Self {
+            total: 0,
+            pool: std::array::from_fn(|_| None),
+            rng,
+        }
+    }
+
+    /// The same as `lock`, without moving the pool into a `Vec`.
+    pub fn lock_array(mut self) -> [Option<T>; K] {
+        rng::compact_and_shuffle(&mut self.pool, &mut self.rng);
+        self.pool
+    }
+}
+
+impl<T, const K: usize, R: Rng> StreamSampler for ArrayReservoir<T, K, R> {
+    type Item = T;
+    type Draw = u64;
+
+    fn sample(&mut self, it: Self::Item) -> (u64, u64, Option<Self::Item>) {
+        self.total += 1;
+
+        if K == 0 {
+            return (0, self.total, None);
+        }
+
+        let (r, replaced) = algorithm::r(&mut self.pool, self.total, it, &mut self.rng);
+        (r, self.total, replaced)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        (1..=K as u64).contains(draw)
+    }
+}
+
+impl<T, const K: usize, R: Rng> Inspectable for ArrayReservoir<T, K, R> {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        &self.pool[..]
+    }
+}
+
+impl<T, const K: usize, R: Rng> Lockable for ArrayReservoir<T, K, R> {
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        self.lock_array().into()
+    }
+
+    fn lock_iter(self) -> impl Iterator<Item = Self::Item> {
+        IntoIterator::into_iter(self.lock_array()).flatten()
+    }
+}
+
+impl<T, const K: usize, R: Rng> LockWithWeights for ArrayReservoir<T, K, R> {
+    type Item = T;
+
+    fn lock_with_weights(self) -> Vec<Sampled<T>> {
+        let probability = uniform(K, self.total);
+        self.lock_iter()
+            .map(|item| Sampled { item, probability })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reservoir;
+
+    #[test]
+    fn same_as_reservoir() {
+        let mut array = ArrayReservoir::<u32, 4, Deterministic>::deterministic(8);
+        let mut vec = Reservoir::<u32, Deterministic>::deterministic(4, 8);
+        for it in 0..50 {
+            assert_eq!(array.sample(it), vec.sample(it));
+        }
+
+        assert_eq!(array.lock(), vec.lock());
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut reservoir = ArrayReservoir::<u32, 0>::new();
+        assert_eq!(reservoir.sample(1), (0, 1, None));
+        assert_eq!(reservoir.lock_array(), []);
+    }
+}
@@ -0,0 +1,142 @@
+//! Sampling rows straight out of Arrow record batches, like the ones a Parquet
+//! file reads into, without turning them into row structs. Enabled by the
+//! `arrow` feature.
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_schema::ArrowError;
+use arrow_select::interleave::interleave_record_batch;
+
+use crate::{Algorithm, Reservoir, StreamSampler};
+
+/// Samples `k` rows out of every batch `reader` reads, into one batch of the
+/// reader's schema, in random order. A `ParquetRecordBatchReader` is such a
+/// reader.
+///
+/// Only row positions go through the reservoir. After every batch, the rows
+/// it left held are copied out, and the batch itself is dropped; copied rows
+/// which are replaced later are cleaned up once they outnumber the held ones.
+pub fn sample_record_batches<I: RecordBatchReader>(
+    reader: I,
+    k: usize,
+) -> Result<RecordBatch, ArrowError> {
+    let schema = reader.schema();
+    // (第几份拷贝, 拷贝中的第几行)
+    let mut reservoir = Reservoir::<(usize, usize)>::with_algorithm(k, Algorithm::L);
+    let mut parts: Vec<RecordBatch> = Vec::new();
+    let mut copied = 0;
+
+    for batch in reader {
+        let batch = batch?;
+        let part = parts.len();
+        for row in 0..batch.num_rows() {
+            let _ = reservoir.sample((part, row));
+        }
+
+        let mut rows = Vec::new();
+        for at in reservoir.pool.iter_mut().flatten() {
+            if at.0 == part {
+                rows.push((0, at.1));
+                at.1 = rows.len() - 1;
+            }
+        }
+        if rows.is_empty() {
+            continue;
+        }
+        parts.push(interleave_record_batch(&[&batch], &rows)?);
+        copied += rows.len();
+
+        if copied > 2 * k {
+            let held = gather(&parts, &mut reservoir.pool)?;
+            copied = held.num_rows();
+            parts = vec![held];
+        }
+    }
+
+    reservoir.compact_and_shuffle();
+    if parts.is_empty() {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+    gather(&parts, &mut reservoir.pool)
+}
+
+/// Copies the held rows out of `parts` into one batch, pointing the held
+/// positions at it.
+fn gather(
+    parts: &[RecordBatch],
+    held: &mut [Option<(usize, usize)>],
+) -> Result<RecordBatch, ArrowError> {
+    let parts: Vec<&RecordBatch> = parts.iter().collect();
+    let mut rows = Vec::new();
+    for at in held.iter_mut().flatten() {
+        rows.push(*at);
+        *at = (0, rows.len() - 1);
+    }
+    interleave_record_batch(&parts, &rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Array, Int64Array, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+
+    fn batches(sizes: &[i64]) -> RecordBatchIterator<Vec<Result<RecordBatch, ArrowError>>> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+
+        let mut start = 0;
+        let mut batches = Vec::new();
+        for &size in sizes {
+            let ids: Vec<i64> = (start..start + size).collect();
+            let names: Vec<String> = ids.iter().map(|id| format!("row {}", id)).collect();
+            batches.push(RecordBatch::try_new(
+                Arc::clone(&schema),
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            ));
+            start += size;
+        }
+        RecordBatchIterator::new(batches, schema)
+    }
+
+    #[test]
+    fn rows_stay_whole() {
+        let sample = sample_record_batches(batches(&[100, 0, 7, 1000, 3]), 10).unwrap();
+        assert_eq!(sample.num_rows(), 10);
+
+        let ids = sample
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        let names = sample
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let mut seen: Vec<i64> = ids.values().to_vec();
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), 10);
+        for i in 0..sample.num_rows() {
+            assert!((0..1110).contains(&ids.value(i)));
+            assert_eq!(names.value(i), format!("row {}", ids.value(i)));
+        }
+    }
+
+    #[test]
+    fn short_tables_are_kept_whole() {
+        let sample = sample_record_batches(batches(&[2, 1]), 10).unwrap();
+        assert_eq!(sample.num_rows(), 3);
+
+        let empty = sample_record_batches(batches(&[]), 10).unwrap();
+        assert_eq!(empty.num_rows(), 0);
+        assert_eq!(empty.num_columns(), 2);
+    }
+}
@@ -0,0 +1,217 @@
+//! Sampling a skewed stream into a sample of about as many items of every
+//! label, like a balanced evaluation set.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::{below_u64, shuffle};
+use crate::{Deterministic, Lockable, StreamSampler};
+
+struct Label<T> {
+    seen: u64,
+    items: Vec<T>,
+}
+
+/// A `BalancedSampler` keeps a reservoir of at most `per_label` items for
+/// every label, as `label` tells it, each a uniform sample of the items of
+/// its label. Locking trims every label to as many items as the rarest one
+/// holds, so the sample is balanced.
+///
+/// It keeps a reservoir for every label it sees, so it suits a bounded
+/// number of labels.
+pub struct BalancedSampler<L, T, F, R = ThreadRng> {
+    per_label: usize,
+    label: F,
+    total: u64,
+    // 按首次出现的顺序存放，裁剪时的抽取顺序因而是确定的
+    index: HashMap<L, usize>,
+    labels: Vec<(L, Label<T>)>,
+    rng: R,
+}
+
+impl<L: Hash + Eq + Clone, T, F: FnMut(&T) -> L> BalancedSampler<L, T, F> {
+    pub fn new(per_label: usize, label: F) -> Self {
+        Self::with_rng(per_label, label, thread_rng())
+    }
+}
+
+impl<L: Hash + Eq + Clone, T, F: FnMut(&T) -> L> BalancedSampler<L, T, F, Deterministic> {
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(per_label: usize, label: F, seed: u64) -> Self {
+        Self::with_rng(per_label, label, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<L: Hash + Eq + Clone, T, F: FnMut(&T) -> L, R: Rng> BalancedSampler<L, T, F, R> {
+    pub fn with_rng(per_label: usize, label: F, rng: R) -> Self {
+        Self {
+            per_label,
+            label,
+            total: 0,
+            index: HashMap::new(),
+            labels: Vec::new(),
+            rng,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// How many items of `label` were fed, held or not.
+    pub fn seen(&self, label: &L) -> u64 {
+        self.index.get(label).map_or(0, |&i| self.labels[i].1.seen)
+    }
+
+    /// The held items of every label, untrimmed, in the order the labels
+    /// were first seen.
+    pub fn labels(&self) -> impl Iterator<Item = (&L, &[T])> {
+        self.labels
+            .iter()
+            .map(|(label, l)| (label, l.items.as_slice()))
+    }
+
+    /// How many items of every label a balanced sample takes, the count of
+    /// the rarest label held.
+    pub fn balanced_count(&self) -> usize {
+        self.labels
+            .iter()
+            .map(|(_, l)| l.items.len())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Locks the sampler, handing out the balanced sample by label: a
+    /// uniform sample of `balanced_count` items of every label.
+    pub fn into_labels(self) -> HashMap<L, Vec<T>> {
+        self.trim().0.into_iter().collect()
+    }
+
+    /// The trimmed labels, in the order they were first seen.
+    fn trim(self) -> (Vec<(L, Vec<T>)>, R) {
+        let n = self.balanced_count();
+        let mut rng = self.rng;
+        let labels = self
+            .labels
+            .into_iter()
+            .map(|(label, mut l)| {
+                // 先打乱再截断，留下的仍是均匀的
+                shuffle(&mut l.items, &mut rng);
+                l.items.truncate(n);
+                (label, l.items)
+            })
+            .collect();
+        (labels, rng)
+    }
+}
+
+impl<L: Hash + Eq + Clone, T, F: FnMut(&T) -> L, R: Rng> StreamSampler
+    for BalancedSampler<L, T, F, R>
+{
+    type Item = T;
+    /// Whether the item is held in the reservoir of its label.
+    type Draw = bool;
+
+    fn sample(&mut self, it: T) -> (bool, u64, Option<T>) {
+        self.total += 1;
+        let label = (self.label)(&it);
+        let labels = &mut self.labels;
+        let i = *self.index.entry(label).or_insert_with_key(|label| {
+            let new = Label {
+                seen: 0,
+                items: Vec::new(),
+            };
+            labels.push((label.clone(), new));
+            labels.len() - 1
+        });
+        let l = &mut self.labels[i].1;
+        l.seen += 1;
+
+        if l.items.len() < self.per_label {
+            l.items.push(it);
+            return (true, self.total, None);
+        }
+        let j = below_u64(&mut self.rng, l.seen);
+        match usize::try_from(j).ok().and_then(|j| l.items.get_mut(j)) {
+            Some(slot) => (true, self.total, Some(std::mem::replace(slot, it))),
+            None => (false, self.total, None),
+        }
+    }
+
+    fn is_held(&self, draw: &bool) -> bool {
+        *draw
+    }
+}
+
+/// Hands out the balanced sample of every label, shuffled together.
+impl<L: Hash + Eq + Clone, T, F: FnMut(&T) -> L, R: Rng> Lockable for BalancedSampler<L, T, F, R> {
+    fn lock(self) -> Vec<Option<T>> {
+        let (labels, mut rng) = self.trim();
+        let mut held: Vec<Option<T>> = labels
+            .into_iter()
+            .flat_map(|(_, items)| items)
+            .map(Some)
+            .collect();
+        shuffle(&mut held, &mut rng);
+        held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balances_skewed_labels() {
+        // 标签为是否整除 100，正例只占百分之一
+        let mut sampler = BalancedSampler::deterministic(50, |it: &u32| it.is_multiple_of(100), 1);
+        let _ = sampler.sample_all(0..2_000);
+        assert_eq!(sampler.seen(&true), 20);
+        assert_eq!(sampler.balanced_count(), 20);
+
+        let labels = sampler.into_labels();
+        assert_eq!(labels[&true].len(), 20);
+        assert_eq!(labels[&false].len(), 20);
+        assert!(labels[&false].iter().all(|it| it % 100 != 0));
+
+        let mut sampler = BalancedSampler::deterministic(10, |it: &u32| it % 3, 1);
+        let _ = sampler.sample_all(0..10_000);
+        let held = sampler.lock_compact();
+        assert_eq!(held.len(), 30);
+        for label in 0..3 {
+            assert_eq!(held.iter().filter(|&&it| it % 3 == label).count(), 10);
+        }
+    }
+
+    #[test]
+    fn lock_is_reproducible() {
+        let lock = || {
+            let mut sampler = BalancedSampler::deterministic(5, |it: &u32| it % 7, 3);
+            let _ = sampler.sample_all(0..1_000);
+            sampler.lock()
+        };
+        // 每次的 HashMap 种子都不一样，结果却要相同
+        assert_eq!(lock(), lock());
+    }
+
+    #[test]
+    fn labels_stay_uniform() {
+        let mut counts = [0u32; 20];
+        for seed in 0..2_000 {
+            let mut sampler = BalancedSampler::deterministic(8, |it: &usize| it % 2, seed);
+            let _ = sampler.sample_all(0..20);
+            for it in sampler.into_labels().into_values().flatten() {
+                counts[it] += 1;
+            }
+        }
+        // 每个条目的入选概率都应接近 8 / 10
+        assert!(
+            counts.iter().all(|&c| (1_500..=1_700).contains(&c)),
+            "{:?}",
+            counts
+        );
+    }
+}
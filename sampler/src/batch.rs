@@ -0,0 +1,145 @@
+//! Sampling a slice of items at once, much faster than one `sample` call per
+//! item once the pool is full.
+use std::convert::TryFrom;
+
+use rand::Rng;
+
+use crate::{algorithm, Algorithm, Reservoir, StreamSampler};
+
+/// How many random words Algorithm R draws at once.
+const BLOCK: usize = 256;
+
+impl<T: Clone, R: Rng> Reservoir<T, R> {
+    /// Samples a clone of every item of `items`, like `sample_all`, returning
+    /// how many were held.
+    ///
+    /// Once the pool is full, Algorithm R draws random words for a block of
+    /// items at once and picks the accepted ones without branching, cloning
+    /// only those; Algorithm L and X jump over the items they skip. With no
+    /// deletions or pins pending, that is, see [`Reservoir::delete`] and
+    /// [`Reservoir::pin`], which sample item by item.
+    ///
+    /// Algorithm R draws differently here than in `sample`, so a seed gives
+    /// another, though just as uniform, sample than item by item.
+    pub fn sample_slice(&mut self, items: &[T]) -> u64 {
+        let cap = self.pool.len();
+        let mut held = 0;
+        let mut rest = items;
+        // 填充阶段、有删除或固定槽位时逐个抽取
+        while let Some((it, tail)) = rest.split_first() {
+            if cap > 0 && self.total >= cap as u64 && self.deleted == 0 && self.pins.is_empty() {
+                break;
+            }
+            let (r, _, _) = self.sample(it.clone());
+            held += u64::from(self.is_held(&r));
+            rest = tail;
+        }
+
+        held + match self.algorithm {
+            Algorithm::R => self.sample_slice_r(rest),
+            Algorithm::L | Algorithm::X => self.sample_slice_skipping(rest),
+        }
+    }
+
+    fn sample_slice_r(&mut self, items: &[T]) -> u64 {
+        let cap = self.pool.len() as u64;
+        let mut held = 0;
+        let mut words = [0u64; BLOCK];
+        let mut hits = [(0usize, 0u64); BLOCK];
+        for block in items.chunks(BLOCK) {
+            let words = &mut words[..block.len()];
+            self.rng.fill(words);
+            let base = self.total;
+            let n = algorithm::r_block(base, cap, words, &mut hits);
+            for &(i, r) in &hits[..n] {
+                if let Some(it) = block.get(i) {
+                    self.total = base + i as u64 + 1;
+                    let _ = self.place_r(r, it.clone());
+                }
+            }
+            self.total = base + block.len() as u64;
+            held += n as u64;
+        }
+        held
+    }
+
+    fn sample_slice_skipping(&mut self, items: &[T]) -> u64 {
+        let mut held = 0;
+        let mut rest = items;
+        while !rest.is_empty() {
+            // 跳过的条目只计数，不必逐个经过
+            let jump = usize::try_from(self.skip).map_or(rest.len(), |skip| skip.min(rest.len()));
+            self.total += jump as u64;
+            self.skip -= jump as u64;
+            rest = &rest[jump..];
+
+            if let Some((it, tail)) = rest.split_first() {
+                let (r, _, _) = self.sample(it.clone());
+                held += u64::from(self.is_held(&r));
+                rest = tail;
+            }
+        }
+        held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Inspectable};
+
+    #[test]
+    fn same_bookkeeping_as_sample() {
+        let items: Vec<u64> = (0..10_000).collect();
+        for algorithm in [Algorithm::R, Algorithm::L, Algorithm::X] {
+            let rng = Deterministic::seed_from_u64(1);
+            let mut reservoir = Reservoir::with_rng_and_algorithm(10, rng, algorithm);
+            let held = reservoir.sample_slice(&items[..5]) + reservoir.sample_slice(&items[5..]);
+            assert_eq!(reservoir.total(), 10_000);
+            assert!(held >= 10);
+
+            let mut sample: Vec<u64> = reservoir.samples().iter().flatten().copied().collect();
+            sample.sort_unstable();
+            sample.dedup();
+            assert_eq!(sample.len(), 10);
+            // 到达顺序与条目本身一致
+            let by_arrival: Vec<u64> = reservoir
+                .samples_by_arrival()
+                .into_iter()
+                .copied()
+                .collect();
+            assert!(
+                by_arrival.windows(2).all(|w| w[0] < w[1]),
+                "{:?}",
+                algorithm
+            );
+        }
+
+        let mut empty = Reservoir::<u64, Deterministic>::deterministic(0, 1);
+        assert_eq!(empty.sample_slice(&items), 0);
+        assert_eq!(empty.total(), 10_000);
+    }
+
+    #[test]
+    fn slices_stay_uniform() {
+        let items: Vec<usize> = (0..1_000).collect();
+        for algorithm in [Algorithm::R, Algorithm::L] {
+            let mut counts = [0u32; 10];
+            for seed in 0..2_000 {
+                let rng = Deterministic::seed_from_u64(seed);
+                let mut reservoir = Reservoir::with_rng_and_algorithm(10, rng, algorithm);
+                let _ = reservoir.sample_slice(&items);
+                for &it in reservoir.samples().iter().flatten() {
+                    counts[it / 100] += 1;
+                }
+            }
+            // 每一百个条目约中 2000 次
+            assert!(
+                counts.iter().all(|&c| (1_850..=2_150).contains(&c)),
+                "{:?} {:?}",
+                algorithm,
+                counts
+            );
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Bottom-k sampling: every item draws a random key, and the `k` smallest keys
+//! win. The keys make merging exact, which suits samples taken apart.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::merge::{self, MergeError, Mergeable, WireItem};
+use crate::rng::Deterministic;
+
+struct Keyed<T> {
+    key: u64,
+    item: T,
+}
+
+impl<T> PartialEq for Keyed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for Keyed<T> {}
+
+impl<T> PartialOrd for Keyed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Keyed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// A `BottomK` keeps the `k` items of the smallest random keys, a uniform
+/// sample of the items it saw. Merging two of them keeps the `k` smallest
+/// keys of both, exactly the sample one `BottomK` would keep over both parts.
+pub struct BottomK<T, R = ThreadRng> {
+    k: usize,
+    total: u64,
+    // 大顶堆，堆顶是最先被挤出去的
+    held: BinaryHeap<Keyed<T>>,
+    rng: R,
+}
+
+impl<T> BottomK<T> {
+    pub fn with_capacity(k: usize) -> Self {
+        Self::with_rng(k, thread_rng())
+    }
+}
+
+impl<T> BottomK<T, Deterministic> {
+    pub fn deterministic(k: usize, seed: u64) -> Self {
+        Self::with_rng(k, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, R: Rng> BottomK<T, R> {
+    pub fn with_rng(k: usize, rng: R) -> Self {
+        Self {
+            k,
+            total: 0,
+            held: BinaryHeap::with_capacity(k),
+            rng,
+        }
+    }
+
+    /// Returns whether the item is held right now.
+    pub fn sample(&mut self, it: T) -> bool {
+        self.total += 1;
+        let key = self.rng.gen::<u64>();
+        self.offer(key, it)
+    }
+
+    fn offer(&mut self, key: u64, item: T) -> bool {
+        if self.held.len() < self.k {
+            self.held.push(Keyed { key, item });
+            return true;
+        }
+        if self.held.peek().is_none_or(|top| key >= top.key) {
+            return false;
+        }
+
+        let _ = self.held.pop();
+        self.held.push(Keyed { key, item });
+        true
+    }
+
+    /// How many items have been passed through so far.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The held items, in no particular order.
+    pub fn samples(&self) -> impl Iterator<Item = &T> {
+        self.held.iter().map(|keyed| &keyed.item)
+    }
+
+    /// End the sampling process, handing out the held items by their keys,
+    /// smallest first.
+    pub fn lock(self) -> Vec<T> {
+        self.held
+            .into_sorted_vec()
+            .into_iter()
+            .map(|keyed| keyed.item)
+            .collect()
+    }
+}
+
+impl<T: WireItem, R: Rng> Mergeable for BottomK<T, R> {
+    type Rng = R;
+
+    fn merge(&mut self, other: Self) -> Result<(), MergeError> {
+        if other.k < self.k {
+            return Err(MergeError::TooSmall);
+        }
+
+        self.total += other.total;
+        for keyed in other.held {
+            let _ = self.offer(keyed.key, keyed.item);
+        }
+        Ok(())
+    }
+
+    /// The keys travel with the items, so the receiver can merge exactly.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        merge::put_header(&mut out, merge::BOTTOM_K);
+        for n in [self.k as u64, self.total, self.held.len() as u64] {
+            merge::put_varint(&mut out, u128::from(n));
+        }
+        for keyed in self.held.iter() {
+            merge::put_varint(&mut out, u128::from(keyed.key));
+            keyed.item.encode(&mut out);
+        }
+        out
+    }
+
+    fn from_bytes_with_rng(bytes: &[u8], rng: R) -> Result<Self, MergeError> {
+        let input = &mut &bytes[..];
+        merge::get_header(input, merge::BOTTOM_K)?;
+        let k = merge::get_usize(input)?;
+        let total = merge::get_u64(input)?;
+        let held = merge::get_usize(input)?;
+        if held > k || held as u64 > total {
+            return Err(MergeError::Malformed);
+        }
+
+        let held = merge::get_items(input, held, |input| {
+            let key = u64::decode(input)?;
+            Ok(Keyed {
+                key,
+                item: T::decode(input)?,
+            })
+        })?;
+        Ok(Self {
+            k,
+            total,
+            held: held.into(),
+            rng,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_is_exact() {
+        let mut whole = BottomK::<u32, Deterministic>::deterministic(5, 9);
+        let mut a = BottomK::<u32, Deterministic>::deterministic(5, 9);
+        for it in 0..500 {
+            assert_eq!(whole.sample(it), a.sample(it));
+        }
+
+        // 同一个种子接着抽，b 拿到的键和 whole 后半段一样
+        let mut b = BottomK::with_rng(5, whole.rng.clone());
+        for it in 500..1000 {
+            let _ = whole.sample(it);
+            let _ = b.sample(it);
+        }
+
+        let b = BottomK::from_bytes_with_rng(&b.to_bytes(), Deterministic::seed_from_u64(0));
+        a.merge(b.unwrap()).unwrap();
+        assert_eq!(a.total(), 1000);
+        assert_eq!(a.lock(), whole.lock());
+    }
+
+    #[test]
+    fn small_parts_are_kept_whole() {
+        let mut a = BottomK::<String>::with_capacity(4);
+        let mut b = BottomK::<String>::with_capacity(4);
+        let _ = a.sample("a".into());
+        let _ = b.sample("b".into());
+
+        a.merge(b).unwrap();
+        let mut held: Vec<_> = a.samples().cloned().collect();
+        held.sort();
+        assert_eq!(held, ["a", "b"]);
+
+        let small = BottomK::<String>::with_capacity(3);
+        assert_eq!(a.merge(small), Err(MergeError::TooSmall));
+    }
+}
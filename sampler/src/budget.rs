@@ -0,0 +1,222 @@
+//! Sampling items of very different sizes under a memory budget rather than an
+//! item count.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::algorithm::unit;
+
+struct Entry<T> {
+    key: u64,
+    size: usize,
+    it: T,
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// A `BytesBudgetReservoir` gives every item a random priority and holds the
+/// items with the lowest priorities that fit into `budget` bytes together, as
+/// measured by the size function. An item bigger than the whole budget is
+/// never held.
+///
+/// Priorities are drawn by Efraimidis and Spirakis' A-Res, `-ln(u) / w` for
+/// a uniform `u` and the weight `w` of the item, so the heavier an item, the
+/// more likely it is to get a low priority and stay. Every item weighs 1
+/// unless [`weighted_by`](Self::weighted_by) tells otherwise. A big item is
+/// still more likely to be pushed out again, since it takes more of the
+/// budget.
+pub struct BytesBudgetReservoir<T, F, R = ThreadRng, W = fn(&T) -> f64> {
+    total: u64,
+    budget: usize,
+    used: usize,
+    // 被挤出去的最小优先级，之后优先级不低于它的都不要
+    threshold: u64,
+    heap: BinaryHeap<Entry<T>>,
+    size_of: F,
+    weight_of: W,
+    rng: R,
+}
+
+fn unit_weight<T>(_: &T) -> f64 {
+    1.0
+}
+
+impl<T, F: Fn(&T) -> usize> BytesBudgetReservoir<T, F> {
+    pub fn new(budget: usize, size_of: F) -> Self {
+        Self::with_rng(budget, size_of, thread_rng())
+    }
+}
+
+impl<T, F: Fn(&T) -> usize, R: Rng> BytesBudgetReservoir<T, F, R> {
+    pub fn with_rng(budget: usize, size_of: F, rng: R) -> Self {
+        Self {
+            total: 0,
+            budget,
+            used: 0,
+            threshold: u64::MAX,
+            heap: BinaryHeap::new(),
+            size_of,
+            weight_of: unit_weight,
+            rng,
+        }
+    }
+
+    /// Weighs the items sampled from now on by `weight_of`. An item whose
+    /// weight is not positive and finite is never held.
+    pub fn weighted_by<W: Fn(&T) -> f64>(self, weight_of: W) -> BytesBudgetReservoir<T, F, R, W> {
+        BytesBudgetReservoir {
+            total: self.total,
+            budget: self.budget,
+            used: self.used,
+            threshold: self.threshold,
+            heap: self.heap,
+            size_of: self.size_of,
+            weight_of,
+            rng: self.rng,
+        }
+    }
+}
+
+impl<T, F, R, W> BytesBudgetReservoir<T, F, R, W>
+where
+    F: Fn(&T) -> usize,
+    R: Rng,
+    W: Fn(&T) -> f64,
+{
+    /// ## Return
+    /// a tuple contains 3 elements:
+    /// - a `u64` stands for the priority the current item gets, the bits of
+    ///   its A-Res priority, which sort the same way; `u64::MAX` for an item
+    ///   of an invalid weight
+    /// - a `u64` stands for how many items has been passed through so far
+    /// - the items pushed out to make room, the current one included if it
+    ///   does not fit after all.
+    pub fn sample(&mut self, it: T) -> (u64, u64, Vec<T>) {
+        self.total += 1;
+
+        let w = (self.weight_of)(&it);
+        if !(w > 0.0 && w.is_finite()) {
+            return (u64::MAX, self.total, Vec::new());
+        }
+        // 非负浮点数的位模式与数值同序
+        let key = (-unit(&mut self.rng).ln() / w).to_bits();
+        let size = (self.size_of)(&it);
+        let mut evicted = Vec::new();
+        if key >= self.threshold || size > self.budget {
+            return (key, self.total, evicted);
+        }
+
+        self.used += size;
+        self.heap.push(Entry { key, size, it });
+        while self.used > self.budget {
+            match self.heap.pop() {
+                Some(out) => {
+                    self.used -= out.size;
+                    self.threshold = out.key;
+                    evicted.push(out.it);
+                }
+                None => break,
+            }
+        }
+
+        (key, self.total, evicted)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Bytes taken by the held items, never more than the budget.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// The held items, in no particular order.
+    pub fn samples(&self) -> impl Iterator<Item = &T> {
+        self.heap.iter().map(|e| &e.it)
+    }
+
+    /// End the sampling process. The items come out in random order.
+    pub fn lock(self) -> Vec<T> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|e| e.it)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stays_within_budget() {
+        let mut reservoir = BytesBudgetReservoir::new(100, |it: &String| it.len());
+        for i in 0..1000 {
+            let _ = reservoir.sample("x".repeat(i % 30));
+            assert!(reservoir.used() <= 100);
+        }
+
+        let used: usize = reservoir.samples().map(|it| it.len()).sum();
+        assert_eq!(used, reservoir.used());
+        assert!(!reservoir.lock().is_empty());
+    }
+
+    #[test]
+    fn oversized_never_held() {
+        let mut reservoir = BytesBudgetReservoir::new(10, |it: &Vec<u8>| it.len());
+        let _ = reservoir.sample(vec![0; 11]);
+        let _ = reservoir.sample(vec![0; 3]);
+
+        assert_eq!(reservoir.lock(), vec![vec![0; 3]]);
+    }
+
+    #[test]
+    fn heavier_items_stay_more_often() {
+        let mut rng = crate::Deterministic::seed_from_u64(1);
+        // 偶数权重 4，奇数权重 1，每个 1 字节，预算只够留 5 个
+        let mut stayed = [0u32; 2];
+        for _ in 0..2_000 {
+            let mut reservoir = BytesBudgetReservoir::with_rng(5, |_: &u32| 1, &mut rng)
+                .weighted_by(|&it: &u32| if it % 2 == 0 { 4.0 } else { 1.0 });
+            for it in 0..20 {
+                let _ = reservoir.sample(it);
+            }
+            for it in reservoir.lock() {
+                stayed[(it % 2) as usize] += 1;
+            }
+        }
+
+        assert_eq!(stayed[0] + stayed[1], 10_000);
+        assert!(stayed[0] > 3 * stayed[1], "{:?}", stayed);
+
+        let mut reservoir = BytesBudgetReservoir::new(10, |_: &u8| 1).weighted_by(|_| 0.0);
+        assert_eq!(reservoir.sample(1).0, u64::MAX);
+        assert!(reservoir.lock().is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Stopping a long sampling run from elsewhere, like the signal handler of an
+//! operator, leaving the sampler with what it sampled so far to `lock`.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag shared by its clones: cancelling one cancels them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Ends `items` as soon as the token is cancelled.
+    pub fn guard<I: IntoIterator>(&self, items: I) -> Cancellable<I::IntoIter> {
+        Cancellable {
+            items: items.into_iter(),
+            token: self.clone(),
+            every: 1,
+            count: 0,
+            done: false,
+        }
+    }
+}
+
+/// An iterator ending once its [`CancelToken`] is cancelled, see
+/// [`CancelToken::guard`].
+pub struct Cancellable<I> {
+    items: I,
+    token: CancelToken,
+    every: u64,
+    count: u64,
+    done: bool,
+}
+
+impl<I> Cancellable<I> {
+    /// Looks at the token only on every `n` items instead of on every one,
+    /// for items too cheap to sample to pay for even a relaxed atomic load.
+    pub fn check_every(mut self, n: u64) -> Self {
+        self.every = n.max(1);
+        self
+    }
+}
+
+impl<I: Iterator> Iterator for Cancellable<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.done {
+            return None;
+        }
+        if self.count.is_multiple_of(self.every) && self.token.is_cancelled() {
+            self.done = true;
+            return None;
+        }
+        self.count += 1;
+        self.items.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Lockable, Reservoir, StreamSampler};
+
+    #[test]
+    fn stops_when_cancelled() {
+        let token = CancelToken::new();
+        let operator = token.clone();
+        let items = (0..).inspect(move |&it| {
+            if it == 999 {
+                operator.cancel();
+            }
+        });
+
+        let mut reservoir = Reservoir::<u64, Deterministic>::deterministic(10, 1);
+        let _ = reservoir.sample_all_until(items, &token);
+        assert!(token.is_cancelled());
+        assert_eq!(reservoir.total(), 1_000);
+        assert_eq!(reservoir.lock_compact().len(), 10);
+
+        // 已取消的不再取任何条目
+        assert_eq!(token.guard(0..10).count(), 0);
+    }
+
+    #[test]
+    fn checks_on_an_interval() {
+        let token = CancelToken::new();
+        let mut items = token.guard(0..100).check_every(16);
+        assert_eq!(items.by_ref().take(20).count(), 20);
+        token.cancel();
+        // 要到第 32 个才看一眼
+        assert_eq!(items.count(), 12);
+    }
+}
@@ -0,0 +1,272 @@
+//! Cluster sampling: sample the clusters, like user sessions, and keep every
+//! item of the sampled ones, or a sample of them.
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::below_u64;
+use crate::Deterministic;
+
+struct Cluster<T> {
+    priority: u64,
+    seen: u64,
+    items: Vec<T>,
+}
+
+/// A `ClusterSampler` keeps at most `k` clusters, a uniform sample of the
+/// distinct keys. Keys are sampled the way
+/// [`SampledGroupBy`](crate::SampledGroupBy) samples them: by a priority
+/// drawn from their hash, so a cluster is either kept from its first item on
+/// or let go for good, and a kept cluster is complete.
+///
+/// Built by [`ClusterSamplerBuilder::per_cluster`], it samples in two stages
+/// instead: every kept cluster keeps a uniform sample of at most `m` of its
+/// items, like `m` events of each of `k` sessions.
+pub struct ClusterSampler<K, T, S = RandomState, R = ThreadRng> {
+    k: usize,
+    per_cluster: Option<usize>,
+    clusters: HashMap<K, Cluster<T>>,
+    // 优先级 -> 键，最大的最先被淘汰
+    order: BTreeMap<u64, K>,
+    hasher: S,
+    rng: R,
+}
+
+impl<K: Hash + Eq + Clone, T> ClusterSampler<K, T> {
+    pub fn new(k: usize) -> Self {
+        Self::with_hasher(k, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq + Clone, T, S: BuildHasher> ClusterSampler<K, T, S> {
+    /// Priorities come from `hasher`, so a fixed one keeps the same clusters
+    /// from run to run.
+    pub fn with_hasher(k: usize, hasher: S) -> Self {
+        ClusterSamplerBuilder::new(k).hasher(hasher).build()
+    }
+}
+
+impl<K: Hash + Eq + Clone, T, S: BuildHasher, R: Rng> ClusterSampler<K, T, S, R> {
+    /// Feeds an item of the cluster `key`, returning whether the cluster is
+    /// kept. A cluster pushed out takes its items with it.
+    pub fn sample(&mut self, key: K, it: T) -> bool {
+        if let Some(cluster) = self.clusters.get_mut(&key) {
+            add(cluster, it, self.per_cluster, &mut self.rng);
+            return true;
+        }
+
+        let priority = self.hasher.hash_one(&key);
+        // 优先级相同的两个键极少见，后来的直接放弃
+        if self.k == 0 || self.order.contains_key(&priority) {
+            return false;
+        }
+        if self.clusters.len() >= self.k {
+            match self.order.last_key_value() {
+                Some((&max, _)) if priority < max => {
+                    if let Some(evicted) = self.order.remove(&max) {
+                        let _ = self.clusters.remove(&evicted);
+                    }
+                }
+                _ => return false,
+            }
+        }
+
+        let mut cluster = Cluster {
+            priority,
+            seen: 0,
+            items: Vec::new(),
+        };
+        add(&mut cluster, it, self.per_cluster, &mut self.rng);
+        let _ = self.order.insert(cluster.priority, key.clone());
+        let _ = self.clusters.insert(key, cluster);
+        true
+    }
+
+    /// The kept items of the cluster `key`, if it is kept. They are in the
+    /// order they were fed, unless sampled in two stages.
+    pub fn get(&self, key: &K) -> Option<&[T]> {
+        self.clusters.get(key).map(|c| c.items.as_slice())
+    }
+
+    /// How many items of the cluster `key` were fed, kept or not, if it is
+    /// kept. Every kept item stands for `seen / kept` of them.
+    pub fn seen(&self, key: &K) -> Option<u64> {
+        self.clusters.get(key).map(|c| c.seen)
+    }
+
+    /// The kept clusters, in no particular order.
+    pub fn clusters(&self) -> impl Iterator<Item = (&K, &[T])> {
+        self.clusters
+            .iter()
+            .map(|(key, c)| (key, c.items.as_slice()))
+    }
+
+    pub fn into_clusters(self) -> HashMap<K, Vec<T>> {
+        self.clusters
+            .into_iter()
+            .map(|(key, c)| (key, c.items))
+            .collect()
+    }
+}
+
+/// Keeps `it` in `cluster`, or samples it into at most `per_cluster` items
+/// by Algorithm R.
+fn add<T, R: Rng>(cluster: &mut Cluster<T>, it: T, per_cluster: Option<usize>, rng: &mut R) {
+    cluster.seen += 1;
+    match per_cluster {
+        Some(m) if cluster.items.len() >= m => {
+            let j = below_u64(rng, cluster.seen);
+            if let Some(slot) = usize::try_from(j)
+                .ok()
+                .and_then(|j| cluster.items.get_mut(j))
+            {
+                *slot = it;
+            }
+        }
+        _ => cluster.items.push(it),
+    }
+}
+
+/// Configures a [`ClusterSampler`].
+pub struct ClusterSamplerBuilder<S = RandomState, R = ThreadRng> {
+    k: usize,
+    per_cluster: Option<usize>,
+    hasher: S,
+    rng: R,
+}
+
+impl ClusterSamplerBuilder {
+    /// Keeps `k` clusters, and every item of them.
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            per_cluster: None,
+            hasher: RandomState::new(),
+            rng: thread_rng(),
+        }
+    }
+}
+
+impl<S, R> ClusterSamplerBuilder<S, R> {
+    /// Keeps a uniform sample of at most `m` items of every kept cluster.
+    pub fn per_cluster(mut self, m: usize) -> Self {
+        self.per_cluster = Some(m);
+        self
+    }
+
+    /// See [`ClusterSampler::with_hasher`].
+    pub fn hasher<S2: BuildHasher>(self, hasher: S2) -> ClusterSamplerBuilder<S2, R> {
+        ClusterSamplerBuilder {
+            k: self.k,
+            per_cluster: self.per_cluster,
+            hasher,
+            rng: self.rng,
+        }
+    }
+
+    /// The rng sampling the items within the clusters.
+    pub fn rng<R2: Rng>(self, rng: R2) -> ClusterSamplerBuilder<S, R2> {
+        ClusterSamplerBuilder {
+            k: self.k,
+            per_cluster: self.per_cluster,
+            hasher: self.hasher,
+            rng,
+        }
+    }
+
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(self, seed: u64) -> ClusterSamplerBuilder<S, Deterministic> {
+        self.rng(Deterministic::seed_from_u64(seed))
+    }
+
+    pub fn build<K, T>(self) -> ClusterSampler<K, T, S, R> {
+        ClusterSampler {
+            k: self.k,
+            per_cluster: self.per_cluster,
+            clusters: HashMap::new(),
+            order: BTreeMap::new(),
+            hasher: self.hasher,
+            rng: self.rng,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kept_sessions_are_complete() {
+        let mut sessions = ClusterSampler::<u32, (u32, u32)>::new(50);
+        for event in 0..10 {
+            for session in 0..1000 {
+                let _ = sessions.sample(session, (session, event));
+            }
+        }
+
+        assert_eq!(sessions.clusters().count(), 50);
+        for (&session, events) in sessions.clusters() {
+            let expected: Vec<_> = (0..10).map(|event| (session, event)).collect();
+            assert_eq!(events, expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn uniform_over_clusters() {
+        // 大簇和小簇被选中的机会相同
+        let mut big = 0;
+        for _ in 0..1_000 {
+            let mut sampler = ClusterSampler::<u32, u32>::new(1);
+            for it in 0..100 {
+                let _ = sampler.sample(0, it);
+            }
+            for key in 1..4 {
+                let _ = sampler.sample(key, key);
+            }
+            big += usize::from(sampler.get(&0).is_some());
+        }
+        assert!((200..=300).contains(&big), "{}", big);
+
+        let mut none = ClusterSampler::<u32, u32>::new(0);
+        assert!(!none.sample(0, 0));
+        assert!(none.into_clusters().is_empty());
+    }
+
+    #[test]
+    fn two_stages() {
+        let mut sessions = ClusterSamplerBuilder::new(5)
+            .per_cluster(3)
+            .deterministic(27)
+            .build::<u32, u32>();
+        for event in 0..1000 {
+            let _ = sessions.sample(event % 100, event);
+        }
+
+        assert_eq!(sessions.clusters().count(), 5);
+        for (&session, events) in sessions.clusters() {
+            assert_eq!(events.len(), 3);
+            assert!(events.iter().all(|event| event % 100 == session));
+            assert_eq!(sessions.seen(&session), Some(10));
+        }
+
+        // 每个会话内的事件也是均匀抽取的
+        let mut counts = [0u32; 10];
+        for seed in 0..2_000 {
+            let mut sampler = ClusterSamplerBuilder::new(1)
+                .per_cluster(2)
+                .deterministic(seed)
+                .build();
+            for it in 0..10 {
+                let _ = sampler.sample((), it);
+            }
+            for &it in sampler.get(&()).unwrap_or_default() {
+                counts[it] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&c| (320..=480).contains(&c)));
+    }
+}
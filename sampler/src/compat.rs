@@ -0,0 +1,119 @@
+//! `ReservoirSampler` the way it was before 0.2, when it held everything a
+//! sampler does, so code written against it keeps compiling.
+use crate::{Inspectable, Lockable, StreamSampler};
+
+/// Everything a sampler of this crate can do, as the one trait it was before
+/// 0.2. Every sampler implementing [`StreamSampler`], [`Inspectable`] and
+/// [`Lockable`] implements it, so importing it alone still brings their
+/// methods of 0.1 into scope, and a type of another crate may still
+/// implement it alone.
+///
+/// New code imports the three traits through the
+/// [`prelude`](crate::prelude) instead. Importing this one next to them makes
+/// the calls of their methods ambiguous.
+///
+/// ```
+/// // 0.1
+/// use reservoir_sampler::{Reservoir, ReservoirSampler};
+///
+/// let mut reservoir = Reservoir::<u32>::with_capacity(2);
+/// let _ = reservoir.sample(1);
+/// assert_eq!(reservoir.samples(), &[Some(1), None]);
+/// assert_eq!(reservoir.lock_iter().collect::<Vec<_>>(), vec![1]);
+/// ```
+///
+/// ```
+/// // 0.2
+/// use reservoir_sampler::prelude::*;
+/// use reservoir_sampler::Reservoir;
+///
+/// let mut reservoir = Reservoir::<u32>::with_capacity(2);
+/// let _ = reservoir.sample(1);
+/// assert_eq!(reservoir.samples(), &[Some(1), None]);
+/// assert_eq!(reservoir.lock_compact(), vec![1]);
+/// ```
+///
+/// A sampler of another crate implementing it alone still compiles:
+///
+/// ```
+/// use reservoir_sampler::ReservoirSampler;
+///
+/// struct Last(Option<u32>, u64);
+///
+/// impl ReservoirSampler for Last {
+///     type Item = u32;
+///     type Draw = u64;
+///
+///     fn sample(&mut self, it: u32) -> (u64, u64, Option<u32>) {
+///         self.1 += 1;
+///         (1, self.1, self.0.replace(it))
+///     }
+///
+///     fn is_held(&self, draw: &u64) -> bool {
+///         *draw == 1
+///     }
+///
+///     fn samples(&self) -> &[Option<u32>] {
+///         std::slice::from_ref(&self.0)
+///     }
+///
+///     fn lock(self) -> Vec<Option<u32>> {
+///         vec![self.0]
+///     }
+/// }
+///
+/// let mut last = Last(None, 0);
+/// let _ = last.sample(1);
+/// assert_eq!(last.lock_iter().collect::<Vec<_>>(), vec![1]);
+/// ```
+pub trait ReservoirSampler {
+    /// See [`StreamSampler::Item`].
+    type Item;
+    /// See [`StreamSampler::Draw`].
+    type Draw;
+
+    /// See [`StreamSampler::sample`].
+    fn sample(&mut self, it: Self::Item) -> (Self::Draw, u64, Option<Self::Item>);
+
+    /// See [`StreamSampler::is_held`].
+    fn is_held(&self, draw: &Self::Draw) -> bool;
+
+    /// See [`Inspectable::samples`].
+    fn samples(&self) -> &[Option<Self::Item>];
+
+    /// See [`Lockable::lock`].
+    fn lock(self) -> Vec<Option<Self::Item>>;
+
+    /// See [`Lockable::lock_iter`].
+    fn lock_iter(self) -> impl Iterator<Item = Self::Item>
+    where
+        Self: Sized,
+    {
+        self.lock().into_iter().flatten()
+    }
+}
+
+impl<S: StreamSampler + Inspectable + Lockable> ReservoirSampler for S {
+    type Item = <S as StreamSampler>::Item;
+    type Draw = <S as StreamSampler>::Draw;
+
+    fn sample(&mut self, it: Self::Item) -> (Self::Draw, u64, Option<Self::Item>) {
+        StreamSampler::sample(self, it)
+    }
+
+    fn is_held(&self, draw: &Self::Draw) -> bool {
+        StreamSampler::is_held(self, draw)
+    }
+
+    fn samples(&self) -> &[Option<Self::Item>] {
+        Inspectable::samples(self)
+    }
+
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        Lockable::lock(self)
+    }
+
+    fn lock_iter(self) -> impl Iterator<Item = Self::Item> {
+        Lockable::lock_iter(self)
+    }
+}
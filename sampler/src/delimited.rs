@@ -0,0 +1,252 @@
+//! Sampling binary records straight from a reader, like a file of
+//! length-prefixed protobuf messages, without loading it whole.
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+
+use crate::{CancelToken, Lockable, Reservoir, StreamSampler};
+
+/// How the records of a stream are told apart.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Delimiter {
+    /// Every record ends with this byte, like `b'\n'` or `b'\0'`.
+    Byte(u8),
+    /// Every record ends with these bytes, like `b"\r\n"` in RESP. No bytes at
+    /// all is an [`ErrorKind::InvalidInput`].
+    Bytes(Vec<u8>),
+    /// Every record starts with its length as an unsigned varint, as
+    /// protobuf's delimited messages do.
+    Varint,
+    /// Every record starts with its length as a big endian `u32`.
+    U32Be,
+}
+
+/// A uniform sample of `k` records out of `reader`, without their
+/// delimiters, in a random order. A last record lacking its end delimiter is
+/// still taken, but one cut short of its length prefix is an
+/// [`ErrorKind::UnexpectedEof`].
+pub fn sample_delimited<R: Read>(
+    reader: R,
+    delimiter: Delimiter,
+    k: usize,
+) -> io::Result<Vec<Vec<u8>>> {
+    let mut reservoir = Reservoir::with_capacity(k);
+    let _ = sample_delimited_into(reader, &delimiter, &mut reservoir)?;
+    Ok(reservoir.lock_compact())
+}
+
+/// The same as [`sample_delimited`], into any sampler, returning how many
+/// records were read.
+pub fn sample_delimited_into<R: Read, S: StreamSampler<Item = Vec<u8>>>(
+    reader: R,
+    delimiter: &Delimiter,
+    sampler: &mut S,
+) -> io::Result<u64> {
+    sample_records(reader, delimiter, sampler, None)
+}
+
+/// The same as [`sample_delimited_into`], stopping before the next record once
+/// `token` is cancelled.
+pub fn sample_delimited_until<R: Read, S: StreamSampler<Item = Vec<u8>>>(
+    reader: R,
+    delimiter: &Delimiter,
+    sampler: &mut S,
+    token: &CancelToken,
+) -> io::Result<u64> {
+    sample_records(reader, delimiter, sampler, Some(token))
+}
+
+fn sample_records<R: Read, S: StreamSampler<Item = Vec<u8>>>(
+    reader: R,
+    delimiter: &Delimiter,
+    sampler: &mut S,
+    token: Option<&CancelToken>,
+) -> io::Result<u64> {
+    let mut reader = BufReader::new(reader);
+    let mut total = 0;
+    // 读下一条之前先看是否已取消
+    while !token.is_some_and(CancelToken::is_cancelled) {
+        let record = match next_record(&mut reader, delimiter)? {
+            Some(record) => record,
+            None => break,
+        };
+        let _ = sampler.sample(record);
+        total += 1;
+    }
+    Ok(total)
+}
+
+pub(crate) fn next_record<R: BufRead>(
+    reader: &mut R,
+    delimiter: &Delimiter,
+) -> io::Result<Option<Vec<u8>>> {
+    match delimiter {
+        Delimiter::Byte(byte) => until(reader, std::slice::from_ref(byte)),
+        Delimiter::Bytes(bytes) => until(reader, bytes),
+        Delimiter::Varint => match read_varint(reader)? {
+            Some(len) => read_exactly(reader, len).map(Some),
+            None => Ok(None),
+        },
+        Delimiter::U32Be => {
+            let mut prefix = [0u8; 4];
+            if !fill(reader, &mut prefix)? {
+                return Ok(None);
+            }
+            read_exactly(reader, u64::from(u32::from_be_bytes(prefix))).map(Some)
+        }
+    }
+}
+
+/// The bytes up to `end`, or up to the end of the stream.
+fn until<R: BufRead>(reader: &mut R, end: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let last = match end.last() {
+        Some(&last) => last,
+        None => return Err(ErrorKind::InvalidInput.into()),
+    };
+
+    let mut record = Vec::new();
+    loop {
+        if reader.read_until(last, &mut record)? == 0 {
+            // 流结束：没读到任何字节就没有记录
+            return Ok(if record.is_empty() {
+                None
+            } else {
+                Some(record)
+            });
+        }
+        if record.ends_with(end) {
+            record.truncate(record.len() - end.len());
+            return Ok(Some(record));
+        }
+    }
+}
+
+/// Fills `buf`, returning false if the stream had already ended.
+fn fill<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut len = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        if !fill(reader, &mut byte)? {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err(ErrorKind::UnexpectedEof.into())
+            };
+        }
+        len |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(len));
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::InvalidData,
+        "varint longer than 64 bits",
+    ))
+}
+
+/// Reads `len` bytes, growing the buffer as they come, so a corrupt length
+/// does not allocate upfront.
+fn read_exactly<R: Read>(reader: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut record = Vec::new();
+    let _ = reader.take(len).read_to_end(&mut record)?;
+    if usize::try_from(len).map_or(true, |len| record.len() < len) {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Deterministic;
+
+    fn varint(mut n: usize, out: &mut Vec<u8>) {
+        while n >= 0x80 {
+            out.push((n as u8 & 0x7f) | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+
+    #[test]
+    fn every_delimiter() -> io::Result<()> {
+        let records: Vec<Vec<u8>> = (0..200u32).map(|i| vec![1; i as usize]).collect();
+        let mut streams = Vec::new();
+
+        let mut varints = Vec::new();
+        let mut prefixed = Vec::new();
+        let mut resp = Vec::new();
+        for r in &records {
+            varint(r.len(), &mut varints);
+            varints.extend_from_slice(r);
+            prefixed.extend_from_slice(&(r.len() as u32).to_be_bytes());
+            prefixed.extend_from_slice(r);
+            resp.extend_from_slice(r);
+            resp.extend_from_slice(b"\r\n");
+        }
+        streams.push((varints, Delimiter::Varint));
+        streams.push((prefixed, Delimiter::U32Be));
+        streams.push((resp, Delimiter::Bytes(b"\r\n".to_vec())));
+
+        for (stream, delimiter) in streams {
+            let mut reservoir = Reservoir::<_, Deterministic>::deterministic(5, 1);
+            let total = sample_delimited_into(stream.as_slice(), &delimiter, &mut reservoir)?;
+            assert_eq!(total, 200);
+            let sample = reservoir.lock_compact();
+            assert_eq!(sample.len(), 5);
+            assert!(sample.iter().all(|r| records.contains(r)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ends_of_streams() -> io::Result<()> {
+        // 最后一条没有分隔符也算数
+        let mut all = sample_delimited(&b"a\nbb\nccc"[..], Delimiter::Byte(b'\n'), 10)?;
+        all.sort();
+        assert_eq!(all, vec![b"a".to_vec(), b"bb".to_vec(), b"ccc".to_vec()]);
+        assert!(sample_delimited(&b""[..], Delimiter::Varint, 10)?.is_empty());
+
+        let cut = sample_delimited(&[5u8, 1, 2][..], Delimiter::Varint, 10);
+        assert_eq!(cut.map_err(|e| e.kind()), Err(ErrorKind::UnexpectedEof));
+        let cut = sample_delimited(&[0u8, 0][..], Delimiter::U32Be, 10);
+        assert_eq!(cut.map_err(|e| e.kind()), Err(ErrorKind::UnexpectedEof));
+        Ok(())
+    }
+
+    /// A reader cancelling its token on the first read.
+    struct Cancelling<'a>(&'a [u8], CancelToken);
+
+    impl Read for Cancelling<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.1.cancel();
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn stops_when_cancelled() -> io::Result<()> {
+        let token = CancelToken::new();
+        let reader = Cancelling(b"a\nb\nc\n", token.clone());
+        let mut reservoir = Reservoir::<_, Deterministic>::deterministic(5, 1);
+        let total =
+            sample_delimited_until(reader, &Delimiter::Byte(b'\n'), &mut reservoir, &token)?;
+        // 读到的第一条照常抽样，之后就停下
+        assert_eq!(total, 1);
+        assert_eq!(reservoir.lock_compact(), vec![b"a".to_vec()]);
+        Ok(())
+    }
+}
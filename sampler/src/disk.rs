@@ -0,0 +1,243 @@
+//! A reservoir for samples too big to keep in memory. Enabled by the `disk`
+//! feature.
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::algorithm;
+use crate::rng::Deterministic;
+use crate::store::SampleStore;
+
+/// Where the payload of a held item is in the spill file.
+#[derive(Clone, Copy, Debug)]
+struct Loc {
+    offset: u64,
+    len: u64,
+}
+
+/// A `DiskReservoir` only keeps the index of its slots in memory. The payload
+/// of every accepted item is appended to an anonymous temporary file, which is
+/// removed once the reservoir or its locked items are dropped.
+///
+/// Payloads of replaced items are not reclaimed, so the file grows with the
+/// count of accepted items, about `k * ln(n / k)` for `n` items.
+pub struct DiskReservoir<T, R = ThreadRng> {
+    total: u64,
+    index: Vec<Option<Loc>>,
+    file: BufWriter<File>,
+    written: u64,
+    // 写入失败后连文件长度都不知道了，不能再往下写
+    poisoned: bool,
+    rng: R,
+    _item: PhantomData<T>,
+}
+
+impl<T> DiskReservoir<T> {
+    pub fn with_capacity(n: usize) -> io::Result<Self> {
+        Self::with_rng(n, thread_rng())
+    }
+}
+
+impl<T> DiskReservoir<T, Deterministic> {
+    pub fn deterministic(n: usize, seed: u64) -> io::Result<Self> {
+        Self::with_rng(n, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, R: Rng> DiskReservoir<T, R> {
+    pub fn with_rng(n: usize, rng: R) -> io::Result<Self> {
+        Ok(Self {
+            total: 0,
+            index: vec![None; n],
+            file: BufWriter::new(tempfile::tempfile()?),
+            written: 0,
+            poisoned: false,
+            rng,
+            _item: PhantomData,
+        })
+    }
+
+    /// How many items are held right now.
+    pub fn len(&self) -> usize {
+        self.index.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Bytes written to the spill file so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl<T: Serialize, R: Rng> DiskReservoir<T, R> {
+    /// Only an accepted item is serialized and written out.
+    ///
+    /// An item which fails to be written is not counted. Once even the length
+    /// of the spill file is unknown after such a failure, every later call
+    /// fails as well.
+    ///
+    /// ## Return
+    /// a tuple contains 2 elements:
+    /// - a `u64` stands for what random number the current item gets, the
+    ///   item is accepted when it is no greater than the capacity
+    /// - a `u64` stands for how many items has been passed through so far
+    pub fn sample(&mut self, it: T) -> io::Result<(u64, u64)> {
+        if self.poisoned {
+            return Err(io::Error::other(
+                "the spill file is of unknown length after a failed write",
+            ));
+        }
+        self.total += 1;
+
+        if self.index.is_empty() {
+            return Ok((0, self.total));
+        }
+
+        let r = algorithm::r_draw(self.total, &mut self.rng);
+        if r <= self.index.len() as u64 {
+            let loc = match self.spill(&it) {
+                Ok(loc) => loc,
+                Err(e) => {
+                    self.total -= 1;
+                    return Err(e);
+                }
+            };
+            let _ = algorithm::r_place(&mut self.index, self.total, r, loc);
+        }
+
+        Ok((r, self.total))
+    }
+
+    fn spill(&mut self, it: &T) -> io::Result<Loc> {
+        let bytes = bincode::serialize(it).map_err(into_io)?;
+        if let Err(e) = self.file.write_all(&bytes) {
+            // 可能已经写了一部分，之后的偏移要从真正的末尾算起
+            match self.file.stream_position() {
+                Ok(end) => self.written = end,
+                Err(_) => self.poisoned = true,
+            }
+            return Err(e);
+        }
+
+        let loc = Loc {
+            offset: self.written,
+            len: bytes.len() as u64,
+        };
+        self.written += loc.len;
+        Ok(loc)
+    }
+}
+
+impl<T: DeserializeOwned, R> DiskReservoir<T, R> {
+    /// End the sampling process, streaming the held items back from disk one
+    /// at a time, in slot order.
+    pub fn lock(self) -> io::Result<DiskItems<T>> {
+        let file = self.file.into_inner().map_err(|e| e.into_error())?;
+
+        Ok(DiskItems {
+            file,
+            locs: self.index.into_items(),
+            _item: PhantomData,
+        })
+    }
+}
+
+/// The held items of a locked [`DiskReservoir`], read back lazily.
+pub struct DiskItems<T> {
+    file: File,
+    locs: <Vec<Option<Loc>> as SampleStore<Loc>>::IntoItems,
+    _item: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Iterator for DiskItems<T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let loc = self.locs.next()?;
+
+        let mut read = || {
+            let mut bytes = vec![0; loc.len as usize];
+            self.file.seek(SeekFrom::Start(loc.offset))?;
+            self.file.read_exact(&mut bytes)?;
+            bincode::deserialize(&bytes).map_err(into_io)
+        };
+        Some(read())
+    }
+}
+
+fn into_io(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reservoir, StreamSampler};
+
+    #[test]
+    fn same_draws_as_reservoir() -> io::Result<()> {
+        let mut disk = DiskReservoir::<String, Deterministic>::deterministic(4, 11)?;
+        let mut reservoir = Reservoir::<String, Deterministic>::deterministic(4, 11);
+        for i in 0..200 {
+            let it = format!("record #{}", i);
+            let (r, total, _) = reservoir.sample(it.clone());
+            assert_eq!(disk.sample(it)?, (r, total));
+        }
+        assert_eq!(disk.len(), 4);
+
+        let held: Vec<_> = reservoir.iter().cloned().collect();
+        let locked = disk.lock()?.collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(locked, held);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_accepted_items_are_written() -> io::Result<()> {
+        let mut disk = DiskReservoir::<u64>::with_capacity(1)?;
+        for it in 0..1000u64 {
+            let _ = disk.sample(it)?;
+        }
+
+        // 每条 8 字节，被接受的远少于 1000 条
+        assert!(disk.written() < 8 * 100);
+        assert_eq!(disk.lock()?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_write_is_not_counted() -> io::Result<()> {
+        // 只读打开，写入必然失败
+        let spill = tempfile::NamedTempFile::new()?;
+        let mut disk = DiskReservoir::<Vec<u8>, Deterministic> {
+            total: 0,
+            index: vec![None; 1],
+            file: BufWriter::new(File::open(spill.path())?),
+            written: 0,
+            poisoned: false,
+            rng: Deterministic::seed_from_u64(1),
+            _item: PhantomData,
+        };
+
+        // 比缓冲区大，直接写到文件
+        assert!(disk.sample(vec![7; 10_000]).is_err());
+        assert_eq!(disk.total(), 0);
+        assert_eq!(disk.written(), 0);
+        assert!(disk.is_empty());
+
+        Ok(())
+    }
+}
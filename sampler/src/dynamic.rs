@@ -0,0 +1,333 @@
+//! Reservoirs whose capacity follows the stream, like the square root of the
+//! items passed through, or 1% of them, so the capacity is only known once
+//! the stream ends.
+use std::convert::TryFrom;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::{Algorithm, Deterministic, Lockable, Reservoir, StreamSampler};
+
+/// 2^64, the number of keys.
+const KEYS: f64 = 18_446_744_073_709_551_616.0;
+
+/// How many standard deviations the kept items stay above the capacity.
+const MARGIN: f64 = 8.0;
+
+/// How many items a [`DynamicReservoir`] holds once `total` items passed
+/// through. Any `Fn(u64) -> usize` is one.
+pub trait Capacity {
+    fn capacity(&self, total: u64) -> usize;
+}
+
+impl<F: Fn(u64) -> usize> Capacity for F {
+    fn capacity(&self, total: u64) -> usize {
+        self(total)
+    }
+}
+
+/// `base` plus `share` of the items, rounded up, the capacity of a
+/// [`GrowingReservoir`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Share {
+    pub(crate) base: usize,
+    pub(crate) share: f64,
+}
+
+impl Share {
+    /// A `share` out of `0.0..=1.0`, or not a number, is clamped into it.
+    pub fn new(base: usize, share: f64) -> Self {
+        let share = if share.is_nan() {
+            0.0
+        } else {
+            share.clamp(0.0, 1.0)
+        };
+        Self { base, share }
+    }
+}
+
+impl Capacity for Share {
+    fn capacity(&self, total: u64) -> usize {
+        let grown = (self.share * total as f64).ceil() as usize;
+        self.base.saturating_add(grown)
+    }
+}
+
+/// A `DynamicReservoir` samples uniformly into a capacity of `capacity`
+/// applied to `total`, which grows as the stream goes on. The capacity is
+/// settled by `lock`, or fixed by [`DynamicReservoir::fix_capacity`] to sample
+/// on as a plain [`Reservoir`].
+///
+/// Every item draws a random key, and the sample is the items of the smallest
+/// keys. Items whose key is too large to ever make it are dropped on the way,
+/// so about the capacity plus a margin of its square root are kept. The
+/// margin is eight standard deviations, so the kept ones falling short of the
+/// capacity, which shrinks the sample, takes odds far below one in a
+/// billion. That holds for any capacity growing no faster than the total,
+/// like a constant, a square root or a share.
+pub struct DynamicReservoir<T, F, R = ThreadRng> {
+    pub(crate) capacity: F,
+    pub(crate) total: u64,
+    // 保留的概率只降不升，已丢弃的条目才不会再被需要
+    pub(crate) keep: f64,
+    pub(crate) kept: Vec<(u64, T)>,
+    // 攒到这么多再清理一次
+    prune_at: usize,
+    pub(crate) rng: R,
+}
+
+impl<T, F: Capacity> DynamicReservoir<T, F> {
+    pub fn new(capacity: F) -> Self {
+        Self::with_rng(capacity, thread_rng())
+    }
+}
+
+impl<T, F: Capacity> DynamicReservoir<T, F, Deterministic> {
+    /// See [`Reservoir::deterministic`].
+    pub fn deterministic(capacity: F, seed: u64) -> Self {
+        Self::with_rng(capacity, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, F: Capacity, R: Rng> DynamicReservoir<T, F, R> {
+    pub fn with_rng(capacity: F, rng: R) -> Self {
+        Self {
+            capacity,
+            total: 0,
+            keep: 1.0,
+            kept: Vec::new(),
+            prune_at: 64,
+            rng,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The capacity at the current total, but never more than `total`.
+    pub fn capacity(&self) -> usize {
+        let capacity = self.capacity.capacity(self.total);
+        usize::try_from(self.total).map_or(capacity, |total| capacity.min(total))
+    }
+
+    fn keeps(&self, key: u64) -> bool {
+        self.keep >= 1.0 || (key as f64) < self.keep * KEYS
+    }
+
+    /// The kept items of the `k` smallest keys, in the order of their keys,
+    /// which is a random order.
+    fn smallest(mut self, k: usize) -> (Vec<Option<T>>, u64, R) {
+        self.kept.sort_unstable_by_key(|&(key, _)| key);
+        self.kept.truncate(k);
+        let mut pool: Vec<Option<T>> = self.kept.into_iter().map(|(_, it)| Some(it)).collect();
+        pool.resize_with(k, || None);
+        (pool, self.total, self.rng)
+    }
+
+    /// Fixes the capacity at `k`, turning into a [`Reservoir`] of the `k`
+    /// items of the smallest keys, which are a uniform sample of everything
+    /// passed through, in a random order. The reservoir samples on from the
+    /// same total and rng. A `k` beyond `capacity` may leave slots empty.
+    pub fn fix_capacity(self, k: usize) -> Reservoir<T, R> {
+        let (pool, total, rng) = self.smallest(k);
+        Reservoir {
+            total,
+            marks: Vec::new(),
+            history: None,
+            pins: Vec::new(),
+            pool,
+            rng,
+            algorithm: Algorithm::R,
+            skip: 0,
+            w: 1.0,
+            deleted: 0,
+            pending_held: 0,
+            pending_unheld: 0,
+            locked: false,
+            generation: 0,
+        }
+    }
+}
+
+impl<T, F: Capacity, R: Rng> StreamSampler for DynamicReservoir<T, F, R> {
+    type Item = T;
+    /// The random key of the item, an item of a smaller key is more likely to
+    /// stay.
+    type Draw = u64;
+
+    /// Items dropped on the way are let go quietly, so nothing is handed
+    /// back.
+    fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.total += 1;
+        let n = self.total as f64;
+        let k = self.capacity.capacity(self.total) as f64 + 1.0;
+        self.keep = self.keep.min((k + MARGIN * k.sqrt() + MARGIN) / n);
+
+        let key = self.rng.gen::<u64>();
+        if self.keeps(key) {
+            self.kept.push((key, it));
+        }
+
+        if self.kept.len() >= self.prune_at {
+            let mut kept = std::mem::take(&mut self.kept);
+            kept.retain(|&(key, _)| self.keeps(key));
+            self.kept = kept;
+            self.prune_at = self.kept.len().saturating_mul(2).max(64);
+        }
+        (key, self.total, None)
+    }
+
+    /// Whether the item is still kept, it may still lose its place before
+    /// the capacity is settled.
+    fn is_held(&self, draw: &u64) -> bool {
+        self.keeps(*draw)
+    }
+}
+
+impl<T, F: Capacity, R: Rng> Lockable for DynamicReservoir<T, F, R> {
+    /// Settles the capacity at the current total.
+    fn lock(self) -> Vec<Option<T>> {
+        let k = self.capacity();
+        self.smallest(k).0
+    }
+}
+
+/// A `GrowingReservoir` samples uniformly into a capacity of `base` plus
+/// `share` of the items passed through, which grows with `total`: a
+/// [`DynamicReservoir`] of a [`Share`].
+pub struct GrowingReservoir<T, R = ThreadRng>(pub(crate) DynamicReservoir<T, Share, R>);
+
+impl<T> GrowingReservoir<T> {
+    pub fn new(base: usize, share: f64) -> Self {
+        Self::with_rng(base, share, thread_rng())
+    }
+}
+
+impl<T> GrowingReservoir<T, Deterministic> {
+    /// See [`Reservoir::deterministic`].
+    pub fn deterministic(base: usize, share: f64, seed: u64) -> Self {
+        Self::with_rng(base, share, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, R: Rng> GrowingReservoir<T, R> {
+    /// A `share` out of `0.0..=1.0`, or not a number, is clamped into it.
+    pub fn with_rng(base: usize, share: f64, rng: R) -> Self {
+        Self(DynamicReservoir::with_rng(Share::new(base, share), rng))
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.total()
+    }
+
+    /// `base` plus `share` of `total`, rounded up, but never more than
+    /// `total`.
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// See [`DynamicReservoir::fix_capacity`].
+    pub fn fix_capacity(self, k: usize) -> Reservoir<T, R> {
+        self.0.fix_capacity(k)
+    }
+}
+
+impl<T, R: Rng> StreamSampler for GrowingReservoir<T, R> {
+    type Item = T;
+    type Draw = u64;
+
+    fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.0.sample(it)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        self.0.is_held(draw)
+    }
+}
+
+impl<T, R: Rng> Lockable for GrowingReservoir<T, R> {
+    fn lock(self) -> Vec<Option<T>> {
+        self.0.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Inspectable;
+
+    #[test]
+    fn capacity_grows_with_total() {
+        let mut growing = GrowingReservoir::<u32, Deterministic>::deterministic(2, 0.01, 1);
+        assert_eq!(growing.capacity(), 0);
+        let _ = growing.sample_all(0..2);
+        assert_eq!(growing.capacity(), 2);
+        let _ = growing.sample_all(2..150);
+        assert_eq!(growing.capacity(), 4);
+
+        let reservoir = growing.fix_capacity(4);
+        assert_eq!(reservoir.total(), 150);
+        assert_eq!(reservoir.samples().len(), 4);
+        assert_eq!(reservoir.iter().count(), 4);
+    }
+
+    #[test]
+    fn square_root() {
+        let sqrt = |total: u64| (total as f64).sqrt() as usize;
+        let mut dynamic = DynamicReservoir::<u32, _, Deterministic>::deterministic(sqrt, 1);
+        let _ = dynamic.sample_all(0..10_000);
+        assert_eq!(dynamic.capacity(), 100);
+        // 只多留了一点余量
+        assert!(dynamic.kept.len() < 400);
+        assert_eq!(dynamic.lock_compact().len(), 100);
+    }
+
+    #[test]
+    fn memory_stays_near_capacity() {
+        let mut growing = GrowingReservoir::<u32, Deterministic>::deterministic(0, 0.01, 1);
+        let _ = growing.sample_all(0..1_000_000);
+        assert_eq!(growing.capacity(), 10_000);
+        assert!(growing.0.kept.len() < 2 * 11_000);
+        assert_eq!(growing.lock_compact().len(), 10_000);
+    }
+
+    #[test]
+    fn uniform_and_reproducible() {
+        let draw = |seed| {
+            let mut growing = GrowingReservoir::<u32, Deterministic>::deterministic(0, 0.1, seed);
+            let _ = growing.sample_all(0..100);
+            growing.fix_capacity(10).lock_compact()
+        };
+        assert_eq!(draw(7), draw(7));
+
+        // 每个条目的入选概率都应接近 10%
+        let mut counts = [0u32; 100];
+        for seed in 0..2_000 {
+            for it in draw(seed) {
+                counts[it as usize] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&c| (140..=260).contains(&c)));
+    }
+
+    #[test]
+    fn square_root_is_uniform() {
+        let draw = |seed| {
+            let sqrt = |total: u64| (total as f64).sqrt() as usize;
+            let mut dynamic = DynamicReservoir::<u32, _, Deterministic>::deterministic(sqrt, seed);
+            let _ = dynamic.sample_all(0..100);
+            dynamic.lock_compact()
+        };
+        assert_eq!(draw(7), draw(7));
+
+        // 每个条目的入选概率都应接近 10%
+        let mut counts = [0u32; 100];
+        for seed in 0..2_000 {
+            for it in draw(seed) {
+                counts[it as usize] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&c| (140..=260).contains(&c)));
+    }
+}
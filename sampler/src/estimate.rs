@@ -0,0 +1,255 @@
+//! Estimating totals, means and counts of the whole stream from a locked
+//! sample, see [`LockWithWeights`](crate::LockWithWeights).
+//!
+//! Totals are Horvitz–Thompson estimates: every held item stands for
+//! `1 / probability` items of the stream. Standard errors use the with
+//! replacement approximation, scaled by one less the mean inclusion
+//! probability, which is exact for a uniform sample.
+use crate::Sampled;
+
+/// An estimate with its standard error.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Estimate {
+    pub value: f64,
+    pub std_error: f64,
+}
+
+impl Estimate {
+    /// The normal confidence interval at `confidence`, like `0.95`.
+    pub fn interval(&self, confidence: f64) -> (f64, f64) {
+        let z = normal_quantile(0.5 + confidence / 2.0);
+        (
+            self.value - z * self.std_error,
+            self.value + z * self.std_error,
+        )
+    }
+}
+
+/// The estimated sum of `value` over the stream.
+pub fn estimate_sum<T>(sample: &[Sampled<T>], value: impl Fn(&T) -> f64) -> Estimate {
+    let expanded: Vec<f64> = sample
+        .iter()
+        .map(|s| value(&s.item) / s.probability)
+        .collect();
+    let total = expanded.iter().sum();
+
+    Estimate {
+        value: total,
+        std_error: std_error(sample, &expanded, total),
+    }
+}
+
+/// The estimated mean of `value` over the stream, the estimated sum over the
+/// estimated count.
+pub fn estimate_mean<T>(sample: &[Sampled<T>], value: impl Fn(&T) -> f64) -> Estimate {
+    let count: f64 = sample.iter().map(Sampled::weight).sum();
+    if sample.is_empty() {
+        return Estimate {
+            value: f64::NAN,
+            std_error: f64::INFINITY,
+        };
+    }
+
+    let mean = estimate_sum(sample, &value).value / count;
+    // 线性化：对残差求和的标准误再除以总数
+    let residuals = estimate_sum(sample, |it| value(it) - mean);
+    Estimate {
+        value: mean,
+        std_error: residuals.std_error / count,
+    }
+}
+
+/// The estimated count of items of the stream `pred` matches.
+pub fn estimate_count_where<T>(sample: &[Sampled<T>], pred: impl Fn(&T) -> bool) -> Estimate {
+    estimate_sum(sample, |it| if pred(it) { 1.0 } else { 0.0 })
+}
+
+/// How many items a uniform sample needs, so a proportion estimated from it
+/// is within `margin_of_error` (like `0.05`) of the truth at `confidence`
+/// (like `0.95`). This is Cochran's formula at the worst case proportion of
+/// one half, corrected for a finite population when a hint of its size is
+/// given.
+///
+/// The size is never more than the population hint. Without a hint, it is
+/// never more than [`MAX_SAMPLE_SIZE`].
+///
+/// Returns `None` unless `confidence` and `margin_of_error` are both between
+/// 0 and 1, or when the size without a hint would be more than
+/// [`MAX_SAMPLE_SIZE`].
+pub fn required_sample_size(
+    population_hint: Option<usize>,
+    confidence: f64,
+    margin_of_error: f64,
+) -> Option<usize> {
+    let valid = |x: f64| x > 0.0 && x < 1.0;
+    if !valid(confidence) || !valid(margin_of_error) {
+        return None;
+    }
+
+    let z = normal_quantile(0.5 + confidence / 2.0);
+    let n = z * z * 0.25 / (margin_of_error * margin_of_error);
+    let n = match population_hint {
+        Some(population) => {
+            let population = population.max(1) as f64;
+            // 有限总体修正；n 为无穷时结果是 NaN，min 会取总体大小
+            (n / (1.0 + (n - 1.0) / population)).min(population)
+        }
+        None if n > MAX_SAMPLE_SIZE as f64 => return None,
+        None => n,
+    };
+    Some(n.ceil() as usize)
+}
+
+/// The biggest size [`required_sample_size`] returns without a population
+/// hint, about 16 million items.
+pub const MAX_SAMPLE_SIZE: usize = 1 << 24;
+
+fn std_error<T>(sample: &[Sampled<T>], expanded: &[f64], total: f64) -> f64 {
+    let n = sample.len() as f64;
+    let fpc = 1.0 - sample.iter().map(|s| s.probability).sum::<f64>() / n;
+    if sample.is_empty() || fpc <= 0.0 {
+        return 0.0;
+    }
+    if sample.len() < 2 {
+        return f64::INFINITY;
+    }
+
+    let mean = total / n;
+    let spread: f64 = expanded.iter().map(|y| (y - mean).powi(2)).sum();
+    (fpc * n / (n - 1.0) * spread).sqrt()
+}
+
+/// The inverse of the standard normal CDF, by Acklam's rational
+/// approximation, good to about 1e-9.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.02425 {
+        return tail((-2.0 * p.ln()).sqrt());
+    }
+    if p > 1.0 - 0.02425 {
+        return -tail((-2.0 * (1.0 - p).ln()).sqrt());
+    }
+
+    let q = p - 0.5;
+    let r = q * q;
+    (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+        / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, LockWithWeights, Reservoir, StreamSampler};
+
+    #[test]
+    fn quantiles() {
+        assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+        assert!((normal_quantile(0.5)).abs() < 1e-12);
+        assert!((normal_quantile(0.001) + 3.090232).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sample_sizes() {
+        assert_eq!(required_sample_size(None, 0.95, 0.05), Some(385));
+        assert_eq!(required_sample_size(Some(1000), 0.95, 0.05), Some(278));
+        assert_eq!(required_sample_size(None, 0.99, 0.01), Some(16588));
+        assert_eq!(required_sample_size(None, 1.0, 0.05), None);
+        assert_eq!(required_sample_size(None, 0.95, 0.0), None);
+    }
+
+    #[test]
+    fn sample_sizes_are_bounded() {
+        assert_eq!(required_sample_size(None, 0.99, 1e-4), None);
+        assert_eq!(required_sample_size(None, 0.95, 1e-300), None);
+        assert_eq!(required_sample_size(Some(500), 0.99, 1e-4), Some(500));
+        assert_eq!(required_sample_size(Some(500), 0.95, 1e-300), Some(500));
+        assert_eq!(
+            required_sample_size(Some(usize::MAX), 0.95, 1e-300),
+            Some(usize::MAX)
+        );
+    }
+
+    #[test]
+    fn full_sample_is_exact() {
+        let sample: Vec<_> = (1..=4)
+            .map(|item| Sampled {
+                item,
+                probability: 1.0,
+            })
+            .collect();
+
+        let sum = estimate_sum(&sample, |&it| f64::from(it));
+        assert_eq!(
+            sum,
+            Estimate {
+                value: 10.0,
+                std_error: 0.0
+            }
+        );
+        assert_eq!(estimate_mean(&sample, |&it| f64::from(it)).value, 2.5);
+        assert_eq!(estimate_count_where(&sample, |&it| it > 2).value, 2.0);
+    }
+
+    #[test]
+    fn intervals_cover_the_truth() {
+        const ROUNDS: u64 = 400;
+
+        let mut covered = 0;
+        for seed in 0..ROUNDS {
+            let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(50, seed);
+            for it in 0..1000 {
+                let _ = reservoir.sample(it);
+            }
+            let sample = reservoir.lock_with_weights();
+
+            // 真实均值 499.5
+            let (low, high) = estimate_mean(&sample, |&it| f64::from(it)).interval(0.95);
+            covered += u64::from(low <= 499.5 && 499.5 <= high);
+
+            let count = estimate_count_where(&sample, |&it| it < 100).value;
+            assert_eq!(count % 20.0, 0.0);
+        }
+
+        assert!((360..=395).contains(&covered), "{}", covered);
+    }
+}
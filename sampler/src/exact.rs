@@ -0,0 +1,179 @@
+//! Sampling an input of known length, which only needs `k` random numbers
+//! instead of one per item.
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use rand::{thread_rng, Rng};
+
+use crate::rng::below_u64;
+use crate::Deterministic;
+
+/// `k` distinct indices out of `0..len`, uniformly, in ascending order, by
+/// Floyd's algorithm. A `k` beyond `len` takes every index.
+pub fn exact_sample<R: Rng + ?Sized>(len: usize, k: usize, rng: &mut R) -> Vec<usize> {
+    sample_range(0..len as u64, k, rng)
+        .into_iter()
+        .map(|i| i as usize)
+        .collect()
+}
+
+/// `k` distinct numbers out of `range`, uniformly, in ascending order, the
+/// same way as [`exact_sample`]. It takes `k` random numbers and the memory
+/// for `k` numbers, however huge the range, so there is no need to feed a
+/// reservoir dummy items to pick random indices. A `k` beyond the length of
+/// the range takes all of it.
+pub fn sample_range<R: Rng + ?Sized>(range: Range<u64>, k: usize, rng: &mut R) -> Vec<u64> {
+    let n = range.end.saturating_sub(range.start);
+    let k = (k as u64).min(n);
+    let mut chosen = BTreeSet::new();
+    for j in n - k..n {
+        let t = below_u64(rng, j + 1);
+        // t 已经选过时改选 j，j 此前不可能被选中
+        if !chosen.insert(range.start + t) {
+            chosen.insert(range.start + j);
+        }
+    }
+    chosen.into_iter().collect()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExactError {
+    /// More than the `len` items promised were fed.
+    TooMany { len: usize },
+    /// Fewer than the `len` items promised were fed.
+    TooFew { len: usize, fed: usize },
+}
+
+/// An `ExactSampler` draws which positions of an input of `len` items to
+/// keep upfront, by [`exact_sample`], then keeps the items at those positions
+/// as they are fed. It holds the caller to `len`, since a sample of a shorter
+/// or longer input would not be uniform.
+pub struct ExactSampler<T> {
+    len: usize,
+    // 倒序存放，下一个要留的下标在末尾
+    chosen: Vec<usize>,
+    fed: usize,
+    held: Vec<T>,
+}
+
+impl<T> ExactSampler<T> {
+    pub fn new(len: usize, k: usize) -> Self {
+        Self::with_rng(len, k, &mut thread_rng())
+    }
+
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(len: usize, k: usize, seed: u64) -> Self {
+        Self::with_rng(len, k, &mut Deterministic::seed_from_u64(seed))
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(len: usize, k: usize, rng: &mut R) -> Self {
+        let mut chosen = exact_sample(len, k, rng);
+        chosen.reverse();
+        Self {
+            len,
+            held: Vec::with_capacity(chosen.len()),
+            chosen,
+            fed: 0,
+        }
+    }
+
+    /// Feeds the next item, returning whether it is kept.
+    pub fn sample(&mut self, it: T) -> Result<bool, ExactError> {
+        if self.fed == self.len {
+            return Err(ExactError::TooMany { len: self.len });
+        }
+        let idx = self.fed;
+        self.fed += 1;
+
+        if self.chosen.last() == Some(&idx) {
+            self.chosen.pop();
+            self.held.push(it);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn fed(&self) -> usize {
+        self.fed
+    }
+
+    /// The items kept so far, in the order they were fed.
+    pub fn samples(&self) -> &[T] {
+        &self.held
+    }
+
+    /// Hands out the kept items in the order they were fed, once every one of
+    /// the `len` items has been.
+    pub fn finish(self) -> Result<Vec<T>, ExactError> {
+        if self.fed < self.len {
+            return Err(ExactError::TooFew {
+                len: self.len,
+                fed: self.fed,
+            });
+        }
+        Ok(self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distinct_and_sorted() {
+        let mut rng = Deterministic::seed_from_u64(1);
+        for (len, k) in [(0, 0), (10, 0), (10, 3), (10, 10), (5, 8)] {
+            let chosen = exact_sample(len, k, &mut rng);
+            assert_eq!(chosen.len(), k.min(len));
+            assert!(chosen.windows(2).all(|w| w[0] < w[1]));
+            assert!(chosen.iter().all(|&i| i < len));
+        }
+    }
+
+    #[test]
+    fn uniform() {
+        let mut rng = Deterministic::seed_from_u64(1);
+        let mut counts = [0u32; 10];
+        for _ in 0..10_000 {
+            for i in exact_sample(10, 3, &mut rng) {
+                counts[i] += 1;
+            }
+        }
+        // 每个下标约 3000 次
+        assert!(counts.iter().all(|&c| (2_800..=3_200).contains(&c)));
+    }
+
+    #[test]
+    fn huge_ranges() {
+        let mut rng = Deterministic::seed_from_u64(1);
+        let chosen = sample_range(u64::MAX - 1_000_000_000_000..u64::MAX, 1_000, &mut rng);
+        assert_eq!(chosen.len(), 1_000);
+        assert!(chosen.windows(2).all(|w| w[0] < w[1]));
+        assert!(chosen[0] >= u64::MAX - 1_000_000_000_000);
+
+        assert_eq!(sample_range(5..8, 10, &mut rng), vec![5, 6, 7]);
+        assert!(sample_range(5..5, 10, &mut rng).is_empty());
+
+        // 与 exact_sample 取同样的随机数
+        let mut a = Deterministic::seed_from_u64(2);
+        let mut b = Deterministic::seed_from_u64(2);
+        let indices: Vec<u64> = exact_sample(100, 10, &mut a)
+            .into_iter()
+            .map(|i| i as u64)
+            .collect();
+        assert_eq!(sample_range(0..100, 10, &mut b), indices);
+    }
+
+    #[test]
+    fn holds_the_caller_to_len() {
+        let mut sampler = ExactSampler::deterministic(5, 2, 27);
+        let kept = (0..5).filter(|&it| sampler.sample(it) == Ok(true)).count();
+        assert_eq!(kept, 2);
+        assert_eq!(sampler.sample(5), Err(ExactError::TooMany { len: 5 }));
+        assert_eq!(sampler.finish().map(|held| held.len()), Ok(2));
+
+        let mut sampler = ExactSampler::deterministic(5, 2, 27);
+        let _ = sampler.sample(0);
+        assert_eq!(sampler.finish(), Err(ExactError::TooFew { len: 5, fed: 1 }));
+    }
+}
@@ -0,0 +1,216 @@
+//! Checking whether a sample holds the categories of the stream in their
+//! proportions, to catch a filter upstream of the sampler biasing it.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::Reservoir;
+
+/// How many of `items` fall into each category.
+pub fn category_histogram<'a, T: 'a, C: Hash + Eq>(
+    items: impl IntoIterator<Item = &'a T>,
+    mut f: impl FnMut(&T) -> C,
+) -> HashMap<C, usize> {
+    let mut histogram = HashMap::new();
+    for it in items {
+        *histogram.entry(f(it)).or_insert(0) += 1;
+    }
+    histogram
+}
+
+impl<T, R> Reservoir<T, R> {
+    /// How many held items fall into each category, see [`FairnessReport`].
+    pub fn category_histogram<C: Hash + Eq>(&self, f: impl FnMut(&T) -> C) -> HashMap<C, usize> {
+        category_histogram(self, f)
+    }
+}
+
+/// One category of a [`FairnessReport`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct CategoryShare<C> {
+    pub category: C,
+    pub held: usize,
+    pub seen: u64,
+    /// How many items of the category a fair sample of the same size holds
+    /// on average.
+    pub expected: f64,
+}
+
+/// Compares the categories of a sample with the ones of the stream, by a
+/// chi-square goodness of fit test. A small `p_value`, like below 0.01, tells
+/// the sample is unlikely to be uniform over the stream counted by `seen`.
+///
+/// The test takes the sample as drawn with replacement, which is close enough
+/// while the sample is a small part of the stream, and wants every expected
+/// count to be about 5 or more.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FairnessReport<C> {
+    /// The categories seen, the most seen first.
+    pub categories: Vec<CategoryShare<C>>,
+    pub chi_square: f64,
+    pub degrees_of_freedom: usize,
+    pub p_value: f64,
+}
+
+impl<C: Hash + Eq + Clone> FairnessReport<C> {
+    /// `held` counts the categories of a locked sample, like by
+    /// [`category_histogram`], and `seen` the ones of every item passed
+    /// through. A category held but never seen makes the p-value 0.
+    pub fn new(held: &HashMap<C, usize>, seen: &HashMap<C, u64>) -> Self {
+        let n: usize = held.values().sum();
+        let seen_total: u64 = seen.values().sum();
+
+        let mut categories: Vec<CategoryShare<C>> = seen
+            .iter()
+            .filter(|(_, &count)| count > 0)
+            .map(|(category, &count)| CategoryShare {
+                category: category.clone(),
+                held: held.get(category).copied().unwrap_or(0),
+                seen: count,
+                expected: n as f64 * count as f64 / seen_total as f64,
+            })
+            .collect();
+        categories.sort_by_key(|share| std::cmp::Reverse(share.seen));
+
+        let unseen = held
+            .iter()
+            .any(|(category, &count)| count > 0 && seen.get(category).copied().unwrap_or(0) == 0);
+        let degrees_of_freedom = categories.len().saturating_sub(1);
+        let (chi_square, p_value) = if unseen {
+            (f64::INFINITY, 0.0)
+        } else if n == 0 || degrees_of_freedom == 0 {
+            (0.0, 1.0)
+        } else {
+            let chi_square = categories
+                .iter()
+                .map(|share| (share.held as f64 - share.expected).powi(2) / share.expected)
+                .sum();
+            (chi_square, chi_square_p(chi_square, degrees_of_freedom))
+        };
+
+        Self {
+            categories,
+            chi_square,
+            degrees_of_freedom,
+            p_value,
+        }
+    }
+}
+
+/// The chance of a chi-square of `df` degrees of freedom to be `x` or more.
+fn chi_square_p(x: f64, df: usize) -> f64 {
+    gamma_q(df as f64 / 2.0, x / 2.0)
+}
+
+/// The regularized upper incomplete gamma function, by its series below
+/// `a + 1` and by its continued fraction above, as in Numerical Recipes.
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const EPS: f64 = 1e-15;
+    const TINY: f64 = 1e-300;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let front = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut ap = a;
+        for _ in 0..500 {
+            ap += 1.0;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * EPS {
+                break;
+            }
+        }
+        return (1.0 - sum * front).max(0.0);
+    }
+
+    // Lentz 法求连分式
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..500 {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let step = d * c;
+        h *= step;
+        if (step - 1.0).abs() < EPS {
+            break;
+        }
+    }
+    (front * h).min(1.0)
+}
+
+/// The log of the gamma function, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const G: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = G[0]
+        + G[1..]
+            .iter()
+            .enumerate()
+            .map(|(i, g)| g / (x + i as f64 + 1.0))
+            .sum::<f64>();
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, StreamSampler};
+
+    #[test]
+    fn p_values() {
+        assert!((chi_square_p(3.841_458_820_694_124, 1) - 0.05).abs() < 1e-9);
+        assert!((chi_square_p(2.0 * 20f64.ln(), 2) - 0.05).abs() < 1e-9);
+        assert!((chi_square_p(18.307_038_053_275_146, 10) - 0.05).abs() < 1e-9);
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn uniform_and_biased_samples() {
+        // 类别为条目除以 10 的余数，流中各占一成
+        let seen: HashMap<u32, u64> = (0..10).map(|c| (c, 10_000)).collect();
+
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(1_000, 1);
+        let _ = reservoir.sample_all(0..100_000);
+        let held = reservoir.category_histogram(|it| it % 10);
+        let report = FairnessReport::new(&held, &seen);
+        assert_eq!(report.degrees_of_freedom, 9);
+        assert_eq!(report.categories.len(), 10);
+        assert!(report.p_value > 0.01, "{:?}", report);
+
+        // 上游过滤掉了九成的类别 0
+        let mut biased = Reservoir::<u32, Deterministic>::deterministic(1_000, 1);
+        let _ = biased.sample_all((0..100_000).filter(|it| it % 10 != 0 || it % 100 == 0));
+        let report = FairnessReport::new(&biased.category_histogram(|it| it % 10), &seen);
+        assert!(report.p_value < 1e-6, "{:?}", report);
+
+        let stray: HashMap<u32, usize> = vec![(11, 1)].into_iter().collect();
+        assert_eq!(FairnessReport::new(&stray, &seen).p_value, 0.0);
+    }
+}
@@ -0,0 +1,87 @@
+//! Sampling straight from sources which can fail, like the lines of a file or
+//! the messages of a socket, see [`StreamSampler::try_sample_from`].
+//!
+//! [`StreamSampler::try_sample_from`]: crate::StreamSampler::try_sample_from
+
+/// What [`StreamSampler::try_sample_from`] does on an error of its source.
+///
+/// [`StreamSampler::try_sample_from`]: crate::StreamSampler::try_sample_from
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OnSourceError {
+    /// Stops at the first error.
+    #[default]
+    Stop,
+    /// Goes on past every error, gathering them.
+    Collect,
+}
+
+/// The errors a source ran into, by their places in the source, counting from
+/// 0, with how many items were held all the same.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourceErrors<E> {
+    pub held: u64,
+    pub errors: Vec<(u64, E)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Deterministic, Inspectable, OnSourceError, Reservoir, SourceErrors, StreamSampler,
+    };
+    use std::io::{self, BufRead, BufReader};
+
+    fn source() -> impl Iterator<Item = Result<u32, String>> {
+        (0..10).map(|it| {
+            if it % 4 == 3 {
+                Err(format!("bad row {}", it))
+            } else {
+                Ok(it)
+            }
+        })
+    }
+
+    #[test]
+    fn stops_or_collects() {
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(10, 1);
+        assert_eq!(
+            reservoir.try_sample_from(source(), OnSourceError::Stop),
+            Err(SourceErrors {
+                held: 3,
+                errors: vec![(3, "bad row 3".into())],
+            })
+        );
+        assert_eq!(reservoir.total(), 3);
+
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(10, 1);
+        let errors = reservoir
+            .try_sample_from(source(), OnSourceError::Collect)
+            .unwrap_err();
+        assert_eq!(errors.held, 8);
+        assert_eq!(
+            errors.errors.iter().map(|(at, _)| *at).collect::<Vec<_>>(),
+            vec![3, 7]
+        );
+        assert_eq!(reservoir.samples().iter().flatten().count(), 8);
+
+        // 没有错误时与 sample_all 一样
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(2, 1);
+        let mut twin = reservoir.clone();
+        assert_eq!(
+            reservoir.try_sample_from((0..100).map(Ok::<_, ()>), OnSourceError::Stop),
+            Ok(twin.sample_all(0..100))
+        );
+        assert_eq!(reservoir.samples(), twin.samples());
+    }
+
+    #[test]
+    fn lines_of_a_reader() -> io::Result<()> {
+        let reader = BufReader::new(&b"a\nb\nc\n"[..]);
+        let mut reservoir = Reservoir::<String, Deterministic>::deterministic(2, 1);
+        let held = reservoir
+            .try_sample_from(reader.lines(), OnSourceError::Stop)
+            .map_err(|mut e| e.errors.remove(0).1)?;
+        assert!(held >= 2);
+        assert_eq!(reservoir.total(), 3);
+        Ok(())
+    }
+}
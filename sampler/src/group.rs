@@ -0,0 +1,178 @@
+//! An approximate group-by over keys of high cardinality, in one pass and
+//! bounded memory: sample the keys, and only aggregate the sampled ones.
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::ops::AddAssign;
+
+/// The aggregate of a retained group.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Group<V> {
+    pub sum: V,
+    pub count: u64,
+}
+
+/// A `SampledGroupBy` keeps at most `k` groups, a uniform sample of the
+/// distinct keys. Every key draws its priority from its hash, the same every
+/// time it shows up, and the `k` keys of the smallest priorities are kept.
+///
+/// Since the priority a key needs only ever drops, a key is either kept from
+/// its first item on or never kept, so the aggregate of a kept group is
+/// exact.
+pub struct SampledGroupBy<K, V, S = RandomState> {
+    k: usize,
+    groups: HashMap<K, (u64, Group<V>)>,
+    // 优先级 -> 键，最大的最先被淘汰
+    order: BTreeMap<u64, K>,
+    hasher: S,
+}
+
+impl<K: Hash + Eq + Clone, V: AddAssign + Default> SampledGroupBy<K, V> {
+    pub fn new(k: usize) -> Self {
+        Self::with_hasher(k, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: AddAssign + Default, S: BuildHasher> SampledGroupBy<K, V, S> {
+    /// Priorities come from `hasher`, so a fixed one keeps the same groups
+    /// from run to run.
+    pub fn with_hasher(k: usize, hasher: S) -> Self {
+        Self {
+            k,
+            groups: HashMap::new(),
+            order: BTreeMap::new(),
+            hasher,
+        }
+    }
+
+    /// Adds `value` to the group of `key`, returning whether the group is
+    /// kept.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        if let Some((_, group)) = self.groups.get_mut(&key) {
+            group.sum += value;
+            group.count += 1;
+            return true;
+        }
+
+        let priority = self.hasher.hash_one(&key);
+        // 优先级相同的两个键极少见，后来的直接放弃
+        if self.k == 0 || self.order.contains_key(&priority) {
+            return false;
+        }
+        if self.groups.len() >= self.k {
+            match self.order.last_key_value() {
+                Some((&max, _)) if priority < max => {
+                    if let Some(evicted) = self.order.remove(&max) {
+                        let _ = self.groups.remove(&evicted);
+                    }
+                }
+                _ => return false,
+            }
+        }
+
+        let mut group = Group::default();
+        group.sum += value;
+        group.count = 1;
+        let _ = self.order.insert(priority, key.clone());
+        let _ = self.groups.insert(key, (priority, group));
+        true
+    }
+
+    pub fn get(&self, key: &K) -> Option<&Group<V>> {
+        self.groups.get(key).map(|(_, group)| group)
+    }
+
+    /// The kept groups, in no particular order.
+    pub fn groups(&self) -> impl Iterator<Item = (&K, &Group<V>)> {
+        self.groups.iter().map(|(key, (_, group))| (key, group))
+    }
+
+    /// An estimate of how many distinct keys have been seen, from how small
+    /// the kept priorities are. Exact while fewer than `k` keys were seen.
+    ///
+    /// With a `k` of at least 2 the estimate is unbiased. No estimate from a
+    /// single priority is, so with a `k` of 1 it is as often too large as too
+    /// small instead, though often far off either way.
+    pub fn estimate_keys(&self) -> f64 {
+        match self.order.last_key_value() {
+            Some((&max, _)) if self.groups.len() >= self.k => {
+                let max = max as f64 / u64::MAX as f64;
+                if self.k == 1 {
+                    // 最小值的中位数约为 ln 2 / n
+                    std::f64::consts::LN_2 / max
+                } else {
+                    (self.k as f64 - 1.0) / max
+                }
+            }
+            _ => self.groups.len() as f64,
+        }
+    }
+
+    pub fn into_groups(self) -> HashMap<K, Group<V>> {
+        self.groups
+            .into_iter()
+            .map(|(key, (_, group))| (key, group))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+
+    use super::*;
+
+    #[test]
+    fn kept_groups_are_exact() {
+        let mut by_user = SampledGroupBy::<u32, u64>::new(10);
+        for round in 0..5u64 {
+            for user in 0..1000 {
+                let _ = by_user.insert(user, round);
+            }
+        }
+
+        assert_eq!(by_user.groups().count(), 10);
+        for (_, group) in by_user.groups() {
+            assert_eq!(*group, Group { sum: 10, count: 5 });
+        }
+
+        let estimate = by_user.estimate_keys();
+        assert!((300.0..3000.0).contains(&estimate), "{}", estimate);
+    }
+
+    #[test]
+    fn one_key_estimates() {
+        let mut estimates: Vec<f64> = (0..201u32)
+            .map(|run| {
+                let hasher = BuildHasherDefault::<DefaultHasher>::default();
+                let mut by_key = SampledGroupBy::<u32, u64, _>::with_hasher(1, hasher);
+                for key in 0..1000 {
+                    let _ = by_key.insert(run * 1000 + key, 1);
+                }
+                by_key.estimate_keys()
+            })
+            .collect();
+        assert!(estimates.iter().all(|&e| e > 0.0));
+
+        // k 为 1 时估计值的中位数接近真实值
+        estimates.sort_by(f64::total_cmp);
+        assert!(
+            (700.0..1400.0).contains(&estimates[100]),
+            "{}",
+            estimates[100]
+        );
+    }
+
+    #[test]
+    fn few_keys_are_all_kept() {
+        let mut by_key = SampledGroupBy::<&str, f64>::new(3);
+        assert!(by_key.insert("a", 1.5));
+        assert!(by_key.insert("b", 2.0));
+        assert!(by_key.insert("a", 1.0));
+
+        assert_eq!(by_key.get(&"a"), Some(&Group { sum: 2.5, count: 2 }));
+        assert_eq!(by_key.estimate_keys(), 2.0);
+        assert_eq!(by_key.into_groups().len(), 2);
+    }
+}
@@ -0,0 +1,76 @@
+//! Sampling gzipped inputs, like rotated log archives, decompressing them as
+//! they are read.
+use std::io::{self, BufRead, BufReader, Read};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::{sample_delimited, Delimiter, Lockable, Reservoir, StreamSampler};
+
+/// The same as [`sample_delimited`], over a gzipped `reader`. Archives of
+/// several gzip members one after another, as `cat a.gz b.gz` makes, are read
+/// through.
+pub fn sample_gz_delimited<R: Read>(
+    reader: R,
+    delimiter: Delimiter,
+    k: usize,
+) -> io::Result<Vec<Vec<u8>>> {
+    sample_delimited(MultiGzDecoder::new(reader), delimiter, k)
+}
+
+/// A uniform sample of `k` lines out of a gzipped `reader`, without their
+/// `\n` or `\r\n`. A line of invalid UTF-8 is an
+/// [`ErrorKind::InvalidData`](io::ErrorKind::InvalidData), sampled or not.
+pub fn sample_gz_lines<R: Read>(reader: R, k: usize) -> io::Result<Vec<String>> {
+    let mut reservoir = Reservoir::with_capacity(k);
+    for line in BufReader::new(MultiGzDecoder::new(reader)).lines() {
+        let _ = reservoir.sample(line?);
+    }
+    Ok(reservoir.lock_compact())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::ErrorKind;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    #[test]
+    fn lines_of_an_archive() -> io::Result<()> {
+        let log: String = (0..1000).map(|i| format!("line {}\r\n", i)).collect();
+        let mut archive = gzip(log.as_bytes())?;
+        // 两个 gzip 成员首尾相接
+        archive.extend(gzip(b"line 1000\n")?);
+
+        let lines = sample_gz_lines(archive.as_slice(), 2_000)?;
+        assert_eq!(lines.len(), 1001);
+        assert!(lines.iter().any(|line| line == "line 1000"));
+        assert!(lines.iter().all(|line| line.starts_with("line ")));
+
+        assert_eq!(sample_gz_lines(archive.as_slice(), 10)?.len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_input() -> io::Result<()> {
+        let invalid = gzip(&[0xff, b'\n'])?;
+        let err = sample_gz_lines(invalid.as_slice(), 1).map_err(|e| e.kind());
+        assert_eq!(err, Err(ErrorKind::InvalidData));
+
+        // 没被抽中的行也要检查
+        let mut log = vec![0xff, b'\n'];
+        log.extend((0..1000).flat_map(|i| format!("line {}\n", i).into_bytes()));
+        let err = sample_gz_lines(gzip(&log)?.as_slice(), 1).map_err(|e| e.kind());
+        assert_eq!(err, Err(ErrorKind::InvalidData));
+
+        assert!(sample_gz_delimited(&b"not gzip"[..], Delimiter::Varint, 1).is_err());
+        Ok(())
+    }
+}
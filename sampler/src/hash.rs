@@ -0,0 +1,177 @@
+//! Tamper evidence for live draws: a BLAKE3 hash of the whole state of a
+//! sampler, which the host can publish as the draw goes on. Enabled by the
+//! `state-hash` feature.
+use std::hash::{Hash, Hasher};
+
+use rand::rngs::ThreadRng;
+
+use crate::merge::algorithm_tag;
+use crate::{Deterministic, GrowingReservoir, Reservoir};
+
+/// A [`Hasher`] feeding BLAKE3. Integers are written as fixed width little
+/// endian bytes, and `usize` as a `u64`, so the same state hashes the same on
+/// every platform.
+#[derive(Default)]
+pub struct StateHasher(blake3::Hasher);
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn finalize(&self) -> [u8; 32] {
+        *self.0.finalize().as_bytes()
+    }
+}
+
+impl Hasher for StateHasher {
+    /// Only the first 8 bytes of the BLAKE3 hash, see `finalize` for all.
+    fn finish(&self) -> u64 {
+        let mut head = [0; 8];
+        head.copy_from_slice(&self.finalize()[..8]);
+        u64::from_le_bytes(head)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let _ = self.0.update(bytes);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64)
+    }
+}
+
+/// Whatever a sampler can feed into a [`StateHasher`].
+pub trait HashState {
+    fn hash_state(&self, state: &mut StateHasher);
+}
+
+/// The seed and position of the ChaCha20 stream.
+impl HashState for Deterministic {
+    fn hash_state(&self, state: &mut StateHasher) {
+        let (seed, stream, word_pos) = self.state();
+        state.write(&seed);
+        state.write_u64(stream);
+        state.write_u128(word_pos);
+    }
+}
+
+/// The state of the thread local RNG cannot be read, so it is left out.
+impl HashState for ThreadRng {
+    fn hash_state(&self, _: &mut StateHasher) {}
+}
+
+impl<T: Hash, R: HashState> HashState for Reservoir<T, R> {
+    fn hash_state(&self, state: &mut StateHasher) {
+        state.write_u64(algorithm_tag(self.algorithm));
+        self.total.hash(state);
+        self.pool.hash(state);
+        self.skip.hash(state);
+        self.w.to_bits().hash(state);
+        self.deleted.hash(state);
+        self.pending_held.hash(state);
+        self.pending_unheld.hash(state);
+        self.rng.hash_state(state);
+    }
+}
+
+/// Only a share can be hashed, for closures can not.
+impl<T: Hash, R: HashState> HashState for GrowingReservoir<T, R> {
+    fn hash_state(&self, state: &mut StateHasher) {
+        let growing = &self.0;
+        growing.capacity.base.hash(state);
+        growing.capacity.share.to_bits().hash(state);
+        growing.total.hash(state);
+        growing.keep.to_bits().hash(state);
+        growing.kept.hash(state);
+        growing.rng.hash_state(state);
+    }
+}
+
+impl<T: Hash, R: HashState> Reservoir<T, R> {
+    /// A BLAKE3 hash of the whole state, the RNG included when its state can
+    /// be read, as for [`Deterministic`]. Any change to the state, like a
+    /// swapped item, changes the hash.
+    pub fn state_hash(&self) -> [u8; 32] {
+        let mut state = StateHasher::new();
+        self.hash_state(&mut state);
+        state.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Algorithm, StreamSampler};
+
+    #[test]
+    fn same_state_same_hash() {
+        let mut a = Reservoir::<u32, Deterministic>::deterministic(3, 8);
+        let mut b = Reservoir::<u32, Deterministic>::deterministic(3, 8);
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        for it in 0..20 {
+            let _ = a.sample(it);
+            let _ = b.sample(it);
+        }
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        let _ = a.update_if_held(|_| true, |it| *it += 1);
+        assert_ne!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn rng_is_hashed() {
+        let a = Reservoir::<u32, Deterministic>::deterministic(3, 8);
+        let b = Reservoir::<u32, Deterministic>::deterministic(3, 9);
+        assert_ne!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn algorithm_is_hashed() {
+        let rng = || Deterministic::seed_from_u64(8);
+        let r = Reservoir::<u32, _>::with_rng_and_algorithm(3, rng(), Algorithm::R);
+        let mut l = Reservoir::<u32, _>::with_rng_and_algorithm(3, rng(), Algorithm::L);
+        assert_ne!(r.state_hash(), l.state_hash());
+
+        // 算法按编码中的数字喂入，不依赖 Debug 的输出
+        l.algorithm = Algorithm::R;
+        assert_eq!(r.state_hash(), l.state_hash());
+    }
+}
@@ -0,0 +1,155 @@
+//! Heavy hitter sampling: instead of a uniform sample, keep the items which
+//! come by the most often.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{Inspectable, Lockable, StreamSampler};
+
+/// The SpaceSaving algorithm with `k` counters. Any item occurring more than
+/// `n / k` times in a stream of `n` items is guaranteed to be held, and each
+/// held count overestimates the true one by at most its `error`.
+///
+/// Being deterministic, the first element `sample` returns is not a random
+/// number but the 1-based slot the current item is counted in, or 0 if the
+/// sampler has no capacity.
+pub struct SpaceSaving<T> {
+    total: u64,
+    pool: Vec<Option<T>>,
+    counts: Vec<u64>,
+    errors: Vec<u64>,
+    index: HashMap<T, usize>,
+}
+
+impl<T: Hash + Eq + Clone> SpaceSaving<T> {
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            total: 0,
+            pool: std::vec::from_elem(Option::<T>::None, n),
+            counts: vec![0; n],
+            errors: vec![0; n],
+            index: HashMap::with_capacity(n),
+        }
+    }
+
+    /// The held items with their estimated count and the maximum
+    /// overestimation of that count, in slot order.
+    pub fn counts(&self) -> impl Iterator<Item = (&T, u64, u64)> {
+        self.pool
+            .iter()
+            .zip(self.counts.iter().zip(&self.errors))
+            .filter_map(|(it, (&count, &error))| it.as_ref().map(|it| (it, count, error)))
+    }
+
+    /// The `n` most frequent held items, most frequent first.
+    pub fn top(&self, n: usize) -> Vec<(&T, u64)> {
+        let mut top: Vec<_> = self.counts().map(|(it, count, _)| (it, count)).collect();
+        top.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        top.truncate(n);
+        top
+    }
+}
+
+impl<T: Hash + Eq + Clone> StreamSampler for SpaceSaving<T> {
+    type Item = T;
+    /// The slot the item is counted in, counting from 1, or 0 when there is
+    /// no slot at all.
+    type Draw = u64;
+
+    fn sample(&mut self, it: Self::Item) -> (u64, u64, Option<Self::Item>) {
+        self.total += 1;
+
+        if let Some(&slot) = self.index.get(&it) {
+            self.counts[slot] += 1;
+            return (slot as u64 + 1, self.total, None);
+        }
+
+        // 有空位先占空位，否则顶替计数最小的
+        let slot = match self.pool.iter().position(|it| it.is_none()) {
+            Some(slot) => slot,
+            None => match (0..self.counts.len()).min_by_key(|&i| self.counts[i]) {
+                Some(slot) => slot,
+                None => return (0, self.total, None),
+            },
+        };
+
+        let min = self.counts[slot];
+        self.index.insert(it.clone(), slot);
+        let replaced = self.pool[slot].replace(it);
+        if let Some(old) = &replaced {
+            self.index.remove(old);
+        }
+        self.counts[slot] = min + 1;
+        self.errors[slot] = min;
+
+        (slot as u64 + 1, self.total, replaced)
+    }
+
+    /// Every item is held, at least until a rarer one comes.
+    fn is_held(&self, &draw: &u64) -> bool {
+        draw >= 1
+    }
+}
+
+impl<T: Hash + Eq + Clone> Inspectable for SpaceSaving<T> {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        &self.pool[..]
+    }
+}
+
+impl<T: Hash + Eq + Clone> Lockable for SpaceSaving<T> {
+    /// The held items, most frequent first.
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        let mut held: Vec<_> = self.pool.into_iter().zip(self.counts).collect();
+        held.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        held.into_iter().map(|(it, _)| it).collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone> crate::Snapshot for SpaceSaving<T> {
+    type Item = T;
+
+    fn snapshot(&self) -> crate::SamplerSnapshot<T> {
+        let counts: Vec<_> = self.counts.iter().map(u64::to_string).collect();
+        let errors: Vec<_> = self.errors.iter().map(u64::to_string).collect();
+
+        crate::SamplerSnapshot::new("space-saving", self.total, self.pool.clone())
+            .with_parameter("counts", counts.join(","))
+            .with_parameter("errors", errors.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequent_items_are_held() {
+        let mut sampler = SpaceSaving::with_capacity(3);
+        for i in 0..1000u32 {
+            // 7 和 11 是高频项
+            let it = match i % 4 {
+                0 | 1 => 7,
+                2 => 11,
+                _ => 100 + i,
+            };
+            let _ = sampler.sample(it);
+        }
+
+        let top = sampler.top(2);
+        assert_eq!(*top[0].0, 7);
+        assert_eq!(*top[1].0, 11);
+        assert!(top[0].1 >= 500);
+
+        let locked = sampler.lock();
+        assert_eq!(locked[0], Some(7));
+        assert_eq!(locked[1], Some(11));
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut sampler = SpaceSaving::with_capacity(0);
+        assert_eq!(sampler.sample(1), (0, 1, None));
+        assert!(sampler.lock().is_empty());
+    }
+}
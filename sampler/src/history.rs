@@ -0,0 +1,100 @@
+//! Remembering the latest replacements of a [`Reservoir`], to tell why an
+//! item fell out of the sample.
+use std::collections::VecDeque;
+
+use rand::Rng;
+
+use crate::Reservoir;
+
+/// An item a [`Reservoir`] replaced.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Eviction<T> {
+    pub item: T,
+    pub slot: usize,
+    /// The stream index of the replaced item, counting from 1, or 0 when it
+    /// is not known, see [`Reservoir::samples_by_arrival`].
+    pub arrived: u64,
+    /// The stream index of the item which took its place.
+    pub replaced_at: u64,
+}
+
+#[derive(Clone)]
+pub(crate) struct History<T> {
+    depth: usize,
+    // 记录时复制被替换的条目，替换出的那份仍交给调用者
+    clone: fn(&T) -> T,
+    evictions: VecDeque<Eviction<T>>,
+}
+
+impl<T> History<T> {
+    pub(crate) fn record(&mut self, item: &T, slot: usize, arrived: u64, replaced_at: u64) {
+        if self.evictions.len() == self.depth {
+            let _ = self.evictions.pop_front();
+        }
+        self.evictions.push_back(Eviction {
+            item: (self.clone)(item),
+            slot,
+            arrived,
+            replaced_at,
+        });
+    }
+}
+
+impl<T: Clone, R: Rng> Reservoir<T, R> {
+    /// Keeps the latest `depth` replacements, see
+    /// [`Reservoir::recent_evictions`]. A `depth` of 0 keeps none.
+    pub fn with_history(mut self, depth: usize) -> Self {
+        self.history = Some(depth).filter(|&depth| depth > 0).map(|depth| History {
+            depth,
+            clone: T::clone,
+            evictions: VecDeque::with_capacity(depth),
+        });
+        self
+    }
+}
+
+impl<T, R: Rng> Reservoir<T, R> {
+    /// The latest replacements, the most recent first, when the reservoir was
+    /// made [`Reservoir::with_history`]. Items removed otherwise, like by
+    /// `delete` or `downsize`, are not replacements.
+    pub fn recent_evictions(&self) -> impl Iterator<Item = &Eviction<T>> {
+        self.history
+            .iter()
+            .flat_map(|history| history.evictions.iter().rev())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Algorithm, Deterministic, Reservoir, StreamSampler};
+
+    #[test]
+    fn keeps_latest_replacements() {
+        for &algorithm in &[Algorithm::R, Algorithm::L] {
+            let rng = Deterministic::seed_from_u64(1);
+            let mut reservoir =
+                Reservoir::with_rng_and_algorithm(4, rng, algorithm).with_history(3);
+            let mut replaced = Vec::new();
+            for it in 0..1_000u64 {
+                let (_, _, out) = reservoir.sample(it);
+                replaced.extend(out);
+            }
+            assert!(replaced.len() > 3);
+
+            let evictions: Vec<_> = reservoir.recent_evictions().collect();
+            assert_eq!(evictions.len(), 3);
+            let latest: Vec<u64> = replaced.iter().rev().take(3).copied().collect();
+            assert_eq!(evictions.iter().map(|e| e.item).collect::<Vec<_>>(), latest);
+            for eviction in evictions {
+                // 条目即其序号减一
+                assert_eq!(eviction.arrived, eviction.item + 1);
+                assert!(eviction.replaced_at > eviction.arrived);
+                assert!(eviction.slot < 4);
+            }
+        }
+
+        let mut plain = Reservoir::<u64, Deterministic>::deterministic(1, 1).with_history(0);
+        let _ = plain.sample_all(0..10);
+        assert_eq!(plain.recent_evictions().count(), 0);
+    }
+}
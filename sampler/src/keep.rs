@@ -0,0 +1,105 @@
+//! The usual log sampling policy as a preset: keep every item that matters,
+//! like errors, and sample the rest.
+use rand::rngs::ThreadRng;
+use rand::Rng;
+
+use crate::{Inspectable, Lockable, Reservoir, StreamSampler};
+
+/// Starts a [`KeepOrSample`], keeping every item `pred` matches, like
+/// `always_keep(|log: &Log| log.status >= 500).up_to(1000).else_reservoir(100)`.
+pub fn always_keep<P>(pred: P) -> AlwaysKeep<P> {
+    AlwaysKeep {
+        pred,
+        cap: usize::MAX,
+    }
+}
+
+pub struct AlwaysKeep<P> {
+    pred: P,
+    cap: usize,
+}
+
+impl<P> AlwaysKeep<P> {
+    /// Keeps no more than `cap` matching items; the ones after are sampled
+    /// like the rest.
+    pub fn up_to(mut self, cap: usize) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    /// Samples every item not kept into a [`Reservoir`] of `k` items.
+    pub fn else_reservoir<T: Clone>(self, k: usize) -> KeepOrSample<T, P> {
+        self.else_reservoir_with(Reservoir::with_capacity(k))
+    }
+
+    pub fn else_reservoir_with<T, R: Rng>(
+        self,
+        reservoir: Reservoir<T, R>,
+    ) -> KeepOrSample<T, P, R> {
+        KeepOrSample {
+            pred: self.pred,
+            cap: self.cap,
+            kept: Vec::new(),
+            reservoir,
+        }
+    }
+}
+
+/// Keeps every item its predicate matches, up to a cap, and samples the rest.
+pub struct KeepOrSample<T, P, R = ThreadRng> {
+    pred: P,
+    cap: usize,
+    kept: Vec<T>,
+    reservoir: Reservoir<T, R>,
+}
+
+impl<T, P: FnMut(&T) -> bool, R: Rng> KeepOrSample<T, P, R> {
+    /// Returns whether the item is held right now, kept or sampled.
+    pub fn sample(&mut self, it: T) -> bool {
+        if self.kept.len() < self.cap && (self.pred)(&it) {
+            self.kept.push(it);
+            return true;
+        }
+
+        let (r, _, _) = self.reservoir.sample(it);
+        self.reservoir.is_held(&r)
+    }
+
+    /// The items kept for matching, in order.
+    pub fn kept(&self) -> &[T] {
+        &self.kept
+    }
+
+    pub fn samples(&self) -> &[Option<T>] {
+        self.reservoir.samples()
+    }
+
+    /// End the sampling process, handing out the kept items and the sampled
+    /// ones apart.
+    pub fn lock(self) -> (Vec<T>, Vec<T>) {
+        (self.kept, self.reservoir.lock_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_kept() {
+        // (序号, 状态码)
+        let mut sampler = always_keep(|&(_, status): &(u32, u16)| status >= 500)
+            .up_to(3)
+            .else_reservoir(2);
+        for i in 0..100 {
+            let status = if i % 10 == 0 { 503 } else { 200 };
+            let _ = sampler.sample((i, status));
+        }
+
+        assert_eq!(sampler.kept(), &[(0, 503), (10, 503), (20, 503)]);
+        let (kept, sampled) = sampler.lock();
+        assert_eq!(kept.len(), 3);
+        assert_eq!(sampled.len(), 2);
+        assert!(sampled.iter().all(|&(i, _)| i > 20 || i % 10 != 0));
+    }
+}
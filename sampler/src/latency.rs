@@ -0,0 +1,146 @@
+//! A preset for sampling traces: slow requests are more likely to be kept,
+//! and every kept one remembers how likely that was, so the true latency
+//! distribution can still be estimated.
+use std::time::Duration;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::weights::{LockWithWeights, Sampled};
+use crate::TicketReservoir;
+
+/// A traced request held by a [`LatencyBiasedSampler`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trace<T> {
+    pub item: T,
+    pub latency: Duration,
+    pub tickets: u64,
+}
+
+/// The default weight: one ticket per started millisecond, at least one.
+pub fn millis(latency: Duration) -> u64 {
+    (latency.as_millis() as u64).saturating_add(1)
+}
+
+/// A `LatencyBiasedSampler` gives every request `weight(latency)` tickets in a
+/// [`TicketReservoir`], so a request twice as slow is about twice as likely to
+/// be kept.
+pub struct LatencyBiasedSampler<T, F = fn(Duration) -> u64, R = ThreadRng> {
+    k: usize,
+    reservoir: TicketReservoir<Trace<T>, R>,
+    weight: F,
+}
+
+impl<T: Clone> LatencyBiasedSampler<T> {
+    /// Weighs requests by [`millis`].
+    pub fn new(k: usize) -> Self {
+        Self::with_weight(k, millis, thread_rng())
+    }
+}
+
+impl<T: Clone, F: Fn(Duration) -> u64, R: Rng> LatencyBiasedSampler<T, F, R> {
+    pub fn with_weight(k: usize, weight: F, rng: R) -> Self {
+        Self {
+            k,
+            reservoir: TicketReservoir::with_rng(k, rng),
+            weight,
+        }
+    }
+}
+
+impl<T, F: Fn(Duration) -> u64, R: Rng> LatencyBiasedSampler<T, F, R> {
+    /// Samples a request which took `latency`. A request given no ticket is
+    /// counted, but never kept.
+    pub fn sample(&mut self, it: T, latency: Duration) -> (u128, u128, Option<T>) {
+        let tickets = (self.weight)(latency);
+        let trace = Trace {
+            item: it,
+            latency,
+            tickets,
+        };
+
+        let (drawn, total, replaced) = self.reservoir.sample(trace, tickets);
+        (drawn, total, replaced.map(|t| t.item))
+    }
+
+    pub fn samples(&self) -> &[Option<Trace<T>>] {
+        self.reservoir.samples()
+    }
+
+    /// The probability a request of `tickets` tickets sampled next is taken
+    /// in: `k * tickets / all tickets`, capped at 1, and 1 while the pool has
+    /// not filled up. Whether it stays held is up to the requests after it,
+    /// see `lock`.
+    pub fn probability(&self, tickets: u64) -> f64 {
+        inclusion(
+            self.k,
+            self.reservoir.total(),
+            self.reservoir.tickets(),
+            tickets,
+        )
+    }
+
+    /// End the sampling process, handing out every kept request with its
+    /// exact probability of being kept, as its [`TicketReservoir`] reports
+    /// it.
+    pub fn lock(self) -> Vec<Sampled<Trace<T>>> {
+        self.reservoir.lock_with_weights()
+    }
+}
+
+impl<T, F: Fn(Duration) -> u64, R: Rng> LockWithWeights for LatencyBiasedSampler<T, F, R> {
+    type Item = Trace<T>;
+
+    fn lock_with_weights(self) -> Vec<Sampled<Trace<T>>> {
+        self.lock()
+    }
+}
+
+fn inclusion(k: usize, total: u64, all: u128, tickets: u64) -> f64 {
+    if total <= k as u64 {
+        return 1.0;
+    }
+    (k as f64 * tickets as f64 / all as f64).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Deterministic;
+
+    #[test]
+    fn slow_requests_are_kept_more() {
+        let mut slow = 0;
+        for seed in 0..500 {
+            let rng = Deterministic::seed_from_u64(seed);
+            let mut sampler = LatencyBiasedSampler::with_weight(1, millis, rng);
+            for i in 0..9 {
+                let _ = sampler.sample(i, Duration::from_millis(9));
+            }
+            let _ = sampler.sample(9, Duration::from_millis(89));
+
+            let kept = sampler.lock();
+            slow += usize::from(kept[0].item.item == 9);
+        }
+
+        // 慢请求占一半的票
+        assert!((200..300).contains(&slow), "{}", slow);
+    }
+
+    #[test]
+    fn estimates_the_count() {
+        let mut estimated = 0.0;
+        const ROUNDS: u64 = 300;
+        for seed in 0..ROUNDS {
+            let rng = Deterministic::seed_from_u64(seed);
+            let mut sampler = LatencyBiasedSampler::with_weight(10, millis, rng);
+            for i in 0..200u64 {
+                let _ = sampler.sample(i, Duration::from_millis(i % 50));
+            }
+            estimated += sampler.lock().iter().map(Sampled::weight).sum::<f64>();
+        }
+
+        let mean = estimated / ROUNDS as f64;
+        assert!((195.0..205.0).contains(&mean), "{}", mean);
+    }
+}
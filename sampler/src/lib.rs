@@ -6,91 +6,912 @@
 //! to accept any new sample more, it can `lock` the result.
 //!
 //! [Reservoir Algorithm](https://en.wikipedia.org/wiki/Reservoir_sampling)
-use rand::random;
+//!
+//! ## Traits
+//! What a sampler can do is split into [`StreamSampler`], [`Inspectable`] and
+//! [`Lockable`], since some samplers can not do all of it. Import them all
+//! through the [`prelude`]:
+//!
+//! ```
+//! use reservoir_sampler::prelude::*;
+//! ```
+//!
+//! Before 0.2, [`ReservoirSampler`] was the one trait holding `sample`,
+//! `samples` and `lock`. It is still there, implemented for every sampler of
+//! the three traits, so code importing it alone keeps compiling; see it for
+//! how to move on to the prelude.
+//!
+//! ## Panics
+//! No sampler in this crate is meant to panic on any sequence of `sample`,
+//! `samples` and `lock` calls, whatever the capacity is. Clippy only rules out
+//! explicit panics, `unwrap` and `expect` here, not indexing or arithmetic
+//! overflow; the `fuzz` directory of the repository drives random call
+//! sequences to catch those. Counts are `u64`, which no stream overflows.
+#![cfg_attr(
+    not(test),
+    deny(clippy::panic, clippy::unwrap_used, clippy::expect_used)
+)]
+use std::fmt;
+use std::iter::Flatten;
+use std::{mem, slice, vec};
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
 
-pub trait ReservoirSampler {
+use crate::history::History;
+use crate::rng::{below, below_u64};
+
+mod accumulate;
+mod adaptive;
+mod algorithm;
+mod array;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod balanced;
+mod batch;
+mod bottomk;
+mod budget;
+mod cancel;
+mod cluster;
+mod compat;
+mod delimited;
+#[cfg(feature = "disk")]
+mod disk;
+mod dynamic;
+pub mod estimate;
+mod exact;
+mod fairness;
+mod fallible;
+mod group;
+#[cfg(feature = "flate2")]
+mod gz;
+#[cfg(feature = "state-hash")]
+mod hash;
+mod heavy;
+mod history;
+mod keep;
+mod latency;
+mod memory;
+mod merge;
+#[cfg(feature = "object-store")]
+mod objects;
+mod outcome;
+mod oversample;
+mod packed;
+mod pin;
+mod poisson;
+#[cfg(feature = "polars")]
+mod polars;
+pub mod prelude;
+mod probability;
+mod quota;
+mod rate;
+mod registry;
+mod replica;
+mod rng;
+mod rotate;
+pub mod service;
+mod shared;
+#[cfg(feature = "sketches")]
+pub mod sketches;
+mod small;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod sources;
+mod split;
+mod store;
+mod systematic;
+mod tap;
+mod tee;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod tickets;
+pub mod util;
+mod weighted;
+mod weights;
+mod windows;
+
+pub use crate::accumulate::{Accumulated, Accumulator, Summary};
+pub use crate::adaptive::AdaptiveSampler;
+pub use crate::algorithm::Algorithm;
+pub use crate::array::ArrayReservoir;
+#[cfg(feature = "arrow")]
+pub use crate::arrow::sample_record_batches;
+pub use crate::balanced::BalancedSampler;
+pub use crate::bottomk::BottomK;
+pub use crate::budget::BytesBudgetReservoir;
+pub use crate::cancel::{CancelToken, Cancellable};
+pub use crate::cluster::{ClusterSampler, ClusterSamplerBuilder};
+pub use crate::compat::ReservoirSampler;
+pub use crate::delimited::{
+    sample_delimited, sample_delimited_into, sample_delimited_until, Delimiter,
+};
+#[cfg(feature = "disk")]
+pub use crate::disk::{DiskItems, DiskReservoir};
+pub use crate::dynamic::{Capacity, DynamicReservoir, GrowingReservoir, Share};
+pub use crate::exact::{exact_sample, sample_range, ExactError, ExactSampler};
+pub use crate::fairness::{category_histogram, CategoryShare, FairnessReport};
+pub use crate::fallible::{OnSourceError, SourceErrors};
+pub use crate::group::{Group, SampledGroupBy};
+#[cfg(feature = "flate2")]
+pub use crate::gz::{sample_gz_delimited, sample_gz_lines};
+#[cfg(feature = "state-hash")]
+pub use crate::hash::{HashState, StateHasher};
+pub use crate::heavy::SpaceSaving;
+pub use crate::history::Eviction;
+pub use crate::keep::{always_keep, AlwaysKeep, KeepOrSample};
+pub use crate::latency::{millis, LatencyBiasedSampler, Trace};
+pub use crate::memory::{BudgetPolicy, Loss, SizeHint};
+pub use crate::merge::{MergeError, Mergeable, WireItem, MAX_WIRE_CAPACITY, WIRE_VERSION};
+#[cfg(feature = "object-store")]
+pub use crate::objects::{ObjectList, ObjectReader, ObjectRecords};
+pub use crate::outcome::{SampleOutcome, SamplerError};
+pub use crate::oversample::Oversampler;
+pub use crate::packed::PackedReservoir;
+pub use crate::poisson::{Inclusion, PoissonSampler, Pps};
+#[cfg(feature = "polars")]
+pub use crate::polars::{sample_data_frame, sample_data_frames, sample_lazy_frame};
+pub use crate::probability::{inclusion_probability, replacement_probability};
+pub use crate::quota::QuotaReservoir;
+pub use crate::rate::RateLimited;
+pub use crate::registry::{RegistryError, SamplerRegistry};
+pub use crate::replica::{Patch, PatchError};
+pub use crate::rng::Deterministic;
+pub use crate::rotate::{RotatingSampler, Rotation, Window};
+pub use crate::shared::SharedReservoir;
+pub use crate::small::SmallReservoir;
+#[cfg(feature = "serde")]
+pub use crate::snapshot::{
+    ReservoirState, SamplerSnapshot, Snapshot, SnapshotError, SnapshotV1, VersionedSnapshot,
+    SNAPSHOT_VERSION,
+};
+pub use crate::sources::{ItemSource, PrioritySources};
+pub use crate::split::{Partition, Split, Splitter};
+pub use crate::store::{PackedStore, SampleStore, StoreReservoir};
+pub use crate::systematic::SystematicSampler;
+pub use crate::tap::{DecisionTap, Tapped};
+pub use crate::tee::{Branch, Tee};
+pub use crate::tickets::TicketReservoir;
+pub use crate::weighted::sample_indices_weighted;
+pub use crate::weights::{LockWithWeights, Sampled};
+pub use crate::windows::WindowManager;
+
+pub trait StreamSampler {
     /// Each sampler only processes the same type of items.
     type Item;
 
+    /// What an item draws, like the integer of Algorithm R, or the float key
+    /// of a weighted algorithm. Only the sampler itself can tell whether a
+    /// draw got the item held, see [`StreamSampler::is_held`].
+    type Draw;
+
     /// A sampler processes exactly one item each time, for the items come in as
     /// a stream.
     ///
     /// ## Return
     /// the `sample` function return a tuple contains 3 elements:
-    /// - a `usize` stands for what random number the current item gets
-    /// - a `usize` stands for how many items has been passed through so far
+    /// - the draw the current item gets
+    /// - a `u64` stands for how many items has been passed through so far, a
+    ///   count which outgrows a 32-bit `usize` soon enough
     /// - an option of item that is replaced by the current item.
-    fn sample(&mut self, it: Self::Item) -> (usize, usize, Option<Self::Item>);
+    fn sample(&mut self, it: Self::Item) -> (Self::Draw, u64, Option<Self::Item>);
+
+    /// Whether the item which got `draw` from `sample` is held.
+    fn is_held(&self, draw: &Self::Draw) -> bool;
+
+    /// Samples every item of `items`, returning how many of them were held
+    /// when they were sampled.
+    fn sample_all<I>(&mut self, items: I) -> u64
+    where
+        I: IntoIterator<Item = Self::Item>,
+        Self: Sized,
+    {
+        let mut held = 0;
+        for it in items {
+            let (draw, _, _) = self.sample(it);
+            if self.is_held(&draw) {
+                held += 1;
+            }
+        }
+        held
+    }
+
+    /// The same as `sample_all`, stopping early once `token` is cancelled,
+    /// see [`CancelToken::guard`] to stop any other run.
+    fn sample_all_until<I>(&mut self, items: I, token: &CancelToken) -> u64
+    where
+        I: IntoIterator<Item = Self::Item>,
+        Self: Sized,
+    {
+        self.sample_all(token.guard(items))
+    }
+
+    /// The same as `sample_all`, but for a source which can fail, like the
+    /// lines of a file, returning how many items were held. On an error,
+    /// `policy` tells whether to stop or to go on, reporting every error
+    /// then.
+    fn try_sample_from<I, E>(
+        &mut self,
+        items: I,
+        policy: OnSourceError,
+    ) -> Result<u64, SourceErrors<E>>
+    where
+        I: IntoIterator<Item = Result<Self::Item, E>>,
+        Self: Sized,
+    {
+        let mut held = 0;
+        let mut errors = Vec::new();
+        for (at, it) in (0..).zip(items) {
+            match it {
+                Ok(it) => {
+                    let (draw, _, _) = self.sample(it);
+                    if self.is_held(&draw) {
+                        held += 1;
+                    }
+                }
+                Err(e) => {
+                    errors.push((at, e));
+                    if policy == OnSourceError::Stop {
+                        break;
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(held)
+        } else {
+            Err(SourceErrors { held, errors })
+        }
+    }
+}
 
+/// A sampler keeping its pool in memory, so it can show the held items at any
+/// time.
+pub trait Inspectable: StreamSampler {
     /// A reservoir should know which items are held no matter if the sampling
     /// process is finished.
+    ///
+    /// They come in slot order. Unless a sampler says otherwise, that order is
+    /// unspecified, and may change as items pass through.
     fn samples(&self) -> &[Option<Self::Item>];
 
+    /// Whether some slot is still empty, which is when every item passing
+    /// through gets held. A sampler of no capacity never warms up.
+    fn is_warming_up(&self) -> bool {
+        self.samples().iter().any(Option::is_none)
+    }
+}
+
+/// A sampler which can end the sampling process and hand out what it holds.
+pub trait Lockable: StreamSampler {
     /// End the sampling process. Shuffling the order of the result is allowed.
     fn lock(self) -> Vec<Option<Self::Item>>;
+
+    /// End the sampling process like `lock`, but only hand out the held items,
+    /// one at a time. Samplers which can avoid building the `Vec` do so.
+    fn lock_iter(self) -> impl Iterator<Item = Self::Item>
+    where
+        Self: Sized,
+    {
+        self.lock().into_iter().flatten()
+    }
+
+    /// End the sampling process like `lock`, leaving the empty slots out.
+    fn lock_compact(self) -> Vec<Self::Item>
+    where
+        Self: Sized,
+    {
+        self.lock_iter().collect()
+    }
+}
+
+// 转发给借用和装箱的采样器，方便放在别的结构体里的采样器传给泛型函数
+impl<S: StreamSampler + ?Sized> StreamSampler for &mut S {
+    type Item = S::Item;
+    type Draw = S::Draw;
+
+    fn sample(&mut self, it: Self::Item) -> (Self::Draw, u64, Option<Self::Item>) {
+        (**self).sample(it)
+    }
+
+    fn is_held(&self, draw: &Self::Draw) -> bool {
+        (**self).is_held(draw)
+    }
+}
+
+impl<S: Inspectable + ?Sized> Inspectable for &mut S {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        (**self).samples()
+    }
+}
+
+/// A borrowed sampler can not be used up, so locking it hands out a copy of
+/// the pool, leaving the sampler as it is. The sampler's own rng is not in
+/// reach, so the copy is not shuffled: held items come first in slot order,
+/// and the same pool always gives the same copy. Shuffle it with a seeded rng
+/// when the order matters.
+impl<S> Lockable for &mut S
+where
+    S: Inspectable + ?Sized,
+    S::Item: Clone,
+{
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        let mut pool = Inspectable::samples(self).to_vec();
+        // 稳定排序，空槽挪到后面
+        pool.sort_by_key(Option::is_none);
+        pool
+    }
+}
+
+impl<S: StreamSampler + ?Sized> StreamSampler for Box<S> {
+    type Item = S::Item;
+    type Draw = S::Draw;
+
+    fn sample(&mut self, it: Self::Item) -> (Self::Draw, u64, Option<Self::Item>) {
+        (**self).sample(it)
+    }
+
+    fn is_held(&self, draw: &Self::Draw) -> bool {
+        (**self).is_held(draw)
+    }
+}
+
+impl<S: Inspectable + ?Sized> Inspectable for Box<S> {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        (**self).samples()
+    }
+}
+
+impl<S: Lockable> Lockable for Box<S> {
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        (*self).lock()
+    }
+
+    fn lock_iter(self) -> impl Iterator<Item = Self::Item> {
+        (*self).lock_iter()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ReservoirError {
+    ZeroCapacity,
+    InvalidAccuracy,
 }
 
 /// A `Reservoir` is a just a pool, but for random number generation, `total`
 /// items' count passed through is known.
-pub struct Reservoir<T> {
-    total: usize,
+///
+/// The first `capacity` items always fill the pool: once `t` of them passed
+/// through, the first `t` slots hold exactly those items, and the others are
+/// empty. `R` keeps them in a random order, `L` and `X` in their order of
+/// arrival. See [`Reservoir::first_k_passthrough`].
+///
+/// `samples` shows the pool in slot order, which is not the order of
+/// arrival: an `R` reservoir moves items around while it fills, and
+/// replacements land in random slots. [`Reservoir::samples_by_arrival`]
+/// orders the held items by when they came.
+///
+/// A clone samples on from the same state, with a copy of the rng, which
+/// makes it a branch of the same draw when the rng is [`Deterministic`].
+///
+/// Making a reservoir allocates its pool only. What it tracks of every slot,
+/// for [`Reservoir::diff`] and [`Reservoir::samples_by_arrival`], is
+/// allocated once the first item comes in. For many tiny reservoirs, see
+/// [`SmallReservoir`], or [`ArrayReservoir`] when the capacity is known at
+/// compile time.
+#[derive(Clone)]
+pub struct Reservoir<T, R = ThreadRng> {
+    total: u64,
     pool: Vec<Option<T>>,
+    rng: R,
+    algorithm: Algorithm,
+    // 算法 L/X：还要跳过多少个，以及 L 的 w
+    skip: u64,
+    w: f64,
+    // 删除：总共删了多少个，以及尚待补偿的（在样本中 / 不在样本中）
+    deleted: u64,
+    pending_held: u64,
+    pending_unheld: u64,
+    // compact_and_shuffle 之后不宜再抽取
+    locked: bool,
+    // 复制：槽位每变一次代数加一
+    generation: u64,
+    // 槽位第一次变化时才分配，在此之前都是 0
+    marks: Vec<Marks>,
+    history: Option<History<T>>,
+    // 钉住的槽位，升序
+    pins: Vec<usize>,
+}
+
+/// What a [`Reservoir`] knows of a slot besides its item.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub(crate) struct Marks {
+    /// The generation the slot last changed in, for [`Reservoir::diff`].
+    pub(crate) stamp: u64,
+    /// The stream index of the held item, counting from 1 and counting
+    /// deleted items too, or 0 when it is not known.
+    pub(crate) arrival: u64,
+}
+
+/// Compares what is sampled so far, leaving the rngs out, since most of them
+/// can not be compared, and the generations of [`Reservoir::diff`] too.
+impl<T: PartialEq, R> PartialEq for Reservoir<T, R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.total == other.total
+            && self.pool == other.pool
+            && self.algorithm == other.algorithm
+            && self.skip == other.skip
+            && self.w == other.w
+            && self.deleted == other.deleted
+            && self.pending_held == other.pending_held
+            && self.pending_unheld == other.pending_unheld
+            && self.locked == other.locked
+            && self.pins == other.pins
+    }
+}
+
+/// Only shows the counts, for the pool may be huge.
+impl<T, R> fmt::Debug for Reservoir<T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reservoir")
+            .field("capacity", &self.pool.len())
+            .field("total", &self.total)
+            .field("filled", &self.pool.iter().flatten().count())
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<T: Clone> Reservoir<T> {
+    /// A reservoir with no capacity never keeps anything, see
+    /// [`Reservoir::try_with_capacity`] to reject it upfront.
     pub fn with_capacity(n: usize) -> Self {
+        Self::with_rng(n, thread_rng())
+    }
+
+    pub fn try_with_capacity(n: usize) -> Result<Self, ReservoirError> {
+        Self::try_with_rng(n, thread_rng())
+    }
+
+    pub fn with_algorithm(n: usize, algorithm: Algorithm) -> Self {
+        Self::with_rng_and_algorithm(n, thread_rng(), algorithm)
+    }
+
+    /// A reservoir just big enough for proportions estimated from it to be
+    /// within `margin_of_error` at `confidence`, see
+    /// [`estimate::required_sample_size`]. Without a population hint, an
+    /// accuracy needing more than [`estimate::MAX_SAMPLE_SIZE`] items is
+    /// [`ReservoirError::InvalidAccuracy`].
+    pub fn with_target_accuracy(
+        population_hint: Option<usize>,
+        confidence: f64,
+        margin_of_error: f64,
+    ) -> Result<Self, ReservoirError> {
+        let n = estimate::required_sample_size(population_hint, confidence, margin_of_error)
+            .ok_or(ReservoirError::InvalidAccuracy)?;
+        Self::try_with_capacity(n)
+    }
+}
+
+impl<T: Clone> Reservoir<T, Deterministic> {
+    /// A reservoir whose samples only depend on `seed` and the incoming items,
+    /// so a draw can be replayed and verified on any other machine.
+    pub fn deterministic(n: usize, seed: u64) -> Self {
+        Self::with_rng(n, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T: Clone, R: Rng> Reservoir<T, R> {
+    pub fn with_rng(n: usize, rng: R) -> Self {
+        Self::with_rng_and_algorithm(n, rng, Algorithm::R)
+    }
+
+    pub fn with_rng_and_algorithm(n: usize, rng: R, algorithm: Algorithm) -> Self {
         Self {
             total: 0,
             pool: std::vec::from_elem(Option::<T>::None, n),
+            rng,
+            algorithm,
+            skip: 0,
+            w: 1.0,
+            deleted: 0,
+            pending_held: 0,
+            pending_unheld: 0,
+            locked: false,
+            generation: 0,
+            marks: Vec::new(),
+            history: None,
+            pins: Vec::new(),
         }
     }
+
+    pub fn try_with_rng(n: usize, rng: R) -> Result<Self, ReservoirError> {
+        if n == 0 {
+            return Err(ReservoirError::ZeroCapacity);
+        }
+        Ok(Self::with_rng(n, rng))
+    }
 }
 
-impl<T> ReservoirSampler for Reservoir<T> {
-    type Item = T;
+impl<T, R: Rng> Reservoir<T, R> {
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
 
-    fn sample(&mut self, it: Self::Item) -> (usize, usize, Option<Self::Item>) {
-        let pool_cap = self.pool.capacity();
+    /// How many items have been passed through so far, less the deleted ones.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
 
-        self.total += 1;
+    /// Whether the next item passes straight into an empty slot, which every
+    /// item does until `capacity` items have passed through, so a UI can tell
+    /// filling from replacing. Deletions still to be made up for, or a
+    /// shuffle by `compact_and_shuffle`, end the passthrough early.
+    pub fn first_k_passthrough(&self) -> bool {
+        if self.locked || self.pending_held + self.pending_unheld > 0 {
+            return false;
+        }
+        if self.deleted == 0 {
+            return self.total < self.pool.len() as u64;
+        }
+        self.pool.iter().any(Option::is_none)
+    }
+
+    fn next_skip(&mut self) {
+        let k = self.pool.len();
+        self.skip = match self.algorithm {
+            Algorithm::R => 0,
+            Algorithm::L => {
+                self.w = algorithm::l_next_w(self.w, k, &mut self.rng);
+                algorithm::l_skip(self.w, &mut self.rng)
+            }
+            Algorithm::X => algorithm::x_skip(self.total, k, &mut self.rng),
+        };
+    }
+
+    /// Retracts one item passed through earlier, like a user who unsubscribed,
+    /// removing it from the sample when it is held there. `pred` identifies
+    /// the item, and the first held one it matches is removed. Returns whether
+    /// the retracted item was held.
+    ///
+    /// Call it once for every retracted item, held or not, so the following
+    /// items can make up for the deletions by random pairing, which keeps the
+    /// sample uniform over the items which were not retracted. After the first
+    /// deletion, `L` and `X` reservoirs fall back to the draws of `R`.
+    pub fn delete(&mut self, mut pred: impl FnMut(&T) -> bool) -> bool {
+        if self.total == 0 {
+            return false;
+        }
+        self.total -= 1;
+        self.deleted += 1;
+
+        match self
+            .pool
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(&mut pred))
+        {
+            Some(slot) => {
+                self.pool[slot] = None;
+                self.touch(slot);
+                self.pins.retain(|&pin| pin != slot);
+                self.pending_held += 1;
+                true
+            }
+            None => {
+                self.pending_unheld += 1;
+                false
+            }
+        }
+    }
+
+    /// Whether any held item matches `pred`, for checking whether an entity
+    /// is in the sample right now.
+    pub fn contains(&self, pred: impl FnMut(&T) -> bool) -> bool {
+        self.find(pred).is_some()
+    }
+
+    /// The first held item `pred` matches.
+    pub fn find(&self, mut pred: impl FnMut(&T) -> bool) -> Option<&T> {
+        self.pool.iter().flatten().find(|it| pred(it))
+    }
+
+    /// Amends a held item in place, for streams where later records update
+    /// earlier ones. `f` runs on the first held item `pred` matches, if any,
+    /// and nothing is drawn, so no item's chance to be held changes. Returns
+    /// whether an item was updated.
+    pub fn update_if_held(
+        &mut self,
+        mut pred: impl FnMut(&T) -> bool,
+        f: impl FnOnce(&mut T),
+    ) -> bool {
+        let slot = self
+            .pool
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(&mut pred));
+        match slot.and_then(|slot| self.pool[slot].as_mut().map(|it| (slot, it))) {
+            Some((slot, it)) => {
+                f(it);
+                self.touch(slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How many items have been retracted by [`Reservoir::delete`].
+    pub fn deletions(&self) -> u64 {
+        self.deleted
+    }
+
+    /// Random pairing: while deletions are not yet made up for, an item takes
+    /// the place of a deleted held item with the chance of `pending_held` out
+    /// of all pending ones, and otherwise pairs with a deleted unheld one.
+    fn sample_after_deletion(&mut self, it: T) -> (u64, u64, Option<T>) {
+        let pool_cap = self.pool.len() as u64;
+        let rejected = (pool_cap + 1).max(self.total);
 
-        // 概率渐小的随机替换
-        let r = random::<usize>() % self.total + 1;
-        let mut replaced = None;
-        if r <= pool_cap {
-            replaced = self.pool[r - 1].take();
-            self.pool[r - 1] = Some(it);
+        let pending = self.pending_held + self.pending_unheld;
+        if pending > 0 {
+            if below_u64(&mut self.rng, pending) >= self.pending_held {
+                self.pending_unheld -= 1;
+                return (rejected, self.total, None);
+            }
+            self.pending_held -= 1;
+        }
+
+        // 补偿完毕后，有空位说明不足容量的数据都在样本中
+        if let Some(slot) = self.pool.iter().position(Option::is_none) {
+            self.pool[slot] = Some(it);
+            self.touch(slot);
+            self.set_arrival(slot, self.arrival());
+            return (slot as u64 + 1, self.total, None);
         }
 
-        if self.total <= pool_cap && r < self.total {
-            self.pool[self.total - 1] = replaced.take();
+        if !self.pins.is_empty() {
+            return self.sample_pinned(it);
         }
+        self.sample_r(it)
+    }
+
+    fn sample_r(&mut self, it: T) -> (u64, u64, Option<T>) {
+        let r = algorithm::r_draw(self.total, &mut self.rng);
+        self.place_r(r, it)
+    }
 
+    /// Algorithm R once the `total`-th item drew `r`.
+    fn place_r(&mut self, r: u64, it: T) -> (u64, u64, Option<T>) {
+        let replaced = if r > self.pool.len() as u64 {
+            None
+        } else {
+            algorithm::r_place(&mut self.pool, self.total, r, it)
+        };
+        if let Some(slot) = r
+            .checked_sub(1)
+            .filter(|&slot| slot < self.pool.len() as u64)
+        {
+            self.touch(slot as usize);
+            self.record_eviction(slot as usize, &replaced);
+            // 仍在填充时，被替换的条目挪到了第 total 个槽位
+            if self.total <= self.pool.len() as u64 {
+                self.touch(self.total as usize - 1);
+                let moved = self.marks(slot as usize).arrival;
+                self.set_arrival(self.total as usize - 1, moved);
+            }
+            self.set_arrival(slot as usize, self.arrival());
+        }
         (r, self.total, replaced)
     }
 
-    fn samples(&self) -> &[Option<Self::Item>] {
-        &self.pool[..]
+    /// The stream index of the item just passed through, counting from 1 and
+    /// counting deleted items too.
+    fn arrival(&self) -> u64 {
+        self.total + self.deleted
     }
 
-    fn lock(mut self) -> Vec<Option<Self::Item>> {
-        let mut i = self.total;
-        while i < self.pool.capacity() {
-            i += 1;
+    /// Remembers the item `slot` held when it is replaced, before the slot's
+    /// arrival is overwritten.
+    fn record_eviction(&mut self, slot: usize, replaced: &Option<T>) {
+        let (arrived_at, replaced_at) = (self.marks(slot).arrival, self.arrival());
+        if let (Some(history), Some(item)) = (&mut self.history, replaced) {
+            history.record(item, slot, arrived_at, replaced_at);
+        }
+    }
+
+    /// Marks `slot` as changed, for [`Reservoir::diff`].
+    pub(crate) fn touch(&mut self, slot: usize) {
+        self.generation += 1;
+        let generation = self.generation;
+        if let Some(marks) = self.marks_mut(slot) {
+            marks.stamp = generation;
+        }
+    }
+
+    /// Marks every slot as changed, for [`Reservoir::diff`].
+    pub(crate) fn touch_all(&mut self) {
+        self.generation += 1;
+        self.marks.resize(self.pool.len(), Marks::default());
+        for marks in &mut self.marks {
+            marks.stamp = self.generation;
+        }
+    }
 
-            let r = random::<usize>() % i + 1;
-            if r <= self.pool.capacity() {
-                self.pool[i - 1] = self.pool[r - 1].take();
+    pub(crate) fn set_arrival(&mut self, slot: usize, arrival: u64) {
+        if let Some(marks) = self.marks_mut(slot) {
+            marks.arrival = arrival;
+        }
+    }
+
+    /// The marks of `slot`, all 0 until a slot first changes.
+    pub(crate) fn marks(&self, slot: usize) -> Marks {
+        self.marks.get(slot).copied().unwrap_or_default()
+    }
+
+    /// The marks of `slot` to change, made for every slot the first time.
+    pub(crate) fn marks_mut(&mut self, slot: usize) -> Option<&mut Marks> {
+        if self.marks.is_empty() {
+            self.marks = vec![Marks::default(); self.pool.len()];
+        }
+        self.marks.get_mut(slot)
+    }
+
+    /// Takes the marks out, slot by slot, to move them along with the items.
+    pub(crate) fn take_marks(&mut self) -> impl Iterator<Item = Marks> {
+        mem::take(&mut self.marks)
+            .into_iter()
+            .chain(std::iter::repeat(Marks::default()))
+    }
+
+    /// Gathers the retained items at the front of the pool, then shuffles the
+    /// whole pool, so every retained item ends up in a uniformly random slot.
+    /// When fewer items than the capacity have passed through, the empty slots
+    /// are spread uniformly as well, instead of always trailing the pool.
+    ///
+    /// `lock` runs this step before handing the pool out. Sampling afterwards
+    /// may overwrite items, which [`Reservoir::try_sample`] reports.
+    pub fn compact_and_shuffle(&mut self) {
+        self.locked = true;
+        self.pins.clear();
+        // 条目带着序号一起打乱
+        let mut pool: Vec<Option<(T, Marks)>> = mem::take(&mut self.pool)
+            .into_iter()
+            .zip(self.take_marks())
+            .map(|(slot, marks)| slot.map(|it| (it, marks)))
+            .collect();
+        rng::compact_and_shuffle(&mut pool, &mut self.rng);
+        (self.pool, self.marks) = pool
+            .into_iter()
+            .map(|slot| match slot {
+                Some((it, marks)) => (Some(it), marks),
+                None => (None, Marks::default()),
+            })
+            .unzip();
+        self.touch_all();
+    }
+
+    /// End the sampling process, handing out the pool in slot order, the way
+    /// `samples` shows it, without the shuffle of `lock`.
+    pub fn into_samples(self) -> Vec<Option<T>> {
+        self.pool
+    }
+
+    /// The held items, in slot order, skipping the empty slots.
+    pub fn iter(&self) -> Flatten<slice::Iter<'_, Option<T>>> {
+        self.pool.iter().flatten()
+    }
+
+    /// The held items in the order they passed through, which stays put while
+    /// the pool fills and shuffles, unlike `samples`. Items whose arrival is
+    /// not known, like after a merge or a restore, come first, in slot order.
+    pub fn samples_by_arrival(&self) -> Vec<&T> {
+        let mut held: Vec<(u64, &T)> = self
+            .pool
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, slot)| Some((self.marks(idx).arrival, slot.as_ref()?)))
+            .collect();
+        held.sort_by_key(|&(arrival, _)| arrival);
+        held.into_iter().map(|(_, it)| it).collect()
+    }
+}
+
+/// Locks the reservoir, so the held items come out shuffled, like
+/// `lock_iter`.
+impl<T, R: Rng> IntoIterator for Reservoir<T, R> {
+    type Item = T;
+    type IntoIter = Flatten<vec::IntoIter<Option<T>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Lockable::lock(self).into_iter().flatten()
+    }
+}
+
+impl<'a, T, R> IntoIterator for &'a Reservoir<T, R> {
+    type Item = &'a T;
+    type IntoIter = Flatten<slice::Iter<'a, Option<T>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pool.iter().flatten()
+    }
+}
+
+impl<T, R: Rng> StreamSampler for Reservoir<T, R> {
+    type Item = T;
+    /// The items of `1..=capacity` are held.
+    type Draw = u64;
+
+    fn sample(&mut self, it: Self::Item) -> (u64, u64, Option<Self::Item>) {
+        let pool_cap = self.pool.len();
+
+        self.total += 1;
+
+        // 容量为 0 时只计数，不抽取
+        if pool_cap == 0 {
+            return (0, self.total, None);
+        }
+
+        if self.deleted > 0 {
+            return self.sample_after_deletion(it);
+        }
+
+        if !self.pins.is_empty() {
+            return self.sample_pinned(it);
+        }
+
+        if self.algorithm == Algorithm::R {
+            return self.sample_r(it);
+        }
+
+        // L 和 X：先按顺序填满，之后跳过若干个再替换
+        if self.total <= pool_cap as u64 {
+            self.pool[self.total as usize - 1] = Some(it);
+            self.touch(self.total as usize - 1);
+            self.set_arrival(self.total as usize - 1, self.arrival());
+            if self.total == pool_cap as u64 {
+                self.next_skip();
             }
+            return (self.total, self.total, None);
         }
 
+        if self.skip > 0 {
+            self.skip -= 1;
+            return (self.total, self.total, None);
+        }
+
+        let slot = below(&mut self.rng, pool_cap);
+        let replaced = self.pool[slot].replace(it);
+        self.touch(slot);
+        self.record_eviction(slot, &replaced);
+        self.set_arrival(slot, self.arrival());
+        self.next_skip();
+        (slot as u64 + 1, self.total, replaced)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        (1..=self.pool.len() as u64).contains(draw)
+    }
+}
+
+impl<T, R: Rng> Inspectable for Reservoir<T, R> {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        &self.pool[..]
+    }
+}
+
+impl<T, R: Rng> Lockable for Reservoir<T, R> {
+    fn lock(mut self) -> Vec<Option<Self::Item>> {
+        self.compact_and_shuffle();
         self.pool
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use std::ops::Range;
+
+    // 不用 super::*，以免兼容的 ReservoirSampler 让方法调用有歧义
+    use super::{
+        Algorithm, Deterministic, Inspectable, Lockable, Reservoir, ReservoirError, StreamSampler,
+    };
 
     #[test]
     fn test() {
@@ -104,4 +925,370 @@ mod tests {
 
         println!("result: {:?}", reservoir.lock());
     }
+
+    #[test]
+    fn every_algorithm_is_uniform() {
+        const CAP: usize = 3;
+        const TOTAL: usize = 12;
+        const ROUNDS: u64 = 6000;
+
+        for &algorithm in &[Algorithm::R, Algorithm::L, Algorithm::X] {
+            let mut hits = [0u64; TOTAL];
+            for seed in 0..ROUNDS {
+                let rng = Deterministic::seed_from_u64(seed);
+                let mut reservoir = Reservoir::with_rng_and_algorithm(CAP, rng, algorithm);
+                for it in 0..TOTAL {
+                    let _ = reservoir.sample(it);
+                }
+                for &it in reservoir.iter() {
+                    hits[it] += 1;
+                }
+            }
+
+            // 每个元素被选中的概率都应是 CAP / TOTAL
+            let expected = ROUNDS * CAP as u64 / TOTAL as u64;
+            for &count in &hits {
+                assert!(
+                    count.abs_diff(expected) < expected / 8,
+                    "{:?}: {:?}",
+                    algorithm,
+                    hits
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_after_deletions() {
+        const CAP: usize = 3;
+        const ROUNDS: u64 = 6000;
+
+        let mut hits = [0u64; 14];
+        for seed in 0..ROUNDS {
+            let mut reservoir = Reservoir::<usize, Deterministic>::deterministic(CAP, seed);
+            for it in 0..10 {
+                let _ = reservoir.sample(it);
+            }
+            for gone in 0..4 {
+                let _ = reservoir.delete(|&it| it == gone);
+            }
+            for it in 10..14 {
+                let _ = reservoir.sample(it);
+            }
+
+            assert_eq!(reservoir.deletions(), 4);
+            assert_eq!(reservoir.iter().count(), CAP);
+            for &it in reservoir.iter() {
+                hits[it] += 1;
+            }
+        }
+
+        // 剩下的 10 个元素被选中的概率都应是 CAP / 10
+        assert_eq!(&hits[..4], &[0; 4]);
+        let expected = ROUNDS * CAP as u64 / 10;
+        for &count in &hits[4..] {
+            assert!(count.abs_diff(expected) < expected / 8, "{:?}", hits);
+        }
+    }
+
+    #[test]
+    fn update_if_held() {
+        let mut reservoir = Reservoir::<(u32, u32), Deterministic>::deterministic(2, 5);
+        for id in 0..2 {
+            let _ = reservoir.sample((id, 0));
+        }
+
+        assert!(reservoir.update_if_held(|&(id, _)| id == 1, |it| it.1 += 1));
+        assert!(!reservoir.update_if_held(|&(id, _)| id == 7, |it| it.1 += 1));
+        assert!(reservoir.contains(|&(id, _)| id == 0));
+        assert!(!reservoir.contains(|&(id, _)| id == 7));
+        assert_eq!(reservoir.find(|&(id, _)| id == 1), Some(&(1, 1)));
+        assert_eq!(reservoir.samples(), &[Some((0, 0)), Some((1, 1))]);
+        assert_eq!(reservoir.total, 2);
+    }
+
+    #[test]
+    fn target_accuracy() {
+        let reservoir = Reservoir::<u32>::with_target_accuracy(None, 0.95, 0.05);
+        assert_eq!(reservoir.map(|r| r.samples().len()), Ok(385));
+
+        let reservoir = Reservoir::<u32>::with_target_accuracy(None, 0.95, 2.0);
+        assert_eq!(reservoir.err(), Some(ReservoirError::InvalidAccuracy));
+
+        let reservoir = Reservoir::<u32>::with_target_accuracy(None, 0.95, 1e-6);
+        assert_eq!(reservoir.err(), Some(ReservoirError::InvalidAccuracy));
+        let reservoir = Reservoir::<u32>::with_target_accuracy(Some(100), 0.95, 1e-6);
+        assert_eq!(reservoir.map(|r| r.samples().len()), Ok(100));
+    }
+
+    #[test]
+    fn lock_iter() {
+        let mut reservoir = Reservoir::<i32>::with_capacity(5);
+        for it in 0..3 {
+            let _ = reservoir.sample(it);
+        }
+
+        let mut held: Vec<_> = reservoir.lock_iter().collect();
+        held.sort_unstable();
+        assert_eq!(held, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let result = Reservoir::<i32>::try_with_capacity(0);
+        assert_eq!(result.err(), Some(ReservoirError::ZeroCapacity));
+
+        let mut reservoir = Reservoir::<i32>::with_capacity(0);
+        assert_eq!(reservoir.sample(1), (0, 1, None));
+        assert_eq!(reservoir.sample(2), (0, 2, None));
+        assert!(reservoir.lock().is_empty());
+    }
+
+    #[test]
+    fn deterministic_replay() {
+        let draw = |seed| {
+            let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(3, seed);
+            for it in 0..100 {
+                let _ = reservoir.sample(it);
+            }
+            reservoir.lock()
+        };
+
+        assert_eq!(draw(27), draw(27));
+    }
+
+    #[test]
+    fn deterministic_is_pinned() {
+        // Any change here breaks every published draw, see `Deterministic`.
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(3, 27);
+        for it in 0..100 {
+            let _ = reservoir.sample(it);
+        }
+
+        assert_eq!(reservoir.lock(), vec![Some(48), Some(59), Some(10)]);
+    }
+
+    #[test]
+    fn totals_beyond_u32() {
+        let mut reservoir = Reservoir::<u64, Deterministic>::deterministic(4, 27);
+        for it in 0..4 {
+            let _ = reservoir.sample(it);
+        }
+        // 直接跳到 2^33，相当于已经过了八十多亿个
+        let start = 1 << 33;
+        reservoir.total = start;
+
+        let (mut kept, mut beyond) = (0, 0);
+        for it in 1..=10_000 {
+            let (r, total, _) = reservoir.sample(it);
+            assert_eq!(total, start + it);
+            assert!((1..=total).contains(&r));
+            kept += usize::from(r <= 4);
+            beyond += usize::from(r > u64::from(u32::MAX));
+        }
+        assert_eq!(reservoir.total(), start + 10_000);
+        // 每个被保留的概率约为 4 / 2^33，抽到的数约一半超出 u32
+        assert!(kept <= 1);
+        assert!((4_500..5_500).contains(&beyond));
+    }
+
+    #[test]
+    fn provided_methods() {
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(5, 1);
+        assert!(reservoir.is_warming_up());
+        // 没装满之前每个都会被留下
+        assert_eq!(reservoir.sample_all(0..3), 3);
+        assert!(reservoir.is_warming_up());
+        assert!(reservoir.sample_all(3..100) < 97);
+        assert!(!reservoir.is_warming_up());
+        assert_eq!(reservoir.lock_compact().len(), 5);
+
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(5, 1);
+        let _ = reservoir.sample_all(0..3);
+        let mut kept = reservoir.lock_compact();
+        kept.sort_unstable();
+        assert_eq!(kept, vec![0, 1, 2]);
+
+        assert!(!Reservoir::<i32>::with_capacity(0).is_warming_up());
+    }
+
+    #[test]
+    fn first_k_fill_the_pool() {
+        for algorithm in [Algorithm::R, Algorithm::L, Algorithm::X] {
+            for seed in 0..50 {
+                let rng = Deterministic::seed_from_u64(seed);
+                let mut reservoir = Reservoir::with_rng_and_algorithm(8, rng, algorithm);
+                for t in 0..8 {
+                    assert!(reservoir.first_k_passthrough());
+                    let (draw, _, replaced) = reservoir.sample(t);
+                    assert!(reservoir.is_held(&draw));
+                    assert_eq!(replaced, None);
+
+                    // 前 t + 1 个槽位恰好是前 t + 1 个条目
+                    let t = t as usize + 1;
+                    let mut filled: Vec<_> = reservoir.samples()[..t].iter().flatten().collect();
+                    filled.sort_unstable();
+                    assert!(filled.into_iter().copied().eq(0..t as i32));
+                    assert!(reservoir.samples()[t..].iter().all(Option::is_none));
+                }
+                assert!(!reservoir.first_k_passthrough());
+            }
+        }
+
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(3, 1);
+        let _ = reservoir.sample_all(0..5);
+        let _ = reservoir.delete(|_| true);
+        assert!(!reservoir.first_k_passthrough());
+        let mut locked = Reservoir::<i32, Deterministic>::deterministic(3, 1);
+        locked.compact_and_shuffle();
+        assert!(!locked.first_k_passthrough());
+    }
+
+    #[test]
+    fn samples_by_arrival() {
+        for &algorithm in &[Algorithm::R, Algorithm::L, Algorithm::X] {
+            let rng = Deterministic::seed_from_u64(1);
+            let mut reservoir = Reservoir::with_rng_and_algorithm(8, rng, algorithm);
+            // 还没有抽取时，槽位的标记不占内存
+            assert!(reservoir.marks.is_empty());
+            assert_eq!(reservoir.samples_by_arrival(), Vec::<&i32>::new());
+            let _ = reservoir.sample_all(0..5);
+            assert_eq!(reservoir.marks.len(), 8);
+            assert_eq!(reservoir.samples_by_arrival(), vec![&0, &1, &2, &3, &4]);
+
+            // 条目即其序号，按到达排序后应递增
+            let _ = reservoir.sample_all(5..1_000);
+            let _ = reservoir.delete(|_| true);
+            let _ = reservoir.sample_all(1_000..1_010);
+            let by_arrival = reservoir.samples_by_arrival();
+            assert_eq!(by_arrival.len(), 8);
+            assert!(by_arrival.windows(2).all(|w| w[0] < w[1]));
+
+            let before: Vec<i32> = by_arrival.into_iter().copied().collect();
+            reservoir.compact_and_shuffle();
+            let after: Vec<i32> = reservoir
+                .samples_by_arrival()
+                .into_iter()
+                .copied()
+                .collect();
+            assert_eq!(before, after);
+        }
+    }
+
+    /// Only asks for a sampler, taking it by value.
+    fn held<S: Lockable<Item = i32>>(mut sampler: S, items: Range<i32>) -> usize {
+        let _ = sampler.sample_all(items);
+        sampler.lock_compact().len()
+    }
+
+    #[test]
+    fn borrowed_and_boxed() {
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(5, 1);
+        assert_eq!(held(&mut reservoir, 0..3), 3);
+        // 借出去的采样器还能接着用
+        assert_eq!(reservoir.total(), 3);
+        assert_eq!(held(&mut reservoir, 3..100), 5);
+        assert_eq!(reservoir.total(), 100);
+        // 借来的不打乱，每次交出同样的副本
+        let slots = reservoir.samples().to_vec();
+        assert_eq!((&mut reservoir).lock(), slots);
+        assert_eq!(reservoir.samples(), &slots[..]);
+
+        let mut partial = Reservoir::<i32, Deterministic>::deterministic(5, 1);
+        partial.pool = vec![None, Some(1), None, Some(0), None];
+        let copy = (&mut partial).lock();
+        assert_eq!(copy, [Some(1), Some(0), None, None, None]);
+        assert_eq!((&mut partial).lock(), copy);
+
+        let boxed = Box::new(Reservoir::<i32, Deterministic>::deterministic(5, 1));
+        assert_eq!(held(boxed, 0..3), 3);
+
+        let mut boxed: Box<dyn Inspectable<Item = i32, Draw = u64>> =
+            Box::new(Reservoir::<i32, Deterministic>::deterministic(5, 1));
+        let _ = boxed.sample(7);
+        assert_eq!(boxed.samples().iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn iterate_held_items() {
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(5, 1);
+        let _ = reservoir.sample_all(0..3);
+        let held: Vec<_> = reservoir.samples().iter().flatten().collect();
+        assert_eq!(reservoir.iter().collect::<Vec<_>>(), held);
+
+        let mut sum = 0;
+        for &it in &reservoir {
+            sum += it;
+        }
+        assert_eq!(sum, 3);
+
+        let mut kept: Vec<_> = reservoir.into_iter().collect();
+        kept.sort_unstable();
+        assert_eq!(kept, [0, 1, 2]);
+    }
+
+    #[test]
+    fn clone_branches_the_draw() {
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(3, 27);
+        let _ = reservoir.sample_all(0..50);
+        let mut branch = reservoir.clone();
+        assert_eq!(branch, reservoir);
+
+        let _ = reservoir.sample_all(50..100);
+        assert_ne!(branch, reservoir);
+        let _ = branch.sample_all(50..100);
+        assert_eq!(branch, reservoir);
+        assert_eq!(branch.lock(), vec![Some(48), Some(59), Some(10)]);
+    }
+
+    #[test]
+    fn debug_hides_the_pool() {
+        let mut reservoir = Reservoir::<String, Deterministic>::deterministic(4, 1);
+        let _ = reservoir.sample_all(["secret".to_string(), "payload".to_string()]);
+        assert_eq!(
+            format!("{:?}", reservoir),
+            "Reservoir { capacity: 4, total: 2, filled: 2, algorithm: R, .. }"
+        );
+    }
+
+    #[test]
+    fn compact_and_shuffle_keeps_items() {
+        let mut reservoir = Reservoir::<i32, Deterministic>::deterministic(5, 1);
+        for it in 0..3 {
+            let _ = reservoir.sample(it);
+        }
+        reservoir.compact_and_shuffle();
+
+        let mut kept: Vec<_> = reservoir.iter().copied().collect();
+        kept.sort_unstable();
+        assert_eq!(kept, vec![0, 1, 2]);
+        assert_eq!(reservoir.samples().len(), 5);
+    }
+
+    #[test]
+    fn lock_spreads_items_uniformly() {
+        const CAP: usize = 4;
+        const ROUNDS: u64 = 4000;
+
+        // 只来了 2 个人，每人落在每个位置的次数应当差不多
+        let mut hits = [[0u64; CAP]; 2];
+        for seed in 0..ROUNDS {
+            let mut reservoir = Reservoir::<usize, Deterministic>::deterministic(CAP, seed);
+            let _ = reservoir.sample(0);
+            let _ = reservoir.sample(1);
+
+            for (slot, it) in reservoir.lock().into_iter().enumerate() {
+                if let Some(it) = it {
+                    hits[it][slot] += 1;
+                }
+            }
+        }
+
+        let expected = ROUNDS / CAP as u64;
+        for slots in &hits {
+            for &count in slots {
+                assert!(count.abs_diff(expected) < expected / 10, "{:?}", hits);
+            }
+        }
+    }
 }
@@ -0,0 +1,151 @@
+//! Telling roughly how many bytes held items take, and making room when a
+//! [`SamplerRegistry`](crate::SamplerRegistry) outgrows its byte budget.
+use std::mem;
+
+use rand::Rng;
+
+use crate::{rng, Algorithm, Marks, Reservoir};
+
+/// About how many bytes an item takes, its heap allocations included.
+pub trait SizeHint {
+    fn size_hint(&self) -> usize;
+}
+
+macro_rules! fixed_size {
+    ($($t:ty),*) => {
+        $(
+            impl SizeHint for $t {
+                fn size_hint(&self) -> usize {
+                    mem::size_of::<$t>()
+                }
+            }
+        )*
+    };
+}
+
+fixed_size!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char);
+
+impl SizeHint for String {
+    fn size_hint(&self) -> usize {
+        mem::size_of::<String>() + self.capacity()
+    }
+}
+
+impl<T: SizeHint> SizeHint for Vec<T> {
+    fn size_hint(&self) -> usize {
+        let spare = self.capacity() - self.len();
+        mem::size_of::<Vec<T>>()
+            + self.iter().map(SizeHint::size_hint).sum::<usize>()
+            + spare * mem::size_of::<T>()
+    }
+}
+
+impl<T: SizeHint> SizeHint for Option<T> {
+    fn size_hint(&self) -> usize {
+        match self {
+            Some(it) => it.size_hint(),
+            None => mem::size_of::<Option<T>>(),
+        }
+    }
+}
+
+/// How a registry over its byte budget makes room, one sampler at a time
+/// until it fits again.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BudgetPolicy {
+    /// Locks and removes the sampler fed least recently.
+    EvictIdlest,
+    /// Halves the capacity of the sampler taking the most bytes, keeping a
+    /// uniform half of its items. A sampler of capacity 1 is evicted.
+    DownsizeLargest,
+}
+
+/// The items a registry is about to let go of to stay within its budget,
+/// handed to the host first.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Loss<T> {
+    /// The whole sampler is evicted, with what it held.
+    Evicted(Vec<T>),
+    /// The sampler is downsized to `capacity`, dropping these items.
+    Downsized { capacity: usize, dropped: Vec<T> },
+}
+
+impl<T, R: Rng> Reservoir<T, R> {
+    /// Shrinks the capacity to `k`, handing out the items which no longer
+    /// fit. The kept items are a uniform sample of the held ones, so the
+    /// reservoir stays a uniform sample of everything passed through, and
+    /// samples on. An `L` reservoir samples like an `X` one from then on,
+    /// since `w` depends on the capacity.
+    pub fn downsize(&mut self, k: usize) -> Vec<T> {
+        if k >= self.pool.len() {
+            return Vec::new();
+        }
+
+        self.pins.clear();
+        // 只打乱保留的条目，空位仍在末尾，和填充中的状态一致
+        let mut held: Vec<(T, Marks)> = mem::take(&mut self.pool)
+            .into_iter()
+            .zip(self.take_marks())
+            .filter_map(|(slot, marks)| Some((slot?, marks)))
+            .collect();
+        rng::shuffle(&mut held, &mut self.rng);
+        let dropped = held.split_off(k.min(held.len()));
+        (self.pool, self.marks) = held
+            .into_iter()
+            .map(|(it, marks)| (Some(it), marks))
+            .unzip();
+        self.pool.resize_with(k, || None);
+        self.marks.resize(k, Marks::default());
+        self.touch_all();
+
+        if self.algorithm == Algorithm::L {
+            self.algorithm = Algorithm::X;
+        }
+        if self.algorithm != Algorithm::R && k > 0 && self.total >= k as u64 {
+            self.next_skip();
+        }
+        dropped.into_iter().map(|(it, _)| it).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Inspectable, StreamSampler};
+
+    #[test]
+    fn size_hints() {
+        assert_eq!(7u32.size_hint(), 4);
+        let s = String::with_capacity(10);
+        assert_eq!(s.size_hint(), mem::size_of::<String>() + 10);
+        assert_eq!(vec![1u64, 2].size_hint(), mem::size_of::<Vec<u64>>() + 16);
+    }
+
+    #[test]
+    fn downsize_stays_uniform() {
+        let mut counts = [0u32; 20];
+        for seed in 0..2_000 {
+            let rng = Deterministic::seed_from_u64(seed);
+            let mut reservoir = Reservoir::with_rng_and_algorithm(10, rng, Algorithm::L);
+            let _ = reservoir.sample_all(0..10);
+            assert_eq!(reservoir.downsize(4).len(), 6);
+            assert_eq!(reservoir.algorithm(), Algorithm::X);
+            let _ = reservoir.sample_all(10..20);
+            for &it in reservoir.iter() {
+                counts[it as usize] += 1;
+            }
+        }
+        // 每个条目的入选概率都应接近 4 / 20
+        assert!(
+            counts.iter().all(|&c| (330..=470).contains(&c)),
+            "{:?}",
+            counts
+        );
+
+        let mut filling = Reservoir::<u32, Deterministic>::deterministic(10, 1);
+        let _ = filling.sample_all(0..3);
+        assert!(filling.downsize(5).is_empty());
+        assert_eq!(filling.samples().iter().flatten().count(), 3);
+        assert_eq!(filling.samples()[3..], [None, None]);
+    }
+}
@@ -0,0 +1,533 @@
+//! Merging partial samples of one stream, taken apart by map/reduce workers,
+//! and shipping them between the workers in a compact binary encoding.
+//!
+//! Every encoding starts with [`WIRE_VERSION`] and a byte telling which
+//! sampler wrote it, followed by the sampler's counters and held items.
+//! Integers are LEB128 varints, so small counters take a byte. Items encode
+//! themselves through [`WireItem`]. The RNG is never encoded, and neither are
+//! the pins of a [`Reservoir`], which merging drops anyway, see
+//! [`Reservoir::pin`].
+use std::convert::TryFrom;
+
+use rand::Rng;
+
+use crate::rng::below;
+use crate::{Algorithm, Reservoir};
+
+/// The version of the binary encoding. It changes whenever the layout does;
+/// older encodings are rejected rather than misread.
+pub const WIRE_VERSION: u8 = 1;
+
+/// The largest capacity a decoded sampler may have. The empty slots of a
+/// sampler are left out of the encoding, so its capacity cannot be checked
+/// against the bytes, and a bigger one is rejected as
+/// [`MergeError::Malformed`] rather than allocated.
+pub const MAX_WIRE_CAPACITY: usize = 1 << 24;
+
+pub(crate) const RESERVOIR: u8 = 1;
+pub(crate) const BOTTOM_K: u8 = 2;
+pub(crate) const TICKETS: u8 = 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MergeError {
+    /// The bytes end in the middle of a value.
+    Truncated,
+    /// The bytes were written by another version of the encoding.
+    UnknownVersion,
+    /// The bytes were written by another kind of sampler.
+    WrongKind,
+    /// The bytes hold a value no sampler writes.
+    Malformed,
+    /// The other sampler holds fewer items than this one needs.
+    TooSmall,
+    /// A sampler still has deletions to make up for, see
+    /// [`Reservoir::delete`].
+    PendingDeletions,
+}
+
+/// An item which can travel in the binary encoding.
+pub trait WireItem: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Reads the item off the front of `input`, advancing it.
+    fn decode(input: &mut &[u8]) -> Result<Self, MergeError>;
+}
+
+/// A sampler whose partial samples can be merged, and sent over the wire.
+pub trait Mergeable: Sized {
+    /// The RNG a decoded sampler draws with from then on.
+    type Rng;
+
+    /// Merges in the sample of another part of the stream, as if this sampler
+    /// had seen both parts. The other sampler must be at least as big.
+    fn merge(&mut self, other: Self) -> Result<(), MergeError>;
+
+    fn to_bytes(&self) -> Vec<u8>;
+
+    fn from_bytes_with_rng(bytes: &[u8], rng: Self::Rng) -> Result<Self, MergeError>;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, MergeError>
+    where
+        Self::Rng: Default,
+    {
+        Self::from_bytes_with_rng(bytes, Default::default())
+    }
+}
+
+pub(crate) fn put_varint(out: &mut Vec<u8>, mut n: u128) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn get_varint(input: &mut &[u8]) -> Result<u128, MergeError> {
+    let mut n = 0u128;
+    for shift in (0..128).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or(MergeError::Truncated)?;
+        *input = rest;
+        n |= u128::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(MergeError::Malformed)
+}
+
+pub(crate) fn get_usize(input: &mut &[u8]) -> Result<usize, MergeError> {
+    usize::try_from(get_varint(input)?).map_err(|_| MergeError::Malformed)
+}
+
+/// The number an algorithm is written as, in the wire format and in state
+/// hashes.
+pub(crate) fn algorithm_tag(algorithm: Algorithm) -> u64 {
+    match algorithm {
+        Algorithm::R => 0,
+        Algorithm::L => 1,
+        Algorithm::X => 2,
+    }
+}
+
+/// A capacity, no bigger than [`MAX_WIRE_CAPACITY`].
+pub(crate) fn get_capacity(input: &mut &[u8]) -> Result<usize, MergeError> {
+    match get_usize(input)? {
+        capacity if capacity <= MAX_WIRE_CAPACITY => Ok(capacity),
+        _ => Err(MergeError::Malformed),
+    }
+}
+
+pub(crate) fn get_u64(input: &mut &[u8]) -> Result<u64, MergeError> {
+    u64::try_from(get_varint(input)?).map_err(|_| MergeError::Malformed)
+}
+
+fn get_bytes<'a>(input: &mut &'a [u8], n: usize) -> Result<&'a [u8], MergeError> {
+    if input.len() < n {
+        return Err(MergeError::Truncated);
+    }
+    let (bytes, rest) = input.split_at(n);
+    *input = rest;
+    Ok(bytes)
+}
+
+pub(crate) fn put_header(out: &mut Vec<u8>, kind: u8) {
+    out.push(WIRE_VERSION);
+    out.push(kind);
+}
+
+pub(crate) fn get_header(input: &mut &[u8], kind: u8) -> Result<(), MergeError> {
+    let header = get_bytes(input, 2)?;
+    if header[0] != WIRE_VERSION {
+        return Err(MergeError::UnknownVersion);
+    }
+    if header[1] != kind {
+        return Err(MergeError::WrongKind);
+    }
+    Ok(())
+}
+
+/// Reads `n` items, leaving no byte behind.
+pub(crate) fn get_items<T>(
+    input: &mut &[u8],
+    n: usize,
+    mut item: impl FnMut(&mut &[u8]) -> Result<T, MergeError>,
+) -> Result<Vec<T>, MergeError> {
+    // 长度来自外部数据，不能直接按它分配
+    let mut items = Vec::with_capacity(n.min(input.len()));
+    for _ in 0..n {
+        items.push(item(input)?);
+    }
+    if !input.is_empty() {
+        return Err(MergeError::Malformed);
+    }
+    Ok(items)
+}
+
+/// Picks up to `k` items out of the held items of two parts of a stream. Every
+/// pick takes a uniformly random item of `a` with the chance of `wa` out of
+/// `wa + wb`, and of `b` otherwise. With `shrink`, a pick takes one off the
+/// weight of its side, which draws the parts' items without replacement.
+pub(crate) fn pick<T, R: Rng + ?Sized>(
+    mut a: Vec<T>,
+    mut wa: u128,
+    mut b: Vec<T>,
+    mut wb: u128,
+    k: usize,
+    shrink: bool,
+    rng: &mut R,
+) -> Vec<T> {
+    let mut picked = Vec::with_capacity(k);
+    while picked.len() < k && !(a.is_empty() && b.is_empty()) {
+        let from_a = if a.is_empty() || b.is_empty() || wa + wb == 0 {
+            b.is_empty()
+        } else {
+            rng.gen_range(0..wa + wb) < wa
+        };
+
+        let (side, weight) = if from_a {
+            (&mut a, &mut wa)
+        } else {
+            (&mut b, &mut wb)
+        };
+        let i = below(rng, side.len());
+        picked.push(side.swap_remove(i));
+        if shrink {
+            *weight = weight.saturating_sub(1);
+        }
+    }
+    picked
+}
+
+macro_rules! unsigned {
+    ($($t:ty),*) => {$(
+        impl WireItem for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                put_varint(out, *self as u128);
+            }
+
+            fn decode(input: &mut &[u8]) -> Result<Self, MergeError> {
+                <$t>::try_from(get_varint(input)?).map_err(|_| MergeError::Malformed)
+            }
+        }
+    )*};
+}
+
+// 有符号整数先做 zigzag，绝对值小的负数也只占一个字节
+macro_rules! signed {
+    ($($t:ty),*) => {$(
+        impl WireItem for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                let n = *self as i128;
+                put_varint(out, ((n << 1) ^ (n >> 127)) as u128);
+            }
+
+            fn decode(input: &mut &[u8]) -> Result<Self, MergeError> {
+                let z = get_varint(input)?;
+                let n = ((z >> 1) as i128) ^ -((z & 1) as i128);
+                <$t>::try_from(n).map_err(|_| MergeError::Malformed)
+            }
+        }
+    )*};
+}
+
+unsigned!(u8, u16, u32, u64, u128, usize);
+signed!(i8, i16, i32, i64, isize);
+
+impl WireItem for f64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, MergeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(get_bytes(input, 8)?);
+        Ok(f64::from_le_bytes(bytes))
+    }
+}
+
+impl WireItem for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(u8::from(*self));
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, MergeError> {
+        match get_bytes(input, 1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(MergeError::Malformed),
+        }
+    }
+}
+
+impl WireItem for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_varint(out, self.len() as u128);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, MergeError> {
+        let n = get_usize(input)?;
+        let bytes = get_bytes(input, n)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| MergeError::Malformed)
+    }
+}
+
+impl<T: WireItem> WireItem for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_varint(out, self.len() as u128);
+        for it in self {
+            it.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, MergeError> {
+        let n = get_usize(input)?;
+        let mut items = Vec::with_capacity(n.min(input.len()));
+        for _ in 0..n {
+            items.push(T::decode(input)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<A: WireItem, B: WireItem> WireItem for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, MergeError> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+/// Merging draws how many items each part keeps without replacement, so the
+/// merged sample is uniform over both parts. A merged `L` reservoir samples
+/// like an `X` one from then on, since `w` cannot be recovered.
+impl<T: WireItem, R: Rng> Mergeable for Reservoir<T, R> {
+    type Rng = R;
+
+    fn merge(&mut self, other: Self) -> Result<(), MergeError> {
+        let k = self.pool.len();
+        if other.pool.len() < k {
+            return Err(MergeError::TooSmall);
+        }
+        if self.pending_held + self.pending_unheld + other.pending_held + other.pending_unheld > 0 {
+            return Err(MergeError::PendingDeletions);
+        }
+
+        let mine = std::mem::take(&mut self.pool)
+            .into_iter()
+            .flatten()
+            .collect();
+        let theirs = other.pool.into_iter().flatten().collect();
+        let (wa, wb) = (self.total as u128, other.total as u128);
+        let picked = pick(mine, wa, theirs, wb, k, true, &mut self.rng);
+
+        self.pool = picked.into_iter().map(Some).collect();
+        self.pool.resize_with(k, || None);
+        self.marks.clear();
+        self.touch_all();
+        self.pins.clear();
+        self.total += other.total;
+        self.deleted += other.deleted;
+        if self.algorithm != Algorithm::R && k > 0 && self.total >= k as u64 {
+            self.algorithm = Algorithm::X;
+            self.next_skip();
+        }
+        Ok(())
+    }
+
+    /// Held items are written in slot order, leaving the empty slots out.
+    /// Pins are left out too, so a decoded reservoir has none.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_header(&mut out, RESERVOIR);
+        for n in [
+            self.pool.len() as u64,
+            self.total,
+            algorithm_tag(self.algorithm),
+            self.skip,
+            self.deleted,
+            self.pending_held,
+            self.pending_unheld,
+        ] {
+            put_varint(&mut out, u128::from(n));
+        }
+        out.extend_from_slice(&self.w.to_le_bytes());
+
+        let held: Vec<&T> = self.pool.iter().flatten().collect();
+        put_varint(&mut out, held.len() as u128);
+        for it in held {
+            it.encode(&mut out);
+        }
+        out
+    }
+
+    fn from_bytes_with_rng(bytes: &[u8], rng: R) -> Result<Self, MergeError> {
+        let input = &mut &bytes[..];
+        get_header(input, RESERVOIR)?;
+        let capacity = get_capacity(input)?;
+        let total = get_u64(input)?;
+        let algorithm = match get_varint(input)? {
+            0 => Algorithm::R,
+            1 => Algorithm::L,
+            2 => Algorithm::X,
+            _ => return Err(MergeError::Malformed),
+        };
+        let skip = get_u64(input)?;
+        let deleted = get_u64(input)?;
+        let pending_held = get_u64(input)?;
+        let pending_unheld = get_u64(input)?;
+        let w = f64::decode(input)?;
+        let held = get_usize(input)?;
+        if held > capacity || held as u64 > total {
+            return Err(MergeError::Malformed);
+        }
+
+        let mut pool: Vec<Option<T>> = get_items(input, held, |input| T::decode(input).map(Some))?;
+        pool.resize_with(capacity, || None);
+        let mut reservoir = Self {
+            total,
+            marks: Vec::new(),
+            history: None,
+            pins: Vec::new(),
+            pool,
+            rng,
+            algorithm,
+            skip,
+            w,
+            deleted,
+            pending_held,
+            pending_unheld,
+            locked: false,
+            generation: 0,
+        };
+        // 新的副本要从 diff(0) 拿到所有槽位
+        reservoir.touch_all();
+        Ok(reservoir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Inspectable, StreamSampler};
+
+    #[test]
+    fn items_round_trip() {
+        let mut out = Vec::new();
+        (-3i32, "反应".to_string()).encode(&mut out);
+        vec![u64::MAX, 0].encode(&mut out);
+        2.5f64.encode(&mut out);
+
+        let input = &mut &out[..];
+        assert_eq!(<(i32, String)>::decode(input), Ok((-3, "反应".to_string())));
+        assert_eq!(Vec::<u64>::decode(input), Ok(vec![u64::MAX, 0]));
+        assert_eq!(f64::decode(input), Ok(2.5));
+        assert!(input.is_empty());
+        assert_eq!(
+            u8::decode(&mut &[0x80, 0x02][..]),
+            Err(MergeError::Malformed)
+        );
+    }
+
+    #[test]
+    fn reservoir_round_trip() {
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(3, 1);
+        for it in 0..10 {
+            let _ = reservoir.sample(it);
+        }
+
+        let bytes = reservoir.to_bytes();
+        assert_eq!(bytes[..2], [WIRE_VERSION, RESERVOIR]);
+        let decoded = Reservoir::<u32>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.samples(), reservoir.samples());
+        assert_eq!(decoded.total(), 10);
+
+        assert_eq!(
+            Reservoir::<u32>::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(MergeError::Truncated)
+        );
+        let mut newer = bytes.clone();
+        newer[0] += 1;
+        assert_eq!(
+            Reservoir::<u32>::from_bytes(&newer).err(),
+            Some(MergeError::UnknownVersion)
+        );
+    }
+
+    #[test]
+    fn huge_capacity_is_rejected() {
+        let mut bytes = Vec::new();
+        put_header(&mut bytes, RESERVOIR);
+        for n in [1 << 50, 0, 0, 0, 0, 0, 0] {
+            put_varint(&mut bytes, n);
+        }
+        bytes.extend_from_slice(&0f64.to_le_bytes());
+        put_varint(&mut bytes, 0);
+        assert_eq!(
+            Reservoir::<u32>::from_bytes(&bytes).err(),
+            Some(MergeError::Malformed)
+        );
+
+        // 持有的比见过的还多
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(3, 1);
+        let _ = reservoir.sample_all(0..2);
+        let mut bytes = reservoir.to_bytes();
+        bytes[3] = 1;
+        assert_eq!(
+            Reservoir::<u32>::from_bytes(&bytes).err(),
+            Some(MergeError::Malformed)
+        );
+    }
+
+    #[test]
+    fn merged_parts_are_uniform() {
+        const CAP: usize = 3;
+        const ROUNDS: u64 = 6000;
+
+        let mut hits = [0u64; 12];
+        for seed in 0..ROUNDS {
+            // 两个分片大小不同：前 4 个和后 8 个
+            let mut a = Reservoir::<usize, Deterministic>::deterministic(CAP, seed);
+            let mut b = Reservoir::<usize, Deterministic>::deterministic(CAP, seed + ROUNDS);
+            for it in 0..4 {
+                let _ = a.sample(it);
+            }
+            for it in 4..12 {
+                let _ = b.sample(it);
+            }
+
+            let wire = b.to_bytes();
+            let b = Reservoir::from_bytes_with_rng(&wire, Deterministic::seed_from_u64(0));
+            a.merge(b.unwrap()).unwrap();
+            assert_eq!(a.total(), 12);
+            for &it in a.samples().iter().flatten() {
+                hits[it] += 1;
+            }
+        }
+
+        let expected = ROUNDS * CAP as u64 / 12;
+        for &count in &hits {
+            assert!(count.abs_diff(expected) < expected / 8, "{:?}", hits);
+        }
+    }
+
+    #[test]
+    fn merge_needs_a_big_enough_part() {
+        let mut big = Reservoir::<u8>::with_capacity(3);
+        let small = Reservoir::<u8>::with_capacity(2);
+        assert_eq!(big.merge(small), Err(MergeError::TooSmall));
+
+        let mut a = Reservoir::<u8>::with_algorithm(2, Algorithm::L);
+        let mut b = Reservoir::<u8>::with_capacity(2);
+        let _ = b.sample(1);
+        let _ = b.delete(|&it| it == 1);
+        assert_eq!(a.merge(b), Err(MergeError::PendingDeletions));
+    }
+}
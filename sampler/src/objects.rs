@@ -0,0 +1,197 @@
+//! Streaming items straight out of an object store, like S3 or GCS, so a
+//! dataset is sampled without ever touching the local disk.
+//!
+//! Object stores are async while [`ItemSource`] is not, so every adapter here
+//! blocks on a tokio runtime `handle`. They must be read off that runtime's
+//! worker threads, like from [`tokio::task::spawn_blocking`], or tokio
+//! panics.
+use std::io::{self, BufReader, Read};
+use std::sync::Arc;
+
+use futures_util::stream::{BoxStream, StreamExt};
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore};
+use tokio::runtime::Handle;
+
+use crate::delimited::next_record;
+use crate::{Delimiter, ItemSource};
+
+type Chunks = BoxStream<'static, object_store::Result<bytes::Bytes>>;
+
+/// An object read chunk by chunk, holding one chunk at a time. It is opened
+/// on the first read.
+pub struct ObjectReader {
+    store: Arc<dyn ObjectStore>,
+    path: Path,
+    handle: Handle,
+    chunks: Option<Chunks>,
+    chunk: bytes::Bytes,
+}
+
+impl ObjectReader {
+    pub fn new(store: Arc<dyn ObjectStore>, path: Path, handle: Handle) -> Self {
+        Self {
+            store,
+            path,
+            handle,
+            chunks: None,
+            chunk: bytes::Bytes::new(),
+        }
+    }
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            let chunks = match &mut self.chunks {
+                Some(chunks) => chunks,
+                None => {
+                    let got = self.handle.block_on(self.store.get(&self.path));
+                    self.chunks
+                        .insert(got.map_err(io::Error::other)?.into_stream())
+                }
+            };
+            match self.handle.block_on(chunks.next()) {
+                Some(chunk) => self.chunk = chunk.map_err(io::Error::other)?,
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len());
+        buf[..n].copy_from_slice(&self.chunk.split_to(n));
+        Ok(n)
+    }
+}
+
+/// The records of an object, told apart by a [`Delimiter`], as an
+/// [`ItemSource`]. A failed read ends the records, see
+/// [`ObjectRecords::take_error`].
+pub struct ObjectRecords {
+    reader: BufReader<ObjectReader>,
+    delimiter: Delimiter,
+    error: Option<io::Error>,
+}
+
+impl ObjectRecords {
+    pub fn new(reader: ObjectReader, delimiter: Delimiter) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            delimiter,
+            error: None,
+        }
+    }
+
+    /// The lines of an object, without their `\n`.
+    pub fn lines(reader: ObjectReader) -> Self {
+        Self::new(reader, Delimiter::Byte(b'\n'))
+    }
+
+    /// The error which ended the records early, if any.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl ItemSource for ObjectRecords {
+    type Item = Vec<u8>;
+
+    fn next_item(&mut self) -> Option<Vec<u8>> {
+        if self.error.is_some() {
+            return None;
+        }
+        next_record(&mut self.reader, &self.delimiter).unwrap_or_else(|e| {
+            self.error = Some(e);
+            None
+        })
+    }
+}
+
+/// The objects under a prefix, as an [`ItemSource`], listed page by page. A
+/// failed listing ends the objects, see [`ObjectList::take_error`].
+pub struct ObjectList {
+    objects: BoxStream<'static, object_store::Result<ObjectMeta>>,
+    handle: Handle,
+    error: Option<object_store::Error>,
+}
+
+impl ObjectList {
+    pub fn new(store: &dyn ObjectStore, prefix: Option<&Path>, handle: Handle) -> Self {
+        Self {
+            objects: store.list(prefix),
+            handle,
+            error: None,
+        }
+    }
+
+    /// The error which ended the listing early, if any.
+    pub fn take_error(&mut self) -> Option<object_store::Error> {
+        self.error.take()
+    }
+}
+
+impl ItemSource for ObjectList {
+    type Item = ObjectMeta;
+
+    fn next_item(&mut self) -> Option<ObjectMeta> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.handle.block_on(self.objects.next())? {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Lockable, PrioritySources, Reservoir};
+    use object_store::memory::InMemory;
+    use tokio::runtime::Builder;
+
+    #[test]
+    fn lines_and_objects() -> Result<(), Box<dyn std::error::Error>> {
+        let runtime = Builder::new_current_thread().build()?;
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        for day in 1..=3 {
+            let log: String = (0..100).map(|i| format!("{}-{}\n", day, i)).collect();
+            let path = Path::from(format!("logs/{}.log", day));
+            let _ = runtime.block_on(store.put(&path, log.into_bytes().into()))?;
+        }
+
+        let mut list = ObjectList::new(
+            store.as_ref(),
+            Some(&Path::from("logs")),
+            runtime.handle().clone(),
+        );
+        let mut sources = PrioritySources::new();
+        while let Some(meta) = list.next_item() {
+            let reader = ObjectReader::new(store.clone(), meta.location, runtime.handle().clone());
+            sources = sources.source(ObjectRecords::lines(reader), 1);
+        }
+        assert!(list.take_error().is_none());
+
+        let mut reservoir = Reservoir::<_, Deterministic>::deterministic(10, 1);
+        assert_eq!(sources.feed(&mut reservoir), 300);
+        assert_eq!(reservoir.lock_compact().len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_object() -> io::Result<()> {
+        let runtime = Builder::new_current_thread().build()?;
+        let reader = ObjectReader::new(
+            Arc::new(InMemory::new()),
+            Path::from("nope"),
+            runtime.handle().clone(),
+        );
+        let mut records = ObjectRecords::lines(reader);
+        assert_eq!(records.next_item(), None);
+        assert!(records.take_error().is_some());
+        Ok(())
+    }
+}
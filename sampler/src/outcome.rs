@@ -0,0 +1,102 @@
+//! A sampling call which reports misuse instead of quietly going on, see
+//! [`Reservoir::try_sample`].
+use rand::Rng;
+
+use crate::{Reservoir, StreamSampler};
+
+/// What became of an item passed to [`Reservoir::try_sample`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum SampleOutcome<T> {
+    /// The item is held in `slot`, counting from 0, in the place of
+    /// `replaced`.
+    Held { slot: usize, replaced: Option<T> },
+    /// The item is not held.
+    Rejected,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SamplerError {
+    /// The reservoir has no room, so it could never hold anything.
+    ZeroCapacity,
+    /// `total` would not fit a `u64` anymore.
+    Overflow,
+    /// The pool has been shuffled by `compact_and_shuffle`, so a slot may
+    /// hold an item where sampling expects an empty one.
+    Locked,
+}
+
+impl<T, R: Rng> Reservoir<T, R> {
+    /// The same as `sample`, but the item is turned away, and nothing is
+    /// counted, when the reservoir cannot sample it faithfully.
+    pub fn try_sample(&mut self, it: T) -> Result<SampleOutcome<T>, SamplerError> {
+        if self.pool.is_empty() {
+            return Err(SamplerError::ZeroCapacity);
+        }
+        if self.locked {
+            return Err(SamplerError::Locked);
+        }
+        if self.total.checked_add(1).is_none() {
+            return Err(SamplerError::Overflow);
+        }
+
+        let (r, _, replaced) = self.sample(it);
+        Ok(match r.checked_sub(1) {
+            Some(slot) if slot < self.pool.len() as u64 => SampleOutcome::Held {
+                slot: slot as usize,
+                replaced,
+            },
+            _ => SampleOutcome::Rejected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Algorithm, Inspectable};
+
+    #[test]
+    fn try_sample() {
+        for algorithm in [Algorithm::R, Algorithm::L, Algorithm::X] {
+            let mut reservoir = Reservoir::with_rng_and_algorithm(
+                2,
+                crate::Deterministic::seed_from_u64(27),
+                algorithm,
+            );
+            assert_eq!(
+                reservoir.try_sample(1),
+                Ok(SampleOutcome::Held {
+                    slot: 0,
+                    replaced: None
+                })
+            );
+            let mut held = 1;
+            for it in 2..100 {
+                match reservoir.try_sample(it).unwrap() {
+                    SampleOutcome::Held { slot, .. } => {
+                        assert_eq!(reservoir.samples()[slot], Some(it));
+                        held += 1;
+                    }
+                    SampleOutcome::Rejected => {}
+                }
+            }
+            assert!((2..99).contains(&held));
+        }
+    }
+
+    #[test]
+    fn misuse_is_reported() {
+        let mut reservoir = Reservoir::<i32>::with_capacity(0);
+        assert_eq!(reservoir.try_sample(1), Err(SamplerError::ZeroCapacity));
+        assert_eq!(reservoir.total(), 0);
+
+        let mut reservoir = Reservoir::<i32>::with_capacity(3);
+        let _ = reservoir.try_sample(1);
+        reservoir.compact_and_shuffle();
+        assert_eq!(reservoir.try_sample(2), Err(SamplerError::Locked));
+
+        let mut reservoir = Reservoir::<i32>::with_capacity(3);
+        reservoir.total = u64::MAX;
+        assert_eq!(reservoir.try_sample(1), Err(SamplerError::Overflow));
+    }
+}
@@ -0,0 +1,131 @@
+//! Two-phase sampling: keep more items than needed during the stream, then
+//! subsample down to `k` once the items which do not qualify are known.
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::Deterministic;
+use crate::weights::{LockWithWeights, Sampled};
+use crate::{Inspectable, Lockable, Reservoir, StreamSampler};
+
+/// An `Oversampler` holds `factor * k` items while sampling, so filtering the
+/// held items afterwards, like dropping duplicates or ineligible ones, still
+/// leaves `k` of them as long as no more than `(factor - 1) * k` are dropped.
+///
+/// The held items are a uniform sample of the stream, so any `k` of the ones
+/// which survive the filter are a uniform sample of the qualified items.
+pub struct Oversampler<T, R = ThreadRng> {
+    inner: Reservoir<T, R>,
+}
+
+impl<T: Clone> Oversampler<T> {
+    /// `factor` of 0 is treated as 1, that is no oversampling.
+    pub fn new(k: usize, factor: usize) -> Self {
+        Self::with_rng(k, factor, thread_rng())
+    }
+}
+
+impl<T: Clone> Oversampler<T, Deterministic> {
+    pub fn deterministic(k: usize, factor: usize, seed: u64) -> Self {
+        Self::with_rng(k, factor, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T: Clone, R: Rng> Oversampler<T, R> {
+    pub fn with_rng(k: usize, factor: usize, rng: R) -> Self {
+        Self {
+            inner: Reservoir::with_rng(k.saturating_mul(factor.max(1)), rng),
+        }
+    }
+}
+
+impl<T, R: Rng> Oversampler<T, R> {
+    /// End the sampling process, keeping `k` of the held items at random.
+    pub fn subsample(self, k: usize) -> Vec<T> {
+        self.subsample_where(k, |_| true)
+    }
+
+    /// End the sampling process, keeping `k` of the held items for which
+    /// `keep` returns `true`, at random. Fewer come out only when fewer
+    /// qualify.
+    pub fn subsample_where(self, k: usize, mut keep: impl FnMut(&T) -> bool) -> Vec<T> {
+        // lock 之后已经打乱，取前 k 个即为均匀的子样本
+        self.inner
+            .lock_iter()
+            .filter(|it| keep(it))
+            .take(k)
+            .collect()
+    }
+}
+
+impl<T, R: Rng> StreamSampler for Oversampler<T, R> {
+    type Item = T;
+    type Draw = u64;
+
+    fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.inner.sample(it)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        self.inner.is_held(draw)
+    }
+}
+
+impl<T, R: Rng> Inspectable for Oversampler<T, R> {
+    fn samples(&self) -> &[Option<T>] {
+        self.inner.samples()
+    }
+}
+
+impl<T, R: Rng> Lockable for Oversampler<T, R> {
+    fn lock(self) -> Vec<Option<T>> {
+        self.inner.lock()
+    }
+}
+
+impl<T, R: Rng> LockWithWeights for Oversampler<T, R> {
+    type Item = T;
+
+    fn lock_with_weights(self) -> Vec<Sampled<T>> {
+        self.inner.lock_with_weights()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filtering_still_leaves_k() {
+        let mut sampler = Oversampler::<u32, Deterministic>::deterministic(5, 3, 7);
+        for it in 0..1000 {
+            let _ = sampler.sample(it);
+        }
+        assert_eq!(sampler.samples().len(), 15);
+
+        let odd = sampler.subsample_where(5, |it| it % 2 == 1);
+        assert_eq!(odd.len(), 5);
+        assert!(odd.iter().all(|it| it % 2 == 1));
+    }
+
+    #[test]
+    fn subsample_is_uniform() {
+        const TOTAL: usize = 10;
+        const ROUNDS: u64 = 4000;
+
+        let mut hits = [0u64; TOTAL];
+        for seed in 0..ROUNDS {
+            let mut sampler = Oversampler::<usize, Deterministic>::deterministic(2, 2, seed);
+            for it in 0..TOTAL {
+                let _ = sampler.sample(it);
+            }
+            for it in sampler.subsample(2) {
+                hits[it] += 1;
+            }
+        }
+
+        let expected = ROUNDS * 2 / TOTAL as u64;
+        for &count in &hits {
+            assert!(count.abs_diff(expected) < expected / 8, "{:?}", hits);
+        }
+    }
+}
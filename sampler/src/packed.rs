@@ -0,0 +1,118 @@
+//! A reservoir for small items, without the `Option` around every slot.
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::{self, Deterministic};
+use crate::store::{PackedStore, SampleStore};
+use crate::weights::{uniform, LockWithWeights, Sampled};
+use crate::{algorithm, StreamSampler};
+
+/// A `PackedReservoir` samples exactly like a [`Reservoir`](crate::Reservoir),
+/// but since the first items always fill the pool front to back, it can keep
+/// them without an `Option` each. For small `Copy` items this halves the
+/// memory, and `samples` is a plain slice of the held items.
+pub struct PackedReservoir<T, R = ThreadRng> {
+    total: u64,
+    pool: PackedStore<T>,
+    rng: R,
+}
+
+impl<T> PackedReservoir<T> {
+    pub fn with_capacity(n: usize) -> Self {
+        Self::with_rng(n, thread_rng())
+    }
+}
+
+impl<T> PackedReservoir<T, Deterministic> {
+    pub fn deterministic(n: usize, seed: u64) -> Self {
+        Self::with_rng(n, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, R: Rng> PackedReservoir<T, R> {
+    pub fn with_rng(n: usize, rng: R) -> Self {
+        Self {
+            total: 0,
+            pool: PackedStore::with_capacity(n),
+            rng,
+        }
+    }
+
+    pub fn samples(&self) -> &[T] {
+        self.pool.items()
+    }
+
+    /// End the sampling process. The held items come out shuffled.
+    pub fn lock(mut self) -> Vec<T> {
+        let mut items: Vec<T> = self.pool.into_items().collect();
+        rng::shuffle(&mut items, &mut self.rng);
+        items
+    }
+
+    /// The same as `lock`, as an iterator.
+    pub fn lock_iter(self) -> impl Iterator<Item = T> {
+        self.lock().into_iter()
+    }
+}
+
+impl<T, R: Rng> StreamSampler for PackedReservoir<T, R> {
+    type Item = T;
+    /// The items of `1..=capacity` are held.
+    type Draw = u64;
+
+    fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.total += 1;
+
+        if self.pool.capacity() == 0 {
+            return (0, self.total, None);
+        }
+
+        let (r, replaced) = algorithm::r(&mut self.pool, self.total, it, &mut self.rng);
+        (r, self.total, replaced)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        (1..=self.pool.capacity() as u64).contains(draw)
+    }
+}
+
+impl<T, R: Rng> LockWithWeights for PackedReservoir<T, R> {
+    type Item = T;
+
+    fn lock_with_weights(self) -> Vec<Sampled<T>> {
+        let probability = uniform(self.pool.capacity(), self.total);
+        self.lock()
+            .into_iter()
+            .map(|item| Sampled { item, probability })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reservoir, StreamSampler};
+
+    #[test]
+    fn same_draws_as_reservoir() {
+        let mut packed = PackedReservoir::<u8, Deterministic>::deterministic(5, 3);
+        let mut reservoir = Reservoir::<u8, Deterministic>::deterministic(5, 3);
+        for it in 0..40 {
+            assert_eq!(packed.sample(it), reservoir.sample(it));
+
+            let held: Vec<_> = reservoir.iter().copied().collect();
+            assert_eq!(packed.samples(), &held[..]);
+        }
+    }
+
+    #[test]
+    fn fewer_items_than_capacity() {
+        let mut packed = PackedReservoir::<u8>::with_capacity(5);
+        let _ = packed.sample(1);
+        let _ = packed.sample(2);
+
+        let mut locked = packed.lock();
+        locked.sort_unstable();
+        assert_eq!(locked, vec![1, 2]);
+    }
+}
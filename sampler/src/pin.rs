@@ -0,0 +1,160 @@
+//! Pinning held items, so that they are never replaced, like the first
+//! occurrence of every error class.
+//!
+//! Pinned items are taken out of the sampling: the unpinned slots keep a
+//! uniform sample of the items which are not pinned. With `p` items pinned out
+//! of a capacity of `k`, the `n`-th item is held with the chance of
+//! `(k - p) / (n - p)`, replacing an unpinned slot at random, instead of
+//! `k / n`. `L` and `X` reservoirs fall back to the draws of `R` while anything
+//! is pinned.
+use rand::Rng;
+
+use crate::rng::below_u64;
+use crate::{Algorithm, Reservoir};
+
+impl<T, R: Rng> Reservoir<T, R> {
+    /// Pins the item held in `slot`, the index into `samples`. Returns whether
+    /// the slot holds an item, an empty slot can not be pinned.
+    ///
+    /// Pins follow their items until they are unpinned, deleted, or the pool
+    /// is rearranged by `compact_and_shuffle`, `downsize` or `merge`, which
+    /// drop every pin. A snapshot keeps the pins, but the binary encoding of
+    /// [`Mergeable`](crate::Mergeable) does not.
+    pub fn pin(&mut self, slot: usize) -> bool {
+        if !matches!(self.pool.get(slot), Some(Some(_))) {
+            return false;
+        }
+        if let Err(at) = self.pins.binary_search(&slot) {
+            self.pins.insert(at, slot);
+        }
+        true
+    }
+
+    /// Pins every held item `pred` matches. Returns how many were not pinned
+    /// yet.
+    pub fn pin_where(&mut self, mut pred: impl FnMut(&T) -> bool) -> usize {
+        let slots: Vec<usize> = self
+            .pool
+            .iter()
+            .enumerate()
+            .filter(|(slot, it)| {
+                it.as_ref().is_some_and(&mut pred) && self.pins.binary_search(slot).is_err()
+            })
+            .map(|(slot, _)| slot)
+            .collect();
+        for &slot in &slots {
+            let _ = self.pin(slot);
+        }
+        slots.len()
+    }
+
+    /// Returns whether `slot` was pinned.
+    pub fn unpin(&mut self, slot: usize) -> bool {
+        match self.pins.binary_search(&slot) {
+            Ok(at) => {
+                let _ = self.pins.remove(at);
+                // L 和 X 的跳过数在钉住期间失效，重新抽
+                if self.pins.is_empty()
+                    && self.deleted == 0
+                    && self.algorithm != Algorithm::R
+                    && self.total >= self.pool.len() as u64
+                {
+                    self.next_skip();
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// The pinned slots, in ascending order.
+    pub fn pins(&self) -> &[usize] {
+        &self.pins
+    }
+
+    /// Samples around the pinned slots, filling empty slots in order first,
+    /// since no item is replaced while the pool fills.
+    pub(crate) fn sample_pinned(&mut self, it: T) -> (u64, u64, Option<T>) {
+        let k = self.pool.len() as u64;
+        let rejected = (k + 1).max(self.total);
+
+        if let Some(slot) = self.pool.iter().position(Option::is_none) {
+            self.pool[slot] = Some(it);
+            self.touch(slot);
+            self.set_arrival(slot, self.arrival());
+            return (slot as u64 + 1, self.total, None);
+        }
+
+        let p = self.pins.len() as u64;
+        let r = below_u64(&mut self.rng, self.total.saturating_sub(p).max(1));
+        if r >= k - p {
+            return (rejected, self.total, None);
+        }
+
+        // 第 r 个未钉住的槽位
+        let slot = match (0..self.pool.len())
+            .filter(|slot| self.pins.binary_search(slot).is_err())
+            .nth(r as usize)
+        {
+            Some(slot) => slot,
+            None => return (rejected, self.total, None),
+        };
+        let replaced = self.pool[slot].replace(it);
+        self.touch(slot);
+        self.record_eviction(slot, &replaced);
+        self.set_arrival(slot, self.arrival());
+        (slot as u64 + 1, self.total, replaced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Algorithm, Deterministic, Inspectable, Reservoir, StreamSampler};
+
+    #[test]
+    fn pinned_items_stay() {
+        for &algorithm in &[Algorithm::R, Algorithm::X] {
+            let rng = Deterministic::seed_from_u64(1);
+            let mut reservoir = Reservoir::with_rng_and_algorithm(5, rng, algorithm);
+            let _ = reservoir.sample_all(0..5);
+            assert!(!reservoir.pin(7));
+            assert_eq!(reservoir.pin_where(|&it| it % 2 == 0), 3);
+            assert_eq!(reservoir.pin_where(|&it| it == 0), 0);
+            assert_eq!(reservoir.pins().len(), 3);
+
+            let _ = reservoir.sample_all(5..10_000);
+            for even in [0, 2, 4] {
+                assert!(reservoir.contains(|&it| it == even));
+            }
+            assert_eq!(reservoir.iter().filter(|&&it| it >= 5).count(), 2);
+
+            // 取消钉住后照常被替换
+            for slot in reservoir.pins().to_vec() {
+                assert!(reservoir.unpin(slot));
+            }
+            let _ = reservoir.sample_all(10_000..1_000_000);
+            assert!(!reservoir.contains(|&it| it < 5));
+        }
+    }
+
+    #[test]
+    fn unpinned_slots_stay_uniform() {
+        let mut counts = [0u32; 20];
+        for seed in 0..4_000 {
+            let mut reservoir = Reservoir::<usize, Deterministic>::deterministic(4, seed);
+            let _ = reservoir.sample_all(0..4);
+            let _ = reservoir.pin_where(|&it| it == 0);
+            let _ = reservoir.sample_all(4..20);
+            for &it in reservoir.samples().iter().flatten() {
+                counts[it] += 1;
+            }
+        }
+        assert_eq!(counts[0], 4_000);
+        // 其余 19 个条目的入选概率都应接近 3 / 19
+        assert!(
+            counts[1..].iter().all(|&c| (520..=750).contains(&c)),
+            "{:?}",
+            counts
+        );
+    }
+}
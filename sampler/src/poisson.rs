@@ -0,0 +1,178 @@
+//! Poisson sampling: every item is kept or not on its own, by a probability
+//! of its own, as survey sampling does. The sample size is random, but every
+//! kept item knows its probability, for Horvitz–Thompson estimates.
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::weights::{LockWithWeights, Sampled};
+use crate::{Deterministic, Lockable, StreamSampler};
+
+/// How likely a [`PoissonSampler`] is to keep an item. Any `Fn(&T) -> f64` is
+/// one.
+pub trait Inclusion<T> {
+    fn probability(&self, it: &T) -> f64;
+}
+
+impl<T, F: Fn(&T) -> f64> Inclusion<T> for F {
+    fn probability(&self, it: &T) -> f64 {
+        self(it)
+    }
+}
+
+/// Probability proportional to size, πps: an item of size `size(it)` is kept
+/// with probability `expected * size(it) / total_size`, capped at 1, so
+/// about `expected` items are kept out of items of sizes adding up to
+/// `total_size`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pps<G> {
+    expected: f64,
+    total_size: f64,
+    size: G,
+}
+
+impl<G> Pps<G> {
+    pub fn new(expected: f64, total_size: f64, size: G) -> Self {
+        Self {
+            expected,
+            total_size,
+            size,
+        }
+    }
+}
+
+impl<T, G: Fn(&T) -> f64> Inclusion<T> for Pps<G> {
+    fn probability(&self, it: &T) -> f64 {
+        self.expected * (self.size)(it) / self.total_size
+    }
+}
+
+/// A `PoissonSampler` keeps every item with the probability `inclusion`
+/// gives it, independently of the others, so it never lets go of a kept
+/// item. A probability out of `0.0..=1.0`, or not a number, is clamped into
+/// it.
+pub struct PoissonSampler<T, F, R = ThreadRng> {
+    inclusion: F,
+    total: u64,
+    held: Vec<Sampled<T>>,
+    rng: R,
+}
+
+impl<T, F: Inclusion<T>> PoissonSampler<T, F> {
+    pub fn new(inclusion: F) -> Self {
+        Self::with_rng(inclusion, thread_rng())
+    }
+}
+
+impl<T, F: Inclusion<T>> PoissonSampler<T, F, Deterministic> {
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(inclusion: F, seed: u64) -> Self {
+        Self::with_rng(inclusion, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, F: Inclusion<T>, R: Rng> PoissonSampler<T, F, R> {
+    pub fn with_rng(inclusion: F, rng: R) -> Self {
+        Self {
+            inclusion,
+            total: 0,
+            held: Vec::new(),
+            rng,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The items kept so far, in the order they were fed, with their
+    /// probabilities.
+    pub fn samples(&self) -> &[Sampled<T>] {
+        &self.held
+    }
+}
+
+impl<T, F: Inclusion<T>, R: Rng> StreamSampler for PoissonSampler<T, F, R> {
+    type Item = T;
+    /// Whether the item is kept, which it is for good.
+    type Draw = bool;
+
+    fn sample(&mut self, it: T) -> (bool, u64, Option<T>) {
+        self.total += 1;
+        let p = self.inclusion.probability(&it);
+        let probability = if p.is_nan() { 0.0 } else { p.clamp(0.0, 1.0) };
+
+        // 概率为 0 的条目不取随机数
+        let kept = probability > 0.0 && self.rng.gen::<f64>() < probability;
+        if kept {
+            self.held.push(Sampled {
+                item: it,
+                probability,
+            });
+        }
+        (kept, self.total, None)
+    }
+
+    fn is_held(&self, draw: &bool) -> bool {
+        *draw
+    }
+}
+
+impl<T, F: Inclusion<T>, R: Rng> Lockable for PoissonSampler<T, F, R> {
+    fn lock(self) -> Vec<Option<T>> {
+        self.held.into_iter().map(|s| Some(s.item)).collect()
+    }
+}
+
+impl<T, F: Inclusion<T>, R: Rng> LockWithWeights for PoissonSampler<T, F, R> {
+    type Item = T;
+
+    fn lock_with_weights(self) -> Vec<Sampled<T>> {
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estimate::estimate_sum;
+
+    #[test]
+    fn keeps_by_probability() {
+        let mut sampler = PoissonSampler::deterministic(|it: &u32| f64::from(*it % 3) / 2.0, 1);
+        let _ = sampler.sample_all(0..30_000);
+        assert_eq!(sampler.total(), 30_000);
+
+        let mut counts = [0u32; 3];
+        for s in sampler.samples() {
+            counts[(s.item % 3) as usize] += 1;
+            assert_eq!(s.probability, f64::from(s.item % 3) / 2.0);
+        }
+        // 余 0 的从不入选，余 2 的总是入选
+        assert_eq!(counts[0], 0);
+        assert!((4_700..=5_300).contains(&counts[1]));
+        assert_eq!(counts[2], 10_000);
+
+        let mut clamped = PoissonSampler::deterministic(|_: &u32| f64::NAN, 1);
+        assert_eq!(clamped.sample_all(0..10), 0);
+    }
+
+    #[test]
+    fn pps_estimates_are_unbiased() {
+        let sizes: Vec<f64> = (1..=100).map(f64::from).collect();
+        let total_size: f64 = sizes.iter().sum();
+        let truth: f64 = sizes.iter().map(|s| s * s).sum();
+
+        let mut estimates = 0.0;
+        let mut kept = 0;
+        for seed in 0..2_000 {
+            let pps = Pps::new(10.0, total_size, |size: &f64| *size);
+            let mut sampler = PoissonSampler::deterministic(pps, seed);
+            kept += sampler.sample_all(sizes.iter().copied());
+            let sample = sampler.lock_with_weights();
+            estimates += estimate_sum(&sample, |size| size * size).value;
+        }
+
+        assert!((kept as f64 / 2_000.0 - 10.0).abs() < 0.2);
+        assert!((estimates / 2_000.0 / truth - 1.0).abs() < 0.02);
+    }
+}
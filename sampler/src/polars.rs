@@ -0,0 +1,118 @@
+//! Sampling rows out of Polars frames, returning a small `DataFrame`.
+//! Enabled by the `polars` feature.
+use polars::prelude::{DataFrame, IdxCa, IdxSize, LazyFrame, PolarsResult, Schema};
+
+use crate::{Algorithm, Reservoir, StreamSampler};
+
+/// Samples `k` rows of `frame`, collecting it once.
+///
+/// The whole collected frame is in memory while it is sampled. For a source
+/// too big for that, read it in batches and pass them to
+/// [`sample_data_frames`].
+pub fn sample_lazy_frame(frame: LazyFrame, k: usize) -> PolarsResult<DataFrame> {
+    sample_data_frame(&frame.collect()?, k)
+}
+
+/// Samples `k` rows of a `DataFrame` already in memory, the same way.
+pub fn sample_data_frame(frame: &DataFrame, k: usize) -> PolarsResult<DataFrame> {
+    sample_data_frames(frame.schema(), std::iter::once(Ok(frame.clone())), k)
+}
+
+/// Samples `k` rows out of every frame of `frames`, which share `schema`,
+/// into one frame, in random order. Without any rows, the frame is empty but
+/// keeps the schema.
+///
+/// Only row positions go through the reservoir. After every frame, the rows
+/// it left held are copied out, and copied rows which are replaced later are
+/// cleaned up once they outnumber the held ones.
+pub fn sample_data_frames<I>(schema: &Schema, frames: I, k: usize) -> PolarsResult<DataFrame>
+where
+    I: IntoIterator<Item = PolarsResult<DataFrame>>,
+{
+    // (是否在当前这一块中, 第几行)
+    let mut reservoir = Reservoir::<(bool, usize)>::with_algorithm(k, Algorithm::L);
+    let mut copied = DataFrame::empty_with_schema(schema);
+
+    for frame in frames {
+        let frame = frame?;
+        for row in 0..frame.height() {
+            let _ = reservoir.sample((true, row));
+        }
+
+        let mut rows = Vec::new();
+        for at in reservoir.pool.iter_mut().flatten() {
+            if at.0 {
+                rows.push(at.1 as IdxSize);
+                *at = (false, copied.height() + rows.len() - 1);
+            }
+        }
+        if rows.is_empty() {
+            continue;
+        }
+        let _ = copied.vstack_mut_owned(frame.take(&IdxCa::from_vec("".into(), rows))?)?;
+
+        if copied.height() > 2 * k {
+            copied = gather(&copied, &mut reservoir.pool)?;
+        }
+    }
+
+    reservoir.compact_and_shuffle();
+    gather(&copied, &mut reservoir.pool)
+}
+
+/// Copies the held rows out of `copied`, pointing the held positions at the
+/// new frame.
+fn gather(copied: &DataFrame, held: &mut [Option<(bool, usize)>]) -> PolarsResult<DataFrame> {
+    let mut rows = Vec::new();
+    for at in held.iter_mut().flatten() {
+        rows.push(at.1 as IdxSize);
+        *at = (false, rows.len() - 1);
+    }
+    copied.take(&IdxCa::from_vec("".into(), rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::prelude::*;
+
+    use super::*;
+
+    fn table(rows: i64) -> DataFrame {
+        let ids: Vec<i64> = (0..rows).collect();
+        let names: Vec<String> = ids.iter().map(|id| format!("row {}", id)).collect();
+        df!("id" => ids, "name" => names).unwrap()
+    }
+
+    #[test]
+    fn rows_stay_whole() {
+        let rows = 100_005;
+        let sample = sample_lazy_frame(table(rows).lazy(), 10).unwrap();
+        assert_eq!(sample.height(), 10);
+
+        let ids = sample.column("id").unwrap().i64().unwrap();
+        let names = sample.column("name").unwrap().str().unwrap();
+        let mut seen: Vec<i64> = ids.into_no_null_iter().collect();
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), 10);
+        for (id, name) in ids.into_no_null_iter().zip(names.into_no_null_iter()) {
+            assert!((0..rows).contains(&id));
+            assert_eq!(name, format!("row {}", id));
+        }
+    }
+
+    #[test]
+    fn small_frames_are_kept_whole() {
+        let frames = vec![Ok(table(2)), Ok(table(0)), Ok(table(1))];
+        let schema = table(0).schema().clone();
+        assert_eq!(sample_data_frames(&schema, frames, 10).unwrap().height(), 3);
+
+        let none = sample_data_frames(&schema, Vec::new(), 10).unwrap();
+        assert_eq!(none.height(), 0);
+        assert_eq!(none.width(), 2);
+
+        let empty = sample_data_frame(&table(0), 10).unwrap();
+        assert_eq!(empty.height(), 0);
+        assert_eq!(empty.width(), 2);
+    }
+}
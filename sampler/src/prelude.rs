@@ -0,0 +1,12 @@
+//! The traits the samplers are used through, to import them all at once:
+//!
+//! ```
+//! use reservoir_sampler::prelude::*;
+//! use reservoir_sampler::Reservoir;
+//!
+//! let mut reservoir = Reservoir::<u32>::with_capacity(2);
+//! let _ = reservoir.sample(1);
+//! assert_eq!(reservoir.samples(), &[Some(1), None]);
+//! assert_eq!(reservoir.lock_compact(), vec![1]);
+//! ```
+pub use crate::{Inspectable, Lockable, StreamSampler};
@@ -0,0 +1,71 @@
+//! The chances of a uniform reservoir, like Algorithm R, as `const fn`s, so a
+//! live draw can show them without sampling anything.
+
+/// The chance that an item which has passed through is held, once `total`
+/// items have passed through a pool of `k` slots. Every item shares the same
+/// chance, which is `k / total` once the pool is full.
+///
+/// ```
+/// use reservoir_sampler::inclusion_probability;
+///
+/// const EACH: f64 = inclusion_probability(200, 10);
+/// assert_eq!(EACH, 0.05);
+/// assert_eq!(inclusion_probability(3, 10), 1.0);
+/// ```
+pub const fn inclusion_probability(total: u64, k: usize) -> f64 {
+    if total <= k as u64 {
+        return 1.0;
+    }
+    k as f64 / total as f64
+}
+
+/// The chance that the `total`-th item, when it arrives at a pool of `k`
+/// slots, replaces a held one. Until the pool is full the item takes an empty
+/// slot instead, which replaces nobody.
+///
+/// ```
+/// use reservoir_sampler::replacement_probability;
+///
+/// assert_eq!(replacement_probability(10, 10), 0.0);
+/// assert_eq!(replacement_probability(20, 10), 0.5);
+/// ```
+pub const fn replacement_probability(total: u64, k: usize) -> f64 {
+    if total <= k as u64 {
+        return 0.0;
+    }
+    k as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Reservoir, StreamSampler};
+
+    #[test]
+    fn edges() {
+        assert_eq!(inclusion_probability(0, 0), 1.0);
+        assert_eq!(inclusion_probability(1, 0), 0.0);
+        assert_eq!(inclusion_probability(u64::MAX, 1), 1.0 / u64::MAX as f64);
+        assert_eq!(replacement_probability(0, 0), 0.0);
+        assert_eq!(replacement_probability(1, 0), 0.0);
+    }
+
+    #[test]
+    fn matches_algorithm_r() {
+        let (runs, k, n) = (4_000, 3, 12);
+        let (mut held, mut replaced) = (0, 0);
+        for seed in 0..runs {
+            let mut reservoir = Reservoir::<u64, Deterministic>::deterministic(k, seed);
+            for it in 1..=n {
+                let (_, _, out) = reservoir.sample(it);
+                replaced += u64::from(it == n && out.is_some());
+            }
+            held += reservoir.iter().filter(|&&it| it == 1).count();
+        }
+
+        let held = held as f64 / runs as f64;
+        let replaced = replaced as f64 / runs as f64;
+        assert!((held - inclusion_probability(n, k)).abs() < 0.03);
+        assert!((replaced - replacement_probability(n, k)).abs() < 0.03);
+    }
+}
@@ -0,0 +1,224 @@
+//! A reservoir where no category, like the service a log line comes from,
+//! may take more than its quota of the slots.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::{below, below_u64, compact_and_shuffle};
+use crate::{Deterministic, Inspectable, Lockable, StreamSampler};
+
+/// A `QuotaReservoir` holds at most `k` items, at most `quota` of them of
+/// one category, as `category` tells it.
+///
+/// An item of a category below its quota is held like by Algorithm R, and
+/// once the pool is full, it replaces an item of the category holding the
+/// most slots. An item of a category at its quota only ever replaces an item
+/// of its own, with the chance of `quota` out of the items of the category
+/// so far, so the held items of a category stay a uniform sample of it. The
+/// whole sample is not uniform any more, which is the point.
+///
+/// It counts the items of every category it sees, so it suits a bounded
+/// number of categories.
+pub struct QuotaReservoir<T, C, F, R = ThreadRng> {
+    quota: usize,
+    category: F,
+    total: u64,
+    pool: Vec<Option<T>>,
+    // 每个槽位中条目的类别
+    categories: Vec<Option<C>>,
+    held: HashMap<C, usize>,
+    seen: HashMap<C, u64>,
+    rng: R,
+}
+
+impl<T, C: Hash + Eq + Clone, F: FnMut(&T) -> C> QuotaReservoir<T, C, F> {
+    pub fn new(k: usize, quota: usize, category: F) -> Self {
+        Self::with_rng(k, quota, category, thread_rng())
+    }
+}
+
+impl<T, C: Hash + Eq + Clone, F: FnMut(&T) -> C> QuotaReservoir<T, C, F, Deterministic> {
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(k: usize, quota: usize, category: F, seed: u64) -> Self {
+        Self::with_rng(k, quota, category, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, C: Hash + Eq + Clone, F: FnMut(&T) -> C, R: Rng> QuotaReservoir<T, C, F, R> {
+    pub fn with_rng(k: usize, quota: usize, category: F, rng: R) -> Self {
+        Self {
+            quota,
+            category,
+            total: 0,
+            pool: std::iter::repeat_with(|| None).take(k).collect(),
+            categories: vec![None; k],
+            held: HashMap::new(),
+            seen: HashMap::new(),
+            rng,
+        }
+    }
+
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// How many held items are of `category`.
+    pub fn held(&self, category: &C) -> usize {
+        self.held.get(category).copied().unwrap_or(0)
+    }
+
+    /// A random slot holding an item of `category`.
+    fn slot_of(&mut self, category: &C) -> Option<usize> {
+        let n = self.held(category);
+        if n == 0 {
+            return None;
+        }
+        let nth = below(&mut self.rng, n);
+        self.categories
+            .iter()
+            .enumerate()
+            .filter(|(_, at)| at.as_ref() == Some(category))
+            .map(|(slot, _)| slot)
+            .nth(nth)
+    }
+
+    /// The category holding the most slots, the first one in slot order on
+    /// a tie.
+    fn largest(&self) -> Option<C> {
+        let mut largest: Option<(&C, usize)> = None;
+        for category in self.categories.iter().flatten() {
+            let n = self.held(category);
+            if largest.is_none_or(|(_, most)| n > most) {
+                largest = Some((category, n));
+            }
+        }
+        largest.map(|(category, _)| category.clone())
+    }
+
+    fn put(&mut self, slot: usize, category: C, it: T) -> Option<T> {
+        if let Some(old) = self.categories[slot].replace(category.clone()) {
+            if let Some(n) = self.held.get_mut(&old) {
+                *n -= 1;
+            }
+        }
+        *self.held.entry(category).or_insert(0) += 1;
+        self.pool[slot].replace(it)
+    }
+}
+
+impl<T, C: Hash + Eq + Clone, F: FnMut(&T) -> C, R: Rng> StreamSampler
+    for QuotaReservoir<T, C, F, R>
+{
+    type Item = T;
+    /// The items of `1..=k` are held, in that slot.
+    type Draw = u64;
+
+    fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.total += 1;
+        let k = self.pool.len() as u64;
+        let rejected = (k + 1).max(self.total);
+
+        let category = (self.category)(&it);
+        let seen = self.seen.entry(category.clone()).or_insert(0);
+        *seen += 1;
+        let seen = *seen;
+
+        // 类别已满额：只在本类别内按算法 R 替换
+        let slot = if self.held(&category) >= self.quota {
+            if below_u64(&mut self.rng, seen) >= self.quota as u64 {
+                None
+            } else {
+                self.slot_of(&category)
+            }
+        } else if let Some(slot) = self.pool.iter().position(Option::is_none) {
+            Some(slot)
+        } else if below_u64(&mut self.rng, self.total) >= k {
+            None
+        } else {
+            match self.largest() {
+                Some(largest) => self.slot_of(&largest),
+                None => None,
+            }
+        };
+
+        match slot {
+            Some(slot) => {
+                let replaced = self.put(slot, category, it);
+                (slot as u64 + 1, self.total, replaced)
+            }
+            None => (rejected, self.total, None),
+        }
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        (1..=self.pool.len() as u64).contains(draw)
+    }
+}
+
+impl<T, C: Hash + Eq + Clone, F: FnMut(&T) -> C, R: Rng> Inspectable
+    for QuotaReservoir<T, C, F, R>
+{
+    fn samples(&self) -> &[Option<T>] {
+        &self.pool
+    }
+}
+
+impl<T, C: Hash + Eq + Clone, F: FnMut(&T) -> C, R: Rng> Lockable for QuotaReservoir<T, C, F, R> {
+    fn lock(mut self) -> Vec<Option<T>> {
+        compact_and_shuffle(&mut self.pool, &mut self.rng);
+        self.pool
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chatty_category_is_capped() {
+        // (服务, 序号)：服务 0 占了九成的日志
+        let mut reservoir =
+            QuotaReservoir::deterministic(10, 4, |&(service, _): &(u8, u32)| service, 1);
+        for i in 0..10_000u32 {
+            let service = if i % 10 == 0 {
+                (i / 10 % 3) as u8 + 1
+            } else {
+                0
+            };
+            let _ = reservoir.sample((service, i));
+            assert!(reservoir.held(&0) <= 4);
+        }
+        assert_eq!(reservoir.held(&0), 4);
+        assert_eq!(reservoir.samples().iter().flatten().count(), 10);
+        for service in 1..=3 {
+            assert!(reservoir.held(&service) >= 1);
+        }
+
+        let held = reservoir.lock_compact();
+        assert_eq!(held.iter().filter(|&&(service, _)| service == 0).count(), 4);
+    }
+
+    #[test]
+    fn quota_keeps_a_category_uniform() {
+        let mut counts = [0u32; 20];
+        for seed in 0..4_000 {
+            let mut reservoir = QuotaReservoir::deterministic(8, 2, |_: &usize| (), seed);
+            let _ = reservoir.sample_all(0..20);
+            for &it in reservoir.samples().iter().flatten() {
+                counts[it] += 1;
+            }
+        }
+        // 只有一个类别时每个条目的入选概率都应接近 2 / 20
+        assert!(
+            counts.iter().all(|&c| (330..=480).contains(&c)),
+            "{:?}",
+            counts
+        );
+    }
+}
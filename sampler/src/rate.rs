@@ -0,0 +1,134 @@
+//! Capping how fast a sampler accepts items, so a burst of entries, like a bot
+//! flooding a giveaway endpoint, cannot churn the pool in a moment.
+use std::time::{Duration, Instant};
+
+use crate::{Inspectable, Lockable, StreamSampler};
+
+/// A `RateLimited` sampler accepts at most `max_accepts` items per `window`,
+/// by a token bucket: every acceptance takes a token, and tokens come back
+/// steadily, up to `max_accepts` at once.
+///
+/// While the bucket is empty, items are dropped before they reach the sampler
+/// inside, so they neither change the pool nor count toward its total.
+pub struct RateLimited<S> {
+    sampler: S,
+    max_accepts: u32,
+    window: Duration,
+    tokens: f64,
+    last: Option<Instant>,
+    total: u64,
+    throttled: u64,
+}
+
+impl<S: StreamSampler> RateLimited<S> {
+    pub fn new(sampler: S, max_accepts: u32, window: Duration) -> Self {
+        Self {
+            sampler,
+            max_accepts,
+            window,
+            tokens: f64::from(max_accepts),
+            last: None,
+            total: 0,
+            throttled: 0,
+        }
+    }
+
+    /// The same as `sample`, at the given time instead of now. A dropped
+    /// item draws `None`.
+    pub fn sample_at(
+        &mut self,
+        it: S::Item,
+        now: Instant,
+    ) -> (Option<S::Draw>, u64, Option<S::Item>) {
+        self.refill(now);
+        if self.tokens < 1.0 {
+            self.throttled += 1;
+            return (None, self.total, None);
+        }
+
+        let (r, total, replaced) = self.sampler.sample(it);
+        self.total = total;
+        if self.sampler.is_held(&r) {
+            self.tokens -= 1.0;
+        }
+        (Some(r), total, replaced)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let max = f64::from(self.max_accepts);
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            let window = self.window.as_secs_f64();
+            // 窗口为 0 视为不限速
+            let refilled = if window > 0.0 {
+                elapsed / window * max
+            } else {
+                max
+            };
+            self.tokens = (self.tokens + refilled).min(max);
+        }
+        self.last = Some(now);
+    }
+
+    /// How many items have been dropped for an empty bucket.
+    pub fn throttled(&self) -> u64 {
+        self.throttled
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sampler
+    }
+}
+
+impl<S: StreamSampler> StreamSampler for RateLimited<S> {
+    type Item = S::Item;
+    type Draw = Option<S::Draw>;
+
+    fn sample(&mut self, it: Self::Item) -> (Self::Draw, u64, Option<Self::Item>) {
+        self.sample_at(it, Instant::now())
+    }
+
+    fn is_held(&self, draw: &Self::Draw) -> bool {
+        draw.as_ref().is_some_and(|draw| self.sampler.is_held(draw))
+    }
+}
+
+impl<S: Inspectable> Inspectable for RateLimited<S> {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        self.sampler.samples()
+    }
+}
+
+impl<S: Lockable> Lockable for RateLimited<S> {
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        self.sampler.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Reservoir};
+
+    #[test]
+    fn bursts_are_capped() {
+        let reservoir = Reservoir::<u32, Deterministic>::deterministic(100, 1);
+        let mut limited = RateLimited::new(reservoir, 10, Duration::from_secs(60));
+
+        // 同一时刻涌入 50 个，只有 10 个能进
+        let start = Instant::now();
+        for it in 0..50 {
+            let _ = limited.sample_at(it, start);
+        }
+        assert_eq!(limited.throttled(), 40);
+        assert_eq!(limited.samples().iter().flatten().count(), 10);
+
+        // 半个窗口之后恢复 5 个
+        let later = start + Duration::from_secs(30);
+        for it in 50..60 {
+            let _ = limited.sample_at(it, later);
+        }
+        assert_eq!(limited.throttled(), 45);
+        assert_eq!(limited.into_inner().total(), 15);
+    }
+}
@@ -0,0 +1,354 @@
+//! Keeping one sampler per tenant, like one per customer of an ingestion
+//! service, under one memory budget for all of them.
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::memory::{BudgetPolicy, Loss, SizeHint};
+use crate::{Lockable, Reservoir, StreamSampler};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegistryError {
+    /// A sampler of this name is already registered.
+    Exists,
+    NoSuchSampler,
+    /// The new sampler does not fit into what is left of the budget.
+    OverBudget {
+        available: usize,
+    },
+}
+
+struct Tenant<T> {
+    k: usize,
+    reservoir: Reservoir<T>,
+    fed: Instant,
+    // 持有条目的字节数估计
+    bytes: usize,
+}
+
+type LossHook<K, T> = Box<dyn FnMut(&K, &Loss<T>)>;
+
+struct ByteBudget<K, T> {
+    bytes: usize,
+    policy: BudgetPolicy,
+    size: fn(&T) -> usize,
+    on_loss: LossHook<K, T>,
+}
+
+/// A `SamplerRegistry` keeps a [`Reservoir`] per name. Their capacities add
+/// up to at most `budget` slots, which is what bounds the memory, so a
+/// sampler is only created while it fits. Locking or evicting one frees its
+/// slots again.
+///
+/// Items of very different sizes are better bounded by bytes, see
+/// [`SamplerRegistry::byte_budget`].
+pub struct SamplerRegistry<K, T> {
+    budget: usize,
+    used: usize,
+    tenants: HashMap<K, Tenant<T>>,
+    bytes: Option<ByteBudget<K, T>>,
+}
+
+impl<K: Hash + Eq + Clone, T: Clone> SamplerRegistry<K, T> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            used: 0,
+            tenants: HashMap::new(),
+            bytes: None,
+        }
+    }
+
+    /// Also bounds the held items to about `bytes` bytes all together, as
+    /// told by their [`SizeHint`]. Once a feed takes the registry over it,
+    /// `policy` makes room, telling `on_loss` which items are about to go
+    /// before they do, like to spill them somewhere.
+    pub fn byte_budget(
+        mut self,
+        bytes: usize,
+        policy: BudgetPolicy,
+        on_loss: impl FnMut(&K, &Loss<T>) + 'static,
+    ) -> Self
+    where
+        T: SizeHint,
+    {
+        self.bytes = Some(ByteBudget {
+            bytes,
+            policy,
+            size: T::size_hint,
+            on_loss: Box::new(on_loss),
+        });
+        self
+    }
+
+    /// About how many bytes the held items of `name` take, or 0 without a
+    /// byte budget.
+    pub fn bytes(&self, name: &K) -> Option<usize> {
+        self.tenants.get(name).map(|tenant| tenant.bytes)
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.tenants.values().map(|tenant| tenant.bytes).sum()
+    }
+
+    /// How many slots of the budget are taken.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn len(&self) -> usize {
+        self.tenants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tenants.is_empty()
+    }
+
+    pub fn create(&mut self, name: K, k: usize) -> Result<(), RegistryError> {
+        self.create_at(name, k, Instant::now())
+    }
+
+    /// The same as `create`, at the given time instead of now, which counts
+    /// as the sampler's last feed.
+    pub fn create_at(&mut self, name: K, k: usize, now: Instant) -> Result<(), RegistryError> {
+        if self.tenants.contains_key(&name) {
+            return Err(RegistryError::Exists);
+        }
+        let available = self.budget - self.used;
+        if k > available {
+            return Err(RegistryError::OverBudget { available });
+        }
+
+        self.used += k;
+        let tenant = Tenant {
+            k,
+            reservoir: Reservoir::with_capacity(k),
+            fed: now,
+            bytes: 0,
+        };
+        let _ = self.tenants.insert(name, tenant);
+        Ok(())
+    }
+
+    pub fn feed(&mut self, name: &K, it: T) -> Result<(u64, u64, Option<T>), RegistryError> {
+        self.feed_at(name, it, Instant::now())
+    }
+
+    /// The same as `feed`, at the given time instead of now.
+    pub fn feed_at(
+        &mut self,
+        name: &K,
+        it: T,
+        now: Instant,
+    ) -> Result<(u64, u64, Option<T>), RegistryError> {
+        let tenant = self
+            .tenants
+            .get_mut(name)
+            .ok_or(RegistryError::NoSuchSampler)?;
+        tenant.fed = now;
+
+        let size = match &self.bytes {
+            Some(budget) => budget.size,
+            None => return Ok(tenant.reservoir.sample(it)),
+        };
+        let bytes = size(&it);
+        let (draw, total, replaced) = tenant.reservoir.sample(it);
+        if tenant.reservoir.is_held(&draw) {
+            tenant.bytes += bytes;
+        }
+        if let Some(replaced) = &replaced {
+            tenant.bytes = tenant.bytes.saturating_sub(size(replaced));
+        }
+        self.enforce_byte_budget();
+        Ok((draw, total, replaced))
+    }
+
+    /// Makes room by the policy until the held items fit into the byte
+    /// budget.
+    fn enforce_byte_budget(&mut self) {
+        let mut budget = match self.bytes.take() {
+            Some(budget) => budget,
+            None => return,
+        };
+
+        while self.total_bytes() > budget.bytes {
+            let victim = match budget.policy {
+                BudgetPolicy::EvictIdlest => self.tenants.iter().min_by_key(|(_, t)| t.fed),
+                BudgetPolicy::DownsizeLargest => self.tenants.iter().max_by_key(|(_, t)| t.bytes),
+            };
+            let (name, k) = match victim {
+                Some((name, tenant)) => (name.clone(), tenant.k),
+                None => break,
+            };
+
+            if budget.policy == BudgetPolicy::EvictIdlest || k <= 1 {
+                if let Ok(held) = self.lock(&name) {
+                    (budget.on_loss)(&name, &Loss::Evicted(held));
+                }
+                continue;
+            }
+
+            if let Some(tenant) = self.tenants.get_mut(&name) {
+                let capacity = k / 2;
+                let dropped = tenant.reservoir.downsize(capacity);
+                (budget.on_loss)(&name, &Loss::Downsized { capacity, dropped });
+                tenant.k = capacity;
+                tenant.bytes = tenant.reservoir.iter().map(budget.size).sum();
+                self.used -= k - capacity;
+            }
+        }
+        self.bytes = Some(budget);
+    }
+
+    pub fn get(&self, name: &K) -> Option<&Reservoir<T>> {
+        self.tenants.get(name).map(|tenant| &tenant.reservoir)
+    }
+
+    /// Locks the sampler of `name`, handing out its held items, and starts
+    /// over with a fresh one of the same capacity, like at the end of a
+    /// billing period.
+    pub fn rotate(&mut self, name: &K) -> Result<Vec<T>, RegistryError> {
+        let tenant = self
+            .tenants
+            .get_mut(name)
+            .ok_or(RegistryError::NoSuchSampler)?;
+        let fresh = Reservoir::with_capacity(tenant.k);
+        let old = std::mem::replace(&mut tenant.reservoir, fresh);
+        tenant.bytes = 0;
+        Ok(old.lock_compact())
+    }
+
+    /// Locks the sampler of `name` and removes it, freeing its slots.
+    pub fn lock(&mut self, name: &K) -> Result<Vec<T>, RegistryError> {
+        let tenant = self
+            .tenants
+            .remove(name)
+            .ok_or(RegistryError::NoSuchSampler)?;
+        self.used -= tenant.k;
+        Ok(tenant.reservoir.lock_compact())
+    }
+
+    /// Locks and removes every sampler not fed for `idle` by `now`, handing
+    /// out their held items, so nothing sampled is lost.
+    pub fn evict_idle(&mut self, idle: Duration, now: Instant) -> Vec<(K, Vec<T>)> {
+        let names: Vec<K> = self
+            .tenants
+            .iter()
+            .filter(|(_, tenant)| now.saturating_duration_since(tenant.fed) >= idle)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let held = self.lock(&name).ok()?;
+                Some((name, held))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifecycle() {
+        let mut registry = SamplerRegistry::<&str, u32>::new(10);
+        assert_eq!(registry.create("acme", 6), Ok(()));
+        assert_eq!(registry.create("acme", 1), Err(RegistryError::Exists));
+        assert_eq!(
+            registry.create("globex", 5),
+            Err(RegistryError::OverBudget { available: 4 })
+        );
+        assert_eq!(registry.create("globex", 4), Ok(()));
+        assert_eq!(registry.used(), 10);
+
+        for it in 0..100 {
+            assert!(registry.feed(&"acme", it).is_ok());
+        }
+        assert_eq!(
+            registry.feed(&"initech", 0),
+            Err(RegistryError::NoSuchSampler)
+        );
+
+        let first = registry.rotate(&"acme").unwrap();
+        assert_eq!(first.len(), 6);
+        assert_eq!(registry.get(&"acme").map(|r| r.total()), Some(0));
+        assert_eq!(registry.used(), 10);
+
+        let _ = registry.feed(&"globex", 7);
+        assert_eq!(registry.lock(&"globex"), Ok(vec![7]));
+        assert_eq!(registry.used(), 6);
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn evicts_idle_samplers() {
+        let start = Instant::now();
+        let minute = Duration::from_secs(60);
+        let mut registry = SamplerRegistry::<u32, u32>::new(100);
+        for tenant in 0..3 {
+            registry.create_at(tenant, 10, start).unwrap();
+            let _ = registry.feed_at(&tenant, tenant, start);
+        }
+        let _ = registry.feed_at(&1, 1, start + 5 * minute);
+
+        let mut evicted = registry.evict_idle(2 * minute, start + 6 * minute);
+        evicted.sort();
+        assert_eq!(evicted, vec![(0, vec![0]), (2, vec![2])]);
+        assert_eq!(registry.used(), 10);
+        assert!(registry.get(&1).is_some());
+    }
+
+    #[test]
+    fn byte_budget() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let lost = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&lost);
+        let mut registry = SamplerRegistry::<u32, u64>::new(100).byte_budget(
+            8 * 12,
+            BudgetPolicy::DownsizeLargest,
+            move |name, loss| log.borrow_mut().push((*name, loss.clone())),
+        );
+        registry.create(0, 8).unwrap();
+        registry.create(1, 8).unwrap();
+        for it in 0..10 {
+            let _ = registry.feed(&0, it);
+        }
+        assert!(lost.borrow().is_empty());
+        assert_eq!(registry.bytes(&0), Some(64));
+
+        // 第二个采样器装到 5 个时超出预算，最大的被减半
+        for it in 0..5 {
+            let _ = registry.feed(&1, it);
+        }
+        assert_eq!(lost.borrow().len(), 1);
+        match &lost.borrow()[0] {
+            (
+                0,
+                Loss::Downsized {
+                    capacity: 4,
+                    dropped,
+                },
+            ) => assert_eq!(dropped.len(), 4),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(registry.total_bytes(), 8 * 9);
+        assert_eq!(registry.used(), 12);
+        assert_eq!(registry.get(&0).map(|r| r.iter().count()), Some(4));
+
+        let mut registry = SamplerRegistry::<u32, u64>::new(100).byte_budget(
+            8,
+            BudgetPolicy::EvictIdlest,
+            |_, _| {},
+        );
+        registry.create(0, 1).unwrap();
+        registry.create(1, 1).unwrap();
+        let _ = registry.feed(&0, 0);
+        let _ = registry.feed(&1, 1);
+        assert!(registry.get(&0).is_none());
+        assert_eq!(registry.bytes(&1), Some(8));
+    }
+}
@@ -0,0 +1,244 @@
+//! Mirroring a live [`Reservoir`] in another process, like a dashboard, by
+//! shipping only the slots changed since the replica last caught up.
+//!
+//! Every change to a slot bumps the reservoir's generation. A replica at
+//! generation `g` asks for [`Reservoir::diff`] of `g`, and applies the
+//! [`Patch`] it gets, which brings it to the generation of the reservoir.
+use rand::Rng;
+
+use crate::merge::{
+    get_header, get_items, get_u64, get_usize, put_header, put_varint, MergeError, WireItem,
+};
+use crate::{Marks, Reservoir};
+
+pub(crate) const PATCH: u8 = 4;
+
+/// The slots of a [`Reservoir`] changed from one generation to another.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Patch<T> {
+    pub since: u64,
+    pub generation: u64,
+    pub capacity: usize,
+    pub total: u64,
+    /// The changed slots, by index, with what they hold now.
+    pub slots: Vec<(usize, Option<T>)>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PatchError {
+    /// The replica is older than the patch, so it misses the changes in
+    /// between. It needs a patch since its own generation.
+    Gap { generation: u64, since: u64 },
+    /// The patch is of a reservoir of another capacity.
+    WrongCapacity,
+}
+
+impl<T: Clone, R: Rng> Reservoir<T, R> {
+    /// How many times the slots have changed, see [`Reservoir::diff`].
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The slots changed after generation `since`. A `since` of 0 takes every
+    /// slot ever filled, for a fresh replica.
+    pub fn diff(&self, since: u64) -> Patch<T> {
+        let slots = self
+            .pool
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| self.marks(idx).stamp > since)
+            .map(|(idx, slot)| (idx, slot.clone()))
+            .collect();
+        Patch {
+            since,
+            generation: self.generation,
+            capacity: self.pool.len(),
+            total: self.total,
+            slots,
+        }
+    }
+
+    /// Applies a patch of the reservoir this one mirrors. A patch since an
+    /// older generation than this one's still applies, so patches can be
+    /// replayed, but it never overwrites a slot with an older state: a patch
+    /// of no newer generation than this one is skipped, and so is every slot
+    /// changed by a newer patch than it.
+    pub fn apply_patch(&mut self, patch: Patch<T>) -> Result<(), PatchError> {
+        if patch.capacity != self.pool.len() {
+            return Err(PatchError::WrongCapacity);
+        }
+        if patch.since > self.generation {
+            return Err(PatchError::Gap {
+                generation: self.generation,
+                since: patch.since,
+            });
+        }
+
+        if patch.generation <= self.generation {
+            return Ok(());
+        }
+
+        for (idx, slot) in patch.slots {
+            if idx >= self.pool.len() || self.marks(idx).stamp >= patch.generation {
+                continue;
+            }
+            self.pool[idx] = slot;
+            if let Some(marks) = self.marks_mut(idx) {
+                *marks = Marks {
+                    stamp: patch.generation,
+                    arrival: 0,
+                };
+            }
+        }
+        self.total = patch.total;
+        self.generation = patch.generation;
+        Ok(())
+    }
+}
+
+impl<T: WireItem> Patch<T> {
+    /// The patch in the binary encoding of [`Mergeable`](crate::Mergeable).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_header(&mut out, PATCH);
+        for n in [
+            self.since,
+            self.generation,
+            self.capacity as u64,
+            self.total,
+        ] {
+            put_varint(&mut out, u128::from(n));
+        }
+        put_varint(&mut out, self.slots.len() as u128);
+        for (idx, slot) in &self.slots {
+            put_varint(&mut out, *idx as u128);
+            match slot {
+                Some(it) => {
+                    out.push(1);
+                    it.encode(&mut out);
+                }
+                None => out.push(0),
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MergeError> {
+        let input = &mut &bytes[..];
+        get_header(input, PATCH)?;
+        let since = get_u64(input)?;
+        let generation = get_u64(input)?;
+        let capacity = get_usize(input)?;
+        let total = get_u64(input)?;
+        let n = get_usize(input)?;
+        let slots = get_items(input, n, |input| {
+            let idx = get_usize(input)?;
+            let (&present, rest) = input.split_first().ok_or(MergeError::Truncated)?;
+            *input = rest;
+            match present {
+                0 => Ok((idx, None)),
+                1 => Ok((idx, Some(T::decode(input)?))),
+                _ => Err(MergeError::Malformed),
+            }
+        })?;
+        Ok(Self {
+            since,
+            generation,
+            capacity,
+            total,
+            slots,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Inspectable, Mergeable, StreamSampler};
+
+    #[test]
+    fn replica_catches_up() {
+        let mut live = Reservoir::<u32, Deterministic>::deterministic(10, 1);
+        let mut replica = Reservoir::<u32, Deterministic>::deterministic(10, 2);
+
+        for round in 0..5u32 {
+            let _ = live.sample_all(round * 1_000..(round + 1) * 1_000);
+            let patch = live.diff(replica.generation());
+            assert!(patch.slots.len() <= 10);
+            replica.apply_patch(patch).unwrap();
+            assert_eq!(replica.samples(), live.samples());
+            assert_eq!(replica.total(), live.total());
+        }
+
+        // 没有变化时补丁为空
+        assert!(live.diff(live.generation()).slots.is_empty());
+
+        let _ = live.delete(|_| true);
+        let _ = live.update_if_held(|_| true, |it| *it += 1);
+        let bytes = live.diff(replica.generation()).to_bytes();
+        replica
+            .apply_patch(Patch::from_bytes(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(replica.samples(), live.samples());
+
+        let mut locked = live.clone();
+        locked.compact_and_shuffle();
+        replica
+            .apply_patch(locked.diff(replica.generation()))
+            .unwrap();
+        assert_eq!(replica.samples(), locked.samples());
+    }
+
+    #[test]
+    fn restored_reservoir_ships_every_slot() {
+        let mut live = Reservoir::<u32, Deterministic>::deterministic(3, 1);
+        let _ = live.sample_all(0..10);
+        let restored = Reservoir::<u32>::from_bytes(&live.to_bytes()).unwrap();
+
+        let mut replica = Reservoir::<u32, Deterministic>::deterministic(3, 2);
+        replica.apply_patch(restored.diff(0)).unwrap();
+        assert_eq!(replica.samples(), live.samples());
+    }
+
+    #[test]
+    fn stale_patches_are_skipped() {
+        let mut live = Reservoir::<u32, Deterministic>::deterministic(4, 1);
+        let mut replica = Reservoir::<u32, Deterministic>::deterministic(4, 2);
+        let _ = live.sample_all(0..4);
+        let old = live.diff(0);
+        let _ = live.sample_all(4..100);
+        let new = live.diff(0);
+
+        replica.apply_patch(new).unwrap();
+        replica.apply_patch(old).unwrap();
+        assert_eq!(replica.samples(), live.samples());
+        assert_eq!(replica.total(), 100);
+        assert_eq!(replica.generation(), live.generation());
+    }
+
+    #[test]
+    fn gaps_and_capacity() {
+        let mut live = Reservoir::<u32, Deterministic>::deterministic(2, 1);
+        let _ = live.sample_all(0..10);
+        let patch = live.diff(3);
+
+        let mut replica = Reservoir::<u32, Deterministic>::deterministic(2, 2);
+        assert_eq!(
+            replica.apply_patch(patch),
+            Err(PatchError::Gap {
+                generation: 0,
+                since: 3
+            })
+        );
+
+        let mut small = Reservoir::<u32, Deterministic>::deterministic(1, 2);
+        assert_eq!(
+            small.apply_patch(live.diff(0)),
+            Err(PatchError::WrongCapacity)
+        );
+        assert_eq!(
+            Patch::<u32>::from_bytes(&[crate::WIRE_VERSION, 1]),
+            Err(MergeError::WrongKind)
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Random number plumbing shared by every sampler in this crate.
+use rand::{Error, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// The RNG a deterministic sampler is pinned to. It is a ChaCha20 stream, which
+/// yields the same numbers for the same seed on every platform, including
+/// 32-bit ones and wasm.
+#[derive(Clone, Debug)]
+pub struct Deterministic(ChaCha20Rng);
+
+impl Deterministic {
+    pub fn seed_from_u64(seed: u64) -> Self {
+        Deterministic(ChaCha20Rng::seed_from_u64(seed))
+    }
+
+    /// Seeds with a whole ChaCha20 key, like a hashed beacon value.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Deterministic(ChaCha20Rng::from_seed(seed))
+    }
+
+    /// The key, which `seed_from_u64` expands its seed into.
+    pub fn get_seed(&self) -> [u8; 32] {
+        self.0.get_seed()
+    }
+
+    /// Draws from another of the 2^64 streams of the same key, which do not
+    /// overlap, for deriving several rngs from one seed.
+    pub fn with_stream(mut self, stream: u64) -> Self {
+        self.0.set_stream(stream);
+        self
+    }
+
+    /// The seed, the stream and the word position, which together pin down
+    /// every number still to come.
+    #[cfg(feature = "state-hash")]
+    pub(crate) fn state(&self) -> ([u8; 32], u64, u128) {
+        (
+            self.0.get_seed(),
+            self.0.get_stream(),
+            self.0.get_word_pos(),
+        )
+    }
+}
+
+impl RngCore for Deterministic {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+/// Draws uniformly from `0..n`. The draw always goes through `u64`, so 32-bit
+/// and 64-bit targets consume the RNG in exactly the same way.
+pub(crate) fn below<R: Rng + ?Sized>(rng: &mut R, n: usize) -> usize {
+    below_u64(rng, n as u64) as usize
+}
+
+/// The same as [`below`], for counts which may not fit a `usize`.
+pub(crate) fn below_u64<R: Rng + ?Sized>(rng: &mut R, n: u64) -> u64 {
+    rng.gen_range(0..n)
+}
+
+/// Moves every filled slot to the front of `pool`, then shuffles the whole
+/// pool (empty slots included) with Fisher–Yates, so that each retained item
+/// ends up in a uniformly random slot, and so does each empty one.
+pub(crate) fn compact_and_shuffle<T, R: Rng + ?Sized>(pool: &mut [Option<T>], rng: &mut R) {
+    let mut filled = 0;
+    for i in 0..pool.len() {
+        if pool[i].is_some() {
+            pool.swap(filled, i);
+            filled += 1;
+        }
+    }
+
+    shuffle(pool, rng);
+}
+
+/// Fisher–Yates, drawing through [`below`].
+pub(crate) fn shuffle<T, R: Rng + ?Sized>(items: &mut [T], rng: &mut R) {
+    for i in (1..items.len()).rev() {
+        let j = below(rng, i + 1);
+        items.swap(i, j);
+    }
+}
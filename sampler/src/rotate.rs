@@ -0,0 +1,144 @@
+//! Sampling a stream window by window, like one sample of traces per minute.
+use std::time::{Duration, Instant};
+
+use crate::{Inspectable, Lockable, Reservoir, StreamSampler};
+
+/// When a [`RotatingSampler`] closes its window.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Rotation {
+    /// Once this long has passed since the window opened.
+    Every(Duration),
+    /// Once this many items have passed through the window.
+    Items(u64),
+}
+
+/// The sample of a closed window.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Window<T> {
+    /// Counting from 0.
+    pub index: u64,
+    /// How many items passed through the window.
+    pub total: u64,
+    pub samples: Vec<T>,
+}
+
+/// A `RotatingSampler` keeps a fresh [`Reservoir`] of `k` items for every
+/// window, and hands every closed window to `emit`. To get the windows over a
+/// channel instead, send them from `emit`, like `move |w| { let _ = tx.send(w); }`.
+///
+/// A window rotated by time only closes when an item arrives after its end,
+/// or on `flush`, so a quiet stream emits no empty windows.
+pub struct RotatingSampler<T, F> {
+    k: usize,
+    rotation: Rotation,
+    reservoir: Reservoir<T>,
+    opened: Option<Instant>,
+    index: u64,
+    emit: F,
+}
+
+impl<T: Clone, F: FnMut(Window<T>)> RotatingSampler<T, F> {
+    pub fn new(k: usize, rotation: Rotation, emit: F) -> Self {
+        Self {
+            k,
+            rotation,
+            reservoir: Reservoir::with_capacity(k),
+            opened: None,
+            index: 0,
+            emit,
+        }
+    }
+
+    pub fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.sample_at(it, Instant::now())
+    }
+
+    /// The same as `sample`, at the given time instead of now.
+    pub fn sample_at(&mut self, it: T, now: Instant) -> (u64, u64, Option<T>) {
+        if let (Rotation::Every(interval), Some(opened)) = (self.rotation, self.opened) {
+            if now.saturating_duration_since(opened) >= interval {
+                self.rotate();
+            }
+        }
+        if self.opened.is_none() {
+            self.opened = Some(now);
+        }
+
+        let sampled = self.reservoir.sample(it);
+        if let Rotation::Items(n) = self.rotation {
+            if self.reservoir.total() >= n {
+                self.rotate();
+            }
+        }
+        sampled
+    }
+
+    /// The samples of the open window.
+    pub fn samples(&self) -> &[Option<T>] {
+        self.reservoir.samples()
+    }
+
+    /// Closes the open window, unless no item has passed through it.
+    pub fn rotate(&mut self) {
+        let reservoir = std::mem::replace(&mut self.reservoir, Reservoir::with_capacity(self.k));
+        self.opened = None;
+        if reservoir.total() == 0 {
+            return;
+        }
+
+        let window = Window {
+            index: self.index,
+            total: reservoir.total(),
+            samples: reservoir.lock_iter().collect(),
+        };
+        self.index += 1;
+        (self.emit)(window);
+    }
+
+    /// Closes the open window, at the end of the stream.
+    pub fn flush(mut self) {
+        self.rotate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn every_n_items() {
+        let mut windows = Vec::new();
+        let mut sampler = RotatingSampler::new(2, Rotation::Items(10), |w| windows.push(w));
+        for it in 0..25 {
+            let _ = sampler.sample(it);
+        }
+        sampler.flush();
+
+        assert_eq!(windows.len(), 3);
+        assert_eq!(
+            windows.iter().map(|w| w.total).collect::<Vec<_>>(),
+            [10, 10, 5]
+        );
+        assert!(windows[1].samples.iter().all(|it| (10..20).contains(it)));
+        assert_eq!(windows[2].index, 2);
+    }
+
+    #[test]
+    fn every_minute_over_a_channel() {
+        let (tx, rx) = mpsc::channel();
+        let mut sampler =
+            RotatingSampler::new(3, Rotation::Every(Duration::from_secs(60)), move |w| {
+                let _ = tx.send(w);
+            });
+
+        let start = Instant::now();
+        for (it, secs) in [(1, 0), (2, 30), (3, 61), (4, 200)] {
+            let _ = sampler.sample_at(it, start + Duration::from_secs(secs));
+        }
+        sampler.flush();
+
+        let windows: Vec<_> = rx.iter().map(|w| w.samples.len()).collect();
+        assert_eq!(windows, [2, 1, 1]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Sampling the requests of a running service for offline debugging: record
+//! roughly one in every `N` requests into a bounded reservoir, get notified of
+//! each sampled one, and serve a snapshot of the reservoir from an endpoint.
+//!
+//! With the `tower` feature, a [`SamplingLayer`] records requests of any tower
+//! based server, like axum:
+//!
+//! ```ignore
+//! let sampler = Arc::new(RequestSampler::new(100, 50).on_sampled(|r: &String| {
+//!     tracing::info!(request = %r, "sampled");
+//! }));
+//!
+//! let snapshot = Arc::clone(&sampler);
+//! let app = Router::new()
+//!     .route("/debug/samples", get(move || async move { Json(snapshot.snapshot()) }))
+//!     .layer(SamplingLayer::new(sampler, |req: &Request<Body>| req.uri().to_string()));
+//! ```
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng};
+
+use crate::{Reservoir, SharedReservoir};
+
+type Hook<T> = Box<dyn Fn(&T) + Send + Sync>;
+
+/// A `RequestSampler` lets one in every `every` recorded requests (at random)
+/// into a [`SharedReservoir`] of `k` requests.
+pub struct RequestSampler<T> {
+    every: u64,
+    seen: AtomicU64,
+    reservoir: SharedReservoir<T>,
+    hooks: Vec<Hook<T>>,
+}
+
+impl<T: Clone> RequestSampler<T> {
+    /// `every` of 0 is treated as 1, that is every request is a candidate.
+    pub fn new(k: usize, every: u64) -> Self {
+        Self {
+            every: every.max(1),
+            seen: AtomicU64::new(0),
+            reservoir: SharedReservoir::with_capacity(k),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Adds a hook called with every request that makes it into the
+    /// reservoir, right when it does.
+    pub fn on_sampled(mut self, hook: impl Fn(&T) + Send + Sync + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Records a request, returning whether it was sampled.
+    pub fn record(&self, it: T) -> bool {
+        self.seen.fetch_add(1, Ordering::Relaxed);
+        if self.every > 1 && thread_rng().gen_range(0..self.every) != 0 {
+            return false;
+        }
+
+        // 只复制被留下的请求
+        match self.reservoir.sample_held(it) {
+            Some(kept) => {
+                for hook in &self.hooks {
+                    hook(&kept);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How many requests have been recorded, sampled or not.
+    pub fn seen(&self) -> u64 {
+        self.seen.load(Ordering::Relaxed)
+    }
+
+    /// The requests currently in the reservoir, for serving from an endpoint.
+    pub fn snapshot(&self) -> Vec<T> {
+        self.reservoir.samples().into_iter().flatten().collect()
+    }
+
+    pub fn into_reservoir(self) -> Reservoir<T, StdRng> {
+        self.reservoir.into_inner()
+    }
+}
+
+#[cfg(feature = "tower")]
+pub use self::layer::{SamplingLayer, SamplingService};
+
+#[cfg(feature = "tower")]
+mod layer {
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use super::*;
+
+    /// A tower layer recording every request passing through into a
+    /// [`RequestSampler`], as extracted by `extract`.
+    pub struct SamplingLayer<T, F> {
+        sampler: Arc<RequestSampler<T>>,
+        extract: F,
+    }
+
+    impl<T, F: Clone> Clone for SamplingLayer<T, F> {
+        fn clone(&self) -> Self {
+            Self {
+                sampler: Arc::clone(&self.sampler),
+                extract: self.extract.clone(),
+            }
+        }
+    }
+
+    impl<T, F> SamplingLayer<T, F> {
+        pub fn new(sampler: Arc<RequestSampler<T>>, extract: F) -> Self {
+            Self { sampler, extract }
+        }
+    }
+
+    impl<S, T, F: Clone> Layer<S> for SamplingLayer<T, F> {
+        type Service = SamplingService<S, T, F>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            SamplingService {
+                inner,
+                sampler: Arc::clone(&self.sampler),
+                extract: self.extract.clone(),
+            }
+        }
+    }
+
+    pub struct SamplingService<S, T, F> {
+        inner: S,
+        sampler: Arc<RequestSampler<T>>,
+        extract: F,
+    }
+
+    impl<S: Clone, T, F: Clone> Clone for SamplingService<S, T, F> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+                sampler: Arc::clone(&self.sampler),
+                extract: self.extract.clone(),
+            }
+        }
+    }
+
+    impl<S, T, F, Req> Service<Req> for SamplingService<S, T, F>
+    where
+        S: Service<Req>,
+        T: Clone,
+        F: Fn(&Req) -> T,
+    {
+        type Response = S::Response;
+        type Error = S::Error;
+        type Future = S::Future;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, req: Req) -> Self::Future {
+            let _ = self.sampler.record((self.extract)(&req));
+            self.inner.call(req)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[test]
+    fn hooks_see_sampled_requests() {
+        let hooked = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hooked);
+        let sampler = RequestSampler::new(3, 1).on_sampled(move |_: &String| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let mut sampled = 0;
+        for i in 0..100 {
+            if sampler.record(format!("GET /item/{}", i)) {
+                sampled += 1;
+            }
+        }
+
+        assert_eq!(sampler.seen(), 100);
+        assert_eq!(hooked.load(Ordering::Relaxed), sampled);
+        assert_eq!(sampler.snapshot().len(), 3);
+    }
+
+    #[test]
+    fn only_sampled_requests_are_cloned() {
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        struct Request;
+        impl Clone for Request {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                Request
+            }
+        }
+
+        let sampler = RequestSampler::new(3, 1);
+        let sampled = (0..1000).filter(|_| sampler.record(Request)).count();
+        assert_eq!(CLONES.load(Ordering::Relaxed), sampled);
+    }
+
+    #[test]
+    fn one_in_n() {
+        let sampler = RequestSampler::new(10_000, 10);
+        let sampled = (0..10_000).filter(|&i| sampler.record(i)).count();
+        assert!((700..1300).contains(&sampled), "{}", sampled);
+    }
+
+    #[cfg(feature = "tower")]
+    #[test]
+    fn layer_records_requests() {
+        use std::convert::Infallible;
+        use std::future::Ready;
+        use tower_layer::Layer;
+        use tower_service::Service;
+
+        struct Echo;
+        impl Service<u32> for Echo {
+            type Response = u32;
+            type Error = Infallible;
+            type Future = Ready<Result<u32, Infallible>>;
+
+            fn poll_ready(
+                &mut self,
+                _: &mut std::task::Context<'_>,
+            ) -> std::task::Poll<Result<(), Infallible>> {
+                std::task::Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: u32) -> Self::Future {
+                std::future::ready(Ok(req))
+            }
+        }
+
+        let sampler = Arc::new(RequestSampler::new(2, 1));
+        let mut service = SamplingLayer::new(Arc::clone(&sampler), |req: &u32| *req).layer(Echo);
+        for req in 0..5 {
+            assert_eq!(service.call(req).into_inner(), Ok(req));
+        }
+
+        assert_eq!(sampler.seen(), 5);
+        assert_eq!(sampler.snapshot().len(), 2);
+    }
+}
@@ -0,0 +1,127 @@
+//! A reservoir which can be shared between threads, for example in the state
+//! of a web service, without any locking on the caller side.
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{Inspectable, Lockable, Reservoir, StreamSampler};
+
+/// A `SharedReservoir` samples through `&self`, so it can live in an `Arc`.
+/// Every call takes an internal lock for as short as the call itself.
+///
+/// A panic in another thread while holding the lock does not poison the
+/// reservoir: the sample stays valid, since no call leaves it half updated.
+pub struct SharedReservoir<T, R = StdRng> {
+    inner: Mutex<Reservoir<T, R>>,
+}
+
+impl<T: Clone> SharedReservoir<T> {
+    /// Seeds a fresh `StdRng` from the OS, since the thread local RNG of
+    /// `Reservoir::with_capacity` cannot move between threads.
+    pub fn with_capacity(n: usize) -> Self {
+        Self::new(Reservoir::with_rng(n, StdRng::from_entropy()))
+    }
+}
+
+impl<T, R: Rng> SharedReservoir<T, R> {
+    pub fn new(reservoir: Reservoir<T, R>) -> Self {
+        Self {
+            inner: Mutex::new(reservoir),
+        }
+    }
+
+    fn guard(&self) -> MutexGuard<'_, Reservoir<T, R>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The same as [`StreamSampler::sample`], through a shared reference.
+    pub fn sample(&self, it: T) -> (u64, u64, Option<T>) {
+        self.guard().sample(it)
+    }
+
+    /// Samples `it` and hands back a copy of it if the reservoir holds it,
+    /// under one lock, so only held items are cloned.
+    pub fn sample_held(&self, it: T) -> Option<T>
+    where
+        T: Clone,
+    {
+        let mut reservoir = self.guard();
+        let (r, _, _) = reservoir.sample(it);
+        if !reservoir.is_held(&r) {
+            return None;
+        }
+        // 被留下的条目在第 r 个槽位
+        reservoir.samples().get(r as usize - 1).cloned().flatten()
+    }
+
+    /// A copy of the currently held items.
+    pub fn samples(&self) -> Vec<Option<T>>
+    where
+        T: Clone,
+    {
+        self.guard().samples().to_vec()
+    }
+
+    /// Runs `f` on the reservoir while holding the lock, for looking at the
+    /// held items without copying them.
+    pub fn with<U>(&self, f: impl FnOnce(&Reservoir<T, R>) -> U) -> U {
+        f(&self.guard())
+    }
+
+    pub fn into_inner(self) -> Reservoir<T, R> {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn lock(self) -> Vec<Option<T>> {
+        self.into_inner().lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn sample_from_threads() {
+        let shared = Arc::new(SharedReservoir::<u32>::with_capacity(10));
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for i in 0..250 {
+                        let _ = shared.sample(t * 1000 + i);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(shared.samples().iter().flatten().count(), 10);
+        assert_eq!(shared.with(|r| r.samples().len()), 10);
+
+        let shared = Arc::try_unwrap(shared).ok().unwrap();
+        assert_eq!(shared.lock().iter().flatten().count(), 10);
+    }
+
+    #[test]
+    fn sample_held_copies_held_items() {
+        let shared = SharedReservoir::new(Reservoir::<u32, _>::deterministic(3, 27));
+        for it in 0..100 {
+            match shared.sample_held(it) {
+                Some(held) => {
+                    assert_eq!(held, it);
+                    assert!(shared.samples().contains(&Some(it)));
+                }
+                None => assert!(!shared.samples().contains(&Some(it))),
+            }
+        }
+    }
+}
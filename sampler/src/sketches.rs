@@ -0,0 +1,429 @@
+//! Companion sketches which are fed by the same stream pass as a sampler, for
+//! the questions a uniform sample alone answers poorly, like percentiles.
+//!
+//! Enabled by the `sketches` feature.
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+use rand::rngs::ThreadRng;
+use rand::Rng;
+
+use crate::split::KeyHasher;
+use crate::{Inspectable, Lockable, Reservoir, StreamSampler};
+
+/// A Greenwald-Khanna quantile sketch. Every answered quantile is within
+/// `epsilon * n` ranks of the exact one, while only `O(log(epsilon * n) /
+/// epsilon)` values are kept.
+#[derive(Clone, Debug)]
+pub struct GkSketch<V> {
+    epsilon: f64,
+    count: usize,
+    // (value, g, delta): `g` is the rank gap to the previous tuple, `delta` the
+    // uncertainty of the rank of `value`.
+    tuples: Vec<(V, usize, usize)>,
+}
+
+impl<V: PartialOrd> GkSketch<V> {
+    /// `epsilon` is clamped into `(0, 1]`.
+    pub fn new(epsilon: f64) -> Self {
+        let epsilon = if epsilon > 0.0 { epsilon.min(1.0) } else { 1.0 };
+
+        Self {
+            epsilon,
+            count: 0,
+            tuples: Vec::new(),
+        }
+    }
+
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    /// How many values have been inserted so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    fn band(&self) -> usize {
+        (2.0 * self.epsilon * self.count as f64).floor() as usize
+    }
+
+    pub fn insert(&mut self, v: V) {
+        let idx = self.tuples.partition_point(|(t, _, _)| *t <= v);
+        let delta = if idx == 0 || idx == self.tuples.len() {
+            0
+        } else {
+            self.band().saturating_sub(1)
+        };
+        self.tuples.insert(idx, (v, 1, delta));
+        self.count += 1;
+
+        let period = (1.0 / (2.0 * self.epsilon)).floor().max(1.0) as usize;
+        if self.count.is_multiple_of(period) {
+            self.compress();
+        }
+    }
+
+    fn compress(&mut self) {
+        let band = self.band();
+        let mut i = self.tuples.len().saturating_sub(2);
+        while i >= 1 {
+            let (_, g, _) = self.tuples[i];
+            let (_, next_g, next_delta) = self.tuples[i + 1];
+            if g + next_g + next_delta <= band {
+                self.tuples[i + 1].1 += g;
+                self.tuples.remove(i);
+            }
+            i -= 1;
+        }
+    }
+
+    /// The value at quantile `phi` (clamped into `[0, 1]`), `None` before any
+    /// value is inserted.
+    pub fn quantile(&self, phi: f64) -> Option<&V> {
+        let phi = phi.clamp(0.0, 1.0);
+        let rank = (phi * self.count as f64).ceil();
+        let bound = rank + self.epsilon * self.count as f64;
+
+        let mut rmin = 0;
+        let mut prev = self.tuples.first().map(|(v, _, _)| v);
+        for (v, g, delta) in &self.tuples {
+            rmin += g;
+            if (rmin + delta) as f64 > bound {
+                return prev;
+            }
+            prev = Some(v);
+        }
+
+        prev
+    }
+}
+
+/// A `StreamSummarizer` keeps a uniform sample of the items and a quantile
+/// sketch of a value derived from each of them, in one pass.
+pub struct StreamSummarizer<T, V, F, R = ThreadRng> {
+    reservoir: Reservoir<T, R>,
+    sketch: GkSketch<V>,
+    value: F,
+}
+
+impl<T, V, F> StreamSummarizer<T, V, F>
+where
+    T: Clone,
+    V: PartialOrd,
+    F: Fn(&T) -> V,
+{
+    pub fn new(k: usize, epsilon: f64, value: F) -> Self {
+        Self::with_reservoir(Reservoir::with_capacity(k), epsilon, value)
+    }
+}
+
+impl<T, V, F, R> StreamSummarizer<T, V, F, R>
+where
+    V: PartialOrd,
+    F: Fn(&T) -> V,
+    R: Rng,
+{
+    pub fn with_reservoir(reservoir: Reservoir<T, R>, epsilon: f64, value: F) -> Self {
+        Self {
+            reservoir,
+            sketch: GkSketch::new(epsilon),
+            value,
+        }
+    }
+
+    pub fn sketch(&self) -> &GkSketch<V> {
+        &self.sketch
+    }
+
+    pub fn quantile(&self, phi: f64) -> Option<&V> {
+        self.sketch.quantile(phi)
+    }
+
+    /// Ends the pass, handing out both the locked sample and the sketch.
+    pub fn finish(self) -> (Vec<Option<T>>, GkSketch<V>) {
+        (self.reservoir.lock(), self.sketch)
+    }
+}
+
+impl<T, V, F, R> StreamSampler for StreamSummarizer<T, V, F, R>
+where
+    V: PartialOrd,
+    F: Fn(&T) -> V,
+    R: Rng,
+{
+    type Item = T;
+    type Draw = u64;
+
+    fn sample(&mut self, it: Self::Item) -> (u64, u64, Option<Self::Item>) {
+        self.sketch.insert((self.value)(&it));
+        self.reservoir.sample(it)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        self.reservoir.is_held(draw)
+    }
+}
+
+impl<T, V, F, R> Inspectable for StreamSummarizer<T, V, F, R>
+where
+    V: PartialOrd,
+    F: Fn(&T) -> V,
+    R: Rng,
+{
+    fn samples(&self) -> &[Option<Self::Item>] {
+        self.reservoir.samples()
+    }
+}
+
+impl<T, V, F, R> Lockable for StreamSummarizer<T, V, F, R>
+where
+    V: PartialOrd,
+    F: Fn(&T) -> V,
+    R: Rng,
+{
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        self.reservoir.lock()
+    }
+}
+
+/// A HyperLogLog distinct count estimator. With precision `p` it keeps `2^p`
+/// one-byte registers, and its relative standard error is about
+/// `1.04 / sqrt(2^p)`.
+#[derive(Clone, Debug)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    /// Precision 14: 16 KiB of registers, about 0.8% standard error.
+    fn default() -> Self {
+        Self::new(14)
+    }
+}
+
+impl HyperLogLog {
+    /// `precision` is clamped into `4..=16`.
+    pub fn new(precision: u8) -> Self {
+        let precision = precision.clamp(4, 16);
+
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Items are hashed by the fixed hasher of
+    /// [`Splitter::hash_by`](crate::Splitter::hash_by), so the same items fill
+    /// the same registers on every platform and Rust version.
+    pub fn insert<H: Hash + ?Sized>(&mut self, it: &H) {
+        let hash = BuildHasherDefault::<KeyHasher>::default().hash_one(it);
+
+        let p = u32::from(self.precision);
+        let idx = (hash >> (64 - p)) as usize;
+        let rest = (hash << p) | (1 << (p - 1));
+        let rho = rest.leading_zeros() as u8 + 1;
+        if let Some(reg) = self.registers.get_mut(idx) {
+            *reg = (*reg).max(rho);
+        }
+    }
+
+    /// Estimated count of distinct items inserted so far.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&r| 2f64.powi(-i32::from(r)))
+            .sum();
+        let raw = alpha * m * m / sum;
+
+        // 小基数时用线性计数修正
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
+
+/// A sampler that also feeds every passing item into a [`HyperLogLog`], so one
+/// pass yields both the sample and an estimate of the distinct items seen.
+pub struct WithCardinality<S> {
+    sampler: S,
+    hll: HyperLogLog,
+}
+
+impl<S> WithCardinality<S>
+where
+    S: StreamSampler,
+    S::Item: Hash,
+{
+    pub fn new(sampler: S, hll: HyperLogLog) -> Self {
+        Self { sampler, hll }
+    }
+
+    pub fn sampler(&self) -> &S {
+        &self.sampler
+    }
+
+    pub fn estimate(&self) -> f64 {
+        self.hll.estimate()
+    }
+}
+
+impl<S> WithCardinality<S>
+where
+    S: Lockable,
+    S::Item: Hash,
+{
+    /// Ends the pass, handing out both the locked sample and the estimator.
+    pub fn finish(self) -> (Vec<Option<S::Item>>, HyperLogLog) {
+        (self.sampler.lock(), self.hll)
+    }
+}
+
+impl<S> StreamSampler for WithCardinality<S>
+where
+    S: StreamSampler,
+    S::Item: Hash,
+{
+    type Item = S::Item;
+    type Draw = S::Draw;
+
+    fn sample(&mut self, it: Self::Item) -> (S::Draw, u64, Option<Self::Item>) {
+        self.hll.insert(&it);
+        self.sampler.sample(it)
+    }
+
+    fn is_held(&self, draw: &S::Draw) -> bool {
+        self.sampler.is_held(draw)
+    }
+}
+
+impl<S> Inspectable for WithCardinality<S>
+where
+    S: Inspectable,
+    S::Item: Hash,
+{
+    fn samples(&self) -> &[Option<Self::Item>] {
+        self.sampler.samples()
+    }
+}
+
+impl<S> Lockable for WithCardinality<S>
+where
+    S: Lockable,
+    S::Item: Hash,
+{
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        self.sampler.lock()
+    }
+}
+
+impl<T: Hash, R: Rng> Reservoir<T, R> {
+    /// Attaches a default [`HyperLogLog`] to the reservoir.
+    pub fn with_cardinality_estimator(self) -> WithCardinality<Self> {
+        WithCardinality::new(self, HyperLogLog::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_sketch() {
+        let sketch = GkSketch::<u32>::new(0.01);
+        assert_eq!(sketch.quantile(0.5), None);
+    }
+
+    #[test]
+    fn quantiles_within_error() {
+        let epsilon = 0.01;
+        let n = 10_000;
+        let mut sketch = GkSketch::new(epsilon);
+        // 乱序插入
+        for i in 0..n {
+            sketch.insert((i * 7919) % n);
+        }
+
+        for &phi in &[0.0, 0.1, 0.5, 0.9, 0.99, 1.0] {
+            let got = *sketch.quantile(phi).unwrap() as f64;
+            let exact = phi * n as f64;
+            assert!(
+                (got - exact).abs() <= 2.0 * epsilon * n as f64,
+                "{}: {}",
+                phi,
+                got
+            );
+        }
+        assert!(sketch.tuples.len() < n / 10);
+    }
+
+    #[test]
+    fn summarize() {
+        let mut summarizer = StreamSummarizer::new(5, 0.05, |it: &u32| *it);
+        for it in 1..=100 {
+            let _ = summarizer.sample(it);
+        }
+
+        let median = *summarizer.quantile(0.5).unwrap();
+        assert!((45..=55).contains(&median));
+
+        let (samples, sketch) = summarizer.finish();
+        assert_eq!(samples.iter().flatten().count(), 5);
+        assert_eq!(sketch.count(), 100);
+    }
+
+    #[test]
+    fn hll_small_and_large() {
+        let mut hll = HyperLogLog::default();
+        for it in 0..10u32 {
+            hll.insert(&it);
+            hll.insert(&it);
+        }
+        assert!((hll.estimate() - 10.0).abs() < 1.0);
+
+        let mut hll = HyperLogLog::new(12);
+        for it in 0..100_000u64 {
+            hll.insert(&(it % 50_000));
+        }
+        let err = (hll.estimate() - 50_000.0).abs() / 50_000.0;
+        assert!(err < 0.05, "{}", hll.estimate());
+    }
+
+    #[test]
+    fn hll_registers_are_fixed() {
+        // 0u32 的哈希是 0x6683_21fe_5c9f_6e52：高 4 位选第 6 个寄存器
+        let mut hll = HyperLogLog::new(4);
+        hll.insert(&0u32);
+        let mut registers = [0; 16];
+        registers[6] = 2;
+        assert_eq!(hll.registers, registers);
+    }
+
+    #[test]
+    fn reservoir_with_cardinality() {
+        let mut sampler = Reservoir::<u32>::with_capacity(3).with_cardinality_estimator();
+        for it in 0..1000 {
+            let _ = sampler.sample(it % 100);
+        }
+
+        let (samples, hll) = sampler.finish();
+        assert_eq!(samples.iter().flatten().count(), 3);
+        assert!((hll.estimate() - 100.0).abs() < 5.0);
+    }
+}
@@ -0,0 +1,220 @@
+//! A reservoir for the many tiny samples of tracing and the like, held inline
+//! instead of on the heap.
+use std::iter::{Flatten, Take};
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::{self, Deterministic};
+use crate::store::SampleStore;
+use crate::weights::{uniform, LockWithWeights, Sampled};
+use crate::{algorithm, Inspectable, Lockable, StreamSampler};
+
+const INLINE: usize = 16;
+
+enum Pool<T> {
+    Inline([Option<T>; INLINE], usize),
+    Heap(Vec<Option<T>>),
+}
+
+impl<T> Pool<T> {
+    fn new(n: usize) -> Self {
+        if n <= INLINE {
+            Pool::Inline(std::array::from_fn(|_| None), n)
+        } else {
+            Pool::Heap(std::iter::repeat_with(|| None).take(n).collect())
+        }
+    }
+
+    fn slots(&self) -> &[Option<T>] {
+        match self {
+            Pool::Inline(slots, n) => &slots[..*n],
+            Pool::Heap(slots) => slots,
+        }
+    }
+
+    fn slots_mut(&mut self) -> &mut [Option<T>] {
+        match self {
+            Pool::Inline(slots, n) => &mut slots[..*n],
+            Pool::Heap(slots) => slots,
+        }
+    }
+}
+
+impl<T> SampleStore<T> for Pool<T> {
+    type IntoItems = Items<T>;
+
+    fn capacity(&self) -> usize {
+        self.slots().len()
+    }
+
+    fn set(&mut self, idx: usize, it: T) -> Option<T> {
+        match self.slots_mut().get_mut(idx) {
+            Some(slot) => slot.replace(it),
+            None => Some(it),
+        }
+    }
+
+    fn into_items(self) -> Items<T> {
+        match self {
+            Pool::Inline(slots, n) => {
+                Items::Inline(IntoIterator::into_iter(slots).take(n).flatten())
+            }
+            Pool::Heap(slots) => Items::Heap(slots.into_iter().flatten()),
+        }
+    }
+}
+
+enum Items<T> {
+    Inline(Flatten<Take<std::array::IntoIter<Option<T>, INLINE>>>),
+    Heap(Flatten<std::vec::IntoIter<Option<T>>>),
+}
+
+impl<T> Iterator for Items<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self {
+            Items::Inline(items) => items.next(),
+            Items::Heap(items) => items.next(),
+        }
+    }
+}
+
+/// A `SmallReservoir` samples by Algorithm R exactly like a
+/// [`Reservoir`](crate::Reservoir) of the same capacity and rng, but holds a
+/// pool of up to [`SmallReservoir::INLINE`] slots inline, so making one does
+/// not allocate, while a larger pool goes on the heap. It suits making
+/// thousands of short lived samplers a second, where the capacity is only
+/// known at run time; see [`ArrayReservoir`](crate::ArrayReservoir) when it
+/// is known at compile time.
+pub struct SmallReservoir<T, R = ThreadRng> {
+    total: u64,
+    pool: Pool<T>,
+    rng: R,
+}
+
+impl<T, R> SmallReservoir<T, R> {
+    /// How many slots are held inline.
+    pub const INLINE: usize = INLINE;
+}
+
+impl<T> SmallReservoir<T> {
+    pub fn with_capacity(n: usize) -> Self {
+        Self::with_rng(n, thread_rng())
+    }
+}
+
+impl<T> SmallReservoir<T, Deterministic> {
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(n: usize, seed: u64) -> Self {
+        Self::with_rng(n, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, R: Rng> SmallReservoir<T, R> {
+    pub fn with_rng(n: usize, rng: R) -> Self {
+        Self {
+            total: 0,
+            pool: Pool::new(n),
+            rng,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Whether the pool is held inline, that is the capacity is no more than
+    /// [`SmallReservoir::INLINE`].
+    pub fn is_inline(&self) -> bool {
+        matches!(self.pool, Pool::Inline(..))
+    }
+
+    fn shuffled(mut self) -> Pool<T> {
+        rng::compact_and_shuffle(self.pool.slots_mut(), &mut self.rng);
+        self.pool
+    }
+}
+
+impl<T, R: Rng> StreamSampler for SmallReservoir<T, R> {
+    type Item = T;
+    type Draw = u64;
+
+    fn sample(&mut self, it: Self::Item) -> (u64, u64, Option<Self::Item>) {
+        self.total += 1;
+
+        if self.pool.capacity() == 0 {
+            return (0, self.total, None);
+        }
+
+        let (r, replaced) = algorithm::r(&mut self.pool, self.total, it, &mut self.rng);
+        (r, self.total, replaced)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        (1..=self.pool.capacity() as u64).contains(draw)
+    }
+}
+
+impl<T, R: Rng> Inspectable for SmallReservoir<T, R> {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        self.pool.slots()
+    }
+}
+
+impl<T, R: Rng> Lockable for SmallReservoir<T, R> {
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        match self.shuffled() {
+            Pool::Inline(slots, n) => IntoIterator::into_iter(slots).take(n).collect(),
+            Pool::Heap(slots) => slots,
+        }
+    }
+
+    /// Hands out the held items without allocating.
+    fn lock_iter(self) -> impl Iterator<Item = Self::Item> {
+        self.shuffled().into_items()
+    }
+}
+
+impl<T, R: Rng> LockWithWeights for SmallReservoir<T, R> {
+    type Item = T;
+
+    fn lock_with_weights(self) -> Vec<Sampled<T>> {
+        let probability = uniform(self.pool.capacity(), self.total);
+        self.lock_iter()
+            .map(|item| Sampled { item, probability })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reservoir;
+
+    #[test]
+    fn same_as_reservoir() {
+        for k in [0, 1, 4, INLINE, INLINE + 1, 40] {
+            let mut small = SmallReservoir::<u32, Deterministic>::deterministic(k, 8);
+            let mut vec = Reservoir::<u32, Deterministic>::deterministic(k, 8);
+            assert_eq!(small.is_inline(), k <= INLINE);
+            for it in 0..200 {
+                assert_eq!(small.sample(it), vec.sample(it));
+            }
+            assert_eq!(small.samples(), vec.samples());
+            assert_eq!(small.total(), 200);
+
+            assert_eq!(small.lock(), vec.lock());
+        }
+    }
+
+    #[test]
+    fn lock_iter_takes_held_items() {
+        let mut small = SmallReservoir::<u32, Deterministic>::deterministic(8, 1);
+        let _ = small.sample_all(0..5);
+        let mut held: Vec<u32> = small.lock_iter().collect();
+        held.sort_unstable();
+        assert_eq!(held, vec![0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,477 @@
+//! A stable, versioned picture of a live sampler, for operational tooling to
+//! fetch and display across services, and for long-lived jobs to checkpoint
+//! a [`Reservoir`] and pick it up again. Enabled by the `serde` feature.
+//!
+//! Stored snapshots are read back as a [`VersionedSnapshot`], which takes
+//! every version so far, and `migrate` brings any of them up to the current
+//! [`SamplerSnapshot`].
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Algorithm, Marks, Reservoir};
+
+/// The version of the [`SamplerSnapshot`] schema. It changes when a field
+/// changes meaning or goes away, and when a field is added which an older
+/// reader must not silently go without. Other new fields come with serde
+/// defaults and keep the version, like `pins` of [`ReservoirState`].
+///
+/// Version 2 added the [`ReservoirState`] a checkpoint needs: a reader of
+/// version 1 would drop it and sample on from the wrong state.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// What a [`Reservoir`] needs besides its pool to sample on where it left
+/// off, except for the rng.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReservoirState {
+    /// How many items algorithm L or X still skips.
+    pub skip: u64,
+    /// The `w` of algorithm L.
+    pub w: f64,
+    pub deleted: u64,
+    pub pending_held: u64,
+    pub pending_unheld: u64,
+    pub locked: bool,
+    /// The pinned slots, in ascending order, see [`Reservoir::pin`].
+    #[serde(default)]
+    pub pins: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SamplerSnapshot<T> {
+    pub version: u32,
+    /// Which sampler took the snapshot, like `"R"` for [`Reservoir`].
+    pub algorithm: String,
+    pub capacity: usize,
+    /// How many items have been passed through.
+    pub total: u64,
+    /// The slots, in slot order.
+    pub samples: Vec<Option<T>>,
+    /// Whatever else the algorithm needs to be understood, by name.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    /// Only taken of a [`Reservoir`].
+    #[serde(default)]
+    pub state: Option<ReservoirState>,
+}
+
+impl<T> SamplerSnapshot<T> {
+    pub fn new(algorithm: &str, total: u64, samples: Vec<Option<T>>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            algorithm: algorithm.into(),
+            capacity: samples.len(),
+            total,
+            samples,
+            parameters: BTreeMap::new(),
+            state: None,
+        }
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: impl ToString) -> Self {
+        self.parameters.insert(name.into(), value.to_string());
+        self
+    }
+}
+
+/// A sampler which can take a [`SamplerSnapshot`] of itself.
+pub trait Snapshot {
+    type Item;
+
+    fn snapshot(&self) -> SamplerSnapshot<Self::Item>;
+}
+
+impl<T: Clone, R> Snapshot for Reservoir<T, R> {
+    type Item = T;
+
+    fn snapshot(&self) -> SamplerSnapshot<T> {
+        let algorithm = match self.algorithm {
+            Algorithm::R => "R",
+            Algorithm::L => "L",
+            Algorithm::X => "X",
+        };
+        let mut snapshot = SamplerSnapshot::new(algorithm, self.total, self.pool.clone());
+        snapshot.state = Some(ReservoirState {
+            skip: self.skip,
+            w: self.w,
+            deleted: self.deleted,
+            pending_held: self.pending_held,
+            pending_unheld: self.pending_unheld,
+            locked: self.locked,
+            pins: self.pins.clone(),
+        });
+        snapshot
+    }
+}
+
+/// The first schema, which had no [`ReservoirState`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotV1<T> {
+    pub version: u32,
+    pub algorithm: String,
+    pub capacity: usize,
+    pub total: u64,
+    pub samples: Vec<Option<T>>,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl<T> SnapshotV1<T> {
+    pub fn migrate(self) -> SamplerSnapshot<T> {
+        SamplerSnapshot {
+            version: SNAPSHOT_VERSION,
+            algorithm: self.algorithm,
+            capacity: self.capacity,
+            total: self.total,
+            samples: self.samples,
+            parameters: self.parameters,
+            state: None,
+        }
+    }
+}
+
+/// A snapshot of any version so far, told apart by its `version` field. A
+/// version from a newer crate is a deserialization error.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    untagged,
+    try_from = "AnyVersion<T>",
+    bound(deserialize = "T: Deserialize<'de>")
+)]
+pub enum VersionedSnapshot<T> {
+    V1(SnapshotV1<T>),
+    V2(SamplerSnapshot<T>),
+}
+
+impl<T> VersionedSnapshot<T> {
+    /// Brings the snapshot up to the current version.
+    pub fn migrate(self) -> SamplerSnapshot<T> {
+        match self {
+            Self::V1(v1) => v1.migrate(),
+            Self::V2(v2) => v2,
+        }
+    }
+}
+
+/// Every field of every version, to read one of them before knowing which.
+#[derive(Deserialize)]
+struct AnyVersion<T> {
+    version: u32,
+    algorithm: String,
+    capacity: usize,
+    total: u64,
+    samples: Vec<Option<T>>,
+    #[serde(default)]
+    parameters: BTreeMap<String, String>,
+    #[serde(default)]
+    state: Option<ReservoirState>,
+}
+
+struct UnknownVersion(u32);
+
+impl fmt::Display for UnknownVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown snapshot version {}", self.0)
+    }
+}
+
+impl<T> TryFrom<AnyVersion<T>> for VersionedSnapshot<T> {
+    type Error = UnknownVersion;
+
+    fn try_from(any: AnyVersion<T>) -> Result<Self, UnknownVersion> {
+        match any.version {
+            1 => Ok(Self::V1(SnapshotV1 {
+                version: any.version,
+                algorithm: any.algorithm,
+                capacity: any.capacity,
+                total: any.total,
+                samples: any.samples,
+                parameters: any.parameters,
+            })),
+            2 => Ok(Self::V2(SamplerSnapshot {
+                version: any.version,
+                algorithm: any.algorithm,
+                capacity: any.capacity,
+                total: any.total,
+                samples: any.samples,
+                parameters: any.parameters,
+                state: any.state,
+            })),
+            v => Err(UnknownVersion(v)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SnapshotError {
+    /// The snapshot was taken of another sampler than a [`Reservoir`], like
+    /// `"tickets"`.
+    NotAReservoir(String),
+    /// `capacity` is not the count of `samples`.
+    WrongCapacity,
+    /// More items are held than `total` counts.
+    MoreHeldThanSeen,
+    /// More deletions are pending than were made.
+    PendingDeletions,
+    /// A pin is out of order, or on a slot holding no item.
+    InvalidPin(usize),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAReservoir(algorithm) => {
+                write!(f, "a snapshot of {:?} is not of a reservoir", algorithm)
+            }
+            Self::WrongCapacity => write!(f, "the capacity is not the count of the slots"),
+            Self::MoreHeldThanSeen => write!(f, "more items are held than have passed through"),
+            Self::PendingDeletions => write!(f, "more deletions are pending than were made"),
+            Self::InvalidPin(slot) => write!(f, "slot {} can not be pinned", slot),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl<T, R> Reservoir<T, R> {
+    /// Picks up sampling from a snapshot, with `rng`, which is all a
+    /// snapshot leaves out. A snapshot without its [`ReservoirState`], like
+    /// one migrated from version 1, samples on by [`Algorithm::R`], the only
+    /// algorithm which needs no state.
+    ///
+    /// A snapshot no reservoir could have taken is rejected, rather than
+    /// sampled on into a reservoir which is not uniform.
+    pub fn from_snapshot(snapshot: SamplerSnapshot<T>, rng: R) -> Result<Self, SnapshotError> {
+        let algorithm = match snapshot.algorithm.as_str() {
+            "R" => Algorithm::R,
+            "L" => Algorithm::L,
+            "X" => Algorithm::X,
+            _ => return Err(SnapshotError::NotAReservoir(snapshot.algorithm)),
+        };
+        let (algorithm, state) = match snapshot.state {
+            Some(state) => (algorithm, state),
+            None => (
+                Algorithm::R,
+                ReservoirState {
+                    skip: 0,
+                    w: 1.0,
+                    deleted: 0,
+                    pending_held: 0,
+                    pending_unheld: 0,
+                    locked: false,
+                    pins: Vec::new(),
+                },
+            ),
+        };
+        if snapshot.capacity != snapshot.samples.len() {
+            return Err(SnapshotError::WrongCapacity);
+        }
+        if snapshot.samples.iter().flatten().count() as u64 > snapshot.total {
+            return Err(SnapshotError::MoreHeldThanSeen);
+        }
+        if state.pending_held.saturating_add(state.pending_unheld) > state.deleted {
+            return Err(SnapshotError::PendingDeletions);
+        }
+        let mut last = None;
+        for &pin in &state.pins {
+            if last >= Some(pin) || !matches!(snapshot.samples.get(pin), Some(Some(_))) {
+                return Err(SnapshotError::InvalidPin(pin));
+            }
+            last = Some(pin);
+        }
+
+        // 每个槽位都算改过，新的副本要从 diff(0) 拿到所有槽位
+        Ok(Reservoir {
+            total: snapshot.total,
+            marks: vec![
+                Marks {
+                    stamp: 1,
+                    arrival: 0,
+                };
+                snapshot.samples.len()
+            ],
+            history: None,
+            pins: state.pins,
+            pool: snapshot.samples,
+            rng,
+            algorithm,
+            skip: state.skip,
+            w: state.w,
+            deleted: state.deleted,
+            pending_held: state.pending_held,
+            pending_unheld: state.pending_unheld,
+            locked: state.locked,
+            generation: 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Inspectable, SpaceSaving, StreamSampler, TicketReservoir};
+    use rand::thread_rng;
+
+    #[test]
+    fn reservoir_round_trip() {
+        let mut reservoir = Reservoir::<String>::with_capacity(2);
+        let _ = reservoir.sample("a".into());
+
+        let snapshot = reservoir.snapshot();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert_eq!(snapshot.capacity, 2);
+        assert_eq!(snapshot.total, 1);
+        assert_eq!(snapshot.samples, vec![Some("a".to_string()), None]);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SamplerSnapshot<String>>(&json).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn parameters() {
+        let mut tickets = TicketReservoir::<u8>::with_capacity(1);
+        let _ = tickets.sample(1, 5);
+        let snapshot = tickets.snapshot();
+        assert_eq!(snapshot.algorithm, "tickets");
+        assert_eq!(snapshot.parameters["tickets"], "5");
+
+        let mut heavy = SpaceSaving::with_capacity(2);
+        let _ = heavy.sample(1u8);
+        let _ = heavy.sample(1u8);
+        assert_eq!(heavy.snapshot().parameters["counts"], "2,0");
+    }
+
+    #[test]
+    fn older_snapshot_without_parameters() {
+        let json = r#"{"version":1,"algorithm":"R","capacity":1,"total":0,"samples":[null]}"#;
+        let snapshot: VersionedSnapshot<u8> = serde_json::from_str(json).unwrap();
+        assert!(matches!(snapshot, VersionedSnapshot::V1(_)));
+
+        let snapshot = snapshot.migrate();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert!(snapshot.parameters.is_empty());
+        assert_eq!(snapshot.state, None);
+    }
+
+    #[test]
+    fn v1_snapshots_are_still_read() {
+        let json = r#"{"version":1,"algorithm":"R","capacity":2,"total":7,
+            "samples":[4,null],"parameters":{"seed":"27"}}"#;
+        let stored: VersionedSnapshot<u32> = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            stored,
+            VersionedSnapshot::V1(SnapshotV1 {
+                version: 1,
+                algorithm: "R".into(),
+                capacity: 2,
+                total: 7,
+                samples: vec![Some(4), None],
+                parameters: vec![("seed".to_string(), "27".to_string())]
+                    .into_iter()
+                    .collect(),
+            })
+        );
+
+        let snapshot = stored.migrate();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert_eq!(snapshot.total, 7);
+        assert_eq!(snapshot.samples, vec![Some(4), None]);
+        assert_eq!(snapshot.parameters["seed"], "27");
+        assert_eq!(snapshot.state, None);
+    }
+
+    #[test]
+    fn checkpoint_and_resume() {
+        let rng = Deterministic::seed_from_u64(1);
+        let mut reservoir = Reservoir::with_rng_and_algorithm(10, rng, Algorithm::L);
+        let _ = reservoir.sample_all(0..1_000);
+        assert!(reservoir.pin(3));
+        let json = serde_json::to_string(&reservoir.snapshot()).unwrap();
+
+        // 换个版本的 crate 读回来，接着抽
+        let stored: VersionedSnapshot<u32> = serde_json::from_str(&json).unwrap();
+        let rng = Deterministic::seed_from_u64(2);
+        let mut resumed = Reservoir::from_snapshot(stored.migrate(), rng).unwrap();
+        assert_eq!(resumed, reservoir);
+        assert_eq!(resumed.diff(0).slots.len(), 10);
+        assert_eq!(resumed.pins(), &[3]);
+        let _ = resumed.sample_all(1_000..2_000);
+        assert_eq!(resumed.total(), 2_000);
+
+        let v1 = r#"{"version":1,"algorithm":"L","capacity":1,"total":5,"samples":[3]}"#;
+        let stored: VersionedSnapshot<u32> = serde_json::from_str(v1).unwrap();
+        let resumed = Reservoir::from_snapshot(stored.migrate(), thread_rng()).unwrap();
+        assert_eq!(resumed.algorithm(), Algorithm::R);
+        assert_eq!(resumed.samples(), &[Some(3)]);
+
+        let tickets = TicketReservoir::<u32>::with_capacity(1).snapshot();
+        assert_eq!(
+            Reservoir::from_snapshot(tickets, thread_rng()).err(),
+            Some(SnapshotError::NotAReservoir("tickets".into()))
+        );
+
+        let v3 = r#"{"version":3,"algorithm":"R","capacity":0,"total":0,"samples":[]}"#;
+        assert!(serde_json::from_str::<VersionedSnapshot<u32>>(v3).is_err());
+    }
+
+    #[test]
+    fn pins_are_checked() {
+        let mut reservoir = Reservoir::<u32>::with_capacity(3);
+        let _ = reservoir.sample(1);
+        let mut snapshot = reservoir.snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let without = json.replace(r#","pins":[]"#, "");
+        assert_ne!(without, json);
+        assert_eq!(
+            serde_json::from_str::<SamplerSnapshot<u32>>(&without).unwrap(),
+            snapshot
+        );
+
+        // 钉在空槽位上
+        if let Some(state) = &mut snapshot.state {
+            state.pins = vec![1];
+        }
+        assert_eq!(
+            Reservoir::from_snapshot(snapshot, thread_rng()).err(),
+            Some(SnapshotError::InvalidPin(1))
+        );
+    }
+
+    #[test]
+    fn inconsistent_snapshots_are_rejected() {
+        let mut reservoir = Reservoir::<u32>::with_capacity(3);
+        let _ = reservoir.sample_all(0..2);
+        let snapshot = reservoir.snapshot();
+        let rejected = |edit: fn(&mut SamplerSnapshot<u32>)| {
+            let mut snapshot = snapshot.clone();
+            edit(&mut snapshot);
+            Reservoir::from_snapshot(snapshot, thread_rng()).err()
+        };
+
+        assert_eq!(
+            rejected(|s| s.capacity = 4),
+            Some(SnapshotError::WrongCapacity)
+        );
+        assert_eq!(
+            rejected(|s| s.total = 1),
+            Some(SnapshotError::MoreHeldThanSeen)
+        );
+        let error = rejected(|s| {
+            if let Some(state) = &mut s.state {
+                state.pending_unheld = 1;
+            }
+        });
+        assert_eq!(error, Some(SnapshotError::PendingDeletions));
+        assert_eq!(
+            error.unwrap().to_string(),
+            "more deletions are pending than were made"
+        );
+        assert_eq!(rejected(|_| {}), None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Feeding a sampler from several sources at once, like entries from two apps
+//! with different volumes.
+use crate::StreamSampler;
+
+/// Anything items can be pulled out of one at a time. Every iterator is one.
+pub trait ItemSource {
+    type Item;
+
+    fn next_item(&mut self) -> Option<Self::Item>;
+}
+
+impl<I: Iterator> ItemSource for I {
+    type Item = I::Item;
+
+    fn next_item(&mut self) -> Option<Self::Item> {
+        self.next()
+    }
+}
+
+/// `PrioritySources` interleaves its sources by their ratios: in every round,
+/// a source of ratio `3` hands out 3 items for every 1 of a source of ratio
+/// `1`. A drained source is skipped from then on, and a source of ratio `0`
+/// only hands out items once all other sources are drained.
+///
+/// The ratios only decide the order the items come out in, never whether an
+/// item comes out: once every source is drained, as [`PrioritySources::feed`]
+/// does, a uniform sampler gave every item the same chance whatever the
+/// ratios. They only shift the odds when the stream is cut short, like by
+/// `take(n)`, which reads more of the sources of larger ratios.
+///
+/// The items of each source keep their order, so stopping the stream early
+/// leaves each class sampled uniformly from the part of it that was read.
+pub struct PrioritySources<'a, T> {
+    sources: Vec<(Box<dyn ItemSource<Item = T> + 'a>, u32)>,
+    current: usize,
+    taken: u32,
+}
+
+impl<'a, T> Default for PrioritySources<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> PrioritySources<'a, T> {
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            current: 0,
+            taken: 0,
+        }
+    }
+
+    /// Adds a source handing out `ratio` items a round, see the type docs
+    /// for what the ratio does and does not change.
+    pub fn source(mut self, source: impl ItemSource<Item = T> + 'a, ratio: u32) -> Self {
+        self.sources.push((Box::new(source), ratio));
+        self
+    }
+
+    /// Samples every item of every source, returning how many were fed.
+    /// Since nothing is left out, the ratios do not change the chances of
+    /// any item; sample `self.take(n)` to read only the first `n` items.
+    pub fn feed<S: StreamSampler<Item = T>>(self, sampler: &mut S) -> usize {
+        self.map(|it| {
+            let _ = sampler.sample(it);
+        })
+        .count()
+    }
+}
+
+impl<'a, T> Iterator for PrioritySources<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // 按比例轮流取，取空的来源直接移除
+        while !self.sources.is_empty() {
+            if self.current >= self.sources.len() {
+                self.current = 0;
+            }
+
+            let only_zero = self.sources.iter().all(|(_, r)| *r == 0);
+            let (source, ratio) = &mut self.sources[self.current];
+            if self.taken >= *ratio && !only_zero {
+                self.current += 1;
+                self.taken = 0;
+                continue;
+            }
+
+            match source.next_item() {
+                Some(it) => {
+                    self.taken += 1;
+                    return Some(it);
+                }
+                None => {
+                    let _ = self.sources.remove(self.current);
+                    self.taken = 0;
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Reservoir};
+
+    #[test]
+    fn interleaves_by_ratio() {
+        let vip = vec!["v1", "v2", "v3"];
+        let normal = vec!["n1", "n2", "n3", "n4", "n5", "n6"];
+        let mixed: Vec<_> = PrioritySources::new()
+            .source(vip.into_iter(), 1)
+            .source(normal.into_iter(), 2)
+            .collect();
+
+        assert_eq!(
+            mixed,
+            ["v1", "n1", "n2", "v2", "n3", "n4", "v3", "n5", "n6"]
+        );
+    }
+
+    #[test]
+    fn drained_and_zero_ratio() {
+        let mixed: Vec<_> = PrioritySources::new()
+            .source(0..2, 0)
+            .source(10..11, 1)
+            .source(20..24, 3)
+            .collect();
+
+        assert_eq!(mixed, [10, 20, 21, 22, 23, 0, 1]);
+    }
+
+    #[test]
+    fn feed() {
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(5, 2);
+        let fed = PrioritySources::new()
+            .source(0..100, 1)
+            .source(100..400, 3)
+            .feed(&mut reservoir);
+
+        assert_eq!(fed, 400);
+        assert_eq!(reservoir.total(), 400);
+    }
+
+    #[test]
+    fn cut_short_by_ratio() {
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(5, 2);
+        let first = PrioritySources::new()
+            .source(0..100, 1)
+            .source(100..400, 3)
+            .take(40);
+        let _ = reservoir.sample_all(first);
+
+        // 截断后，比例大的来源读得更多
+        assert_eq!(reservoir.total(), 40);
+        assert!(reservoir
+            .iter()
+            .all(|&it| it < 10 || (100..130).contains(&it)));
+    }
+}
@@ -0,0 +1,305 @@
+//! Splitting a stream into train, validation and test sets in one pass, for
+//! datasets too big to shuffle.
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::below_u64;
+use crate::Deterministic;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Partition {
+    Train,
+    Validation,
+    Test,
+}
+
+impl Partition {
+    fn index(self) -> usize {
+        match self {
+            Partition::Train => 0,
+            Partition::Validation => 1,
+            Partition::Test => 2,
+        }
+    }
+}
+
+const PARTITIONS: [Partition; 3] = [Partition::Train, Partition::Validation, Partition::Test];
+
+/// The items of every partition, see [`Splitter::into_split`].
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Split<T> {
+    pub train: Vec<T>,
+    pub validation: Vec<T>,
+    pub test: Vec<T>,
+}
+
+struct Part<T> {
+    cap: Option<usize>,
+    seen: u64,
+    items: Vec<T>,
+}
+
+type KeyHash<T> = Box<dyn Fn(&T) -> u64>;
+
+/// The hasher of [`Splitter::hash_by`]: FNV-1a, finished by the SplitMix64
+/// mix so the high bits are spread too. Unlike `DefaultHasher`, it is fixed,
+/// and it writes integers as little endian, `usize` as a `u64`, so a key
+/// hashes the same on every platform and Rust version, as long as its `Hash`
+/// writes the same.
+#[derive(Clone, Copy)]
+pub(crate) struct KeyHasher(u64);
+
+impl Default for KeyHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        let mut z = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64)
+    }
+}
+
+/// A `Splitter` assigns every item to a [`Partition`] at random, by the
+/// ratios it is made with, like `Splitter::new(0.8, 0.1, 0.1)`. Only the
+/// proportions of the ratios matter; a ratio which is not positive gets
+/// nothing.
+///
+/// Items of one key, like the rows of one user, can be kept together by
+/// [`Splitter::hash_by`], and a partition can be capped by
+/// [`Splitter::cap`], keeping a uniform sample of its items.
+pub struct Splitter<T, R = ThreadRng> {
+    // 累积比例，最后一个为 1
+    bounds: [f64; 3],
+    key: Option<KeyHash<T>>,
+    parts: [Part<T>; 3],
+    rng: R,
+}
+
+impl<T> Splitter<T> {
+    pub fn new(train: f64, validation: f64, test: f64) -> Self {
+        Self::with_rng(train, validation, test, thread_rng())
+    }
+}
+
+impl<T> Splitter<T, Deterministic> {
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(train: f64, validation: f64, test: f64, seed: u64) -> Self {
+        Self::with_rng(train, validation, test, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, R: Rng> Splitter<T, R> {
+    pub fn with_rng(train: f64, validation: f64, test: f64, rng: R) -> Self {
+        let ratios = [train, validation, test].map(|r| if r > 0.0 { r } else { 0.0 });
+        let sum: f64 = ratios.iter().sum();
+        let mut bounds = [1.0; 3];
+        if sum > 0.0 && sum.is_finite() {
+            bounds[0] = ratios[0] / sum;
+            bounds[1] = (ratios[0] + ratios[1]) / sum;
+        }
+        Self {
+            bounds,
+            key: None,
+            parts: [(); 3].map(|_| Part {
+                cap: None,
+                seen: 0,
+                items: Vec::new(),
+            }),
+            rng,
+        }
+    }
+
+    /// Keeps a uniform sample of at most `n` items of `partition`, like a
+    /// validation set of a fixed size, instead of all of them.
+    pub fn cap(mut self, partition: Partition, n: usize) -> Self {
+        self.parts[partition.index()].cap = Some(n);
+        self
+    }
+
+    /// Assigns items by the hash of their `key` instead of at random, so
+    /// items of the same key land in the same partition, in this run and
+    /// every other. The hash is a fixed one, so that holds for integer keys
+    /// on every platform and Rust version; other keys keep it as long as the
+    /// standard library hashes them the way it does now.
+    pub fn hash_by<K: Hash>(self, key: impl Fn(&T) -> K + 'static) -> Self {
+        self.hash_by_with(key, BuildHasherDefault::<KeyHasher>::default())
+    }
+
+    /// The same as `hash_by`, hashing by `hasher`.
+    pub fn hash_by_with<K: Hash>(
+        mut self,
+        key: impl Fn(&T) -> K + 'static,
+        hasher: impl BuildHasher + 'static,
+    ) -> Self {
+        self.key = Some(Box::new(move |it| hasher.hash_one(key(it))));
+        self
+    }
+
+    /// Assigns the item to a partition, returning it and whether the item is
+    /// held there, which it may not be in a capped partition.
+    pub fn sample(&mut self, it: T) -> (Partition, bool) {
+        let u = match &self.key {
+            // 取哈希值的高 53 位作为 [0, 1) 中的数
+            Some(key) => (key(&it) >> 11) as f64 / (1u64 << 53) as f64,
+            None => self.rng.gen::<f64>(),
+        };
+        let partition = PARTITIONS
+            .iter()
+            .zip(&self.bounds)
+            .find(|(_, &bound)| u < bound)
+            .map_or(Partition::Test, |(&partition, _)| partition);
+
+        let part = &mut self.parts[partition.index()];
+        part.seen += 1;
+        let held = match part.cap {
+            Some(cap) if part.items.len() >= cap => {
+                let j = below_u64(&mut self.rng, part.seen);
+                match usize::try_from(j).ok().and_then(|j| part.items.get_mut(j)) {
+                    Some(slot) => {
+                        *slot = it;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => {
+                part.items.push(it);
+                true
+            }
+        };
+        (partition, held)
+    }
+
+    /// How many items were assigned to `partition`, held or not.
+    pub fn seen(&self, partition: Partition) -> u64 {
+        self.parts[partition.index()].seen
+    }
+
+    /// The held items of `partition`, in the order they were fed unless it
+    /// is capped.
+    pub fn get(&self, partition: Partition) -> &[T] {
+        &self.parts[partition.index()].items
+    }
+
+    pub fn into_split(self) -> Split<T> {
+        let [train, validation, test] = self.parts.map(|part| part.items);
+        Split {
+            train,
+            validation,
+            test,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_by_ratio() {
+        let mut splitter = Splitter::deterministic(8.0, 1.0, 1.0, 1).cap(Partition::Test, 100);
+        for it in 0..100_000u32 {
+            let _ = splitter.sample(it);
+        }
+        let train = splitter.seen(Partition::Train);
+        assert!((79_000..=81_000).contains(&train), "{}", train);
+        assert!(splitter.seen(Partition::Test) > 9_000);
+        assert_eq!(splitter.get(Partition::Test).len(), 100);
+
+        let split = splitter.into_split();
+        assert_eq!(split.train.len() as u64, train);
+        assert!(split.train.windows(2).all(|w| w[0] < w[1]));
+
+        let mut nothing_to_validate = Splitter::deterministic(1.0, -1.0, 0.0, 1);
+        for it in 0..100 {
+            assert_eq!(nothing_to_validate.sample(it), (Partition::Train, true));
+        }
+    }
+
+    #[test]
+    fn keys_stay_together() {
+        // (用户, 行)
+        let split = |seed| {
+            let mut splitter = Splitter::deterministic(0.6, 0.2, 0.2, seed)
+                .hash_by(|&(user, _): &(u32, u32)| user);
+            for row in 0..10 {
+                for user in 0..1_000 {
+                    let _ = splitter.sample((user, row));
+                }
+            }
+            splitter.into_split()
+        };
+
+        let a = split(1);
+        for part in [&a.train, &a.validation, &a.test] {
+            for &(user, _) in part.iter() {
+                assert_eq!(part.iter().filter(|&&(u, _)| u == user).count(), 10);
+            }
+        }
+        assert!((5_000..=7_000).contains(&a.train.len()));
+        // 与随机数无关，换个种子也一样
+        assert_eq!(a, split(2));
+    }
+
+    #[test]
+    fn key_hash_is_fixed() {
+        let hash = |key: u32| BuildHasherDefault::<KeyHasher>::default().hash_one(key);
+        // 固定的数值，哈希一旦改变，已有的划分就会变
+        assert_eq!(hash(0), 0x6683_21fe_5c9f_6e52);
+        assert_ne!(hash(1), hash(2));
+    }
+}
@@ -0,0 +1,263 @@
+//! Where a sampler keeps its slots. The algorithms only talk to a
+//! [`SampleStore`], so a pool that does not fit in memory can live in a
+//! memory map, a file or a database, and still be sampled into by
+//! [`StoreReservoir`].
+use std::iter::Flatten;
+use std::marker::PhantomData;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rng::Deterministic;
+use crate::{algorithm, StreamSampler};
+
+/// The storage a sampling algorithm samples into. Slots are addressed from 0,
+/// and the algorithms fill them front to back: setting the slot right after
+/// the filled ones appends to the store.
+///
+/// A disk or database backed store implements `set` as a write of the new item
+/// which hands back the old one, and `into_items` as a stream over what is
+/// finally held.
+pub trait SampleStore<T> {
+    type IntoItems: Iterator<Item = T>;
+
+    /// How many slots the store has, which is the `k` of the sample.
+    fn capacity(&self) -> usize;
+
+    /// Puts `it` into slot `idx`, handing back whatever was there. A slot past
+    /// the capacity must refuse the item by handing it back.
+    fn set(&mut self, idx: usize, it: T) -> Option<T>;
+
+    /// Hands out the held items in slot order.
+    fn into_items(self) -> Self::IntoItems;
+}
+
+impl<T> SampleStore<T> for Vec<Option<T>> {
+    type IntoItems = Flatten<std::vec::IntoIter<Option<T>>>;
+
+    fn capacity(&self) -> usize {
+        self.len()
+    }
+
+    fn set(&mut self, idx: usize, it: T) -> Option<T> {
+        match self.get_mut(idx) {
+            Some(slot) => slot.replace(it),
+            None => Some(it),
+        }
+    }
+
+    fn into_items(self) -> Self::IntoItems {
+        self.into_iter().flatten()
+    }
+}
+
+impl<T, const K: usize> SampleStore<T> for [Option<T>; K] {
+    type IntoItems = Flatten<std::array::IntoIter<Option<T>, K>>;
+
+    fn capacity(&self) -> usize {
+        K
+    }
+
+    fn set(&mut self, idx: usize, it: T) -> Option<T> {
+        match self.get_mut(idx) {
+            Some(slot) => slot.replace(it),
+            None => Some(it),
+        }
+    }
+
+    fn into_items(self) -> Self::IntoItems {
+        IntoIterator::into_iter(self).flatten()
+    }
+}
+
+/// A store without the `Option` wrapper around each slot, for the common case
+/// where it fills front to back and is never emptied again. The spare capacity
+/// of the `Vec` is the uninitialized storage and its length is the fill count,
+/// so no slot pays for a discriminant and no `unsafe` is needed.
+pub struct PackedStore<T> {
+    items: Vec<T>,
+    cap: usize,
+}
+
+impl<T> PackedStore<T> {
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(cap),
+            cap,
+        }
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T> SampleStore<T> for PackedStore<T> {
+    type IntoItems = std::vec::IntoIter<T>;
+
+    fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    fn set(&mut self, idx: usize, it: T) -> Option<T> {
+        if let Some(slot) = self.items.get_mut(idx) {
+            return Some(std::mem::replace(slot, it));
+        }
+
+        if idx == self.items.len() && idx < self.cap {
+            self.items.push(it);
+            None
+        } else {
+            Some(it)
+        }
+    }
+
+    fn into_items(self) -> Self::IntoItems {
+        self.items.into_iter()
+    }
+}
+
+/// A `StoreReservoir` runs the reservoir algorithm over any [`SampleStore`].
+/// It does not shuffle on `lock`, since a store may be too big to: the items
+/// come out in slot order, which the algorithm already keeps uniformly random.
+pub struct StoreReservoir<T, S, R = ThreadRng> {
+    total: u64,
+    store: S,
+    rng: R,
+    _item: PhantomData<T>,
+}
+
+impl<T, S: SampleStore<T>> StoreReservoir<T, S> {
+    pub fn new(store: S) -> Self {
+        Self::with_rng(store, thread_rng())
+    }
+}
+
+impl<T, S: SampleStore<T>> StoreReservoir<T, S, Deterministic> {
+    pub fn deterministic(store: S, seed: u64) -> Self {
+        Self::with_rng(store, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T, S: SampleStore<T>, R: Rng> StoreReservoir<T, S, R> {
+    pub fn with_rng(store: S, rng: R) -> Self {
+        Self {
+            total: 0,
+            store,
+            rng,
+            _item: PhantomData,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// End the sampling process, streaming the held items out of the store.
+    pub fn lock(self) -> S::IntoItems {
+        self.store.into_items()
+    }
+}
+
+impl<T, S: SampleStore<T>, R: Rng> StreamSampler for StoreReservoir<T, S, R> {
+    type Item = T;
+    /// The items of `1..=capacity` are held.
+    type Draw = u64;
+
+    fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.total += 1;
+
+        if self.store.capacity() == 0 {
+            return (0, self.total, None);
+        }
+
+        let (r, replaced) = algorithm::r(&mut self.store, self.total, it, &mut self.rng);
+        (r, self.total, replaced)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        (1..=self.store.capacity() as u64).contains(draw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Reservoir, StreamSampler};
+
+    /// Pretends to be an external store, only remembering writes.
+    struct Journal(Vec<(usize, u32)>, usize);
+
+    impl SampleStore<u32> for Journal {
+        type IntoItems = std::vec::IntoIter<u32>;
+
+        fn capacity(&self) -> usize {
+            self.1
+        }
+
+        fn set(&mut self, idx: usize, it: u32) -> Option<u32> {
+            let old = self
+                .0
+                .iter()
+                .rev()
+                .find(|(i, _)| *i == idx)
+                .map(|(_, it)| *it);
+            self.0.push((idx, it));
+            old
+        }
+
+        fn into_items(self) -> Self::IntoItems {
+            (0..self.1)
+                .filter_map(|idx| {
+                    self.0
+                        .iter()
+                        .rev()
+                        .find(|(i, _)| *i == idx)
+                        .map(|(_, it)| *it)
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+        }
+    }
+
+    #[test]
+    fn every_store_samples_alike() {
+        let mut vec = StoreReservoir::deterministic(vec![None; 3], 5);
+        let mut array = StoreReservoir::deterministic([None; 3], 5);
+        let mut packed = StoreReservoir::deterministic(PackedStore::with_capacity(3), 5);
+        let mut journal = StoreReservoir::deterministic(Journal(Vec::new(), 3), 5);
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(3, 5);
+        for it in 0..30 {
+            let expected = reservoir.sample(it);
+            assert_eq!(vec.sample(it), expected);
+            assert_eq!(array.sample(it), expected);
+            assert_eq!(packed.sample(it), expected);
+            assert_eq!(journal.sample(it), expected);
+        }
+
+        let held: Vec<_> = reservoir.iter().copied().collect();
+        assert_eq!(vec.lock().collect::<Vec<_>>(), held);
+        assert_eq!(array.lock().collect::<Vec<_>>(), held);
+        assert_eq!(packed.lock().collect::<Vec<_>>(), held);
+        assert_eq!(journal.lock().collect::<Vec<_>>(), held);
+    }
+
+    #[test]
+    fn external_stores_can_be_tapped() {
+        // 外部存储拿不出 &[Option<T>]，但照样能套上只要求 StreamSampler 的包装
+        let journal = StoreReservoir::deterministic(Journal(Vec::new(), 3), 5);
+        let mut tapped = crate::Tapped::new(journal);
+        let tap = tapped.tap();
+        let fed = crate::PrioritySources::new()
+            .source(0..30u32, 1)
+            .feed(&mut tapped);
+
+        assert_eq!(fed, 30);
+        assert_eq!(tap.retained() + tap.dropped(), 30);
+        assert_eq!(tapped.into_inner().lock().count(), 3);
+    }
+}
@@ -0,0 +1,130 @@
+//! Systematic sampling: every `m`-th item from a random start, which some
+//! audits ask for instead of a simple random sample.
+use rand::{thread_rng, Rng};
+
+use crate::rng::below_u64;
+use crate::{Deterministic, Inspectable, Lockable, StreamSampler};
+
+/// A `SystematicSampler` keeps the items at positions `start`, `start + m`,
+/// `start + 2m` and so on, where `start` is drawn uniformly out of `0..m`
+/// upfront. So every item has the same `1 / m` chance, but unlike a simple
+/// random sample, neighbours are never kept together.
+///
+/// The pool grows by one every `m` items, and nothing is ever replaced.
+pub struct SystematicSampler<T> {
+    interval: u64,
+    start: u64,
+    total: u64,
+    held: Vec<Option<T>>,
+}
+
+impl<T> SystematicSampler<T> {
+    /// An `interval` of 0 is taken as 1, keeping every item.
+    pub fn new(interval: u64) -> Self {
+        Self::with_rng(interval, &mut thread_rng())
+    }
+
+    /// See [`Reservoir::deterministic`](crate::Reservoir::deterministic).
+    pub fn deterministic(interval: u64, seed: u64) -> Self {
+        Self::with_rng(interval, &mut Deterministic::seed_from_u64(seed))
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(interval: u64, rng: &mut R) -> Self {
+        let interval = interval.max(1);
+        Self {
+            interval,
+            start: below_u64(rng, interval),
+            total: 0,
+            held: Vec::new(),
+        }
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// The position of the first kept item, counting from 0.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+impl<T> StreamSampler for SystematicSampler<T> {
+    type Item = T;
+    /// The position of the item, counting from 1 like `total`.
+    type Draw = u64;
+
+    fn sample(&mut self, it: T) -> (u64, u64, Option<T>) {
+        self.total += 1;
+        if self.is_held(&self.total) {
+            self.held.push(Some(it));
+        }
+        (self.total, self.total, None)
+    }
+
+    fn is_held(&self, draw: &u64) -> bool {
+        draw.checked_sub(1)
+            .is_some_and(|idx| idx % self.interval == self.start)
+    }
+}
+
+impl<T> Inspectable for SystematicSampler<T> {
+    fn samples(&self) -> &[Option<T>] {
+        &self.held
+    }
+}
+
+impl<T> Lockable for SystematicSampler<T> {
+    /// The kept items stay in the order of the stream, which is what makes
+    /// the sample systematic.
+    fn lock(self) -> Vec<Option<T>> {
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lockable, Reservoir};
+
+    #[test]
+    fn every_mth_item() {
+        let mut sampler = SystematicSampler::deterministic(10, 27);
+        let start = sampler.start();
+        assert!(start < 10);
+        let _ = sampler.sample_all(0..95u64);
+        let kept = sampler.lock_compact();
+        assert_eq!(kept, (start..95).step_by(10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn every_start_is_as_likely() {
+        let mut counts = [0; 4];
+        for seed in 0..4_000 {
+            counts[SystematicSampler::<u32>::deterministic(4, seed).start() as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| (900..=1_100).contains(&c)));
+
+        let sampler = SystematicSampler::<u32>::new(0);
+        assert_eq!((sampler.interval(), sampler.start()), (1, 0));
+    }
+
+    /// The same function takes either sampler.
+    fn held<S: Lockable<Item = u32>>(mut sampler: S) -> usize {
+        let _ = sampler.sample_all(0..100);
+        sampler.lock_compact().len()
+    }
+
+    #[test]
+    fn swaps_with_a_reservoir() {
+        assert_eq!(held(SystematicSampler::deterministic(10, 1)), 10);
+        assert_eq!(
+            held(Reservoir::<u32, Deterministic>::deterministic(10, 1)),
+            10
+        );
+    }
+}
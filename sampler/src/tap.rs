@@ -0,0 +1,194 @@
+//! Telling others whether the item just sampled was retained, without handing
+//! them the sampler, for example to mark a tracing span as sampled.
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::{Inspectable, Lockable, StreamSampler};
+
+const NONE: u8 = 0;
+const RETAINED: u8 = 1;
+const DROPPED: u8 = 2;
+
+#[derive(Debug, Default)]
+struct Decisions {
+    last: AtomicU8,
+    retained: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// A `Tapped` sampler samples just like the sampler inside, and records every
+/// decision into its [`DecisionTap`]s before `sample` returns.
+pub struct Tapped<S> {
+    sampler: S,
+    decisions: Arc<Decisions>,
+}
+
+impl<S: StreamSampler> Tapped<S> {
+    pub fn new(sampler: S) -> Self {
+        Self {
+            sampler,
+            decisions: Arc::default(),
+        }
+    }
+
+    /// A handle reporting the decisions of this sampler.
+    pub fn tap(&self) -> DecisionTap {
+        DecisionTap {
+            decisions: Arc::clone(&self.decisions),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sampler
+    }
+}
+
+impl<S: StreamSampler> StreamSampler for Tapped<S> {
+    type Item = S::Item;
+    type Draw = S::Draw;
+
+    fn sample(&mut self, it: Self::Item) -> (S::Draw, u64, Option<Self::Item>) {
+        let (r, total, replaced) = self.sampler.sample(it);
+
+        let (last, count) = if self.sampler.is_held(&r) {
+            (RETAINED, &self.decisions.retained)
+        } else {
+            (DROPPED, &self.decisions.dropped)
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        self.decisions.last.store(last, Ordering::Release);
+
+        (r, total, replaced)
+    }
+
+    fn is_held(&self, draw: &S::Draw) -> bool {
+        self.sampler.is_held(draw)
+    }
+}
+
+impl<S: Inspectable> Inspectable for Tapped<S> {
+    fn samples(&self) -> &[Option<Self::Item>] {
+        self.sampler.samples()
+    }
+}
+
+impl<S: Lockable> Lockable for Tapped<S> {
+    fn lock(self) -> Vec<Option<Self::Item>> {
+        self.sampler.lock()
+    }
+}
+
+/// A cheap, clonable handle on the decisions of a [`Tapped`] sampler.
+#[derive(Debug, Clone)]
+pub struct DecisionTap {
+    decisions: Arc<Decisions>,
+}
+
+impl DecisionTap {
+    /// Whether the item of the latest `sample` call was retained, `None`
+    /// before the first call.
+    pub fn last(&self) -> Option<bool> {
+        match self.decisions.last.load(Ordering::Acquire) {
+            NONE => None,
+            last => Some(last == RETAINED),
+        }
+    }
+
+    /// How many items have been retained when they were sampled, including
+    /// the ones replaced later.
+    pub fn retained(&self) -> u64 {
+        self.decisions.retained.load(Ordering::Relaxed)
+    }
+
+    /// How many items have been dropped right away.
+    pub fn dropped(&self) -> u64 {
+        self.decisions.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deterministic, Reservoir};
+
+    #[test]
+    fn reports_every_decision() {
+        let mut sampler = Tapped::new(Reservoir::<u32, Deterministic>::deterministic(3, 9));
+        let tap = sampler.tap();
+        let clone = tap.clone();
+        assert_eq!(tap.last(), None);
+
+        for it in 0..100 {
+            let (r, _, _) = sampler.sample(it);
+            assert_eq!(clone.last(), Some(r <= 3));
+        }
+
+        assert_eq!(tap.retained() + tap.dropped(), 100);
+        assert!(tap.retained() >= 3);
+        assert_eq!(sampler.lock().iter().flatten().count(), 3);
+    }
+
+    /// One slot, held by the item of the smallest random key, so the draws
+    /// are floats.
+    struct MinKey {
+        pool: [Option<u32>; 1],
+        key: f64,
+        total: u64,
+        rng: Deterministic,
+    }
+
+    impl StreamSampler for MinKey {
+        type Item = u32;
+        type Draw = f64;
+
+        fn sample(&mut self, it: u32) -> (f64, u64, Option<u32>) {
+            use rand::Rng;
+
+            self.total += 1;
+            let key = self.rng.gen::<f64>();
+            if key >= self.key {
+                return (key, self.total, None);
+            }
+            self.key = key;
+            (key, self.total, self.pool[0].replace(it))
+        }
+
+        fn is_held(&self, &draw: &f64) -> bool {
+            draw.to_bits() == self.key.to_bits()
+        }
+    }
+
+    impl Inspectable for MinKey {
+        fn samples(&self) -> &[Option<u32>] {
+            &self.pool
+        }
+    }
+
+    impl Lockable for MinKey {
+        fn lock(self) -> Vec<Option<u32>> {
+            self.pool.to_vec()
+        }
+    }
+
+    #[test]
+    fn float_draws() {
+        let mut sampler = Tapped::new(MinKey {
+            pool: [None],
+            key: 1.0,
+            total: 0,
+            rng: Deterministic::seed_from_u64(9),
+        });
+        let tap = sampler.tap();
+
+        let mut lows = 0;
+        for it in 0..1000 {
+            let (_, _, replaced) = sampler.sample(it);
+            let held = sampler.samples()[0] == Some(it);
+            assert_eq!(tap.last(), Some(held));
+            lows += u64::from(held);
+            assert!(!held || replaced.is_some() || it == 0);
+        }
+        assert_eq!(tap.retained(), lows);
+        assert_eq!(tap.dropped(), 1000 - lows);
+    }
+}
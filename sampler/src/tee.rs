@@ -0,0 +1,108 @@
+//! Feeding one stream into several samplers at once, so a huge dataset only
+//! has to be read a single time.
+use crate::Lockable;
+
+/// Anything a [`Tee`] can feed: every sampler, and a `Tee` itself, so more
+/// than two samplers are fed by nesting, like `Tee::new(a, Tee::new(b, c))`.
+pub trait Branch {
+    type Item;
+    type Locked;
+
+    fn feed(&mut self, it: Self::Item);
+
+    fn finish(self) -> Self::Locked;
+}
+
+impl<S: Lockable> Branch for S {
+    type Item = S::Item;
+    type Locked = Vec<Option<S::Item>>;
+
+    fn feed(&mut self, it: Self::Item) {
+        let _ = self.sample(it);
+    }
+
+    fn finish(self) -> Self::Locked {
+        self.lock()
+    }
+}
+
+/// A `Tee` hands a clone of every incoming item to its left branch and the
+/// item itself to its right one.
+pub struct Tee<A, B> {
+    left: A,
+    right: B,
+}
+
+impl<A, B> Tee<A, B>
+where
+    A: Branch,
+    B: Branch<Item = A::Item>,
+    A::Item: Clone,
+{
+    pub fn new(left: A, right: B) -> Self {
+        Self { left, right }
+    }
+
+    pub fn sample(&mut self, it: A::Item) {
+        self.feed(it)
+    }
+
+    pub fn left(&self) -> &A {
+        &self.left
+    }
+
+    pub fn right(&self) -> &B {
+        &self.right
+    }
+
+    /// Locks every branch, in the same shape the branches were nested.
+    pub fn lock(self) -> (A::Locked, B::Locked) {
+        self.finish()
+    }
+}
+
+impl<A, B> Branch for Tee<A, B>
+where
+    A: Branch,
+    B: Branch<Item = A::Item>,
+    A::Item: Clone,
+{
+    type Item = A::Item;
+    type Locked = (A::Locked, B::Locked);
+
+    fn feed(&mut self, it: Self::Item) {
+        self.left.feed(it.clone());
+        self.right.feed(it);
+    }
+
+    fn finish(self) -> Self::Locked {
+        (self.left.finish(), self.right.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Inspectable, Reservoir, SpaceSaving};
+
+    #[test]
+    fn feed_all_branches() {
+        let mut tee = Tee::new(
+            Reservoir::<u32>::with_capacity(2),
+            Tee::new(
+                Reservoir::<u32>::with_capacity(5),
+                SpaceSaving::with_capacity(1),
+            ),
+        );
+
+        for it in 0..100 {
+            tee.sample(it % 3);
+        }
+        assert_eq!(tee.left().samples().len(), 2);
+
+        let (uniform, (bigger, frequent)) = tee.lock();
+        assert_eq!(uniform.iter().flatten().count(), 2);
+        assert_eq!(bigger.iter().flatten().count(), 5);
+        assert_eq!(frequent.len(), 1);
+    }
+}
@@ -0,0 +1,131 @@
+//! Helpers for testing code built on the samplers, enabled by the `testing`
+//! feature. Add it to the `dev-dependencies` only.
+use std::collections::VecDeque;
+
+use rand::{Error, RngCore};
+
+use crate::rng::Deterministic;
+
+/// An RNG which makes the samplers take scripted decisions, so the code around
+/// a sampler (handling replaced items, updating a UI) can be tested against
+/// exactly the outcomes it needs.
+///
+/// `draw(j, n)` queues the one `next_u64` which makes a `gen_range(0..n)`
+/// over `u64` come out as `j`. That is how a [`Reservoir`](crate::Reservoir)
+/// under [`Algorithm::R`](crate::Algorithm::R) decides, and at its `t`-th
+/// item (counting from 1), [`ScriptedDecisions::replace`] and
+/// [`ScriptedDecisions::reject`] spell out the usual decisions. Decisions
+/// drawn another way, like the `f64` skips of algorithms L and X or the keys
+/// of weighted samplers, still consume the queued values, but as raw bits,
+/// which only makes them repeatable. Once the script runs out, decisions come
+/// from a [`Deterministic`] RNG seeded with 0.
+#[derive(Clone, Debug)]
+pub struct ScriptedDecisions {
+    script: VecDeque<u64>,
+    fallback: Deterministic,
+}
+
+impl Default for ScriptedDecisions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptedDecisions {
+    pub fn new() -> Self {
+        Self {
+            script: VecDeque::new(),
+            fallback: Deterministic::seed_from_u64(0),
+        }
+    }
+
+    /// Makes the next draw out of `0..n` come out as `j`. `j` is clamped into
+    /// the range, and `n` of 0 is treated as 1.
+    pub fn draw(mut self, j: u64, n: u64) -> Self {
+        let n = n.max(1);
+        let j = j.min(n - 1);
+
+        // gen_range 用 v * n 的高 64 位作为结果，取使其恰为 j 的最小 v
+        let v = (u128::from(j) << 64).div_ceil(u128::from(n));
+        self.script.push_back(v as u64);
+        self
+    }
+
+    /// Makes the `total`-th item replace `slot` (counting from 0). While the
+    /// pool is filling up, the held item moves to the first empty slot.
+    pub fn replace(self, slot: usize, total: u64) -> Self {
+        self.draw(slot as u64, total)
+    }
+
+    /// Makes the `total`-th item go unsampled, which is only possible once the
+    /// pool is full.
+    pub fn reject(self, total: u64) -> Self {
+        self.draw(total.saturating_sub(1), total)
+    }
+
+    /// How many scripted decisions are left.
+    pub fn remaining(&self) -> usize {
+        self.script.len()
+    }
+}
+
+impl RngCore for ScriptedDecisions {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self.script.pop_front() {
+            Some(v) => v,
+            None => self.fallback.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Inspectable, Reservoir, StreamSampler};
+
+    #[test]
+    fn scripted_replacements() {
+        let script = ScriptedDecisions::new()
+            .replace(0, 1)
+            .replace(1, 2)
+            .replace(0, 3)
+            .reject(4)
+            .replace(1, 5);
+        let mut reservoir = Reservoir::with_rng(2, script);
+
+        assert_eq!(reservoir.sample('a'), (1, 1, None));
+        assert_eq!(reservoir.sample('b'), (2, 2, None));
+        assert_eq!(reservoir.sample('c'), (1, 3, Some('a')));
+        assert_eq!(reservoir.sample('d'), (4, 4, None));
+        assert_eq!(reservoir.sample('e'), (2, 5, Some('b')));
+        assert_eq!(reservoir.samples(), &[Some('c'), Some('e')]);
+    }
+
+    #[test]
+    fn every_draw_lands() {
+        use rand::Rng;
+
+        for n in [1u64, 2, 3, 7, 1000, u64::MAX] {
+            for j in [0, 1, n / 2, n - 1] {
+                let mut rng = ScriptedDecisions::new().draw(j, n);
+                assert_eq!(rng.gen_range(0..n), j.min(n - 1));
+            }
+        }
+    }
+}
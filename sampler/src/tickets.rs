@@ -0,0 +1,413 @@
+//! A weighted sampler that only does integer arithmetic. Every item holds a
+//! number of `u64` tickets instead of a floating point weight, so the same
+//! random draws always lead to the same samples, no matter which platform the
+//! sampling runs on.
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::merge::{self, MergeError, Mergeable, WireItem};
+use crate::rng::{below, compact_and_shuffle, Deterministic};
+use crate::weights::{LockWithWeights, Sampled};
+
+/// A `TicketReservoir` virtually expands every item into as many tickets as it
+/// holds. Once the pool is full, a ticket is drawn out of all tickets seen so
+/// far, and the current item is accepted when the draw lands in the `k * w`
+/// tickets it stands for, which gives the item an inclusion probability of
+/// `k * w / W` (capped at 1). The first `k` items are kept with the chance of
+/// `W_k / W` instead, `W_k` being the tickets they hold between them.
+pub struct TicketReservoir<T, R = ThreadRng> {
+    total: u64,
+    tickets: u128,
+    filled: usize,
+    entries: u64,
+    pool: Vec<Option<T>>,
+    // 每个槽位上的票数
+    held_tickets: Vec<u64>,
+    // 以对数记：槽位上的条目被接受的概率除以它进来时的存活率
+    kept: Vec<f64>,
+    // 以对数记：从头开始一直没被替换掉的概率
+    survival: f64,
+    rng: R,
+}
+
+impl<T: Clone> TicketReservoir<T> {
+    pub fn with_capacity(n: usize) -> Self {
+        Self::with_rng(n, thread_rng())
+    }
+}
+
+impl<T: Clone> TicketReservoir<T, Deterministic> {
+    pub fn deterministic(n: usize, seed: u64) -> Self {
+        Self::with_rng(n, Deterministic::seed_from_u64(seed))
+    }
+}
+
+impl<T: Clone, R: Rng> TicketReservoir<T, R> {
+    pub fn with_rng(n: usize, rng: R) -> Self {
+        Self {
+            total: 0,
+            tickets: 0,
+            filled: 0,
+            entries: 0,
+            pool: std::vec::from_elem(Option::<T>::None, n),
+            held_tickets: vec![0; n],
+            kept: vec![0.0; n],
+            survival: 0.0,
+            rng,
+        }
+    }
+}
+
+impl<T, R: Rng> TicketReservoir<T, R> {
+    /// Samples an item holding `tickets` tickets. An item with no ticket is
+    /// counted, but never accepted.
+    ///
+    /// ## Return
+    /// a tuple contains 3 elements:
+    /// - a `u128` stands for which ticket is drawn for the current item
+    /// - a `u128` stands for how many tickets has been passed through so far
+    /// - an option of item that is replaced by the current item.
+    pub fn sample(&mut self, it: T, tickets: u64) -> (u128, u128, Option<T>) {
+        let pool_cap = self.pool.len();
+
+        self.total += 1;
+        if tickets == 0 || pool_cap == 0 {
+            return (0, self.tickets, None);
+        }
+        self.tickets += u128::from(tickets);
+        self.entries += 1;
+
+        // 先填满池子，之后按票数比例随机替换
+        if self.filled < pool_cap {
+            self.pool[self.filled] = Some(it);
+            self.held_tickets[self.filled] = tickets;
+            self.kept[self.filled] = -self.survival;
+            self.filled += 1;
+            return (self.tickets, self.tickets, None);
+        }
+
+        let drawn = self.rng.gen_range(0..self.tickets);
+        let accepted = (pool_cap as f64 * tickets as f64 / self.tickets as f64).min(1.0);
+        // 每个槽位被替换的概率是 accepted / k；为 1 时原有的条目一个不剩
+        let evicted = accepted / pool_cap as f64;
+        if evicted < 1.0 {
+            self.survival += (-evicted).ln_1p();
+        }
+        let mut replaced = None;
+        if drawn < pool_cap as u128 * u128::from(tickets) {
+            let slot = below(&mut self.rng, pool_cap);
+            replaced = self.pool[slot].replace(it);
+            self.held_tickets[slot] = tickets;
+            self.kept[slot] = accepted.ln() - self.survival;
+        }
+
+        (drawn + 1, self.tickets, replaced)
+    }
+
+    /// How many items have been passed through so far, no matter how many
+    /// tickets they held.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// How many tickets have been passed through so far.
+    pub fn tickets(&self) -> u128 {
+        self.tickets
+    }
+
+    pub fn samples(&self) -> &[Option<T>] {
+        &self.pool[..]
+    }
+
+    /// End the sampling process. The retained items are shuffled into
+    /// uniformly random slots, see [`Reservoir::compact_and_shuffle`].
+    ///
+    /// [`Reservoir::compact_and_shuffle`]: crate::Reservoir::compact_and_shuffle
+    pub fn lock(mut self) -> Vec<Option<T>> {
+        compact_and_shuffle(&mut self.pool, &mut self.rng);
+        self.pool
+    }
+
+    /// The same as `lock`, only handing out the held items.
+    pub fn lock_iter(self) -> impl Iterator<Item = T> {
+        self.lock().into_iter().flatten()
+    }
+}
+
+/// The exact chance every held item had of being kept: the chance it was
+/// accepted, times the chance it survived every draw after. That is
+/// `k * w / W` for an item of `w` tickets out of `W` while no draw is capped
+/// at 1, and `W_k / W` for the first `k` items.
+///
+/// A merged or decoded reservoir does not know when its items came in, so
+/// its held items fall back to `k * w / W`, capped at 1, and 1 while no more
+/// items holding tickets than the capacity have passed through.
+impl<T, R: Rng> LockWithWeights for TicketReservoir<T, R> {
+    type Item = T;
+
+    fn lock_with_weights(mut self) -> Vec<Sampled<T>> {
+        let survival = self.survival;
+        let mut held: Vec<Option<(T, f64)>> = self
+            .pool
+            .into_iter()
+            .zip(self.kept)
+            .map(|(it, kept)| it.map(|it| (it, kept)))
+            .collect();
+        compact_and_shuffle(&mut held, &mut self.rng);
+
+        held.into_iter()
+            .flatten()
+            .map(|(item, kept)| Sampled {
+                item,
+                probability: (kept + survival).exp().min(1.0),
+            })
+            .collect()
+    }
+}
+
+impl<T, R> TicketReservoir<T, R> {
+    /// Forgets when the held items came in, see `lock_with_weights`.
+    fn approximate_kept(&mut self) {
+        let k = self.pool.len();
+        let (entries, all) = (self.entries, self.tickets);
+        self.survival = 0.0;
+        self.kept = self
+            .held_tickets
+            .iter()
+            .map(|&tickets| {
+                if entries <= k as u64 {
+                    0.0
+                } else {
+                    (k as f64 * tickets as f64 / all as f64).min(1.0).ln()
+                }
+            })
+            .collect();
+    }
+}
+
+/// Every merged slot is drawn from either part in proportion to its tickets,
+/// which keeps each item's chance at `k * w / W` over both parts, as near as
+/// the held items allow.
+impl<T: WireItem, R: Rng> Mergeable for TicketReservoir<T, R> {
+    type Rng = R;
+
+    fn merge(&mut self, other: Self) -> Result<(), MergeError> {
+        let k = self.pool.len();
+        if other.pool.len() < k {
+            return Err(MergeError::TooSmall);
+        }
+
+        let held = |pool: Vec<Option<T>>, tickets: Vec<u64>| -> Vec<(T, u64)> {
+            pool.into_iter()
+                .zip(tickets)
+                .filter_map(|(it, tickets)| it.map(|it| (it, tickets)))
+                .collect()
+        };
+        let mine = held(
+            std::mem::take(&mut self.pool),
+            std::mem::take(&mut self.held_tickets),
+        );
+        let theirs = held(other.pool, other.held_tickets);
+        let picked = merge::pick(
+            mine,
+            self.tickets,
+            theirs,
+            other.tickets,
+            k,
+            false,
+            &mut self.rng,
+        );
+
+        self.filled = picked.len();
+        let (pool, tickets): (Vec<_>, Vec<_>) = picked
+            .into_iter()
+            .map(|(it, tickets)| (Some(it), tickets))
+            .unzip();
+        self.pool = pool;
+        self.pool.resize_with(k, || None);
+        self.held_tickets = tickets;
+        self.held_tickets.resize(k, 0);
+        self.total += other.total;
+        self.tickets += other.tickets;
+        self.entries += other.entries;
+        self.approximate_kept();
+        Ok(())
+    }
+
+    /// Held items are written with their tickets, leaving the empty slots out.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        merge::put_header(&mut out, merge::TICKETS);
+        for n in [
+            self.pool.len() as u64,
+            self.total,
+            self.entries,
+            self.filled as u64,
+        ] {
+            merge::put_varint(&mut out, u128::from(n));
+        }
+        merge::put_varint(&mut out, self.tickets);
+        for (it, &tickets) in self.pool.iter().zip(&self.held_tickets) {
+            if let Some(it) = it {
+                merge::put_varint(&mut out, u128::from(tickets));
+                it.encode(&mut out);
+            }
+        }
+        out
+    }
+
+    fn from_bytes_with_rng(bytes: &[u8], rng: R) -> Result<Self, MergeError> {
+        let input = &mut &bytes[..];
+        merge::get_header(input, merge::TICKETS)?;
+        let capacity = merge::get_capacity(input)?;
+        let total = merge::get_u64(input)?;
+        let entries = merge::get_u64(input)?;
+        let filled = merge::get_usize(input)?;
+        let tickets = merge::get_varint(input)?;
+        if filled > capacity || filled as u64 > entries || entries > total {
+            return Err(MergeError::Malformed);
+        }
+
+        let held = merge::get_items(input, filled, |input| {
+            let tickets = u64::decode(input)?;
+            Ok((Some(T::decode(input)?), tickets))
+        })?;
+        let (mut pool, mut held_tickets): (Vec<_>, Vec<_>) = held.into_iter().unzip();
+        pool.resize_with(capacity, || None);
+        held_tickets.resize(capacity, 0);
+        let mut reservoir = Self {
+            total,
+            tickets,
+            filled,
+            entries,
+            pool,
+            held_tickets,
+            kept: Vec::new(),
+            survival: 0.0,
+            rng,
+        };
+        reservoir.approximate_kept();
+        Ok(reservoir)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Clone, R> crate::Snapshot for TicketReservoir<T, R> {
+    type Item = T;
+
+    fn snapshot(&self) -> crate::SamplerSnapshot<T> {
+        crate::SamplerSnapshot::new("tickets", self.total, self.pool.clone())
+            .with_parameter("tickets", self.tickets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_before_replace() {
+        let mut reservoir = TicketReservoir::<i32>::with_capacity(3);
+
+        for it in 1..=3 {
+            let (_, _, replaced) = reservoir.sample(it, 10);
+            assert_eq!(replaced, None);
+        }
+
+        assert_eq!(reservoir.samples(), &[Some(1), Some(2), Some(3)]);
+        assert_eq!(reservoir.tickets(), 30);
+    }
+
+    #[test]
+    fn no_ticket_no_chance() {
+        let mut reservoir = TicketReservoir::<i32>::with_capacity(2);
+
+        for it in 0..100 {
+            let _ = reservoir.sample(it, 0);
+        }
+
+        assert_eq!(reservoir.total(), 100);
+        assert_eq!(reservoir.tickets(), 0);
+        assert_eq!(reservoir.lock(), vec![None, None]);
+    }
+
+    #[test]
+    fn overwhelming_tickets_always_accepted() {
+        let mut reservoir = TicketReservoir::<i32>::with_capacity(2);
+        let _ = reservoir.sample(1, 1);
+        let _ = reservoir.sample(2, 1);
+
+        let (_, _, replaced) = reservoir.sample(3, u64::MAX);
+        assert!(replaced.is_some());
+        assert!(reservoir.samples().contains(&Some(3)));
+    }
+
+    #[test]
+    fn deterministic_is_pinned() {
+        let mut reservoir = TicketReservoir::<u64, Deterministic>::deterministic(3, 27);
+        for it in 0..100 {
+            let _ = reservoir.sample(it, it % 7);
+        }
+
+        assert_eq!(reservoir.lock(), vec![Some(23), Some(58), Some(75)]);
+    }
+
+    #[test]
+    fn first_items_keep_their_share() {
+        const ROUNDS: u64 = 4000;
+        let mut kept = [0u64; 3];
+        for seed in 0..ROUNDS {
+            let mut reservoir = TicketReservoir::<usize, Deterministic>::deterministic(2, seed);
+            for (it, &tickets) in [1, 3, 4].iter().enumerate() {
+                let _ = reservoir.sample(it, tickets);
+            }
+
+            for sampled in reservoir.lock_with_weights() {
+                // 前两个各以 W_2 / W = 4 / 8 留下，第三个必定留下
+                let expected = [0.5, 0.5, 1.0][sampled.item];
+                assert!((sampled.probability - expected).abs() < 1e-9);
+                kept[sampled.item] += 1;
+            }
+        }
+
+        assert_eq!(kept[2], ROUNDS);
+        for &n in &kept[..2] {
+            assert!((1800..2200).contains(&n), "{:?}", kept);
+        }
+    }
+
+    #[test]
+    fn merge_and_round_trip() {
+        let mut a = TicketReservoir::<u32, Deterministic>::deterministic(2, 3);
+        let mut b = TicketReservoir::<u32, Deterministic>::deterministic(2, 4);
+        let _ = a.sample(1, 1);
+        for it in 10..20 {
+            let _ = b.sample(it, 1_000);
+        }
+
+        let bytes = b.to_bytes();
+        let b = TicketReservoir::from_bytes_with_rng(&bytes, Deterministic::seed_from_u64(0));
+        let b = b.unwrap();
+        assert_eq!(b.tickets(), 10_000);
+        assert_eq!(b.to_bytes(), bytes);
+
+        a.merge(b).unwrap();
+        assert_eq!(a.total(), 11);
+        assert_eq!(a.tickets(), 10_001);
+        assert_eq!(a.samples().iter().flatten().count(), 2);
+        assert_eq!(
+            TicketReservoir::<u32>::from_bytes(&a.to_bytes()[..3]).err(),
+            Some(MergeError::Truncated)
+        );
+
+        let mut huge = Vec::new();
+        merge::put_header(&mut huge, merge::TICKETS);
+        for n in [1 << 50, 0, 0, 0, 0] {
+            merge::put_varint(&mut huge, n);
+        }
+        assert_eq!(
+            TicketReservoir::<u32>::from_bytes(&huge).err(),
+            Some(MergeError::Malformed)
+        );
+    }
+}
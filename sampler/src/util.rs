@@ -0,0 +1,82 @@
+//! The shuffles the samplers use themselves, for code around them which needs
+//! to shuffle the same way, like to replay a draw from its seed.
+use rand::Rng;
+
+use crate::rng::{self, below};
+use crate::Deterministic;
+
+/// Fisher–Yates, the shuffle `lock` does, drawing from `rng` the way every
+/// sampler of this crate does, so it is the same on 32-bit and 64-bit
+/// targets.
+pub fn shuffle<T, R: Rng + ?Sized>(items: &mut [T], rng: &mut R) {
+    rng::shuffle(items, rng)
+}
+
+/// Shuffles `items` by a [`Deterministic`] rng of `seed`, the same way on
+/// every platform and in every run.
+pub fn shuffle_seeded<T>(items: &mut [T], seed: u64) {
+    shuffle(items, &mut Deterministic::seed_from_u64(seed))
+}
+
+/// Moves a uniform sample of `k` of `items`, in a random order, to the front,
+/// drawing only `k` random numbers. Returns the sample and the rest, which is
+/// not shuffled. A `k` beyond the length shuffles all of `items`.
+pub fn partial_shuffle_k<'a, T, R: Rng + ?Sized>(
+    items: &'a mut [T],
+    k: usize,
+    rng: &mut R,
+) -> (&'a mut [T], &'a mut [T]) {
+    let n = items.len();
+    let k = k.min(n);
+    for i in 0..k {
+        let j = i + below(rng, n - i);
+        items.swap(i, j);
+    }
+    items.split_at_mut(k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lockable, Reservoir, StreamSampler};
+
+    #[test]
+    fn same_shuffle_as_lock() {
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(10, 27);
+        let _ = reservoir.sample_all(0..10);
+        let mut pool: Vec<Option<u32>> = reservoir.clone().into_samples();
+
+        // 满的池子里 compact_and_shuffle 只是一次洗牌
+        let mut rng = Deterministic::seed_from_u64(27);
+        let _ = (0..10).map(|t| below(&mut rng, t + 1)).count();
+        shuffle(&mut pool, &mut rng);
+        assert_eq!(pool, reservoir.lock());
+
+        let mut a: Vec<u32> = (0..100).collect();
+        let mut b = a.clone();
+        shuffle_seeded(&mut a, 1);
+        shuffle_seeded(&mut b, 1);
+        assert_eq!(a, b);
+        assert_ne!(a, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn partial_shuffle_is_uniform() {
+        let mut rng = Deterministic::seed_from_u64(1);
+        let mut counts = [0u32; 10];
+        for _ in 0..10_000 {
+            let mut items: Vec<usize> = (0..10).collect();
+            let (chosen, rest) = partial_shuffle_k(&mut items, 3, &mut rng);
+            assert_eq!((chosen.len(), rest.len()), (3, 7));
+            for &it in chosen.iter() {
+                counts[it] += 1;
+            }
+        }
+        // 每个条目约 3000 次
+        assert!(counts.iter().all(|&c| (2_800..=3_200).contains(&c)));
+
+        let mut items = [1, 2];
+        let (chosen, rest) = partial_shuffle_k(&mut items, 5, &mut rng);
+        assert_eq!((chosen.len(), rest.len()), (2, 0));
+    }
+}
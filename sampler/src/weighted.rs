@@ -0,0 +1,139 @@
+//! Picking distinct indices with chances proportional to their weights, like
+//! for negative sampling or rebalancing a dataset.
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use rand::Rng;
+
+use crate::algorithm::unit;
+
+/// An index with its key; the greatest keys win.
+struct Keyed(f64, usize);
+
+impl PartialEq for Keyed {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Keyed {}
+
+impl PartialOrd for Keyed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Keyed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// `k` distinct indices into `weights`, in ascending order, drawn without
+/// replacement with chances proportional to the weights, by Efraimidis and
+/// Spirakis' A-ExpJ. It passes over the slice once, drawing random numbers
+/// only for the indices which make it into the sample for a while, about
+/// `k log(n / k)` of them.
+///
+/// A weight which is not positive and finite is never picked, so fewer than
+/// `k` indices come out when fewer weights are.
+pub fn sample_indices_weighted<R: Rng + ?Sized>(
+    weights: &[f64],
+    k: usize,
+    rng: &mut R,
+) -> Vec<usize> {
+    if k == 0 {
+        return Vec::new();
+    }
+    // 键取 ln(u) / w，即 u^(1/w) 的对数，最大的 k 个入选
+    let mut heap: BinaryHeap<Reverse<Keyed>> = BinaryHeap::with_capacity(k);
+
+    let mut jump = 0.0;
+    for (idx, &w) in weights.iter().enumerate() {
+        if !(w > 0.0 && w.is_finite()) {
+            continue;
+        }
+
+        if heap.len() < k {
+            heap.push(Reverse(Keyed(unit(rng).ln() / w, idx)));
+            if heap.len() == k {
+                jump = next_jump(&heap, rng);
+            }
+            continue;
+        }
+
+        jump -= w;
+        if jump > 0.0 {
+            continue;
+        }
+
+        // 跳到的条目，键在 (门槛, 0] 中均匀地取
+        let threshold = heap.peek().map_or(0.0, |Reverse(min)| min.0);
+        let t = (threshold * w).exp();
+        let r = 1.0 - (1.0 - t) * rng.gen::<f64>();
+        let _ = heap.pop();
+        heap.push(Reverse(Keyed(r.ln() / w, idx)));
+        jump = next_jump(&heap, rng);
+    }
+
+    let mut chosen: Vec<usize> = heap.into_iter().map(|Reverse(keyed)| keyed.1).collect();
+    chosen.sort_unstable();
+    chosen
+}
+
+/// How much weight to pass over before the next replacement.
+fn next_jump<R: Rng + ?Sized>(heap: &BinaryHeap<Reverse<Keyed>>, rng: &mut R) -> f64 {
+    let threshold = heap.peek().map_or(0.0, |Reverse(min)| min.0);
+    unit(rng).ln() / threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Deterministic;
+
+    #[test]
+    fn proportional_to_weight() {
+        let mut rng = Deterministic::seed_from_u64(1);
+        let weights = [1.0, 2.0, 0.0, 3.0, 4.0, f64::NAN, -1.0];
+        let mut counts = [0u32; 7];
+        for _ in 0..20_000 {
+            for idx in sample_indices_weighted(&weights, 1, &mut rng) {
+                counts[idx] += 1;
+            }
+        }
+        assert_eq!(counts[2] + counts[5] + counts[6], 0);
+        for (idx, expected) in [(0, 2_000), (1, 4_000), (3, 6_000), (4, 8_000)] {
+            let c = f64::from(counts[idx]);
+            assert!((c - f64::from(expected)).abs() < 300.0, "{:?}", counts);
+        }
+
+        assert_eq!(
+            sample_indices_weighted(&weights, 10, &mut rng),
+            vec![0, 1, 3, 4]
+        );
+        assert!(sample_indices_weighted(&weights, 0, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn equal_weights_are_uniform() {
+        let mut rng = Deterministic::seed_from_u64(1);
+        let weights = vec![0.5; 100];
+        let mut counts = [0u32; 100];
+        for _ in 0..10_000 {
+            let chosen = sample_indices_weighted(&weights, 10, &mut rng);
+            assert_eq!(chosen.len(), 10);
+            assert!(chosen.windows(2).all(|w| w[0] < w[1]));
+            for idx in chosen {
+                counts[idx] += 1;
+            }
+        }
+        // 每个下标约 1000 次
+        assert!(
+            counts.iter().all(|&c| (880..=1_120).contains(&c)),
+            "{:?}",
+            counts
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Samples handed out with their inclusion probabilities, for estimating
+//! totals of the whole stream from a biased sample.
+use rand::Rng;
+
+use crate::{inclusion_probability, Lockable, Reservoir};
+
+/// A held item, with the probability it had of making it into the sample.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sampled<T> {
+    pub item: T,
+    pub probability: f64,
+}
+
+impl<T> Sampled<T> {
+    /// How many items of the stream this one stands for, `1 / probability`,
+    /// the Horvitz–Thompson weight.
+    pub fn weight(&self) -> f64 {
+        1.0 / self.probability
+    }
+}
+
+/// A sampler which can tell, when locked, how likely every held item was to
+/// be held, for Horvitz–Thompson estimates.
+pub trait LockWithWeights {
+    type Item;
+
+    /// The same as `lock`, only handing out the held items, each with its
+    /// inclusion probability.
+    fn lock_with_weights(self) -> Vec<Sampled<Self::Item>>;
+}
+
+/// The inclusion probability of a uniform sample of `held` out of `total`.
+pub(crate) fn uniform(held: usize, total: u64) -> f64 {
+    inclusion_probability(total, held)
+}
+
+/// `held / total`, which is `k / n` once the pool is full, and still holds
+/// after deletions.
+impl<T, R: Rng> LockWithWeights for Reservoir<T, R> {
+    type Item = T;
+
+    fn lock_with_weights(self) -> Vec<Sampled<T>> {
+        let probability = uniform(self.iter().count(), self.total());
+        self.lock_iter()
+            .map(|item| Sampled { item, probability })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArrayReservoir, Deterministic, PackedReservoir, StreamSampler, TicketReservoir};
+
+    #[test]
+    fn uniform_samplers() {
+        let mut reservoir = Reservoir::<u32, Deterministic>::deterministic(4, 1);
+        let mut array = ArrayReservoir::<u32, 4, Deterministic>::deterministic(1);
+        let mut packed = PackedReservoir::<u32, Deterministic>::deterministic(4, 1);
+        for it in 0..2 {
+            let _ = reservoir.sample(it);
+        }
+        assert!(reservoir
+            .lock_with_weights()
+            .iter()
+            .all(|s| s.probability == 1.0));
+
+        for it in 0..100 {
+            let _ = array.sample(it);
+            let _ = packed.sample(it);
+        }
+        for sampled in [array.lock_with_weights(), packed.lock_with_weights()] {
+            assert_eq!(sampled.len(), 4);
+            assert!(sampled.iter().all(|s| s.weight() == 25.0));
+        }
+    }
+
+    #[test]
+    fn tickets() {
+        let mut reservoir = TicketReservoir::<u32, Deterministic>::deterministic(2, 3);
+        let _ = reservoir.sample(0, 1);
+        let _ = reservoir.sample(1, 3);
+        let sampled = reservoir.lock_with_weights();
+        assert!(sampled.iter().all(|s| s.probability == 1.0));
+
+        let mut reservoir = TicketReservoir::<u32, Deterministic>::deterministic(1, 3);
+        for it in 0..10 {
+            let _ = reservoir.sample(it, 1 + u64::from(it == 9));
+        }
+        let sampled = reservoir.lock_with_weights();
+        let expected = if sampled[0].item == 9 {
+            2.0 / 11.0
+        } else {
+            1.0 / 11.0
+        };
+        assert!((sampled[0].probability - expected).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,240 @@
+//! Sampling overlapping windows of one stream, like a 5 minute window opening
+//! every minute, without a sampler per window copying every item.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+
+use crate::rotate::Window;
+
+/// An item with the random key it drew. A window keeps its `k` smallest keys,
+/// which is a uniform sample of the items it saw.
+struct Keyed<T> {
+    key: u64,
+    item: Rc<T>,
+}
+
+impl<T> PartialEq for Keyed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for Keyed<T> {}
+
+impl<T> PartialOrd for Keyed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Keyed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+struct OpenWindow<T> {
+    index: u64,
+    closes: Instant,
+    total: u64,
+    // 大顶堆，堆顶是最先被挤出去的
+    held: BinaryHeap<Keyed<T>>,
+}
+
+/// A `WindowManager` opens a window of `length` every `every`, so windows
+/// overlap when `every` is shorter than `length`, and tumble when they are
+/// equal. Every item draws a single random key, shared by all open windows,
+/// and is kept once behind an `Rc` by the windows which hold it.
+///
+/// Closed windows are handed to `emit`, oldest first. Windows are timed from
+/// the first item, and close when an item arrives after their end, or on
+/// `flush`.
+pub struct WindowManager<T, F, R = ThreadRng> {
+    k: usize,
+    length: Duration,
+    every: Duration,
+    next_opens: Option<Instant>,
+    next_index: u64,
+    open: VecDeque<OpenWindow<T>>,
+    rng: R,
+    emit: F,
+}
+
+impl<T: Clone, F: FnMut(Window<T>)> WindowManager<T, F> {
+    /// `every` of zero is treated as `length`.
+    pub fn sliding(k: usize, length: Duration, every: Duration, emit: F) -> Self {
+        Self::with_rng(k, length, every, thread_rng(), emit)
+    }
+
+    pub fn tumbling(k: usize, length: Duration, emit: F) -> Self {
+        Self::sliding(k, length, length, emit)
+    }
+}
+
+impl<T: Clone, F: FnMut(Window<T>), R: Rng> WindowManager<T, F, R> {
+    pub fn with_rng(k: usize, length: Duration, every: Duration, rng: R, emit: F) -> Self {
+        Self {
+            k,
+            length,
+            // 两者都为 0 时至少前进 1 纳秒，免得死循环
+            every: if every.is_zero() { length } else { every }.max(Duration::from_nanos(1)),
+            next_opens: None,
+            next_index: 0,
+            open: VecDeque::new(),
+            rng,
+            emit,
+        }
+    }
+
+    pub fn sample(&mut self, it: T) -> usize {
+        self.sample_at(it, Instant::now())
+    }
+
+    /// Samples at the given time instead of now, returning how many open
+    /// windows took the item.
+    pub fn sample_at(&mut self, it: T, now: Instant) -> usize {
+        self.close_until(now);
+        self.open_until(now);
+
+        let key = self.rng.gen::<u64>();
+        let k = self.k;
+        let mut shared: Option<Rc<T>> = None;
+        let mut taken = 0;
+        for window in &mut self.open {
+            window.total += 1;
+            let room = window.held.len() < k;
+            if !room && window.held.peek().is_none_or(|top| key >= top.key) {
+                continue;
+            }
+
+            let item = Rc::clone(shared.get_or_insert_with(|| Rc::new(it.clone())));
+            if !room {
+                let _ = window.held.pop();
+            }
+            window.held.push(Keyed { key, item });
+            taken += 1;
+        }
+        taken
+    }
+
+    fn close_until(&mut self, now: Instant) {
+        while self.open.front().is_some_and(|w| w.closes <= now) {
+            if let Some(window) = self.open.pop_front() {
+                self.emit_window(window);
+            }
+        }
+    }
+
+    fn open_until(&mut self, now: Instant) {
+        let mut opens = *self.next_opens.get_or_insert(now);
+        // 中间没有数据、已经结束的窗口一次跳过，不逐个走
+        if let Some(idle) = now.checked_duration_since(opens + self.length) {
+            let skipped = idle.as_nanos() / self.every.as_nanos() + 1;
+            opens += from_nanos(self.every.as_nanos() * skipped);
+            self.next_index += skipped as u64;
+        }
+        while opens <= now {
+            self.open.push_back(OpenWindow {
+                index: self.next_index,
+                closes: opens + self.length,
+                total: 0,
+                held: BinaryHeap::new(),
+            });
+            self.next_index += 1;
+            opens += self.every;
+        }
+        self.next_opens = Some(opens);
+    }
+
+    fn emit_window(&mut self, window: OpenWindow<T>) {
+        let samples = window
+            .held
+            .into_sorted_vec()
+            .into_iter()
+            .map(|keyed| Rc::try_unwrap(keyed.item).unwrap_or_else(|rc| (*rc).clone()))
+            .collect();
+
+        (self.emit)(Window {
+            index: window.index,
+            total: window.total,
+            samples,
+        });
+    }
+
+    /// Closes every open window, at the end of the stream.
+    pub fn flush(mut self) {
+        while let Some(window) = self.open.pop_front() {
+            self.emit_window(window);
+        }
+    }
+}
+
+fn from_nanos(nanos: u128) -> Duration {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).unwrap_or(u64::MAX);
+    Duration::new(secs, (nanos % NANOS_PER_SEC) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sliding_windows_share_items() {
+        let mut windows = Vec::new();
+        let minute = Duration::from_secs(60);
+        let mut manager = WindowManager::sliding(3, 5 * minute, minute, |w| windows.push(w));
+
+        let start = Instant::now();
+        for secs in 0..600u64 {
+            let taken = manager.sample_at(secs, start + Duration::from_secs(secs));
+            assert!(taken <= 5);
+        }
+        manager.flush();
+
+        assert_eq!(windows.len(), 10);
+        for w in &windows {
+            let opens = w.index * 60;
+            assert_eq!(w.total, (600 - opens).min(300));
+            assert_eq!(w.samples.len(), 3);
+            assert!(w.samples.iter().all(|&s| s >= opens && s < opens + 300));
+        }
+    }
+
+    #[test]
+    fn tumbling_skips_quiet_windows() {
+        let mut windows = Vec::new();
+        let minute = Duration::from_secs(60);
+        let mut manager = WindowManager::tumbling(2, minute, |w| windows.push(w));
+
+        let start = Instant::now();
+        for (it, secs) in [(1, 0), (2, 10), (3, 200)] {
+            let _ = manager.sample_at(it, start + Duration::from_secs(secs));
+        }
+        manager.flush();
+
+        let indices: Vec<_> = windows.iter().map(|w| (w.index, w.total)).collect();
+        assert_eq!(indices, [(0, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn long_idle_gaps_are_skipped_at_once() {
+        let mut windows = Vec::new();
+        let micro = Duration::from_micros(1);
+        let mut manager = WindowManager::sliding(1, 2 * micro, micro, |w| windows.push(w));
+
+        let start = Instant::now();
+        let _ = manager.sample_at(1, start);
+        // 一小时里有 36 亿个窗口，逐个走要很久
+        let _ = manager.sample_at(2, start + Duration::from_secs(3_600));
+        manager.flush();
+
+        let indices: Vec<_> = windows.iter().map(|w| (w.index, w.total)).collect();
+        assert_eq!(indices, [(0, 1), (3_599_999_999, 1), (3_600_000_000, 1)]);
+    }
+}